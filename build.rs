@@ -1,23 +1,49 @@
+/// Generate `c/bindings.h` in the directory pointed to by `BDK_FFI_GEN_DIR`, along with the other
+/// generated sources
+///
+/// Nothing is written otherwise, so that building the crate never touches the source tree.
 #[cfg(feature = "c")]
 fn c_build_rs() {
     use std::env;
+    use std::path::PathBuf;
 
     use cbindgen::{Builder, Language};
 
-    // TODO: the directory can be read-only, use OUT_DIR
+    let gen_dir = match env::var_os("BDK_FFI_GEN_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
     let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
 
+    // the `rerun-if-env-changed` in `main` disables the default of rerunning on any change, and
+    // the header also depends on the derive crate, which isn't part of this package
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=derive/src");
+
+    // cbindgen expands the crate with `-Zunpretty=expanded`, which is only accepted by stable
+    // compilers in bootstrap mode. It's only set for the `cargo` it runs, not for the crate itself.
+    env::set_var("RUSTC_BOOTSTRAP", "1");
+
+    // the objects are declared as handles instead of pointers with `c-handles`
+    let features: &[&str] = match env::var_os("CARGO_FEATURE_C_HANDLES") {
+        Some(_) => &["c", "c-handles"],
+        None => &["c"],
+    };
+
     Builder::new()
         .with_crate(crate_dir)
         .with_language(Language::C)
         .with_parse_expand(&["bdk-ffi"])
-        .with_parse_expand_features(&["c"])
+        .with_parse_expand_features(features)
         .generate()
         .expect("Unable to generate bindings")
-        .write_to_file("c/bindings.h");
+        .write_to_file(gen_dir.join("c/bindings.h"));
 }
 
 fn main() {
+    // the derive macros write the generated sources there too
+    println!("cargo:rerun-if-env-changed=BDK_FFI_GEN_DIR");
+
     #[cfg(feature = "c")]
     c_build_rs();
 }
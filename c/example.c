@@ -17,11 +17,11 @@ int main() {
 
     char *address_str = address_to_string(a);
     printf("Address: %s\n", address_str);
-    free(address_str);
+    string_free(address_str);
 
     char *script_hex = script_to_hex(s);
     printf("Script: %s\n", script_hex);
-    free(script_hex);
+    string_free(script_hex);
 
     script_destroy(s);
     network_destroy(n);
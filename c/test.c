@@ -0,0 +1,143 @@
+#include <assert.h>
+#include <stdatomic.h>
+#include <stdio.h>
+#include <string.h>
+#include <time.h>
+
+#include "bindings.h"
+
+#define SCRIPT_HEX "a91457d6b4ded38193013643b03b4472e15f80bc465787"
+
+/* Compare a string returned by the library and release it */
+static void assert_string(char *s, const char *expected) {
+    assert(s != NULL);
+    assert(strcmp(s, expected) == 0);
+    string_free(s);
+}
+
+static void test_byte_buffer(const Script *s) {
+    ByteBuffer bytes = script_to_bytes(s);
+    assert(bytes.len == strlen(SCRIPT_HEX) / 2);
+    assert(bytes.data[0] == 0xa9 && bytes.data[bytes.len - 1] == 0x87);
+
+    Script *copy = NULL;
+    script_from_bytes(bytes.data, bytes.len, &copy);
+    byte_buffer_free(bytes);
+    assert_string(script_to_hex(copy), SCRIPT_HEX);
+    script_destroy(copy);
+}
+
+static void test_cursor(const Script *s) {
+    const char *expected[] = {"OP_HASH160", "57d6b4ded38193013643b03b4472e15f80bc4657", "OP_EQUAL"};
+
+    Cursor_String *cursor = script_instructions(s);
+    for (size_t i = 0; i < sizeof(expected) / sizeof(*expected); i++) {
+        char *item = NULL;
+        assert(script_instructions_next(cursor, &item) == 0);
+        assert_string(item, expected[i]);
+    }
+    char *item = NULL;
+    assert(script_instructions_next(cursor, &item) == CURSOR_END);
+    assert(script_instructions_next(cursor, &item) == CURSOR_END);
+    script_instructions_destroy(cursor);
+
+    /* released without being consumed */
+    script_instructions_destroy(script_instructions(s));
+
+    /* NULL is an empty cursor */
+    assert(script_instructions_next(NULL, &item) == CURSOR_END);
+    script_instructions_destroy(NULL);
+}
+
+static void test_optional_arguments(const Script *s) {
+    assert_string(script_short_hex(s, NULL, NULL), "a91457d6b4ded381...");
+    uint32_t max_chars = 4;
+    assert_string(script_short_hex(s, &max_chars, "~"), "a914~");
+
+    FeeRate *fee_rate = NULL;
+    feerate_from_sat_per_vb(2, &fee_rate);
+    assert(feerate_fee(fee_rate, 10, NULL) == 20);
+    uint32_t min_fee = 50;
+    assert(feerate_fee(fee_rate, 10, &min_fee) == 50);
+    feerate_destroy(fee_rate);
+}
+
+static void test_shared(const Script *s) {
+    FeeRate *fee_rate = NULL;
+    feerate_from_sat_per_vb(2, &fee_rate);
+    FeeRate *clone = feerate_clone_handle(fee_rate);
+
+    feerate_set_sat_per_vb(clone, 3);
+    assert(feerate_get_sat_per_vb(fee_rate) == 3);
+    assert(script_spend_fee(s, fee_rate) == 3 * 23);
+
+    /* the object stays alive until every handle is released */
+    feerate_destroy(fee_rate);
+    assert(feerate_get_sat_per_vb(clone) == 3);
+    feerate_destroy(clone);
+}
+
+static void test_array(void) {
+    Network **networks = NULL;
+    size_t len = 0;
+    network_all(&networks, &len);
+    assert(len > 1);
+
+    /* keep the first element, the array releases the others */
+    Network *first = networks[0];
+    networks[0] = NULL;
+    network_array_free(networks, len);
+    assert_string(network_to_string(first), "bitcoin");
+    network_destroy(first);
+}
+
+struct nonce_result {
+    atomic_bool done;
+    int32_t error;
+    uint32_t nonce;
+};
+
+static void nonce_found(void *user_data, int32_t error, uint32_t nonce) {
+    struct nonce_result *result = user_data;
+    result->error = error;
+    result->nonce = nonce;
+    atomic_store(&result->done, true);
+}
+
+static void test_async(const Script *s) {
+    CancellationToken *token = NULL;
+    cancellationtoken_new(&token);
+    uint32_t expected = 0;
+    assert(script_find_nonce(s, 8, token, &expected) == 0);
+
+    /* the future works on copies, the arguments can be released right away */
+    Script *copy = NULL;
+    script_from_hex(SCRIPT_HEX, &copy);
+    struct nonce_result result = {.done = false};
+    script_find_nonce_async(copy, 8, token, nonce_found, &result);
+    script_destroy(copy);
+    cancellationtoken_destroy(token);
+
+    const struct timespec delay = {.tv_nsec = 1000000};
+    while (!atomic_load(&result.done)) {
+        nanosleep(&delay, NULL);
+    }
+    assert(result.error == 0);
+    assert(result.nonce == expected);
+}
+
+int main() {
+    Script *s = NULL;
+    assert(script_from_hex(SCRIPT_HEX, &s) == 0);
+    assert(script_from_hex("invalid", &s) == -1);
+
+    test_byte_buffer(s);
+    test_cursor(s);
+    test_optional_arguments(s);
+    test_shared(s);
+    test_array();
+    test_async(s);
+
+    script_destroy(s);
+    printf("All tests passed\n");
+}
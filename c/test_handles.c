@@ -0,0 +1,103 @@
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+/* the header generated with `c-handles`, not the one next to this file */
+#include <bindings.h>
+
+#define SCRIPT_HEX "a91457d6b4ded38193013643b03b4472e15f80bc465787"
+
+/* Compare a string returned by the library and release it */
+static void assert_string(char *s, const char *expected) {
+    assert(s != NULL);
+    assert(strcmp(s, expected) == 0);
+    string_free(s);
+}
+
+static void test_released(void) {
+    uint64_t s = 0;
+    assert(script_from_hex(SCRIPT_HEX, &s) == 0);
+    assert(script_destroy(s) == 0);
+
+    char *hex = NULL;
+    assert(script_to_hex(s, &hex) == HANDLE_ERROR_RELEASED);
+    assert(hex == NULL);
+    assert(script_destroy(s) == HANDLE_ERROR_RELEASED);
+
+    /* the slot is reused with a new generation, the old handle stays released */
+    uint64_t other = 0;
+    assert(script_from_hex(SCRIPT_HEX, &other) == 0);
+    assert(other != s);
+    assert(script_to_hex(s, &hex) == HANDLE_ERROR_RELEASED);
+    assert(script_destroy(other) == 0);
+}
+
+static void test_invalid(void) {
+    char *hex = NULL;
+    assert(script_to_hex(0x12345678, &hex) == HANDLE_ERROR_INVALID);
+
+    /* a handle of another type */
+    uint64_t n = network_testnet();
+    assert(script_to_hex(n, &hex) == HANDLE_ERROR_INVALID);
+    assert(script_destroy(n) == HANDLE_ERROR_INVALID);
+    assert(network_destroy(n) == 0);
+}
+
+static void test_byte_buffer_and_cursor(uint64_t s) {
+    ByteBuffer bytes;
+    assert(script_to_bytes(s, &bytes) == 0);
+    assert(bytes.len == strlen(SCRIPT_HEX) / 2);
+    uint64_t copy = script_from_bytes(bytes.data, bytes.len);
+    byte_buffer_free(bytes);
+
+    char *hex = NULL;
+    assert(script_to_hex(copy, &hex) == 0);
+    assert_string(hex, SCRIPT_HEX);
+    assert(script_destroy(copy) == 0);
+
+    uint64_t cursor = 0;
+    assert(script_instructions(s, &cursor) == 0);
+    size_t count = 0;
+    char *item = NULL;
+    int err;
+    while ((err = script_instructions_next(cursor, &item)) == 0) {
+        string_free(item);
+        count++;
+    }
+    assert(err == CURSOR_END);
+    assert(count == 3);
+    assert(script_instructions_destroy(cursor) == 0);
+    assert(script_instructions_next(cursor, &item) == HANDLE_ERROR_RELEASED);
+    assert(script_instructions_destroy(cursor) == HANDLE_ERROR_RELEASED);
+    assert(script_instructions_next(s, &item) == HANDLE_ERROR_INVALID);
+}
+
+static void test_shared(uint64_t s) {
+    uint64_t fee_rate = feerate_from_sat_per_vb(2);
+    uint64_t clone = 0;
+    assert(feerate_clone_handle(fee_rate, &clone) == 0);
+    assert(feerate_set_sat_per_vb(clone, 3) == 0);
+
+    uint32_t fee = 0;
+    assert(script_spend_fee(s, fee_rate, &fee) == 0);
+    assert(fee == 3 * 23);
+
+    assert(feerate_destroy(fee_rate) == 0);
+    assert(feerate_fee(fee_rate, 1, NULL, &fee) == HANDLE_ERROR_RELEASED);
+    assert(feerate_fee(clone, 1, NULL, &fee) == 0);
+    assert(fee == 3);
+    assert(feerate_destroy(clone) == 0);
+}
+
+int main() {
+    uint64_t s = 0;
+    assert(script_from_hex(SCRIPT_HEX, &s) == 0);
+
+    test_released();
+    test_invalid();
+    test_byte_buffer_and_cursor(s);
+    test_shared(s);
+
+    assert(script_destroy(s) == 0);
+    printf("All tests passed\n");
+}
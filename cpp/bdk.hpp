@@ -0,0 +1,374 @@
+// Automatically generated by the `derive` crate, do not edit
+
+#pragma once
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#include <optional>
+#include <stdexcept>
+#include <string>
+#include <utility>
+#include <vector>
+
+namespace bdk {
+
+/// Declarations of the C API, kept in their own namespace so that they don't clash with the
+/// classes that wrap them
+namespace ffi {
+extern "C" {
+#include "../c/bindings.h"
+}
+} // namespace ffi
+
+namespace detail {
+/// Take the ownership of a string returned by the C API
+inline std::string take_string(char *pointer) {
+    std::string string(pointer);
+    ffi::string_free(pointer);
+    return string;
+}
+
+/// Take the ownership of a byte buffer returned by the C API
+inline std::vector<uint8_t> take_bytes(ffi::ByteBuffer buffer) {
+    std::vector<uint8_t> bytes(buffer.data, buffer.data + buffer.len);
+    ffi::byte_buffer_free(buffer);
+    return bytes;
+}
+
+/// Take the ownership of the objects of an array returned by the C API, and release the array
+template <typename T, typename P>
+std::vector<T> take_array(P **array, size_t len, void (*array_free)(P **, size_t)) {
+    std::vector<T> values;
+    values.reserve(len);
+    for (size_t i = 0; i < len; i++) {
+        values.emplace_back(std::exchange(array[i], nullptr));
+    }
+    array_free(array, len);
+    return values;
+}
+} // namespace detail
+
+class BitcoinError : public std::runtime_error {
+public:
+    explicit BitcoinError(int32_t code) : std::runtime_error("BitcoinError"), code(code) {}
+
+    /// Error code returned by the C API
+    int32_t code;
+};
+
+} // namespace bdk
+
+namespace bdk::bitcoin {
+
+class Script;
+
+class Network;
+
+class Address;
+
+class CancellationToken;
+
+class FeeRate;
+
+} // namespace bdk::bitcoin
+
+namespace bdk::bitcoin {
+
+// Not wrapped yet, only available through the C API:
+// - script_instructions: returns a cursor
+// - script_find_nonce_async: async
+// - script_short_hex: takes optional arguments
+class Script {
+public:
+    /// Take the ownership of a pointer returned by the C API
+    explicit Script(ffi::Script *pointer) noexcept : pointer_(pointer) {}
+
+    Script(const Script &) = delete;
+    Script &operator=(const Script &) = delete;
+
+    Script(Script &&other) noexcept : pointer_(std::exchange(other.pointer_, nullptr)) {}
+    Script &operator=(Script &&other) noexcept {
+        std::swap(pointer_, other.pointer_);
+        return *this;
+    }
+
+    ~Script() {
+        if (pointer_ != nullptr) ffi::script_destroy(pointer_);
+    }
+
+    /// Pointer to pass to the C API, still owned by this object
+    ffi::Script *get() const noexcept { return pointer_; }
+
+    explicit Script(const std::string &hex);
+
+    static Script from_bytes(const std::vector<uint8_t> &bytes);
+
+    std::vector<uint8_t> to_bytes() const;
+
+    std::string to_hex() const;
+
+    std::string asm_() const;
+
+    uint32_t find_nonce(uint32_t zero_bits, const CancellationToken &token) const;
+
+    uint32_t spend_fee(const FeeRate &fee_rate) const;
+
+private:
+    ffi::Script *pointer_;
+};
+
+class Network {
+public:
+    /// Take the ownership of a pointer returned by the C API
+    explicit Network(ffi::Network *pointer) noexcept : pointer_(pointer) {}
+
+    Network(const Network &) = delete;
+    Network &operator=(const Network &) = delete;
+
+    Network(Network &&other) noexcept : pointer_(std::exchange(other.pointer_, nullptr)) {}
+    Network &operator=(Network &&other) noexcept {
+        std::swap(pointer_, other.pointer_);
+        return *this;
+    }
+
+    ~Network() {
+        if (pointer_ != nullptr) ffi::network_destroy(pointer_);
+    }
+
+    /// Pointer to pass to the C API, still owned by this object
+    ffi::Network *get() const noexcept { return pointer_; }
+
+    explicit Network(const std::string &s);
+
+    static Network bitcoin();
+
+    static Network testnet();
+
+    static std::vector<Network> all();
+
+    std::string to_string() const;
+
+private:
+    ffi::Network *pointer_;
+};
+
+class Address {
+public:
+    /// Take the ownership of a pointer returned by the C API
+    explicit Address(ffi::Address *pointer) noexcept : pointer_(pointer) {}
+
+    Address(const Address &) = delete;
+    Address &operator=(const Address &) = delete;
+
+    Address(Address &&other) noexcept : pointer_(std::exchange(other.pointer_, nullptr)) {}
+    Address &operator=(Address &&other) noexcept {
+        std::swap(pointer_, other.pointer_);
+        return *this;
+    }
+
+    ~Address() {
+        if (pointer_ != nullptr) ffi::address_destroy(pointer_);
+    }
+
+    /// Pointer to pass to the C API, still owned by this object
+    ffi::Address *get() const noexcept { return pointer_; }
+
+    static std::optional<Address> from_script(const Script &script, const Network &network);
+
+    explicit Address(const std::string &s);
+
+    std::string to_string() const;
+
+    Script script() const;
+
+    Network network() const;
+
+private:
+    ffi::Address *pointer_;
+};
+
+class CancellationToken {
+public:
+    /// Take the ownership of a pointer returned by the C API
+    explicit CancellationToken(ffi::CancellationToken *pointer) noexcept : pointer_(pointer) {}
+
+    CancellationToken(const CancellationToken &) = delete;
+    CancellationToken &operator=(const CancellationToken &) = delete;
+
+    CancellationToken(CancellationToken &&other) noexcept : pointer_(std::exchange(other.pointer_, nullptr)) {}
+    CancellationToken &operator=(CancellationToken &&other) noexcept {
+        std::swap(pointer_, other.pointer_);
+        return *this;
+    }
+
+    ~CancellationToken() {
+        if (pointer_ != nullptr) ffi::cancellationtoken_destroy(pointer_);
+    }
+
+    /// Pointer to pass to the C API, still owned by this object
+    ffi::CancellationToken *get() const noexcept { return pointer_; }
+
+    explicit CancellationToken();
+
+    void cancel() const;
+
+    bool is_cancelled() const;
+
+private:
+    ffi::CancellationToken *pointer_;
+};
+
+// Not wrapped yet, only available through the C API:
+// - feerate_fee: takes optional arguments
+class FeeRate {
+public:
+    /// Take the ownership of a pointer returned by the C API
+    explicit FeeRate(ffi::FeeRate *pointer) noexcept : pointer_(pointer) {}
+
+    FeeRate(const FeeRate &) = delete;
+    FeeRate &operator=(const FeeRate &) = delete;
+
+    FeeRate(FeeRate &&other) noexcept : pointer_(std::exchange(other.pointer_, nullptr)) {}
+    FeeRate &operator=(FeeRate &&other) noexcept {
+        std::swap(pointer_, other.pointer_);
+        return *this;
+    }
+
+    ~FeeRate() {
+        if (pointer_ != nullptr) ffi::feerate_destroy(pointer_);
+    }
+
+    /// Pointer to pass to the C API, still owned by this object
+    ffi::FeeRate *get() const noexcept { return pointer_; }
+
+    explicit FeeRate(uint32_t sat_per_vb);
+
+    uint32_t sat_per_vb() const;
+
+    void set_sat_per_vb(uint32_t sat_per_vb);
+
+private:
+    ffi::FeeRate *pointer_;
+};
+
+} // namespace bdk::bitcoin
+
+namespace bdk::bitcoin {
+
+inline Script::Script(const std::string &hex) : pointer_(nullptr) {
+    int32_t code = ffi::script_from_hex(hex.c_str(), &pointer_);
+    if (code != 0) throw BitcoinError(code);
+}
+
+inline Script Script::from_bytes(const std::vector<uint8_t> &bytes) {
+    ffi::Script *out = nullptr;
+    ffi::script_from_bytes(bytes.data(), bytes.size(), &out);
+    return Script(out);
+}
+
+inline std::vector<uint8_t> Script::to_bytes() const {
+    return detail::take_bytes(ffi::script_to_bytes(pointer_));
+}
+
+inline std::string Script::to_hex() const {
+    return detail::take_string(ffi::script_to_hex(pointer_));
+}
+
+inline std::string Script::asm_() const {
+    return detail::take_string(ffi::script_asm(pointer_));
+}
+
+inline uint32_t Script::find_nonce(uint32_t zero_bits, const CancellationToken &token) const {
+    uint32_t out = {};
+    int32_t code = ffi::script_find_nonce(pointer_, zero_bits, token.get(), &out);
+    if (code != 0) throw BitcoinError(code);
+    return out;
+}
+
+inline uint32_t Script::spend_fee(const FeeRate &fee_rate) const {
+    return ffi::script_spend_fee(pointer_, fee_rate.get());
+}
+
+inline Network::Network(const std::string &s) : pointer_(nullptr) {
+    int32_t code = ffi::network_from_string(s.c_str(), &pointer_);
+    if (code != 0) throw BitcoinError(code);
+}
+
+inline Network Network::bitcoin() {
+    ffi::Network *out = nullptr;
+    ffi::network_bitcoin(&out);
+    return Network(out);
+}
+
+inline Network Network::testnet() {
+    ffi::Network *out = nullptr;
+    ffi::network_testnet(&out);
+    return Network(out);
+}
+
+inline std::vector<Network> Network::all() {
+    ffi::Network **out = nullptr; size_t len = 0;
+    ffi::network_all(&out, &len);
+    return detail::take_array<Network>(out, len, ffi::network_array_free);
+}
+
+inline std::string Network::to_string() const {
+    return detail::take_string(ffi::network_to_string(pointer_));
+}
+
+inline std::optional<Address> Address::from_script(const Script &script, const Network &network) {
+    ffi::Address *out = nullptr;
+    ffi::address_from_script(script.get(), network.get(), &out);
+    if (out == nullptr) return std::nullopt;
+    return Address(out);
+}
+
+inline Address::Address(const std::string &s) : pointer_(nullptr) {
+    int32_t code = ffi::address_from_string(s.c_str(), &pointer_);
+    if (code != 0) throw BitcoinError(code);
+}
+
+inline std::string Address::to_string() const {
+    return detail::take_string(ffi::address_to_string(pointer_));
+}
+
+inline Script Address::script() const {
+    ffi::Script *out = nullptr;
+    ffi::address_get_script(pointer_, &out);
+    return Script(out);
+}
+
+inline Network Address::network() const {
+    ffi::Network *out = nullptr;
+    ffi::address_network(pointer_, &out);
+    return Network(out);
+}
+
+inline CancellationToken::CancellationToken() : pointer_(nullptr) {
+    ffi::cancellationtoken_new(&pointer_);
+}
+
+inline void CancellationToken::cancel() const {
+    ffi::cancellationtoken_cancel(pointer_);
+}
+
+inline bool CancellationToken::is_cancelled() const {
+    return ffi::cancellationtoken_is_cancelled(pointer_);
+}
+
+inline FeeRate::FeeRate(uint32_t sat_per_vb) : pointer_(nullptr) {
+    ffi::feerate_from_sat_per_vb(sat_per_vb, &pointer_);
+}
+
+inline uint32_t FeeRate::sat_per_vb() const {
+    return ffi::feerate_get_sat_per_vb(pointer_);
+}
+
+inline void FeeRate::set_sat_per_vb(uint32_t sat_per_vb) {
+    ffi::feerate_set_sat_per_vb(pointer_, sat_per_vb);
+}
+
+} // namespace bdk::bitcoin
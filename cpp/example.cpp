@@ -0,0 +1,21 @@
+#include <iostream>
+
+#include "bdk.hpp"
+
+namespace bitcoin = bdk::bitcoin;
+
+int main() {
+    bitcoin::Script script("a91457d6b4ded38193013643b03b4472e15f80bc465787");
+    bitcoin::Network network = bitcoin::Network::testnet();
+
+    if (auto address = bitcoin::Address::from_script(script, network)) {
+        std::cout << "Address: " << address->to_string() << std::endl;
+        std::cout << "Script: " << address->script().to_hex() << std::endl;
+    }
+
+    try {
+        bitcoin::Address invalid("invalid");
+    } catch (const bdk::BitcoinError &e) {
+        std::cout << "Error: " << e.what() << " (" << e.code << ")" << std::endl;
+    }
+}
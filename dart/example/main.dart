@@ -0,0 +1,16 @@
+import 'package:bdk/bdk.dart';
+
+void main() {
+  final script = Script.fromHex('a91457d6b4ded38193013643b03b4472e15f80bc465787');
+  final network = Network.testnet();
+
+  final address = Address.fromScript(script, network)!;
+  print('Address: $address');
+  print('Script: ${address.script.toHex()}');
+
+  try {
+    Address.fromString('invalid');
+  } on BitcoinError catch (e) {
+    print('Error: $e');
+  }
+}
@@ -0,0 +1,432 @@
+// Automatically generated by the `derive` crate, do not edit
+
+import 'dart:ffi';
+import 'dart:io';
+import 'dart:typed_data';
+
+import 'package:ffi/ffi.dart';
+
+DynamicLibrary _open() {
+  final path = Platform.environment['BDK_FFI_LIB'];
+  if (path != null) return DynamicLibrary.open(path);
+  // the library is statically linked into the app on iOS
+  if (Platform.isIOS) return DynamicLibrary.process();
+  return DynamicLibrary.open('libbdk_ffi.so');
+}
+
+final _lib = _open();
+
+typedef _StringFreeC = Void Function(Pointer<Utf8>);
+typedef _StringFreeDart = void Function(Pointer<Utf8>);
+final _stringFree = _lib.lookupFunction<_StringFreeC, _StringFreeDart>('string_free');
+
+/// Copy a string returned by the library and release it
+String _takeString(Pointer<Utf8> pointer) {
+  try {
+    return pointer.toDartString();
+  } finally {
+    _stringFree(pointer);
+  }
+}
+
+final class _ByteBuffer extends Struct {
+  external Pointer<Uint8> data;
+  @Size()
+  external int len;
+  @Size()
+  external int cap;
+}
+
+typedef _ByteBufferFreeC = Void Function(_ByteBuffer);
+typedef _ByteBufferFreeDart = void Function(_ByteBuffer);
+final _byteBufferFree =
+    _lib.lookupFunction<_ByteBufferFreeC, _ByteBufferFreeDart>('byte_buffer_free');
+
+/// Copy the bytes returned by the library and release them
+Uint8List _takeBytes(_ByteBuffer buffer) {
+  try {
+    return Uint8List.fromList(buffer.data.asTypedList(buffer.len));
+  } finally {
+    _byteBufferFree(buffer);
+  }
+}
+
+/// Take the ownership of the objects of an array returned by the library, and release the array
+List<T> _takeArray<T, P extends NativeType>(Pointer<Pointer<P>> array, int len,
+    T Function(Pointer<P>) take, void Function(Pointer<Pointer<P>>, int) free) {
+  final values = <T>[];
+  for (var i = 0; i < len; i++) {
+    values.add(take(array[i]));
+    array[i] = nullptr;
+  }
+  free(array, len);
+  return values;
+}
+
+/// Copy bytes to native memory, which is only borrowed by the library
+Pointer<Uint8> _toNativeBytes(Uint8List bytes, Allocator allocator) {
+  final pointer = allocator<Uint8>(bytes.length);
+  pointer.asTypedList(bytes.length).setAll(0, bytes);
+  return pointer;
+}
+
+class BitcoinError implements Exception {
+  /// Error code returned by the library
+  final int code;
+
+  BitcoinError(this.code);
+
+  @override
+  String toString() => 'BitcoinError (code $code)';
+}
+
+final class _Script extends Opaque {}
+
+typedef _ScriptFromHexC = Int32 Function(Pointer<Utf8>, Pointer<Pointer<_Script>>);
+typedef _ScriptFromHexDart = int Function(Pointer<Utf8>, Pointer<Pointer<_Script>>);
+final _scriptFromHex = _lib.lookupFunction<_ScriptFromHexC, _ScriptFromHexDart>('script_from_hex');
+typedef _ScriptFromBytesC = Void Function(Pointer<Uint8>, Size, Pointer<Pointer<_Script>>);
+typedef _ScriptFromBytesDart = void Function(Pointer<Uint8>, int, Pointer<Pointer<_Script>>);
+final _scriptFromBytes = _lib.lookupFunction<_ScriptFromBytesC, _ScriptFromBytesDart>('script_from_bytes');
+typedef _ScriptDestroyC = Void Function(Pointer<_Script>);
+typedef _ScriptDestroyDart = void Function(Pointer<_Script>);
+final _scriptDestroy = _lib.lookupFunction<_ScriptDestroyC, _ScriptDestroyDart>('script_destroy');
+typedef _ScriptToBytesC = _ByteBuffer Function(Pointer<_Script>);
+typedef _ScriptToBytesDart = _ByteBuffer Function(Pointer<_Script>);
+final _scriptToBytes = _lib.lookupFunction<_ScriptToBytesC, _ScriptToBytesDart>('script_to_bytes');
+typedef _ScriptToHexC = Pointer<Utf8> Function(Pointer<_Script>);
+typedef _ScriptToHexDart = Pointer<Utf8> Function(Pointer<_Script>);
+final _scriptToHex = _lib.lookupFunction<_ScriptToHexC, _ScriptToHexDart>('script_to_hex');
+typedef _ScriptAsmC = Pointer<Utf8> Function(Pointer<_Script>);
+typedef _ScriptAsmDart = Pointer<Utf8> Function(Pointer<_Script>);
+final _scriptAsm = _lib.lookupFunction<_ScriptAsmC, _ScriptAsmDart>('script_asm');
+typedef _ScriptFindNonceC = Int32 Function(Pointer<_Script>, Uint32, Pointer<_CancellationToken>, Pointer<Uint32>);
+typedef _ScriptFindNonceDart = int Function(Pointer<_Script>, int, Pointer<_CancellationToken>, Pointer<Uint32>);
+final _scriptFindNonce = _lib.lookupFunction<_ScriptFindNonceC, _ScriptFindNonceDart>('script_find_nonce');
+typedef _ScriptSpendFeeC = Uint32 Function(Pointer<_Script>, Pointer<_FeeRate>);
+typedef _ScriptSpendFeeDart = int Function(Pointer<_Script>, Pointer<_FeeRate>);
+final _scriptSpendFee = _lib.lookupFunction<_ScriptSpendFeeC, _ScriptSpendFeeDart>('script_spend_fee');
+typedef _ScriptArrayFreeC = Void Function(Pointer<Pointer<_Script>>, Size);
+typedef _ScriptArrayFreeDart = void Function(Pointer<Pointer<_Script>>, int);
+final _scriptArrayFree = _lib.lookupFunction<_ScriptArrayFreeC, _ScriptArrayFreeDart>('script_array_free');
+
+// Not wrapped yet, only available through the C API:
+// - script_instructions: returns a cursor
+// - script_find_nonce_async: async
+// - script_short_hex: takes optional arguments
+class Script {
+  static final _finalizer = Finalizer<Pointer<_Script>>(_scriptDestroy);
+
+  final Pointer<_Script> _pointer;
+
+  Script._(this._pointer) {
+    _finalizer.attach(this, _pointer, detach: this);
+  }
+
+  /// Release the native object now instead of waiting for the garbage collector
+  void dispose() {
+    _finalizer.detach(this);
+    _scriptDestroy(_pointer);
+  }
+
+  factory Script.fromHex(String hex) {
+    return using((arena) {
+      final out = arena<Pointer<_Script>>();
+      final code = _scriptFromHex(hex.toNativeUtf8(allocator: arena), out);
+      if (code != 0) throw BitcoinError(code);
+      return Script._(out.value);
+    });
+  }
+
+  static Script fromBytes(Uint8List bytes) {
+    return using((arena) {
+      final out = arena<Pointer<_Script>>();
+      _scriptFromBytes(_toNativeBytes(bytes, arena), bytes.length, out);
+      return Script._(out.value);
+    });
+  }
+
+  Uint8List toBytes() {
+    return _takeBytes(_scriptToBytes(_pointer));
+  }
+
+  String toHex() {
+    return _takeString(_scriptToHex(_pointer));
+  }
+
+  String asm() {
+    return _takeString(_scriptAsm(_pointer));
+  }
+
+  int findNonce(int zeroBits, CancellationToken token) {
+    return using((arena) {
+      final out = arena<Uint32>();
+      final code = _scriptFindNonce(_pointer, zeroBits, token._pointer, out);
+      if (code != 0) throw BitcoinError(code);
+      return out.value;
+    });
+  }
+
+  int spendFee(FeeRate feeRate) {
+    return _scriptSpendFee(_pointer, feeRate._pointer);
+  }
+}
+
+final class _Network extends Opaque {}
+
+typedef _NetworkFromStringC = Int32 Function(Pointer<Utf8>, Pointer<Pointer<_Network>>);
+typedef _NetworkFromStringDart = int Function(Pointer<Utf8>, Pointer<Pointer<_Network>>);
+final _networkFromString = _lib.lookupFunction<_NetworkFromStringC, _NetworkFromStringDart>('network_from_string');
+typedef _NetworkDestroyC = Void Function(Pointer<_Network>);
+typedef _NetworkDestroyDart = void Function(Pointer<_Network>);
+final _networkDestroy = _lib.lookupFunction<_NetworkDestroyC, _NetworkDestroyDart>('network_destroy');
+typedef _NetworkBitcoinC = Void Function(Pointer<Pointer<_Network>>);
+typedef _NetworkBitcoinDart = void Function(Pointer<Pointer<_Network>>);
+final _networkBitcoin = _lib.lookupFunction<_NetworkBitcoinC, _NetworkBitcoinDart>('network_bitcoin');
+typedef _NetworkTestnetC = Void Function(Pointer<Pointer<_Network>>);
+typedef _NetworkTestnetDart = void Function(Pointer<Pointer<_Network>>);
+final _networkTestnet = _lib.lookupFunction<_NetworkTestnetC, _NetworkTestnetDart>('network_testnet');
+typedef _NetworkAllC = Void Function(Pointer<Pointer<Pointer<_Network>>>, Pointer<Size>);
+typedef _NetworkAllDart = void Function(Pointer<Pointer<Pointer<_Network>>>, Pointer<Size>);
+final _networkAll = _lib.lookupFunction<_NetworkAllC, _NetworkAllDart>('network_all');
+typedef _NetworkToStringC = Pointer<Utf8> Function(Pointer<_Network>);
+typedef _NetworkToStringDart = Pointer<Utf8> Function(Pointer<_Network>);
+final _networkToString = _lib.lookupFunction<_NetworkToStringC, _NetworkToStringDart>('network_to_string');
+typedef _NetworkArrayFreeC = Void Function(Pointer<Pointer<_Network>>, Size);
+typedef _NetworkArrayFreeDart = void Function(Pointer<Pointer<_Network>>, int);
+final _networkArrayFree = _lib.lookupFunction<_NetworkArrayFreeC, _NetworkArrayFreeDart>('network_array_free');
+
+class Network {
+  static final _finalizer = Finalizer<Pointer<_Network>>(_networkDestroy);
+
+  final Pointer<_Network> _pointer;
+
+  Network._(this._pointer) {
+    _finalizer.attach(this, _pointer, detach: this);
+  }
+
+  /// Release the native object now instead of waiting for the garbage collector
+  void dispose() {
+    _finalizer.detach(this);
+    _networkDestroy(_pointer);
+  }
+
+  factory Network.fromString(String s) {
+    return using((arena) {
+      final out = arena<Pointer<_Network>>();
+      final code = _networkFromString(s.toNativeUtf8(allocator: arena), out);
+      if (code != 0) throw BitcoinError(code);
+      return Network._(out.value);
+    });
+  }
+
+  static Network bitcoin() {
+    return using((arena) {
+      final out = arena<Pointer<_Network>>();
+      _networkBitcoin(out);
+      return Network._(out.value);
+    });
+  }
+
+  static Network testnet() {
+    return using((arena) {
+      final out = arena<Pointer<_Network>>();
+      _networkTestnet(out);
+      return Network._(out.value);
+    });
+  }
+
+  static List<Network> all() {
+    return using((arena) {
+      final out = arena<Pointer<Pointer<_Network>>>();
+      final outLen = arena<Size>();
+      _networkAll(out, outLen);
+      return _takeArray(out.value, outLen.value, Network._, _networkArrayFree);
+    });
+  }
+
+  @override
+  String toString() {
+    return _takeString(_networkToString(_pointer));
+  }
+}
+
+final class _Address extends Opaque {}
+
+typedef _AddressFromScriptC = Void Function(Pointer<_Script>, Pointer<_Network>, Pointer<Pointer<_Address>>);
+typedef _AddressFromScriptDart = void Function(Pointer<_Script>, Pointer<_Network>, Pointer<Pointer<_Address>>);
+final _addressFromScript = _lib.lookupFunction<_AddressFromScriptC, _AddressFromScriptDart>('address_from_script');
+typedef _AddressFromStringC = Int32 Function(Pointer<Utf8>, Pointer<Pointer<_Address>>);
+typedef _AddressFromStringDart = int Function(Pointer<Utf8>, Pointer<Pointer<_Address>>);
+final _addressFromString = _lib.lookupFunction<_AddressFromStringC, _AddressFromStringDart>('address_from_string');
+typedef _AddressDestroyC = Void Function(Pointer<_Address>);
+typedef _AddressDestroyDart = void Function(Pointer<_Address>);
+final _addressDestroy = _lib.lookupFunction<_AddressDestroyC, _AddressDestroyDart>('address_destroy');
+typedef _AddressToStringC = Pointer<Utf8> Function(Pointer<_Address>);
+typedef _AddressToStringDart = Pointer<Utf8> Function(Pointer<_Address>);
+final _addressToString = _lib.lookupFunction<_AddressToStringC, _AddressToStringDart>('address_to_string');
+typedef _AddressGetScriptC = Void Function(Pointer<_Address>, Pointer<Pointer<_Script>>);
+typedef _AddressGetScriptDart = void Function(Pointer<_Address>, Pointer<Pointer<_Script>>);
+final _addressGetScript = _lib.lookupFunction<_AddressGetScriptC, _AddressGetScriptDart>('address_get_script');
+typedef _AddressNetworkC = Void Function(Pointer<_Address>, Pointer<Pointer<_Network>>);
+typedef _AddressNetworkDart = void Function(Pointer<_Address>, Pointer<Pointer<_Network>>);
+final _addressNetwork = _lib.lookupFunction<_AddressNetworkC, _AddressNetworkDart>('address_network');
+typedef _AddressArrayFreeC = Void Function(Pointer<Pointer<_Address>>, Size);
+typedef _AddressArrayFreeDart = void Function(Pointer<Pointer<_Address>>, int);
+final _addressArrayFree = _lib.lookupFunction<_AddressArrayFreeC, _AddressArrayFreeDart>('address_array_free');
+
+class Address {
+  static final _finalizer = Finalizer<Pointer<_Address>>(_addressDestroy);
+
+  final Pointer<_Address> _pointer;
+
+  Address._(this._pointer) {
+    _finalizer.attach(this, _pointer, detach: this);
+  }
+
+  /// Release the native object now instead of waiting for the garbage collector
+  void dispose() {
+    _finalizer.detach(this);
+    _addressDestroy(_pointer);
+  }
+
+  static Address? fromScript(Script script, Network network) {
+    return using((arena) {
+      final out = arena<Pointer<_Address>>();
+      _addressFromScript(script._pointer, network._pointer, out);
+      return out.value == nullptr ? null : Address._(out.value);
+    });
+  }
+
+  factory Address.fromString(String s) {
+    return using((arena) {
+      final out = arena<Pointer<_Address>>();
+      final code = _addressFromString(s.toNativeUtf8(allocator: arena), out);
+      if (code != 0) throw BitcoinError(code);
+      return Address._(out.value);
+    });
+  }
+
+  @override
+  String toString() {
+    return _takeString(_addressToString(_pointer));
+  }
+
+  Script get script {
+    return using((arena) {
+      final out = arena<Pointer<_Script>>();
+      _addressGetScript(_pointer, out);
+      return Script._(out.value);
+    });
+  }
+
+  Network network() {
+    return using((arena) {
+      final out = arena<Pointer<_Network>>();
+      _addressNetwork(_pointer, out);
+      return Network._(out.value);
+    });
+  }
+}
+
+final class _CancellationToken extends Opaque {}
+
+typedef _CancellationtokenNewC = Void Function(Pointer<Pointer<_CancellationToken>>);
+typedef _CancellationtokenNewDart = void Function(Pointer<Pointer<_CancellationToken>>);
+final _cancellationtokenNew = _lib.lookupFunction<_CancellationtokenNewC, _CancellationtokenNewDart>('cancellationtoken_new');
+typedef _CancellationtokenDestroyC = Void Function(Pointer<_CancellationToken>);
+typedef _CancellationtokenDestroyDart = void Function(Pointer<_CancellationToken>);
+final _cancellationtokenDestroy = _lib.lookupFunction<_CancellationtokenDestroyC, _CancellationtokenDestroyDart>('cancellationtoken_destroy');
+typedef _CancellationtokenCancelC = Void Function(Pointer<_CancellationToken>);
+typedef _CancellationtokenCancelDart = void Function(Pointer<_CancellationToken>);
+final _cancellationtokenCancel = _lib.lookupFunction<_CancellationtokenCancelC, _CancellationtokenCancelDart>('cancellationtoken_cancel');
+typedef _CancellationtokenIsCancelledC = Bool Function(Pointer<_CancellationToken>);
+typedef _CancellationtokenIsCancelledDart = bool Function(Pointer<_CancellationToken>);
+final _cancellationtokenIsCancelled = _lib.lookupFunction<_CancellationtokenIsCancelledC, _CancellationtokenIsCancelledDart>('cancellationtoken_is_cancelled');
+typedef _CancellationtokenArrayFreeC = Void Function(Pointer<Pointer<_CancellationToken>>, Size);
+typedef _CancellationtokenArrayFreeDart = void Function(Pointer<Pointer<_CancellationToken>>, int);
+final _cancellationtokenArrayFree = _lib.lookupFunction<_CancellationtokenArrayFreeC, _CancellationtokenArrayFreeDart>('cancellationtoken_array_free');
+
+class CancellationToken {
+  static final _finalizer = Finalizer<Pointer<_CancellationToken>>(_cancellationtokenDestroy);
+
+  final Pointer<_CancellationToken> _pointer;
+
+  CancellationToken._(this._pointer) {
+    _finalizer.attach(this, _pointer, detach: this);
+  }
+
+  /// Release the native object now instead of waiting for the garbage collector
+  void dispose() {
+    _finalizer.detach(this);
+    _cancellationtokenDestroy(_pointer);
+  }
+
+  factory CancellationToken.new() {
+    return using((arena) {
+      final out = arena<Pointer<_CancellationToken>>();
+      _cancellationtokenNew(out);
+      return CancellationToken._(out.value);
+    });
+  }
+
+  void cancel() {
+    _cancellationtokenCancel(_pointer);
+  }
+
+  bool isCancelled() {
+    return _cancellationtokenIsCancelled(_pointer);
+  }
+}
+
+final class _FeeRate extends Opaque {}
+
+typedef _FeerateFromSatPerVbC = Void Function(Uint32, Pointer<Pointer<_FeeRate>>);
+typedef _FeerateFromSatPerVbDart = void Function(int, Pointer<Pointer<_FeeRate>>);
+final _feerateFromSatPerVb = _lib.lookupFunction<_FeerateFromSatPerVbC, _FeerateFromSatPerVbDart>('feerate_from_sat_per_vb');
+typedef _FeerateDestroyC = Void Function(Pointer<_FeeRate>);
+typedef _FeerateDestroyDart = void Function(Pointer<_FeeRate>);
+final _feerateDestroy = _lib.lookupFunction<_FeerateDestroyC, _FeerateDestroyDart>('feerate_destroy');
+typedef _FeerateGetSatPerVbC = Uint32 Function(Pointer<_FeeRate>);
+typedef _FeerateGetSatPerVbDart = int Function(Pointer<_FeeRate>);
+final _feerateGetSatPerVb = _lib.lookupFunction<_FeerateGetSatPerVbC, _FeerateGetSatPerVbDart>('feerate_get_sat_per_vb');
+typedef _FeerateSetSatPerVbC = Void Function(Pointer<_FeeRate>, Uint32);
+typedef _FeerateSetSatPerVbDart = void Function(Pointer<_FeeRate>, int);
+final _feerateSetSatPerVb = _lib.lookupFunction<_FeerateSetSatPerVbC, _FeerateSetSatPerVbDart>('feerate_set_sat_per_vb');
+typedef _FeerateArrayFreeC = Void Function(Pointer<Pointer<_FeeRate>>, Size);
+typedef _FeerateArrayFreeDart = void Function(Pointer<Pointer<_FeeRate>>, int);
+final _feerateArrayFree = _lib.lookupFunction<_FeerateArrayFreeC, _FeerateArrayFreeDart>('feerate_array_free');
+
+// Not wrapped yet, only available through the C API:
+// - feerate_fee: takes optional arguments
+class FeeRate {
+  static final _finalizer = Finalizer<Pointer<_FeeRate>>(_feerateDestroy);
+
+  final Pointer<_FeeRate> _pointer;
+
+  FeeRate._(this._pointer) {
+    _finalizer.attach(this, _pointer, detach: this);
+  }
+
+  /// Release the native object now instead of waiting for the garbage collector
+  void dispose() {
+    _finalizer.detach(this);
+    _feerateDestroy(_pointer);
+  }
+
+  factory FeeRate.fromSatPerVb(int satPerVb) {
+    return using((arena) {
+      final out = arena<Pointer<_FeeRate>>();
+      _feerateFromSatPerVb(satPerVb, out);
+      return FeeRate._(out.value);
+    });
+  }
+
+  int get satPerVb {
+    return _feerateGetSatPerVb(_pointer);
+  }
+
+  void setSatPerVb(int satPerVb) {
+    _feerateSetSatPerVb(_pointer, satPerVb);
+  }
+}
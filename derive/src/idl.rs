@@ -0,0 +1,677 @@
+//! Machine-readable description of the items exposed by `#[expose_mod]`
+//!
+//! While a module is analyzed for a language, everything it exposes is also collected here and
+//! written to `idl/<language>/<module>.json`, so that generators for other languages can work
+//! from it instead of parsing the generated code. Both the original Rust functions and the ones
+//! the language exports for them are described.
+
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+
+use proc_macro2::{Group, TokenStream as TokenStream2, TokenTree};
+use quote::ToTokens;
+use syn::{
+    parse_quote, Fields, FnArg, Ident, ImplItem, ItemImpl, ItemStruct, Pat, PatIdent, PatType,
+    Receiver, ReturnType, Signature, Type,
+};
+
+use crate::langs::{iterator_item, last_segment, rename_ident, write_generated, Lang, MethodRole};
+use crate::types::*;
+
+/// Directory of the descriptions, relative to the root of the crate
+const IDL_DIR: &str = "idl";
+
+/// Minimal JSON value, enough to serialize the description without extra dependencies
+enum Json {
+    Null,
+    Bool(bool),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn string<S: ToString>(s: S) -> Self {
+        Json::String(s.to_string())
+    }
+
+    fn types<'a, I: IntoIterator<Item = &'a Type>>(types: I) -> Self {
+        Json::Array(
+            types
+                .into_iter()
+                .map(|ty| Json::String(type_name(ty)))
+                .collect(),
+        )
+    }
+
+    fn write(&self, f: &mut String, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth + 1);
+
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) if items.is_empty() => f.write_str("[]"),
+            Json::Array(items) => {
+                f.write_str("[\n")?;
+                for (i, item) in items.iter().enumerate() {
+                    f.write_str(&indent)?;
+                    item.write(f, depth + 1)?;
+                    f.write_str(if i + 1 < items.len() { ",\n" } else { "\n" })?;
+                }
+                write!(f, "{}]", "  ".repeat(depth))
+            }
+            Json::Object(fields) => {
+                f.write_str("{\n")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    f.write_str(&indent)?;
+                    write_string(f, key)?;
+                    f.write_str(": ")?;
+                    value.write(f, depth + 1)?;
+                    f.write_str(if i + 1 < fields.len() { ",\n" } else { "\n" })?;
+                }
+                write!(f, "{}}}", "  ".repeat(depth))
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut String, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+/// Type as it would be written in the source, without the spaces added by the tokenizer
+pub(crate) fn type_name(ty: &Type) -> String {
+    tokens_name(ty.to_token_stream())
+}
+
+fn tokens_name(tokens: TokenStream2) -> String {
+    let mut name = tokens.to_string();
+    for (from, to) in &[
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" <", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        ("& ", "&"),
+        ("* ", "*"),
+        ("( ", "("),
+        (" )", ")"),
+    ] {
+        name = name.replace(from, to);
+    }
+
+    name
+}
+
+/// Remove the path of the items defined in this crate, like `crate::langs::ByteBuffer`, which
+/// is meaningless outside of it
+fn strip_crate_paths(tokens: TokenStream2) -> TokenStream2 {
+    let mut stripped = TokenStream2::new();
+    // last segment seen since `crate`, only kept if no other segment follows it
+    let mut path: Option<Option<Ident>> = None;
+
+    for token in tokens {
+        match (&mut path, token) {
+            (None, TokenTree::Ident(ident)) if ident == "crate" => path = Some(None),
+            (Some(_), TokenTree::Punct(punct)) if punct.as_char() == ':' => {}
+            (Some(segment), TokenTree::Ident(ident)) => *segment = Some(ident),
+            (_, token) => {
+                if let Some(segment) = path.take().flatten() {
+                    stripped.extend(Some(TokenTree::Ident(segment)));
+                }
+                stripped.extend(Some(match token {
+                    TokenTree::Group(group) => {
+                        let mut inner =
+                            Group::new(group.delimiter(), strip_crate_paths(group.stream()));
+                        inner.set_span(group.span());
+                        TokenTree::Group(inner)
+                    }
+                    token => token,
+                }));
+            }
+        }
+    }
+    if let Some(segment) = path.flatten() {
+        stripped.extend(Some(TokenTree::Ident(segment)));
+    }
+
+    stripped
+}
+
+fn role_name(role: MethodRole) -> &'static str {
+    match role {
+        MethodRole::Constructor => "constructor",
+        MethodRole::Destructor => "destructor",
+        MethodRole::Getter => "getter",
+        MethodRole::Setter => "setter",
+        MethodRole::Regular => "regular",
+    }
+}
+
+/// Error type of a `Result`, as seen on the Rust side
+fn error_type(ty: &Type) -> Option<Type> {
+    match_generic_type(ty, parse_quote!(Result))
+        .filter(|inner| inner.len() == 2)
+        .map(|inner| inner[1].clone())
+}
+
+pub struct Argument {
+    name: String,
+    ty: Type,
+    /// Value of an argument marked with `#[default(...)]`, whose type becomes an `Option`
+    default: Option<String>,
+}
+
+impl Argument {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("name", Json::string(&self.name)),
+            ("type", Json::string(type_name(&self.ty))),
+            ("default", self.default.as_ref().map(Json::string).into()),
+        ])
+    }
+}
+
+/// Item of a function returning an `impl Iterator`, and the functions of the C cursors
+pub struct IteratorItem {
+    item: Type,
+    kind: &'static str,
+    next: Option<Export>,
+    destroy: Option<Export>,
+}
+
+impl IteratorItem {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("item", Json::string(type_name(&self.item))),
+            ("kind", Json::string(self.kind)),
+            ("next", self.next.as_ref().map(Export::to_json).into()),
+            ("destroy", self.destroy.as_ref().map(Export::to_json).into()),
+        ])
+    }
+}
+
+pub struct Return {
+    ty: Type,
+    error: Option<(Type, Option<Type>)>,
+    iterator: Option<IteratorItem>,
+}
+
+impl Return {
+    fn to_json(&self) -> Json {
+        let error = self.error.as_ref().map(|(ty, converted)| {
+            Json::Object(vec![
+                ("type", Json::string(type_name(ty))),
+                (
+                    "converted",
+                    converted.as_ref().map(|ty| Json::types(Some(ty))).into(),
+                ),
+            ])
+        });
+
+        Json::Object(vec![
+            ("type", Json::string(type_name(&self.ty))),
+            ("error", error.into()),
+            (
+                "iterator",
+                self.iterator.as_ref().map(IteratorItem::to_json).into(),
+            ),
+        ])
+    }
+}
+
+/// Function generated by the language, with its types as written in the generated code
+pub struct Export {
+    symbol: String,
+    args: Vec<(String, String)>,
+    output: Option<String>,
+}
+
+impl Export {
+    /// Describe `sig`, with `Self` replaced by `self_ty`
+    fn new(symbol: String, sig: &Signature, self_ty: Option<&Ident>) -> Self {
+        let name = |ty: &Type| {
+            let mut tokens = strip_crate_paths(ty.to_token_stream());
+            if let Some(self_ty) = self_ty {
+                tokens = rename_ident(tokens, "Self", self_ty);
+            }
+
+            tokens_name(tokens)
+        };
+
+        let args = sig
+            .inputs
+            .iter()
+            .map(|arg| match arg {
+                FnArg::Receiver(receiver) => {
+                    let ty: Type = match (&receiver.reference, &receiver.mutability) {
+                        (Some(_), Some(_)) => parse_quote!(&mut Self),
+                        (Some(_), None) => parse_quote!(&Self),
+                        (None, _) => parse_quote!(Self),
+                    };
+                    ("self".to_string(), name(&ty))
+                }
+                FnArg::Typed(PatType { pat, ty, .. }) => {
+                    let arg = match &**pat {
+                        Pat::Ident(PatIdent { ident, .. }) => ident.to_string(),
+                        other => other.to_token_stream().to_string(),
+                    };
+                    (arg, name(ty))
+                }
+            })
+            .collect();
+        let output = match &sig.output {
+            ReturnType::Type(_, ty) if **ty != parse_quote!(()) => Some(name(ty)),
+            _ => None,
+        };
+
+        Export {
+            symbol,
+            args,
+            output,
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let args = self
+            .args
+            .iter()
+            .map(|(name, ty)| {
+                Json::Object(vec![
+                    ("name", Json::string(name)),
+                    ("type", Json::string(ty)),
+                ])
+            })
+            .collect();
+
+        Json::Object(vec![
+            ("symbol", Json::string(&self.symbol)),
+            ("args", Json::Array(args)),
+            ("output", self.output.as_ref().map(Json::string).into()),
+        ])
+    }
+}
+
+/// What a function recorded with `record_export` is generated for
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ExportKind {
+    Function,
+    CursorNext,
+    CursorDestroy,
+}
+
+thread_local! {
+    /// Functions generated by the language for the item being exposed, with the name of the Rust
+    /// function they export
+    static EXPORTS: RefCell<Vec<(String, ExportKind, String, Signature)>> = const { RefCell::new(vec![]) };
+}
+
+/// Record the function generated by the language to export the Rust function `name`, under the
+/// name `symbol` it's known by in the language
+pub fn record_export<S: ToString>(name: &Ident, symbol: S, sig: &Signature) {
+    EXPORTS.with(|exports| {
+        exports.borrow_mut().push((
+            name.to_string(),
+            ExportKind::Function,
+            symbol.to_string(),
+            sig.clone(),
+        ))
+    });
+}
+
+/// Record the functions of the cursor returned by the Rust function `name`
+#[cfg(feature = "c")]
+pub fn record_cursor(name: &Ident, next: &Signature, destroy: &Signature) {
+    EXPORTS.with(|exports| {
+        let mut exports = exports.borrow_mut();
+        for (kind, sig) in &[
+            (ExportKind::CursorNext, next),
+            (ExportKind::CursorDestroy, destroy),
+        ] {
+            exports.push((
+                name.to_string(),
+                *kind,
+                sig.ident.to_string(),
+                (*sig).clone(),
+            ));
+        }
+    });
+}
+
+/// Functions recorded while exposing an item, taken with `take_exports`
+pub struct Exports(Vec<(String, ExportKind, String, Signature)>);
+
+/// Take the functions recorded so far by the language
+pub fn take_exports() -> Exports {
+    Exports(EXPORTS.with(|exports| exports.borrow_mut().drain(..).collect()))
+}
+
+impl Exports {
+    fn take(&mut self, name: &str, kind: ExportKind, self_ty: Option<&Ident>) -> Option<Export> {
+        let pos = self
+            .0
+            .iter()
+            .rposition(|(n, k, _, _)| n == name && *k == kind)?;
+        let (_, _, symbol, sig) = self.0.remove(pos);
+
+        Some(Export::new(symbol, &sig, self_ty))
+    }
+}
+
+pub struct Function {
+    name: String,
+    role: Option<MethodRole>,
+    receiver: Option<&'static str>,
+    is_async: bool,
+    args: Vec<Argument>,
+    output: Return,
+    /// `None` when the language doesn't export the function, like the destructors of the
+    /// languages with a garbage collector
+    export: Option<Export>,
+}
+
+impl Function {
+    /// Describe the Rust function `sig` and the function exported for it, taken from `exports`
+    pub fn new<L: Lang>(
+        sig: &Signature,
+        role: Option<MethodRole>,
+        self_ty: Option<&Ident>,
+        exports: &mut Exports,
+    ) -> Self {
+        let name = sig.ident.to_string();
+
+        let mut receiver = None;
+        let mut args = vec![];
+        for arg in &sig.inputs {
+            match arg {
+                FnArg::Receiver(Receiver {
+                    reference,
+                    mutability,
+                    ..
+                }) => {
+                    receiver = Some(match (reference, mutability) {
+                        (Some(_), Some(_)) => "&mut self",
+                        (Some(_), None) => "&self",
+                        (None, _) => "self",
+                    })
+                }
+                FnArg::Typed(arg) => {
+                    let mut arg = arg.clone();
+                    // an invalid default is reported by the language
+                    let default = take_default(&mut arg)
+                        .ok()
+                        .flatten()
+                        .map(|e| e.to_token_stream().to_string());
+                    let PatType { pat, ty, .. } = arg;
+
+                    let name = match &*pat {
+                        Pat::Ident(PatIdent { ident, .. }) => ident.to_string(),
+                        other => other.to_token_stream().to_string(),
+                    };
+
+                    args.push(Argument {
+                        name,
+                        ty: *ty,
+                        default,
+                    });
+                }
+            }
+        }
+
+        let ty = sig.output.as_type();
+        let error = error_type(&ty).map(|error| {
+            let converted = match L::convert_output(ty.clone()) {
+                Ok(Output::Result { err, .. }) => Some(*err),
+                _ => None,
+            };
+
+            (error, converted)
+        });
+        let iterator = iterator_item(&ty).map(|item| IteratorItem {
+            item,
+            kind: L::iterator_kind(),
+            next: exports.take(&name, ExportKind::CursorNext, self_ty),
+            destroy: exports.take(&name, ExportKind::CursorDestroy, self_ty),
+        });
+
+        Function {
+            export: exports.take(&name, ExportKind::Function, self_ty),
+            name,
+            role,
+            receiver,
+            is_async: sig.asyncness.is_some(),
+            args,
+            output: Return {
+                ty,
+                error,
+                iterator,
+            },
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let mut fields = vec![("name", Json::string(&self.name))];
+        if let Some(role) = self.role {
+            fields.push(("role", Json::string(role_name(role))));
+            fields.push(("receiver", self.receiver.map(Json::string).into()));
+        }
+        fields.push(("async", Json::Bool(self.is_async)));
+        fields.push((
+            "args",
+            Json::Array(self.args.iter().map(Argument::to_json).collect()),
+        ));
+        fields.push(("output", self.output.to_json()));
+        fields.push(("export", self.export.as_ref().map(Export::to_json).into()));
+
+        Json::Object(fields)
+    }
+}
+
+pub struct Struct {
+    name: String,
+    /// `None` for impls of structs that are not exposed in the same module
+    opaque: Option<bool>,
+    shared: Option<bool>,
+    fields: Vec<(String, Type)>,
+    methods: Vec<Function>,
+}
+
+impl Struct {
+    pub fn new(structure: &ItemStruct, opts: &[ExposeStructOpts]) -> Self {
+        let opaque = opts.contains(&ExposeStructOpts::Opaque);
+        let fields = match (&structure.fields, opaque) {
+            (Fields::Named(fields), false) => fields
+                .named
+                .iter()
+                .filter_map(|f| Some((f.ident.as_ref()?.to_string(), f.ty.clone())))
+                .collect(),
+            _ => vec![],
+        };
+
+        Struct {
+            name: structure.ident.to_string(),
+            opaque: Some(opaque),
+            shared: Some(opts.contains(&ExposeStructOpts::Shared)),
+            fields,
+            methods: vec![],
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(name, ty)| {
+                Json::Object(vec![
+                    ("name", Json::string(name)),
+                    ("type", Json::string(type_name(ty))),
+                ])
+            })
+            .collect();
+
+        Json::Object(vec![
+            ("name", Json::string(&self.name)),
+            ("opaque", self.opaque.map(Json::Bool).into()),
+            ("shared", self.shared.map(Json::Bool).into()),
+            ("fields", Json::Array(fields)),
+            (
+                "methods",
+                Json::Array(self.methods.iter().map(Function::to_json).collect()),
+            ),
+        ])
+    }
+}
+
+#[derive(Default)]
+pub struct Module {
+    name: String,
+    functions: Vec<Function>,
+    structs: Vec<Struct>,
+    modules: Vec<Module>,
+}
+
+impl Module {
+    pub fn new(ident: &Ident) -> Self {
+        Module {
+            name: ident.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_function(&mut self, function: Function) {
+        self.functions.push(function);
+    }
+
+    pub fn add_struct(&mut self, structure: Struct) {
+        self.structs.push(structure);
+    }
+
+    pub fn add_module(&mut self, module: Module) {
+        self.modules.push(module);
+    }
+
+    /// Describe the methods of an `#[expose_impl]`, as written before the language transformed
+    /// them, along with the functions it exported for them
+    pub fn add_impl<L: Lang>(&mut self, implementation: &ItemImpl, mut exports: Exports) {
+        let self_ty = match last_segment(&implementation.self_ty) {
+            Some(name) => name.clone(),
+            None => return,
+        };
+        let name = self_ty.to_string();
+
+        let methods = implementation.items.iter().filter_map(|item| match item {
+            ImplItem::Method(method) => Some(Function::new::<L>(
+                &method.sig,
+                Some(MethodRole::take_from(&mut method.attrs.clone())),
+                Some(&self_ty),
+                &mut exports,
+            )),
+            _ => None,
+        });
+
+        match self.structs.iter_mut().find(|s| s.name == name) {
+            Some(structure) => structure.methods.extend(methods),
+            None => self.structs.push(Struct {
+                name,
+                opaque: None,
+                shared: None,
+                fields: vec![],
+                methods: methods.collect(),
+            }),
+        }
+    }
+
+    fn functions(&self) -> Box<dyn Iterator<Item = &Function> + '_> {
+        Box::new(
+            self.functions
+                .iter()
+                .chain(self.structs.iter().flat_map(|s| s.methods.iter()))
+                .chain(self.modules.iter().flat_map(Module::functions)),
+        )
+    }
+
+    /// Every error type returned in the module and its submodules, with its converted type
+    fn errors(&self) -> Vec<&(Type, Option<Type>)> {
+        let mut errors: Vec<&(Type, Option<Type>)> = vec![];
+        for error in self.functions().filter_map(|f| f.output.error.as_ref()) {
+            if !errors.iter().any(|(ty, _)| *ty == error.0) {
+                errors.push(error);
+            }
+        }
+
+        errors
+    }
+
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("name", Json::string(&self.name)),
+            (
+                "functions",
+                Json::Array(self.functions.iter().map(Function::to_json).collect()),
+            ),
+            (
+                "structs",
+                Json::Array(self.structs.iter().map(Struct::to_json).collect()),
+            ),
+            (
+                "modules",
+                Json::Array(self.modules.iter().map(Module::to_json).collect()),
+            ),
+        ])
+    }
+
+    /// Write the description of a root module to `idl/<language>/<module>.json`
+    pub fn write<L: Lang>(&self) -> std::io::Result<()> {
+        let language = L::namespace().to_string();
+        let language = language.trim_start_matches('_');
+
+        let errors = self
+            .errors()
+            .into_iter()
+            .map(|(ty, converted)| {
+                Json::Object(vec![
+                    ("type", Json::string(type_name(ty))),
+                    (
+                        "converted",
+                        converted.as_ref().map(|ty| Json::types(Some(ty))).into(),
+                    ),
+                ])
+            })
+            .collect();
+        let root = Json::Object(vec![
+            ("language", Json::string(language)),
+            ("errors", Json::Array(errors)),
+            ("module", self.to_json()),
+        ]);
+
+        let mut content = String::new();
+        root.write(&mut content, 0)
+            .expect("Writing to a String can't fail");
+        content.push('\n');
+
+        write_generated(
+            &format!("{}/{}", IDL_DIR, language),
+            &format!("{}.json", self.name),
+            &content,
+        )
+    }
+}
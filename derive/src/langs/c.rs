@@ -1,38 +1,427 @@
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryInto;
 use std::fmt;
 
-use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::{format_ident, quote, TokenStreamExt};
+use quote::{format_ident, quote, ToTokens};
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{
-    parse_quote, BareFnArg, FnArg, Ident, ImplItem, ImplItemMethod, ItemFn, ItemStruct, ItemTrait,
-    Pat, PatIdent, PatType, Token, TypeBareFn, TypePath,
+    parse_quote, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemFn, ItemStruct, Pat, PatIdent,
+    PatType, Receiver, ReturnType, Token, TypeBareFn, TypePath, TypePtr, TypeReference, TypeSlice,
 };
 
 use super::*;
-use crate::types::*;
+use crate::idl;
+
+#[cfg(feature = "cpp")]
+mod cpp;
+#[cfg(feature = "csharp")]
+mod csharp;
+#[cfg(feature = "dart")]
+mod dart;
+#[cfg(feature = "go")]
+mod go;
+#[cfg(feature = "ruby")]
+mod ruby;
+#[cfg(feature = "swift")]
+mod swift;
+#[cfg(feature = "c-wrapper")]
+mod wrapper;
+
+/// Directory of the Dart library, relative to the root of the crate
+#[cfg(feature = "dart")]
+const DART_DIR: &str = "dart/lib";
+
+/// Directory of the Go module, relative to the root of the crate
+#[cfg(feature = "go")]
+const GO_DIR: &str = "go";
+
+/// Directory of the Ruby sources, relative to the root of the crate
+#[cfg(feature = "ruby")]
+const RUBY_DIR: &str = "ruby/lib";
+
+/// Directory of the C# library, relative to the root of the crate
+#[cfg(feature = "csharp")]
+const CSHARP_DIR: &str = "dotnet/Bdk";
+
+/// Directory of the C++ header, relative to the root of the crate
+#[cfg(feature = "cpp")]
+const CPP_DIR: &str = "cpp";
+
+/// Directory of the Swift package, relative to the root of the crate
+#[cfg(feature = "swift")]
+const SWIFT_DIR: &str = "swift/Sources";
 
 #[derive(Debug)]
 pub struct C;
 
-impl Lang for C {
-    type Error = CError;
+impl C {
+    /// Expose an `async fn` as a function that returns immediately, running the future on the
+    /// library runtime and then calling `__callback` with `__user_data`
+    ///
+    /// The callback receives the output in the same form a blocking function would return it: the
+    /// return value first, if any, followed by what would be written to the out-params. The future
+    /// works on copies of the borrowed arguments, so the caller can release them once the function
+    /// returns.
+    fn expose_async_fn(function: &mut ItemFn) -> Result<(), CError> {
+        let ident = &function.sig.ident;
+        let output = function.sig.output.as_type();
+
+        let (mut args, input_conversion) = Self::convert_fn_args(function.sig.inputs.clone())?;
+        let (copies, borrows) = copy_borrowed_args(&function.sig.inputs)?;
+
+        let ExpandedReturn {
+            ret,
+            extra_args,
+            conv: output_conversion,
+        } = Return(function.sig.output.clone()).expand(
+            &format_ident!("__output"),
+            &format_ident!("__ptr_out"),
+            Self::convert_output,
+        )?;
+
+        let mut callback_types = vec![];
+        let mut callback_args = vec![];
+        if let ReturnType::Type(_, ty) = &ret {
+            callback_types.push((**ty).clone());
+            callback_args.push(format_ident!("__ret"));
+        }
+        // the out-params point to local values, which are then passed to the callback
+        let mut out_values = TokenStream2::default();
+        for arg in &extra_args {
+            if let FnArg::Typed(PatType { pat, ty, .. }) = arg {
+                if let (Pat::Ident(PatIdent { ident, .. }), Type::Ptr(TypePtr { elem, .. })) =
+                    (pat.as_ref(), ty.as_ref())
+                {
+                    let value = format_ident!("{}_value", ident);
+                    out_values.extend(quote! {
+                        let mut #value: #elem = unsafe { std::mem::zeroed() };
+                        let #ident: *mut #elem = &mut #value;
+                    });
+                    callback_types.push((**elem).clone());
+                    callback_args.push(value);
+                }
+            }
+        }
+        args.push(parse_quote!(
+            __callback: extern "C" fn(*mut libc::c_void, #(#callback_types),*)
+        ));
+        args.push(parse_quote!(__user_data: *mut libc::c_void));
+
+        let block = rename_self(function.block.to_token_stream(), &format_ident!("__self"));
+
+        *function = parse_quote! {
+            #[no_mangle]
+            #[allow(non_snake_case, clippy::not_unsafe_ptr_arg_deref)]
+            pub extern "C" fn #ident(#args) {
+                use crate::mapping::{MapFrom, MapTo};
+                use crate::langs::*;
+
+                #input_conversion
+                #copies
+
+                let __future = async move {
+                    #borrows
+                    let __output: #output = async move #block.await;
+                    __output
+                };
+                let __user_data = UserData(__user_data);
+
+                crate::runtime::spawn(async move {
+                    let __output = __future.await;
+
+                    #out_values
+                    let __ret = (move || { #output_conversion })();
+                    __callback(__user_data.0, #(#callback_args),*);
+                });
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Lock the shared objects borrowed by `function`, which receives them as pointers instead
+    ///
+    /// `self_ty` is the type of the impl the function comes from, if any. The lock is held for the
+    /// whole call, so async functions can't borrow shared objects.
+    #[cfg(not(feature = "c-handles"))]
+    fn lock_shared_args(function: &mut ItemFn, self_ty: Option<&Type>) -> Result<(), CError> {
+        let mut block = function.block.to_token_stream();
+        let mut locks = TokenStream2::default();
+
+        for input in function.sig.inputs.iter_mut() {
+            let (ident, elem, mutable, optional) = match input {
+                FnArg::Receiver(Receiver {
+                    reference: Some(_),
+                    mutability,
+                    ..
+                }) if self_ty.is_some_and(is_shared) => {
+                    let ident = format_ident!("__self");
+                    block = rename_self(block, &ident);
+
+                    (ident, parse_quote!(Self), mutability.is_some(), false)
+                }
+                FnArg::Typed(PatType { pat, ty, .. }) => match (pat.as_ref(), borrowed_arg(ty)) {
+                    (Pat::Ident(PatIdent { ident, .. }), Some((reference, optional))) => {
+                        let resolved = match (last_segment(&reference.elem), self_ty) {
+                            (Some(s), Some(self_ty)) if s == "Self" => self_ty,
+                            _ => reference.elem.as_ref(),
+                        };
+                        if !is_shared(resolved) {
+                            continue;
+                        }
+
+                        (
+                            ident.clone(),
+                            (*reference.elem).clone(),
+                            reference.mutability.is_some(),
+                            optional,
+                        )
+                    }
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if function.sig.asyncness.is_some() {
+                return Err(CError::SharedAsync(input.span()));
+            }
+
+            let guard = format_ident!("{}_guard", ident);
+            match (mutable, optional) {
+                (true, false) => {
+                    *input = parse_quote!(#ident: *mut #elem);
+                    locks.extend(quote! {
+                        let mut #guard = unsafe { crate::langs::shared::lock(#ident) };
+                        let #ident: &mut #elem = &mut *#guard;
+                    });
+                }
+                (false, false) => {
+                    *input = parse_quote!(#ident: *const #elem);
+                    locks.extend(quote! {
+                        let #guard = unsafe { crate::langs::shared::lock(#ident) };
+                        let #ident: &#elem = &*#guard;
+                    });
+                }
+                // `NULL` stands for `None`
+                (true, true) => {
+                    *input = parse_quote!(#ident: *mut #elem);
+                    locks.extend(quote! {
+                        let mut #guard = match #ident.is_null() {
+                            true => None,
+                            false => Some(unsafe { crate::langs::shared::lock(#ident) }),
+                        };
+                        let #ident: Option<&mut #elem> = #guard.as_deref_mut();
+                    });
+                }
+                (false, true) => {
+                    *input = parse_quote!(#ident: *const #elem);
+                    locks.extend(quote! {
+                        let #guard = match #ident.is_null() {
+                            true => None,
+                            false => Some(unsafe { crate::langs::shared::lock(#ident) }),
+                        };
+                        let #ident: Option<&#elem> = #guard.as_deref();
+                    });
+                }
+            }
+        }
+
+        if !locks.is_empty() {
+            function.block = parse_quote!({
+                #locks
+
+                let __output = #block;
+                __output
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the handles of the opaque objects used by `function`, which receives them as integers
+    /// instead, returning an error code for the handles that are not valid
+    ///
+    /// `self_ty` is the type of the impl the function comes from, if any. Objects taken by value
+    /// are moved out of the registry, unless other handles still refer to them, and the ones passed
+    /// to destructors are only released. Functions that don't return a `Result`
+    /// are changed to return one with a `HandleError`, otherwise their error type must implement
+    /// `From<HandleError>`. Async functions receive copies of the objects they borrow, which must
+    /// be `Clone`.
+    #[cfg(feature = "c-handles")]
+    fn lookup_handles(function: &mut ItemFn, self_ty: Option<&Type>) -> Result<(), CError> {
+        let resolve = |ty: &Type| -> Option<Type> {
+            let resolved = match (last_segment(ty), self_ty) {
+                (Some(s), Some(self_ty)) if s == "Self" => self_ty,
+                _ => ty,
+            };
+            match struct_kind(resolved) {
+                Some(StructKind::Opaque) | Some(StructKind::Shared) => Some(ty.clone()),
+                _ => None,
+            }
+        };
+
+        let destructor = function.attrs.iter().any(|a| a.path.is_ident("destructor"));
+        let mut block = function.block.to_token_stream();
+        let mut lookups = TokenStream2::default();
+
+        for input in function.sig.inputs.iter_mut() {
+            let (ident, elem, mutable, optional) = match input {
+                FnArg::Receiver(Receiver {
+                    reference: Some(_),
+                    mutability,
+                    ..
+                }) if self_ty.and_then(resolve).is_some() => {
+                    let ident = format_ident!("__self");
+                    block = rename_self(block, &ident);
+
+                    (ident, parse_quote!(Self), mutability.is_some(), false)
+                }
+                FnArg::Typed(PatType { pat, ty, .. }) => match (pat.as_ref(), borrowed_arg(ty)) {
+                    (Pat::Ident(PatIdent { ident, .. }), Some((reference, optional))) => {
+                        match resolve(&reference.elem) {
+                            Some(elem) => (
+                                ident.clone(),
+                                elem,
+                                reference.mutability.is_some(),
+                                optional,
+                            ),
+                            None => continue,
+                        }
+                    }
+                    (Pat::Ident(PatIdent { ident, .. }), None) => match resolve(ty) {
+                        Some(elem) => {
+                            let ident = ident.clone();
+                            lookups.extend(match destructor {
+                                true => quote! {
+                                    let #ident = crate::registry::remove::<#elem>(#ident)?;
+                                },
+                                false => quote! {
+                                    let #ident = crate::registry::take::<#elem>(#ident)?;
+                                },
+                            });
+                            *input = parse_quote!(#ident: u64);
+
+                            continue;
+                        }
+                        None => continue,
+                    },
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let entry = format_ident!("{}_entry", ident);
+            let guard = format_ident!("{}_guard", ident);
+            let asyncness = function.sig.asyncness.is_some();
+            if asyncness && mutable {
+                return Err(CError::HandleAsync(input.span()));
+            }
+
+            *input = parse_quote!(#ident: u64);
+            lookups.extend(match (mutable, optional) {
+                // the future works on a copy, the object is only locked while copying it
+                (false, false) if asyncness => quote! {
+                    let #ident: #elem = {
+                        let #entry = crate::registry::get::<#elem>(#ident)?;
+                        let #guard = #entry.lock().expect("Poisoned object");
+                        std::clone::Clone::clone(&*#guard)
+                    };
+                    let #ident = &#ident;
+                },
+                (false, true) if asyncness => quote! {
+                    let #ident: Option<#elem> = match #ident {
+                        0 => None,
+                        handle => {
+                            let #entry = crate::registry::get::<#elem>(handle)?;
+                            let #guard = #entry.lock().expect("Poisoned object");
+                            Some(std::clone::Clone::clone(&*#guard))
+                        }
+                    };
+                    let #ident = #ident.as_ref();
+                },
+                (true, false) => quote! {
+                    let #entry = crate::registry::get::<#elem>(#ident)?;
+                    let mut #guard = #entry.lock().expect("Poisoned object");
+                    let #ident: &mut #elem = &mut *#guard;
+                },
+                (false, false) => quote! {
+                    let #entry = crate::registry::get::<#elem>(#ident)?;
+                    let #guard = #entry.lock().expect("Poisoned object");
+                    let #ident: &#elem = &*#guard;
+                },
+                // `0` stands for `None`
+                (true, true) => quote! {
+                    let #entry = match #ident {
+                        0 => None,
+                        handle => Some(crate::registry::get::<#elem>(handle)?),
+                    };
+                    let mut #guard = #entry.as_ref().map(|e| e.lock().expect("Poisoned object"));
+                    let #ident: Option<&mut #elem> = #guard.as_deref_mut();
+                },
+                (false, true) => quote! {
+                    let #entry = match #ident {
+                        0 => None,
+                        handle => Some(crate::registry::get::<#elem>(handle)?),
+                    };
+                    let #guard = #entry.as_ref().map(|e| e.lock().expect("Poisoned object"));
+                    let #ident: Option<&#elem> = #guard.as_deref();
+                },
+            });
+        }
+
+        if lookups.is_empty() {
+            return Ok(());
+        }
+        let output = function.sig.output.as_type();
+        function.block = match match_generic_type(&output, parse_quote!(Result)) {
+            Some(_) => parse_quote!({
+                #lookups
+
+                let __output = #block;
+                __output
+            }),
+            None => {
+                function.sig.output =
+                    parse_quote!(-> Result<#output, crate::registry::HandleError>);
+                // the closure keeps the early returns of the body, which aren't wrapped
+                parse_quote!({
+                    #lookups
+
+                    let __output = (|| -> #output #block)();
+                    Ok(__output)
+                })
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Borrow the objects used by `function` through the pointers or handles it receives
+    fn borrow_args(function: &mut ItemFn, self_ty: Option<&Type>) -> Result<(), CError> {
+        #[cfg(not(feature = "c-handles"))]
+        return Self::lock_shared_args(function, self_ty);
+        #[cfg(feature = "c-handles")]
+        return Self::lookup_handles(function, self_ty);
+    }
+
+    /// Replace `function` with the `extern "C"` function that exports it, under the same name
+    fn extern_fn(function: &mut ItemFn) -> Result<(), CError> {
+        // done while the destructors still have their attribute, `lookup_handles` only releases
+        // their handles
+        Self::borrow_args(function, None)?;
 
-    fn expose_fn(function: &mut ItemFn, mod_path: &Vec<Ident>) -> Result<Ident, Self::Error> {
         if let Some(pos) = function
             .attrs
             .iter()
             .position(|a| a.path.is_ident("destructor"))
         {
-            // replace the type with `Destroy<T>`
             function.attrs.remove(pos);
 
+            // replace the type with `Destroy<T>`, the handles are released by `lookup_handles`
+            #[cfg(not(feature = "c-handles"))]
             for input in &mut function.sig.inputs {
                 match input {
                     FnArg::Typed(PatType { ty, .. }) => {
-                        *ty = Box::new(parse_quote!( Destroy<#ty> ));
+                        **ty = parse_quote!( Destroy<#ty> );
                     }
                     FnArg::Receiver(_) => {
                         return Err(CError::DestructorReceiverArgument(input.span()));
@@ -41,6 +430,10 @@ impl Lang for C {
             }
         }
 
+        if function.sig.asyncness.is_some() {
+            return Self::expose_async_fn(function);
+        }
+
         let ident = &function.sig.ident;
 
         let (mut args, input_conversion) = Self::convert_fn_args(function.sig.inputs.clone())?;
@@ -60,94 +453,538 @@ impl Lang for C {
 
         *function = parse_quote! {
             #[no_mangle]
-            #[allow(non_snake_case)]
+            #[allow(non_snake_case, clippy::not_unsafe_ptr_arg_deref)]
             pub extern "C" fn #ident(#args) #ret {
                 use crate::mapping::{MapFrom, MapTo};
                 use crate::langs::*;
 
                 #input_conversion
 
-                let block_closure = move || { #block };
+                // mutable when the block changes a `&mut` argument, like setters do, and the code
+                // wrapped around the block is unreachable when it never ends, like a loop that
+                // only returns from inside
+                #[allow(unused_mut, unreachable_code)]
+                let mut block_closure = move || { #block };
                 let __output = block_closure();
                 #output_conversion
             }
         };
 
+        Ok(())
+    }
+
+    /// Functions of the cursor returned by `symbol`, the export of `method`: `<symbol>_next`
+    /// writes the next item to its out-param, or returns `CURSOR_END` when there are none left,
+    /// and `<symbol>_destroy` releases the cursor
+    fn cursor_fns(method: &Ident, symbol: &Ident, item: &Type) -> Result<Vec<ImplItem>, CError> {
+        let next = format_ident!("{}_next", symbol);
+        let destroy = format_ident!("{}_destroy", symbol);
+
+        #[cfg(not(feature = "c-handles"))]
+        let (mut next_fn, destroy_fn): (ItemFn, ItemFn) = (
+            parse_quote! {
+                pub fn #next(
+                    cursor: *mut crate::langs::Cursor<#item>,
+                ) -> Result<#item, crate::langs::CursorEnd> {
+                    // a `NULL` cursor has no items
+                    match unsafe { cursor.as_mut() } {
+                        Some(cursor) => cursor.next_item(),
+                        None => Err(crate::langs::CursorEnd),
+                    }
+                }
+            },
+            parse_quote! {
+                #[no_mangle]
+                #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                pub extern "C" fn #destroy(cursor: *mut crate::langs::Cursor<#item>) {
+                    unsafe { crate::langs::Cursor::release(cursor) }
+                }
+            },
+        );
+        // the cursors are in the registry too, the handles are checked like the ones of objects
+        #[cfg(feature = "c-handles")]
+        let (mut next_fn, destroy_fn): (ItemFn, ItemFn) = {
+            let mut destroy_fn = parse_quote! {
+                pub fn #destroy(cursor: u64) -> Result<(), crate::registry::HandleError> {
+                    crate::registry::remove::<crate::langs::Cursor<#item>>(cursor)?;
+                    Ok(())
+                }
+            };
+            Self::extern_fn(&mut destroy_fn)?;
+
+            let next_fn = parse_quote! {
+                pub fn #next(cursor: u64) -> Result<#item, crate::langs::CursorError> {
+                    let entry = crate::registry::get::<crate::langs::Cursor<#item>>(cursor)?;
+                    let mut cursor = entry.lock().expect("Poisoned object");
+                    Ok(cursor.next_item()?)
+                }
+            };
+            (next_fn, destroy_fn)
+        };
+        Self::extern_fn(&mut next_fn)?;
+        idl::record_cursor(method, &next_fn.sig, &destroy_fn.sig);
+
+        Ok(vec![next_fn, destroy_fn]
+            .into_iter()
+            .map(
+                |ItemFn {
+                     attrs,
+                     vis,
+                     sig,
+                     block,
+                 }| {
+                    ImplItem::Method(ImplItemMethod {
+                        attrs,
+                        vis,
+                        defaultness: None,
+                        sig,
+                        block: *block,
+                    })
+                },
+            )
+            .collect())
+    }
+
+    /// Items added next to an exposed struct: its `Handle` implementation, and the check that it
+    /// can be sent to other threads when it's shared
+    fn handle_items(ident: &Ident) -> Vec<Item> {
+        let mut items = vec![Self::handle_impl(ident)];
+        if is_shared(&parse_quote!(#ident)) {
+            items.push(assert_send(ident));
+        }
+
+        items
+    }
+
+    /// Implementation of `Handle` for an exposed struct
+    #[cfg(not(feature = "c-handles"))]
+    fn handle_impl(ident: &Ident) -> Item {
+        let (into_handle, release_handle, take_handle) = match is_shared(&parse_quote!(#ident)) {
+            true => (
+                quote!(crate::langs::shared::into_handle(self)),
+                quote!(crate::langs::shared::release_handle(ptr)),
+                // rejected by `convert_input`, other handles could still use the object
+                quote!(unreachable!("Shared objects can't be taken by value")),
+            ),
+            false => (
+                quote!(Box::into_raw(Box::new(self))),
+                quote!(drop(Box::from_raw(ptr))),
+                quote!(*Box::from_raw(ptr)),
+            ),
+        };
+
+        parse_quote! {
+            impl crate::langs::Handle for #ident {
+                fn into_handle(self) -> *mut Self {
+                    #into_handle
+                }
+
+                unsafe fn release_handle(ptr: *mut Self) {
+                    #release_handle
+                }
+
+                unsafe fn take_handle(ptr: *mut Self) -> Self {
+                    #take_handle
+                }
+            }
+        }
+    }
+
+    /// Implementation of `Handle` for an exposed struct, stored in the registry
+    #[cfg(feature = "c-handles")]
+    fn handle_impl(ident: &Ident) -> Item {
+        parse_quote! {
+            impl crate::langs::Handle for #ident {}
+        }
+    }
+}
+
+/// Output of the functions returning an opaque object of type `ty`, which is moved behind a
+/// pointer, or to the registry with the `c-handles` feature
+fn handle_output(ty: Type) -> Output {
+    #[cfg(not(feature = "c-handles"))]
+    return Output::ByReference(Box::new(parse_quote!(*mut #ty)));
+    #[cfg(feature = "c-handles")]
+    return Output::new_map_to_single(ty, parse_quote!(u64));
+}
+
+/// Whether a function takes an `Option` or an argument with a `#[default(...)]`
+#[cfg(feature = "c-wrapper")]
+fn has_optional_args(sig: &syn::Signature) -> bool {
+    sig.inputs.iter().any(|input| match input {
+        FnArg::Typed(PatType { attrs, ty, .. }) => {
+            attrs.iter().any(|a| a.path.is_ident("default"))
+                || match_generic_type(ty, parse_quote!(Option)).is_some()
+        }
+        FnArg::Receiver(_) => false,
+    })
+}
+
+/// Element of a `&[T]` argument
+fn slice_elem(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Reference(TypeReference {
+            elem,
+            mutability: None,
+            ..
+        }) => match elem.as_ref() {
+            Type::Slice(TypeSlice { elem, .. }) => Some(elem),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Types that have the same layout in Rust and C, so that slices of them can be borrowed in place
+fn is_scalar(ty: &Type) -> bool {
+    matches!(
+        ty.to_token_stream().to_string().as_str(),
+        "bool"
+            | "i8"
+            | "i16"
+            | "i32"
+            | "i64"
+            | "isize"
+            | "u8"
+            | "u16"
+            | "u32"
+            | "u64"
+            | "usize"
+            | "f32"
+            | "f64"
+    )
+}
+
+impl Lang for C {
+    type Error = CError;
+
+    fn namespace() -> Ident {
+        format_ident!("__c")
+    }
+
+    fn expose_fn(function: &mut ItemFn, _mod_path: &[Ident]) -> Result<Ident, Self::Error> {
+        let ident = function.sig.ident.clone();
+        Self::extern_fn(function)?;
+        idl::record_export(&ident, &function.sig.ident, &function.sig);
+
         Ok(function.sig.ident.clone())
     }
 
+    // the path is only used to write the files of the wrapping languages
+    #[cfg_attr(
+        not(any(
+            feature = "swift",
+            feature = "cpp",
+            feature = "csharp",
+            feature = "ruby",
+            feature = "dart"
+        )),
+        allow(unused_variables)
+    )]
     fn expose_mod(
         module: &mut ItemMod,
-        mod_path: &Vec<Ident>,
+        mod_path: &[Ident],
         sub_items: Vec<ModuleItem>,
     ) -> Result<Ident, Self::Error> {
         module.vis = parse_quote!(pub);
 
+        let content = &mut module.content.as_mut().expect("Empty module").1;
+        for sub_item in &sub_items {
+            if let ModuleItem::Structure(ident) = sub_item {
+                content.extend(Self::handle_items(ident));
+            }
+        }
+
+        #[cfg(feature = "swift")]
+        if mod_path.len() == 1 {
+            write_generated(
+                &format!("{}/{}", SWIFT_DIR, swift::FFI_MODULE),
+                "module.modulemap",
+                &swift::module_map("../../../c/bindings.h"),
+            )
+            .map_err(CError::Swift)?;
+        }
+
+        #[cfg(feature = "cpp")]
+        if mod_path.len() == 1 {
+            write_generated(CPP_DIR, "bdk.hpp", &cpp::header()).map_err(CError::Cpp)?;
+        }
+
+        #[cfg(feature = "csharp")]
+        if mod_path.len() == 1 {
+            write_generated(CSHARP_DIR, "Bdk.cs", &csharp::source()).map_err(CError::CSharp)?;
+        }
+
+        #[cfg(feature = "ruby")]
+        if mod_path.len() == 1 {
+            write_generated(RUBY_DIR, "bdk.rb", &ruby::entry_point()).map_err(CError::Ruby)?;
+        }
+
+        #[cfg(feature = "dart")]
+        if mod_path.len() == 1 {
+            write_generated(DART_DIR, "bdk.dart", &dart::library()).map_err(CError::Dart)?;
+        }
+
         Ok(module.ident.clone())
     }
 
     fn expose_struct(
         structure: &mut ItemStruct,
         opts: Punctuated<ExposeStructOpts, Token![,]>,
-        mod_path: &Vec<Ident>,
+        _mod_path: &[Ident],
     ) -> Result<Ident, Self::Error> {
-        if opts
-            .iter()
-            .find(|o| **o == ExposeStructOpts::Opaque)
-            .is_none()
-        {
+        let ident = &structure.ident;
+        register_struct(ident, &opts);
+        if struct_kind(&parse_quote!(#ident)) == Some(StructKind::Plain) {
             structure.attrs.push(parse_quote!(#[repr(C)]));
         }
 
         Ok(structure.ident.clone())
     }
 
-    fn expose_impl(
-        implementation: &mut ItemImpl,
-        mod_path: &Vec<Ident>,
-    ) -> Result<(), Self::Error> {
+    fn iterator_kind() -> &'static str {
+        "cursor"
+    }
+
+    fn struct_items(structure: &ItemStruct, _mod_path: &[Ident]) -> Vec<Item> {
+        Self::handle_items(&structure.ident)
+    }
+
+    #[cfg_attr(
+        not(any(feature = "cpp", feature = "csharp", feature = "ruby", feature = "go")),
+        allow(unused_variables)
+    )]
+    fn expose_impl(implementation: &mut ItemImpl, mod_path: &[Ident]) -> Result<(), Self::Error> {
+        #[cfg(feature = "c-wrapper")]
+        let mut wrapped = vec![];
+        #[cfg(feature = "c-wrapper")]
+        let mut skipped = vec![];
+
+        let prefix = match implementation.self_ty.as_ref() {
+            Type::Path(TypePath { path, .. }) => Some(
+                path.segments
+                    .iter()
+                    .map(|s| s.ident.to_string().to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join("_"),
+            ),
+            _ => None,
+        };
+
+        let mut cursor_items = vec![];
         for item in &mut implementation.items {
-            match item {
-                ImplItem::Method(ImplItemMethod {
-                    sig,
-                    vis,
-                    attrs,
-                    block,
-                    ..
-                }) => {
-                    let mut as_fn = ItemFn {
-                        sig: sig.clone(),
-                        vis: vis.clone(),
-                        attrs: attrs.clone(),
-                        block: Box::new(block.clone()),
+            if let ImplItem::Method(ImplItemMethod {
+                sig,
+                vis,
+                attrs,
+                block,
+                ..
+            }) = item
+            {
+                #[cfg(feature = "c-wrapper")]
+                let original_sig = sig.clone();
+                let accessor = accessor_name(attrs, &sig.ident);
+                let role = MethodRole::take_from(&mut attrs.clone());
+                if let MethodRole::Setter = role {
+                    check_setter(sig)?;
+                }
+
+                let mut as_fn = ItemFn {
+                    sig: sig.clone(),
+                    vis: vis.clone(),
+                    attrs: attrs.clone(),
+                    block: Box::new(block.clone()),
+                };
+                if let Some(prefix) = &prefix {
+                    // Add the struct name as prefix, accessors are named after their property
+                    as_fn.sig.ident = match (&role, accessor) {
+                        (MethodRole::Getter, Some(name)) => {
+                            format_ident!("{}_get_{}", prefix, name)
+                        }
+                        (MethodRole::Setter, Some(name)) => {
+                            format_ident!("{}_set_{}", prefix, name)
+                        }
+                        _ => format_ident!("{}_{}", prefix, as_fn.sig.ident),
                     };
-                    if let Type::Path(TypePath { path, .. }) = implementation.self_ty.as_ref() {
-                        // Add the struct name as prefix
-                        as_fn.sig.ident = format_ident!(
-                            "{}_{}",
-                            path.segments
-                                .iter()
-                                .map(|s| s.ident.to_string().to_lowercase())
-                                .collect::<Vec<_>>()
-                                .join("_"),
-                            as_fn.sig.ident
-                        );
+                }
+                let iter_item = iterator_item(&sig.output.as_type());
+                if let Some(item) = &iter_item {
+                    let block = &as_fn.block;
+                    as_fn.sig.output = parse_quote!(-> crate::langs::Cursor<#item>);
+                    as_fn.block = parse_quote!({ crate::langs::Cursor::new(#block) });
+                }
+                Self::borrow_args(&mut as_fn, Some(&implementation.self_ty))?;
+                Self::extern_fn(&mut as_fn)?;
+                idl::record_export(&sig.ident, &as_fn.sig.ident, &as_fn.sig);
+
+                if let Some(item) = &iter_item {
+                    cursor_items.extend(Self::cursor_fns(&sig.ident, &as_fn.sig.ident, item)?);
+                }
+
+                // the wrappers only cover blocking methods, which don't return a cursor and
+                // don't take optional arguments, the others are listed in the generated files
+                #[cfg(feature = "c-wrapper")]
+                {
+                    let symbol = as_fn.sig.ident.clone();
+                    let reason = if original_sig.asyncness.is_some() {
+                        Some("async")
+                    } else if iter_item.is_some() {
+                        Some("returns a cursor")
+                    } else if has_optional_args(&original_sig) {
+                        Some("takes optional arguments")
+                    } else {
+                        None
+                    };
+                    match reason {
+                        Some(reason) => skipped.push(wrapper::Skipped { symbol, reason }),
+                        None => wrapped.push(wrapper::Method {
+                            role,
+                            sig: original_sig,
+                            symbol,
+                        }),
+                    }
+                }
+
+                *sig = as_fn.sig;
+                *vis = as_fn.vis;
+                *attrs = as_fn.attrs;
+                *block = *as_fn.block;
+            }
+        }
+
+        implementation.items.extend(cursor_items);
+
+        // every clone of a shared object must be released with its destructor
+        if let (Some(prefix), true) = (&prefix, is_shared(&implementation.self_ty)) {
+            let ident = format_ident!("{}_clone_handle", prefix);
+            #[cfg(not(feature = "c-handles"))]
+            implementation.items.push(parse_quote! {
+                #[no_mangle]
+                #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                pub extern "C" fn #ident(handle: *const Self) -> *mut Self {
+                    unsafe { crate::langs::shared::clone_handle(handle) }
+                }
+            });
+            #[cfg(feature = "c-handles")]
+            implementation.items.push(parse_quote! {
+                #[no_mangle]
+                #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                pub extern "C" fn #ident(handle: u64, __ptr_out: *mut u64) -> i32 {
+                    use crate::langs::IntoPlatformError;
+
+                    match crate::registry::clone::<Self>(handle) {
+                        Ok(cloned) => {
+                            unsafe { *__ptr_out = cloned };
+                            crate::registry::HandleError::ok()
+                        }
+                        Err(e) => e.into_platform_error(),
                     }
-                    Self::expose_fn(&mut as_fn, mod_path)?;
+                }
+            });
+        }
 
-                    *sig = as_fn.sig;
-                    *vis = as_fn.vis;
-                    *attrs = as_fn.attrs;
-                    *block = *as_fn.block;
+        // arrays of objects are returned by the functions that return a `Vec`
+        if let (Some(prefix), Some(StructKind::Opaque | StructKind::Shared)) =
+            (&prefix, struct_kind(&implementation.self_ty))
+        {
+            let ident = format_ident!("{}_array_free", prefix);
+            #[cfg(not(feature = "c-handles"))]
+            implementation.items.push(parse_quote! {
+                #[no_mangle]
+                #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                pub extern "C" fn #ident(arr: *mut *mut Self, len: usize) {
+                    unsafe { crate::langs::release_handle_array(arr, len) }
+                }
+            });
+            #[cfg(feature = "c-handles")]
+            implementation.items.push(parse_quote! {
+                #[no_mangle]
+                #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                pub extern "C" fn #ident(arr: *mut u64, len: usize) {
+                    unsafe { crate::langs::release_handle_array::<Self>(arr, len) }
                 }
-                _ => {}
+            });
+        }
+
+        // only opaque structs are owned by a class of the wrapping languages
+        #[cfg(feature = "c-wrapper")]
+        let class = match struct_kind(&implementation.self_ty) {
+            Some(StructKind::Opaque | StructKind::Shared) => last_segment(&implementation.self_ty),
+            _ => None,
+        };
+
+        #[cfg(feature = "swift")]
+        if let Some(class) = class {
+            let dir = format!("{}/Bdk", SWIFT_DIR);
+            write_generated(
+                &dir,
+                &format!("{}.swift", class),
+                &swift::class(class, &wrapped, &skipped),
+            )
+            .map_err(CError::Swift)?;
+
+            for error in wrapper::errors(&wrapped) {
+                let error = swift::error_name(&error);
+                write_generated(&dir, &format!("{}.swift", error), &swift::error(&error))
+                    .map_err(CError::Swift)?;
+            }
+        }
+
+        #[cfg(feature = "cpp")]
+        if let Some(class) = class {
+            cpp::add_class(mod_path, class, &wrapped, &skipped);
+        }
+
+        #[cfg(feature = "csharp")]
+        if let Some(class) = class {
+            csharp::add_class(mod_path, class, &wrapped, &skipped);
+        }
+
+        #[cfg(feature = "dart")]
+        if let Some(class) = class {
+            dart::add_class(class, &wrapped, &skipped);
+        }
+
+        #[cfg(feature = "ruby")]
+        if let Some(class) = class {
+            write_generated(
+                &format!("{}/{}", RUBY_DIR, ruby::dir(mod_path)),
+                &format!("{}.rb", ruby::file_name(&class.to_string())),
+                &ruby::class(mod_path, class, &wrapped, &skipped),
+            )
+            .map_err(CError::Ruby)?;
+
+            for error in wrapper::errors(&wrapped) {
+                let (name, file) = ruby::error_file(&error);
+                write_generated(RUBY_DIR, &file, &ruby::error(&name)).map_err(CError::Ruby)?;
+            }
+        }
+
+        #[cfg(feature = "go")]
+        if let Some(class) = class {
+            let dir = std::iter::once(GO_DIR.to_string())
+                .chain(mod_path.iter().map(Ident::to_string))
+                .collect::<Vec<_>>()
+                .join("/");
+            let write = |name: &str, content: &str| {
+                write_generated(&dir, &go::file_name(name), content).map_err(CError::Go)
+            };
+
+            write(
+                &class.to_string(),
+                &go::class(mod_path, class, &wrapped, &skipped),
+            )?;
+            write("Ffi", &go::support(mod_path))?;
+            for error in wrapper::errors(&wrapped) {
+                let error = go::error_name(&error);
+                write(&error, &go::error(mod_path, &error))?;
             }
         }
 
         Ok(())
     }
 
-    // fn expose_trait(tr: &mut ItemTrait, mod_path: &Vec<Ident>) -> Result<Ident, Self::Error> {
+    // fn expose_trait(tr: &mut ItemTrait, mod_path: &[Ident]) -> Result<Ident, Self::Error> {
     //     let ident = tr.ident.clone();
 
     //     tr.ident = format_ident!("_Trait_{}", tr.ident);
@@ -162,16 +999,33 @@ impl Lang for C {
                 ty,
                 vec![parse_quote!(*const libc::c_char)],
             ))
+        } else if ty == parse_quote!(&str) {
+            // borrowed for the duration of the call
+            Ok(Input::new_map_from(
+                ty,
+                vec![parse_quote!(*const libc::c_char)],
+            ))
+        } else if let Some(elem) = slice_elem(&ty) {
+            // borrowed for the duration of the call
+            if !is_scalar(elem) {
+                return Err(CError::UnsupportedSlice(ty.span()));
+            }
+
+            let elem = elem.clone();
+            Ok(Input::new_map_from(
+                ty,
+                vec![parse_quote!(*const #elem), parse_quote!(usize)],
+            ))
         } else if let Some(inner) = match_generic_type(&ty, parse_quote!(Vec)) {
             let inner = inner
                 .into_iter()
                 .collect::<Punctuated<_, Comma>>()
-                .as_tuple();
+                .into_tuple();
             let inner = Self::convert_input(inner)?;
             let sources = inner
                 .get_sources()
                 .into_iter()
-                .collect::<Punctuated<_, Comma>>(); // TODO: as_tuple() ?
+                .collect::<Punctuated<_, Comma>>(); // TODO: into_tuple() ?
 
             Ok(Input::new_map_from(
                 ty,
@@ -181,17 +1035,35 @@ impl Lang for C {
             let inner = inner
                 .into_iter()
                 .collect::<Punctuated<_, Comma>>()
-                .as_tuple();
+                .into_tuple();
             let inner = Self::convert_input(inner)?;
             let sources = inner
                 .get_sources()
                 .into_iter()
-                .collect::<Punctuated<_, Comma>>(); // TODO: as_tuple() ?
+                .collect::<Punctuated<_, Comma>>(); // TODO: into_tuple() ?
 
             Ok(Input::new_map_from(ty, vec![parse_quote!(*mut #sources)]))
+        } else if let Some(inner) = match_generic_type(&ty, parse_quote!(Option)) {
+            // `None` is passed as `NULL`, primitives are passed by pointer for that reason
+            let inner = inner
+                .into_iter()
+                .collect::<Punctuated<_, Comma>>()
+                .into_tuple();
+            if is_scalar(&inner) {
+                return Ok(Input::new_map_from(ty, vec![parse_quote!(*const #inner)]));
+            }
+
+            match Self::convert_input(inner.clone())? {
+                Input::MapFrom { sources, .. } => Ok(Input::new_map_from(ty, sources)),
+                // borrowed objects are already pointers, which can't be `NULL` otherwise
+                Input::Unchanged(_) if matches!(inner, Type::Reference(_)) => {
+                    Ok(Input::new_unchanged(ty))
+                }
+                _ => Err(CError::UnsupportedOption(ty.span())),
+            }
         } else if let Type::BareFn(ref old_bare_fn) = ty {
             if !old_bare_fn.inputs.iter().all(|arg| arg.name.is_some()) {
-                return Err(CError::UnnamedCallbackArguments(old_bare_fn.span()));
+                return Err(LangError::UnnamedCallbackArguments(old_bare_fn.span()).into());
             }
 
             let mut new_bare_fn: TypeBareFn = parse_quote!(unsafe extern "C" fn());
@@ -244,6 +1116,17 @@ impl Lang for C {
                     ts.into()
                 },
             ))
+        } else if let Some(kind @ (StructKind::Opaque | StructKind::Shared)) = struct_kind(&ty) {
+            // moved out of its pointer, only possible when no other handle can refer to it, the
+            // handles are taken out of the registry by `lookup_handles` instead
+            if kind == StructKind::Shared {
+                return Err(CError::ObjectByValue(ty.span()));
+            }
+
+            Ok(Input::new_map_from(
+                ty.clone(),
+                vec![parse_quote!(*mut #ty)],
+            ))
         } else {
             Ok(Input::new_unchanged(ty))
         }
@@ -251,28 +1134,35 @@ impl Lang for C {
 
     fn convert_output(output: Type) -> Result<Output, Self::Error> {
         if output == parse_quote!(Self) {
-            Ok(Output::ByReference(Box::new(parse_quote!(*mut Self))))
+            Ok(handle_output(parse_quote!(Self)))
+        } else if match_generic_type(&output, parse_quote!(crate::langs::Cursor)).is_some() {
+            #[cfg(not(feature = "c-handles"))]
+            return Ok(Output::new_map_to_single(
+                output.clone(),
+                parse_quote!(*mut #output),
+            ));
+            #[cfg(feature = "c-handles")]
+            return Ok(Output::new_map_to_single(output, parse_quote!(u64)));
         } else if output == parse_quote!(String) {
             Ok(Output::new_map_to_single(
                 output,
                 parse_quote!(*mut libc::c_char),
             ))
-        } else if output == parse_quote!(BitcoinError) {
-            Ok(Output::new_map_to_single(output, parse_quote!(i32)))
-        } else if output == parse_quote!(Script) {
-            Ok(Output::ByReference(Box::new(parse_quote!(*mut Script))))
-        } else if output == parse_quote!(Network) {
-            Ok(Output::ByReference(Box::new(parse_quote!(*mut Network))))
+        } else if output == parse_quote!(Vec<u8>) {
+            Ok(Output::new_map_to_single(
+                output,
+                parse_quote!(crate::langs::ByteBuffer),
+            ))
         } else if let Some(inner) = match_generic_type(&output, parse_quote!(Vec)) {
             let inner = inner
                 .into_iter()
                 .collect::<Punctuated<_, Comma>>()
-                .as_tuple();
+                .into_tuple();
             let inner = Self::convert_output(inner)?;
             let targets = inner
                 .get_targets()
                 .into_iter()
-                .collect::<Punctuated<_, Comma>>(); // TODO: as_tuple() ?
+                .collect::<Punctuated<_, Comma>>(); // TODO: into_tuple() ?
 
             Ok(Output::new_map_to_suffix(
                 output,
@@ -285,13 +1175,9 @@ impl Lang for C {
             let inner = inner
                 .into_iter()
                 .collect::<Punctuated<_, Comma>>()
-                .as_tuple();
+                .into_tuple();
             let inner_output = Self::convert_output(inner.clone())?;
-            let targets = inner_output
-                .get_targets()
-                .into_iter()
-                .map(|t| *t)
-                .as_tuple();
+            let targets = inner_output.get_targets().into_tuple();
 
             Ok(Output::new_option(inner, targets))
         } else if let Some(inner) = match_generic_type(&output, parse_quote!(Result)) {
@@ -300,39 +1186,153 @@ impl Lang for C {
                 .map_err(|_| CError::InvalidResult(output.span()))?;
 
             let ok_type = Self::convert_output(inner[0].clone())?;
-            let ok_targets = ok_type.get_targets().into_iter().map(|t| *t).collect();
-            let err_type = Self::convert_output(inner[1].clone())?;
-            let err_target = err_type
-                .get_targets()
-                .into_iter()
-                .map(|t| *t)
-                .collect::<Punctuated<_, Comma>>()
-                .as_tuple(); // the error must always be a single type
+            let ok_targets = ok_type.get_targets();
 
+            // any error implementing `IntoPlatformError` is returned as an error code
             Ok(Output::new_result(
                 inner[0].clone(),
                 inner[1].clone(),
                 ok_targets,
-                err_target,
+                parse_quote!(i32),
             ))
-        } else {
+        } else if let Some(StructKind::Opaque | StructKind::Shared) = struct_kind(&output) {
+            Ok(handle_output(output))
+        } else if is_scalar(&output)
+            || output == parse_quote!(())
+            || struct_kind(&output) == Some(StructKind::Plain)
+        {
             Ok(Output::new_unchanged(output))
+        } else {
+            Err(CError::UnsupportedType(
+                crate::idl::type_name(&output),
+                output.span(),
+            ))
         }
     }
 }
 
 #[derive(Debug)]
 pub enum CError {
+    #[cfg(feature = "swift")]
+    Swift(std::io::Error),
+    #[cfg(feature = "cpp")]
+    Cpp(std::io::Error),
+    #[cfg(feature = "go")]
+    Go(std::io::Error),
+    #[cfg(feature = "csharp")]
+    CSharp(std::io::Error),
+    #[cfg(feature = "ruby")]
+    Ruby(std::io::Error),
+    #[cfg(feature = "dart")]
+    Dart(std::io::Error),
+
     Lang(LangError),
 
-    UnnamedCallbackArguments(Span),
     DestructorReceiverArgument(Span),
+    /// Async functions can't borrow shared objects, which stay locked during the call
+    SharedAsync(Span),
+    /// Async functions work on copies of the objects behind handles, so they can't borrow them
+    /// mutably
+    #[cfg(feature = "c-handles")]
+    HandleAsync(Span),
     InvalidResult(Span),
+    /// Only slices of primitive types can be borrowed from C, other elements must be passed in a
+    /// `Vec`
+    UnsupportedSlice(Span),
+    /// Shared objects can't be moved out of their pointer, other handles could still use them
+    ObjectByValue(Span),
+    /// Only primitives, strings, bytes and exposed structs can be returned, possibly wrapped in
+    /// an `Option`, a `Result` or a `Vec`
+    UnsupportedType(String, Span),
+    /// Optional arguments must be primitives, strings, slices or objects
+    UnsupportedOption(Span),
+}
+
+impl CError {
+    fn span(&self) -> Span {
+        match self {
+            #[cfg(feature = "swift")]
+            CError::Swift(_) => Span::call_site(),
+            #[cfg(feature = "cpp")]
+            CError::Cpp(_) => Span::call_site(),
+            #[cfg(feature = "go")]
+            CError::Go(_) => Span::call_site(),
+            #[cfg(feature = "csharp")]
+            CError::CSharp(_) => Span::call_site(),
+            #[cfg(feature = "ruby")]
+            CError::Ruby(_) => Span::call_site(),
+            #[cfg(feature = "dart")]
+            CError::Dart(_) => Span::call_site(),
+
+            CError::Lang(e) => e.span(),
+
+            CError::DestructorReceiverArgument(span)
+            | CError::SharedAsync(span)
+            | CError::InvalidResult(span)
+            | CError::UnsupportedSlice(span)
+            | CError::ObjectByValue(span)
+            | CError::UnsupportedType(_, span)
+            | CError::UnsupportedOption(span) => *span,
+            #[cfg(feature = "c-handles")]
+            CError::HandleAsync(span) => *span,
+        }
+    }
 }
 
 impl fmt::Display for CError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            #[cfg(feature = "swift")]
+            CError::Swift(e) => write!(f, "unable to write the Swift wrapper: {}", e),
+            #[cfg(feature = "cpp")]
+            CError::Cpp(e) => write!(f, "unable to write the C++ wrapper: {}", e),
+            #[cfg(feature = "go")]
+            CError::Go(e) => write!(f, "unable to write the Go wrapper: {}", e),
+            #[cfg(feature = "csharp")]
+            CError::CSharp(e) => write!(f, "unable to write the C# wrapper: {}", e),
+            #[cfg(feature = "ruby")]
+            CError::Ruby(e) => write!(f, "unable to write the Ruby wrapper: {}", e),
+            #[cfg(feature = "dart")]
+            CError::Dart(e) => write!(f, "unable to write the Dart wrapper: {}", e),
+
+            CError::Lang(e) => write!(f, "{}", e),
+
+            CError::DestructorReceiverArgument(_) => write!(
+                f,
+                "destructors must take the object as a regular argument, like `fn destroy(_s: \
+                 Self)`"
+            ),
+            CError::SharedAsync(_) => write!(
+                f,
+                "async functions can't borrow shared structs, which would stay locked for the \
+                 whole call"
+            ),
+            #[cfg(feature = "c-handles")]
+            CError::HandleAsync(_) => write!(
+                f,
+                "async functions work on copies of the objects behind handles, so they can't \
+                 borrow them mutably"
+            ),
+            CError::InvalidResult(_) => write!(f, "`Result` must have an ok and an error type"),
+            CError::UnsupportedSlice(_) => write!(
+                f,
+                "only slices of primitive types can be borrowed from C, take a `Vec` instead"
+            ),
+            CError::ObjectByValue(_) => write!(
+                f,
+                "shared objects can't be taken by value, take a reference instead"
+            ),
+            CError::UnsupportedType(ty, _) => write!(
+                f,
+                "`{}` can't be returned to C, only primitives, strings, bytes and structs exposed \
+                 with `#[expose_struct]` are supported",
+                ty
+            ),
+            CError::UnsupportedOption(_) => write!(
+                f,
+                "optional arguments must be primitives, strings, slices or objects"
+            ),
+        }
     }
 }
 
@@ -343,3 +1343,9 @@ impl From<LangError> for CError {
         CError::Lang(e)
     }
 }
+
+impl From<CError> for syn::Error {
+    fn from(e: CError) -> Self {
+        syn::Error::new(e.span(), e)
+    }
+}
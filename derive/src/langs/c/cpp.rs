@@ -0,0 +1,537 @@
+use std::cell::RefCell;
+
+use syn::{parse_quote, FnArg, Ident, Receiver, Signature, Type, TypeReference};
+
+use super::super::{last_segment, MethodRole};
+use super::wrapper::*;
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n\n";
+
+/// Path of the C header written by cbindgen, relative to the C++ header
+const C_HEADER: &str = "../c/bindings.h";
+
+/// Namespace that contains everything, the path of the Rust modules is nested inside it
+const ROOT_NAMESPACE: &str = "bdk";
+
+/// Parts of the header generated for an exposed struct
+struct Class {
+    namespace: String,
+    name: String,
+    declaration: String,
+    definitions: String,
+}
+
+thread_local! {
+    // the header is a single file, so the classes are collected while the impls are expanded and
+    // written out with the root module
+    static CLASSES: RefCell<Vec<Class>> = const { RefCell::new(vec![]) };
+    static ERRORS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// C++ keywords that can be valid Rust identifiers
+const KEYWORDS: &[&str] = &[
+    "asm",
+    "auto",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const_cast",
+    "default",
+    "delete",
+    "double",
+    "explicit",
+    "export",
+    "float",
+    "friend",
+    "goto",
+    "inline",
+    "int",
+    "long",
+    "namespace",
+    "new",
+    "operator",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "short",
+    "signed",
+    "sizeof",
+    "static_cast",
+    "switch",
+    "template",
+    "this",
+    "throw",
+    "try",
+    "typedef",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+];
+
+/// Name of a method or argument, with a trailing underscore if it's a C++ keyword
+fn escape(ident: &str) -> String {
+    match KEYWORDS.contains(&ident) {
+        true => format!("{}_", ident),
+        false => ident.into(),
+    }
+}
+
+fn primitive(ty: &Type) -> Option<&'static str> {
+    let cpp = match last_segment(ty)?.to_string().as_str() {
+        "bool" => "bool",
+        "i8" => "int8_t",
+        "i16" => "int16_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "usize" => "size_t",
+        "isize" => "ptrdiff_t",
+        "f32" => "float",
+        "f64" => "double",
+        _ => return None,
+    };
+
+    Some(cpp)
+}
+
+fn strip_reference(ty: &Type) -> &Type {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => elem,
+        ty => ty,
+    }
+}
+
+/// Name of an opaque struct, which is the same for the C++ class and the C type
+fn opaque_name(ty: &Type, class: &str) -> String {
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") | None => class.into(),
+        Some(other) => other.into(),
+    }
+}
+
+/// C++ type returned to the users of the bindings
+fn cpp_type(ty: &Type, class: &str) -> String {
+    let ty = strip_reference(ty);
+
+    if *ty == parse_quote!(()) {
+        "void".into()
+    } else if let Some(inner) = option_type(ty) {
+        format!("std::optional<{}>", cpp_type(&inner, class))
+    } else if let Some((ok, _)) = result_types(ty) {
+        cpp_type(&ok, class)
+    } else if let Some(primitive) = primitive(ty) {
+        primitive.into()
+    } else if is_string(ty) {
+        "std::string".into()
+    } else if is_bytes(ty) {
+        "std::vector<uint8_t>".into()
+    } else if let Some(elem) = object_vec(ty) {
+        format!("std::vector<{}>", cpp_type(&elem, class))
+    } else {
+        opaque_name(ty, class)
+    }
+}
+
+fn param_type(ty: &Type, class: &str) -> String {
+    match ty {
+        Type::Reference(TypeReference {
+            mutability: Some(_),
+            elem,
+            ..
+        }) if is_opaque(elem) => format!("{} &", cpp_type(elem, class)),
+        ty if primitive(strip_reference(ty)).is_some() => format!("{} ", cpp_type(ty, class)),
+        ty => format!("const {} &", cpp_type(ty, class)),
+    }
+}
+
+fn params(sig: &Signature, class: &str) -> String {
+    typed_args(sig)
+        .into_iter()
+        .map(|(name, ty)| format!("{}{}", param_type(ty, class), escape(&name)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Arguments passed to the C function, not including the out-params
+fn call_args(sig: &Signature) -> Vec<String> {
+    let receiver = sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Receiver(Receiver { .. }) => Some("pointer_".to_string()),
+        FnArg::Typed(_) => None,
+    });
+
+    receiver
+        .chain(typed_args(sig).into_iter().map(|(name, ty)| {
+            let name = escape(&name);
+            let ty = strip_reference(ty);
+            if is_string(ty) {
+                format!("{}.c_str()", name)
+            } else if is_bytes(ty) {
+                format!("{0}.data(), {0}.size()", name)
+            } else if is_opaque(ty) {
+                format!("{}.get()", name)
+            } else {
+                name
+            }
+        }))
+        .collect()
+}
+
+/// Declaration of the variable that receives an out-param
+fn out_var(ty: &Type, class: &str) -> String {
+    let ty = option_type(ty).unwrap_or_else(|| ty.clone());
+
+    if is_string(&ty) {
+        "char *out = nullptr;".into()
+    } else if is_bytes(&ty) {
+        "ffi::ByteBuffer out = {};".into()
+    } else if let Some(elem) = object_vec(&ty) {
+        format!(
+            "ffi::{} **out = nullptr; size_t len = 0;",
+            opaque_name(&elem, class)
+        )
+    } else if let Some(primitive) = primitive(&ty) {
+        format!("{} out = {{}};", primitive)
+    } else {
+        format!("ffi::{} *out = nullptr;", opaque_name(&ty, class))
+    }
+}
+
+/// Expression that takes the ownership of `value`, a C value of type `ty`
+fn take(ty: &Type, class: &str, value: &str) -> String {
+    if is_string(ty) {
+        format!("detail::take_string({})", value)
+    } else if is_bytes(ty) {
+        format!("detail::take_bytes({})", value)
+    } else if let Some(elem) = object_vec(ty) {
+        let name = opaque_name(&elem, class);
+        format!(
+            "detail::take_array<{}>({}, len, ffi::{})",
+            name,
+            value,
+            array_free(&name)
+        )
+    } else if is_opaque(ty) {
+        format!("{}({})", opaque_name(ty, class), value)
+    } else {
+        value.into()
+    }
+}
+
+/// Statements calling the C function
+///
+/// When `out` is set the result is written to that variable instead of being returned, which is
+/// used by constructors to initialize the pointer directly.
+fn body(method: &Method, class: &str, out: Option<&str>) -> Vec<String> {
+    let output = method.sig.output.as_type();
+    let mut args = call_args(&method.sig);
+
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(cpp_type(&error, class))),
+        None => (output, None),
+    };
+    let by_ref = by_reference(&ok) || (error.is_some() && ok != parse_quote!(()));
+
+    let mut lines = vec![];
+    if by_ref {
+        match out {
+            Some(out) => args.push(format!("&{}", out)),
+            None => {
+                lines.push(out_var(&ok, class));
+                args.push("&out".into());
+                if object_vec(&ok).is_some() {
+                    args.push("&len".into());
+                }
+            }
+        }
+    }
+
+    let call = format!("ffi::{}({})", method.symbol, args.join(", "));
+    match &error {
+        Some(error) => {
+            lines.push(format!("int32_t code = {};", call));
+            lines.push(format!("if (code != 0) throw {}(code);", error));
+        }
+        None if by_ref || ok == parse_quote!(()) => lines.push(format!("{};", call)),
+        None => {}
+    }
+
+    if out.is_some() || ok == parse_quote!(()) {
+        return lines;
+    }
+
+    if let Some(inner) = option_type(&ok) {
+        lines.push("if (out == nullptr) return std::nullopt;".into());
+        lines.push(format!("return {};", take(&inner, class, "out")));
+    } else if by_ref {
+        lines.push(format!("return {};", take(&ok, class, "out")));
+    } else {
+        lines.push(format!("return {};", take(&ok, class, &call)));
+    }
+
+    lines
+}
+
+fn block(lines: Vec<String>) -> String {
+    let lines = lines
+        .into_iter()
+        .map(|line| format!("    {}\n", line))
+        .collect::<String>();
+
+    format!("{{\n{}}}\n", lines)
+}
+
+/// Constructors return the struct itself, possibly through a `Result`
+fn is_constructor(method: &Method) -> bool {
+    let output = method.sig.output.as_type();
+    let ok = result_types(&output).map_or(output, |(ok, _)| ok);
+
+    method.role == MethodRole::Constructor && is_opaque(&ok)
+}
+
+/// Collect the class that owns the opaque pointer of an exposed struct, which is written to the
+/// header by [`header`]
+pub fn add_class(mod_path: &[Ident], class: &Ident, methods: &[Method], skipped: &[Skipped]) {
+    let name = class.to_string();
+    let namespace = std::iter::once(ROOT_NAMESPACE.to_string())
+        .chain(mod_path.iter().map(Ident::to_string))
+        .collect::<Vec<_>>()
+        .join("::");
+
+    let destroy = methods
+        .iter()
+        .find(|m| m.role == MethodRole::Destructor)
+        .map(|m| &m.symbol);
+
+    let mut public = vec![];
+    let mut definitions = String::new();
+    for method in methods {
+        let sig = &method.sig;
+        let ident = sig.ident.to_string();
+        let params = params(sig, &name);
+        let ret = cpp_type(&sig.output.as_type(), &name);
+
+        match method.role {
+            MethodRole::Destructor => continue,
+            _ if is_constructor(method) => {
+                public.push(format!("explicit {}({});", name, params));
+                definitions.push_str(&format!(
+                    "\ninline {0}::{0}({1}) : pointer_(nullptr) {2}",
+                    name,
+                    params,
+                    block(body(method, &name, Some("pointer_")))
+                ));
+            }
+            _ => {
+                let ident = escape(match method.role {
+                    MethodRole::Getter => ident.strip_prefix("get_").unwrap_or(&ident),
+                    _ => &ident,
+                });
+                let (prefix, suffix) = match sig.inputs.first() {
+                    Some(FnArg::Receiver(Receiver {
+                        mutability: Some(_),
+                        ..
+                    })) => ("", ""),
+                    Some(FnArg::Receiver(_)) => ("", " const"),
+                    _ => ("static ", ""),
+                };
+
+                public.push(format!(
+                    "{}{} {}({}){};",
+                    prefix, ret, ident, params, suffix
+                ));
+                definitions.push_str(&format!(
+                    "\ninline {} {}::{}({}){} {}",
+                    ret,
+                    name,
+                    ident,
+                    params,
+                    suffix,
+                    block(body(method, &name, None))
+                ));
+            }
+        }
+    }
+
+    let destructor = match destroy {
+        Some(destroy) => format!(
+            "~{}() {{\n        if (pointer_ != nullptr) ffi::{}(pointer_);\n    }}",
+            name, destroy
+        ),
+        None => format!("~{}() = default;", name),
+    };
+
+    let declaration = format!(
+        "{3}class {0} {{
+public:
+    /// Take the ownership of a pointer returned by the C API
+    explicit {0}(ffi::{0} *pointer) noexcept : pointer_(pointer) {{}}
+
+    {0}(const {0} &) = delete;
+    {0} &operator=(const {0} &) = delete;
+
+    {0}({0} &&other) noexcept : pointer_(std::exchange(other.pointer_, nullptr)) {{}}
+    {0} &operator=({0} &&other) noexcept {{
+        std::swap(pointer_, other.pointer_);
+        return *this;
+    }}
+
+    {1}
+
+    /// Pointer to pass to the C API, still owned by this object
+    ffi::{0} *get() const noexcept {{ return pointer_; }}
+{2}
+private:
+    ffi::{0} *pointer_;
+}};
+",
+        name,
+        destructor,
+        public
+            .iter()
+            .map(|line| format!("\n    {}\n", line))
+            .collect::<String>(),
+        skipped_note(skipped, "//"),
+    );
+
+    ERRORS.with(|errors| {
+        let mut errors = errors.borrow_mut();
+        for error in super::wrapper::errors(methods) {
+            let error = cpp_type(&error, &name);
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+    });
+    CLASSES.with(|classes| {
+        classes.borrow_mut().push(Class {
+            namespace,
+            name,
+            declaration,
+            definitions,
+        })
+    });
+}
+
+/// Generate the header with all the classes collected so far
+pub fn header() -> String {
+    let classes = CLASSES.with(|classes| classes.take());
+    let errors = ERRORS.with(|errors| errors.take());
+
+    let mut source = format!(
+        "{}#pragma once
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#include <optional>
+#include <stdexcept>
+#include <string>
+#include <utility>
+#include <vector>
+
+namespace {2} {{
+
+/// Declarations of the C API, kept in their own namespace so that they don't clash with the
+/// classes that wrap them
+namespace ffi {{
+extern \"C\" {{
+#include \"{1}\"
+}}
+}} // namespace ffi
+
+namespace detail {{
+/// Take the ownership of a string returned by the C API
+inline std::string take_string(char *pointer) {{
+    std::string string(pointer);
+    ffi::string_free(pointer);
+    return string;
+}}
+
+/// Take the ownership of a byte buffer returned by the C API
+inline std::vector<uint8_t> take_bytes(ffi::ByteBuffer buffer) {{
+    std::vector<uint8_t> bytes(buffer.data, buffer.data + buffer.len);
+    ffi::byte_buffer_free(buffer);
+    return bytes;
+}}
+
+/// Take the ownership of the objects of an array returned by the C API, and release the array
+template <typename T, typename P>
+std::vector<T> take_array(P **array, size_t len, void (*array_free)(P **, size_t)) {{
+    std::vector<T> values;
+    values.reserve(len);
+    for (size_t i = 0; i < len; i++) {{
+        values.emplace_back(std::exchange(array[i], nullptr));
+    }}
+    array_free(array, len);
+    return values;
+}}
+}} // namespace detail
+",
+        HEADER, C_HEADER, ROOT_NAMESPACE
+    );
+
+    for error in errors {
+        source.push_str(&format!(
+            "
+class {0} : public std::runtime_error {{
+public:
+    explicit {0}(int32_t code) : std::runtime_error(\"{0}\"), code(code) {{}}
+
+    /// Error code returned by the C API
+    int32_t code;
+}};
+",
+            error
+        ));
+    }
+    source.push_str(&format!("\n}} // namespace {}\n", ROOT_NAMESPACE));
+
+    // every class is declared before the methods are defined, so that they can refer to each
+    // other regardless of the order
+    let sections = [
+        classes
+            .iter()
+            .map(|c| (c.namespace.as_str(), format!("class {};\n", c.name)))
+            .collect::<Vec<_>>(),
+        classes
+            .iter()
+            .map(|c| (c.namespace.as_str(), c.declaration.clone()))
+            .collect(),
+        classes
+            .iter()
+            .map(|c| (c.namespace.as_str(), c.definitions.clone()))
+            .collect(),
+    ];
+    for section in sections.iter() {
+        let mut current: Option<&str> = None;
+        for (namespace, content) in section {
+            if current != Some(namespace) {
+                if let Some(current) = current {
+                    source.push_str(&format!("\n}} // namespace {}\n", current));
+                }
+                source.push_str(&format!("\nnamespace {} {{\n\n", namespace));
+                current = Some(namespace);
+            } else {
+                source.push('\n');
+            }
+            source.push_str(content.trim_start_matches('\n'));
+        }
+        if let Some(current) = current {
+            source.push_str(&format!("\n}} // namespace {}\n", current));
+        }
+    }
+
+    source
+}
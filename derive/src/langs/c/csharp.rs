@@ -0,0 +1,607 @@
+use std::cell::RefCell;
+
+use syn::{parse_quote, FnArg, Ident, Type, TypeReference};
+
+use super::super::{last_segment, pascal_case, MethodRole};
+use super::wrapper::*;
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n\n";
+
+/// Namespace that contains everything, the path of the Rust modules is nested inside it
+const ROOT_NAMESPACE: &str = "Bdk";
+
+/// Name of the library loaded by `[DllImport]`
+const LIBRARY: &str = "bdk_ffi";
+
+/// C# keywords that can be valid Rust identifiers
+const KEYWORDS: &[&str] = &[
+    "base",
+    "bool",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "event",
+    "explicit",
+    "extern",
+    "finally",
+    "fixed",
+    "float",
+    "foreach",
+    "goto",
+    "implicit",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "string",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+];
+
+/// Parts of the source generated for an exposed struct
+struct Class {
+    namespace: String,
+    imports: String,
+    source: String,
+}
+
+thread_local! {
+    // everything is written to a single file, so the classes are collected while the impls are
+    // expanded and written out with the root module
+    static CLASSES: RefCell<Vec<Class>> = const { RefCell::new(vec![]) };
+    static ERRORS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Name of an argument, prefixed with `@` if it's a C# keyword
+fn escape(ident: &str) -> String {
+    match KEYWORDS.contains(&ident) {
+        true => format!("@{}", ident),
+        false => ident.into(),
+    }
+}
+
+fn primitive(ty: &Type) -> Option<&'static str> {
+    let cs = match last_segment(ty)?.to_string().as_str() {
+        "bool" => "bool",
+        "i8" => "sbyte",
+        "i16" => "short",
+        "i32" => "int",
+        "i64" => "long",
+        "u8" => "byte",
+        "u16" => "ushort",
+        "u32" => "uint",
+        "u64" => "ulong",
+        "usize" => "UIntPtr",
+        "isize" => "IntPtr",
+        "f32" => "float",
+        "f64" => "double",
+        _ => return None,
+    };
+
+    Some(cs)
+}
+
+fn strip_reference(ty: &Type) -> &Type {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => elem,
+        ty => ty,
+    }
+}
+
+fn opaque_name(ty: &Type, class: &str) -> String {
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") | None => class.into(),
+        Some(other) => other.into(),
+    }
+}
+
+/// C# type exposed to the users of the bindings
+fn cs_type(ty: &Type, class: &str) -> String {
+    let ty = strip_reference(ty);
+
+    if *ty == parse_quote!(()) {
+        "void".into()
+    } else if let Some(inner) = option_type(ty) {
+        format!("{}?", cs_type(&inner, class))
+    } else if let Some((ok, _)) = result_types(ty) {
+        cs_type(&ok, class)
+    } else if let Some(primitive) = primitive(ty) {
+        primitive.into()
+    } else if is_string(ty) {
+        "string".into()
+    } else if is_bytes(ty) {
+        "byte[]".into()
+    } else if let Some(elem) = object_vec(ty) {
+        format!("{}[]", cs_type(&elem, class))
+    } else {
+        opaque_name(ty, class)
+    }
+}
+
+/// Type used in the `[DllImport]` declaration, with its marshalling attribute
+fn native_type(ty: &Type, class: &str) -> String {
+    let ty = strip_reference(ty);
+
+    if is_string(ty) {
+        "[MarshalAs(UnmanagedType.LPUTF8Str)] string".into()
+    } else if *ty == parse_quote!(bool) {
+        "[MarshalAs(UnmanagedType.U1)] bool".into()
+    } else if let Some(primitive) = primitive(ty) {
+        primitive.into()
+    } else if is_bytes(ty) {
+        "ByteBuffer".into()
+    } else {
+        format!("{}Handle", opaque_name(ty, class))
+    }
+}
+
+/// Type of the out-param that receives a value of type `ty`
+fn out_type(ty: &Type, class: &str) -> String {
+    let ty = option_type(ty).unwrap_or_else(|| ty.clone());
+
+    match is_string(&ty) || object_vec(&ty).is_some() {
+        true => "IntPtr".into(),
+        false => native_type(&ty, class),
+    }
+}
+
+/// Expression that takes the ownership of `value`, a native value of type `ty`
+fn take(ty: &Type, class: &str, value: &str) -> String {
+    if let Some(inner) = option_type(ty) {
+        let invalid = match is_string(&inner) {
+            true => format!("{} == IntPtr.Zero", value),
+            false if is_opaque(&inner) => format!("{}.IsInvalid", value),
+            false => return take(&inner, class, value),
+        };
+        return format!("{} ? null : {}", invalid, take(&inner, class, value));
+    }
+
+    if is_string(ty) {
+        format!("NativeString.Take({})", value)
+    } else if is_bytes(ty) {
+        format!("NativeBytes.Take({})", value)
+    } else if let Some(elem) = object_vec(ty) {
+        let name = opaque_name(&elem, class);
+        format!(
+            "NativeArray.Take({}, {}Len, p => new {2}(new {2}Handle(p)), Native.{3})",
+            value,
+            value,
+            name,
+            array_free(&name)
+        )
+    } else if is_opaque(ty) {
+        format!("new {}({})", opaque_name(ty, class), value)
+    } else {
+        value.into()
+    }
+}
+
+/// `[DllImport]` declaration of the function exported for `method`
+fn import(method: &Method, class: &str) -> String {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(error)),
+        None => (output, None),
+    };
+
+    let mut params = vec![];
+    if let Some(FnArg::Receiver(_)) = sig.inputs.first() {
+        params.push(format!("{}Handle self", class));
+    }
+    for (name, ty) in typed_args(sig) {
+        params.push(match method.role {
+            // the destructor is called by `ReleaseHandle` with the raw pointer
+            MethodRole::Destructor => format!("IntPtr {}", escape(&name)),
+            // the array is pinned for the duration of the call
+            _ if is_bytes(ty) => format!("byte[] {}, UIntPtr {}Len", escape(&name), name),
+            _ => format!("{} {}", native_type(ty, class), escape(&name)),
+        });
+    }
+    if by_reference(&ok) || (error.is_some() && ok != parse_quote!(())) {
+        params.push(format!("out {} output", out_type(&ok, class)));
+        if object_vec(&ok).is_some() {
+            params.push("out UIntPtr outputLen".into());
+        }
+    }
+
+    let ret = if error.is_some() {
+        "int".to_string()
+    } else if is_string(&ok) {
+        "IntPtr".into()
+    } else if by_reference(&ok) || ok == parse_quote!(()) {
+        "void".into()
+    } else {
+        native_type(&ok, class)
+    };
+    let ret = match ret.as_str() {
+        "[MarshalAs(UnmanagedType.U1)] bool" => {
+            "[return: MarshalAs(UnmanagedType.U1)]\n        internal static extern bool".into()
+        }
+        _ => format!("internal static extern {}", ret),
+    };
+
+    format!(
+        "        [DllImport(Library)]\n        {} {}({});\n",
+        ret,
+        method.symbol,
+        params.join(", ")
+    )
+}
+
+/// Statements calling the imported function
+///
+/// When `out` is set the result is written to that variable instead of being returned, which is
+/// used by constructors to initialize the handle directly.
+fn body(method: &Method, class: &str, out: Option<&str>) -> Vec<String> {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(cs_type(&error, class))),
+        None => (output, None),
+    };
+    let by_ref = by_reference(&ok) || (error.is_some() && ok != parse_quote!(()));
+
+    let mut args = vec![];
+    if let Some(FnArg::Receiver(_)) = sig.inputs.first() {
+        args.push("handle".to_string());
+    }
+    for (name, ty) in typed_args(sig) {
+        let name = escape(&name);
+        args.push(if is_opaque(strip_reference(ty)) {
+            format!("{}.Handle", name)
+        } else if is_bytes(ty) {
+            format!("{0}, (UIntPtr){0}.Length", name)
+        } else {
+            name
+        });
+    }
+    if by_ref {
+        args.push(match out {
+            Some(out) => format!("out {}", out),
+            None => format!("out {} output", out_type(&ok, class)),
+        });
+        if object_vec(&ok).is_some() {
+            args.push("out UIntPtr outputLen".into());
+        }
+    }
+
+    let call = format!("Native.{}({})", method.symbol, args.join(", "));
+    let mut lines = vec![];
+    match &error {
+        Some(error) => {
+            lines.push(format!("int code = {};", call));
+            lines.push(format!("if (code != 0) throw new {}(code);", error));
+        }
+        None if by_ref || ok == parse_quote!(()) => lines.push(format!("{};", call)),
+        None => {}
+    }
+
+    if out.is_none() && ok != parse_quote!(()) {
+        lines.push(match by_ref {
+            true => format!("return {};", take(&ok, class, "output")),
+            false => format!("return {};", take(&ok, class, &call)),
+        });
+    }
+
+    lines
+}
+
+fn block(lines: Vec<String>, indent: &str) -> String {
+    let lines = lines
+        .into_iter()
+        .map(|line| format!("{}    {}\n", indent, line))
+        .collect::<String>();
+
+    format!("{0}{{\n{1}{0}}}\n", indent, lines)
+}
+
+/// Collect the `SafeHandle` and the class of an exposed struct, which are written out by
+/// [`source`]
+pub fn add_class(mod_path: &[Ident], class: &Ident, methods: &[Method], skipped: &[Skipped]) {
+    let name = class.to_string();
+    let namespace = std::iter::once(ROOT_NAMESPACE.to_string())
+        .chain(mod_path.iter().map(|m| pascal_case(&m.to_string())))
+        .collect::<Vec<_>>()
+        .join(".");
+
+    let destroy = methods
+        .iter()
+        .find(|m| m.role == MethodRole::Destructor)
+        .map(|m| &m.symbol);
+
+    let release = match destroy {
+        Some(destroy) => format!("Native.{}(handle);\n            return true;", destroy),
+        None => "// the struct doesn't have a destructor\n            return true;".into(),
+    };
+    let mut source = skipped_note(skipped, "    //");
+    source.push_str(&format!(
+        "    /// Owns the pointer to the native `{0}`, released by its destructor
+    public sealed class {0}Handle : SafeHandle
+    {{
+        public {0}Handle() : base(IntPtr.Zero, true) {{ }}
+
+        internal {0}Handle(IntPtr pointer) : base(IntPtr.Zero, true)
+        {{
+            SetHandle(pointer);
+        }}
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {{
+            {1}
+        }}
+    }}
+
+    public sealed class {0} : IDisposable
+    {{
+        private readonly {0}Handle handle;
+
+        internal {0}({0}Handle handle)
+        {{
+            this.handle = handle;
+        }}
+
+        /// Handle of the native object, still owned by this object
+        public {0}Handle Handle => handle;
+
+        public void Dispose() => handle.Dispose();
+",
+        name, release
+    ));
+
+    let indent = "        ";
+    for method in methods {
+        let sig = &method.sig;
+        let ident = sig.ident.to_string();
+        let ret = cs_type(&sig.output.as_type(), &name);
+        let params = typed_args(sig)
+            .into_iter()
+            .map(|(arg, ty)| format!("{} {}", cs_type(ty, &name), escape(&arg)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let output = sig.output.as_type();
+        let ok = result_types(&output).map_or(output, |(ok, _)| ok);
+
+        if method.role == MethodRole::Destructor {
+            continue;
+        }
+
+        source.push('\n');
+        match method.role {
+            MethodRole::Constructor if is_opaque(&ok) => {
+                source.push_str(&format!("{}public {}({})\n", indent, name, params));
+                source.push_str(&block(body(method, &name, Some("handle")), indent));
+            }
+            MethodRole::Getter => {
+                let property = pascal_case(ident.strip_prefix("get_").unwrap_or(&ident));
+                source.push_str(&format!(
+                    "{0}public {1} {2}\n{0}{{\n{0}    get\n",
+                    indent, ret, property
+                ));
+                source.push_str(&block(body(method, &name, None), "            "));
+                source.push_str(&format!("{}}}\n", indent));
+            }
+            _ => {
+                let modifier = match sig.inputs.first() {
+                    Some(FnArg::Receiver(_)) if ident == "to_string" && params.is_empty() => {
+                        "override "
+                    }
+                    Some(FnArg::Receiver(_)) => "",
+                    _ => "static ",
+                };
+                source.push_str(&format!(
+                    "{}public {}{} {}({})\n",
+                    indent,
+                    modifier,
+                    ret,
+                    pascal_case(&ident),
+                    params
+                ));
+                source.push_str(&block(body(method, &name, None), indent));
+            }
+        }
+    }
+    source.push_str("    }\n");
+
+    let imports = methods
+        .iter()
+        .map(|method| import(method, &name))
+        .chain(std::iter::once(format!(
+            "        [DllImport(Library)]\n        internal static extern void {}(IntPtr array, UIntPtr len);\n",
+            array_free(&name)
+        )))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ERRORS.with(|errors| {
+        let mut errors = errors.borrow_mut();
+        for error in super::wrapper::errors(methods) {
+            let error = cs_type(&error, &name);
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+    });
+    CLASSES.with(|classes| {
+        classes.borrow_mut().push(Class {
+            namespace,
+            imports,
+            source,
+        })
+    });
+}
+
+/// Generate the C# source with all the classes collected so far
+pub fn source() -> String {
+    let classes = CLASSES.with(|classes| classes.take());
+    let errors = ERRORS.with(|errors| errors.take());
+
+    let mut source = format!(
+        "{}#nullable enable
+
+using System;
+using System.Runtime.InteropServices;
+
+namespace {}
+{{
+    internal static class NativeString
+    {{
+        [DllImport(\"{}\")]
+        private static extern void string_free(IntPtr s);
+
+        /// Copy a string returned by the library and release it
+        internal static string Take(IntPtr pointer)
+        {{
+            try
+            {{
+                return Marshal.PtrToStringUTF8(pointer)!;
+            }}
+            finally
+            {{
+                string_free(pointer);
+            }}
+        }}
+    }}
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct ByteBuffer
+    {{
+        public IntPtr Data;
+        public UIntPtr Len;
+        public UIntPtr Cap;
+    }}
+
+    internal static class NativeBytes
+    {{
+        [DllImport(\"{2}\")]
+        private static extern void byte_buffer_free(ByteBuffer buffer);
+
+        /// Copy the bytes returned by the library and release them
+        internal static byte[] Take(ByteBuffer buffer)
+        {{
+            try
+            {{
+                var bytes = new byte[(int)buffer.Len];
+                Marshal.Copy(buffer.Data, bytes, 0, bytes.Length);
+                return bytes;
+            }}
+            finally
+            {{
+                byte_buffer_free(buffer);
+            }}
+        }}
+    }}
+
+    internal static class NativeArray
+    {{
+        /// Take the ownership of the objects of an array returned by the library, and release the
+        /// array
+        internal static T[] Take<T>(IntPtr array, UIntPtr len, Func<IntPtr, T> take, Action<IntPtr, UIntPtr> free)
+        {{
+            var values = new T[(int)len];
+            for (var i = 0; i < values.Length; i++)
+            {{
+                var offset = i * IntPtr.Size;
+                values[i] = take(Marshal.ReadIntPtr(array, offset));
+                Marshal.WriteIntPtr(array, offset, IntPtr.Zero);
+            }}
+            free(array, len);
+            return values;
+        }}
+    }}
+",
+        HEADER, ROOT_NAMESPACE, LIBRARY
+    );
+    for error in errors {
+        source.push_str(&format!(
+            "
+    public class {0} : Exception
+    {{
+        public {0}(int code) : base($\"{0} (code {{code}})\")
+        {{
+            Code = code;
+        }}
+
+        /// Error code returned by the library
+        public int Code {{ get; }}
+    }}
+",
+            error
+        ));
+    }
+    source.push_str("}\n");
+
+    let mut namespaces: Vec<&str> = vec![];
+    for class in &classes {
+        if !namespaces.contains(&class.namespace.as_str()) {
+            namespaces.push(&class.namespace);
+        }
+    }
+    for namespace in namespaces {
+        let classes = classes
+            .iter()
+            .filter(|c| c.namespace == namespace)
+            .collect::<Vec<_>>();
+
+        source.push_str(&format!(
+            "\nnamespace {}\n{{\n    internal static class Native\n    {{\n        private const string Library = \"{}\";\n\n{}    }}\n",
+            namespace,
+            LIBRARY,
+            classes
+                .iter()
+                .map(|c| c.imports.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+        for class in classes {
+            source.push('\n');
+            source.push_str(&class.source);
+        }
+        source.push_str("}\n");
+    }
+
+    source
+}
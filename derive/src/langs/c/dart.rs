@@ -0,0 +1,501 @@
+use std::cell::RefCell;
+
+use syn::{parse_quote, FnArg, Ident, Signature, Type, TypeReference};
+
+use super::super::{camel_case, last_segment, pascal_case, MethodRole};
+use super::wrapper::*;
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n\n";
+
+/// Name of the library loaded with `DynamicLibrary.open`
+const LIBRARY: &str = "libbdk_ffi.so";
+
+/// Dart keywords that can be valid Rust identifiers
+const KEYWORDS: &[&str] = &[
+    "assert", "case", "catch", "class", "const", "default", "do", "extends", "external", "factory",
+    "final", "finally", "get", "late", "new", "null", "operator", "required", "rethrow", "set",
+    "switch", "this", "throw", "try", "var", "void", "with",
+];
+
+thread_local! {
+    // everything is written to a single library, so the classes are collected while the impls
+    // are expanded and written out with the root module
+    static CLASSES: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    static ERRORS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Name of an argument, with a trailing underscore if it's a Dart keyword
+fn escape(ident: &str) -> String {
+    match KEYWORDS.contains(&ident) {
+        true => format!("{}_", ident),
+        false => ident.into(),
+    }
+}
+
+/// Native and Dart types of primitive values
+fn primitive(ty: &Type) -> Option<(&'static str, &'static str)> {
+    let types = match last_segment(ty)?.to_string().as_str() {
+        "bool" => ("Bool", "bool"),
+        "i8" => ("Int8", "int"),
+        "i16" => ("Int16", "int"),
+        "i32" => ("Int32", "int"),
+        "i64" => ("Int64", "int"),
+        "u8" => ("Uint8", "int"),
+        "u16" => ("Uint16", "int"),
+        "u32" => ("Uint32", "int"),
+        "u64" => ("Uint64", "int"),
+        "usize" => ("Size", "int"),
+        "isize" => ("IntPtr", "int"),
+        "f32" => ("Float", "double"),
+        "f64" => ("Double", "double"),
+        _ => return None,
+    };
+
+    Some(types)
+}
+
+fn strip_reference(ty: &Type) -> &Type {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => elem,
+        ty => ty,
+    }
+}
+
+fn opaque_name(ty: &Type, class: &str) -> String {
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") | None => class.into(),
+        Some(other) => other.into(),
+    }
+}
+
+/// Dart type exposed to the users of the bindings
+fn dart_type(ty: &Type, class: &str) -> String {
+    let ty = strip_reference(ty);
+
+    if *ty == parse_quote!(()) {
+        "void".into()
+    } else if let Some(inner) = option_type(ty) {
+        format!("{}?", dart_type(&inner, class))
+    } else if let Some((ok, _)) = result_types(ty) {
+        dart_type(&ok, class)
+    } else if let Some((_, dart)) = primitive(ty) {
+        dart.into()
+    } else if is_string(ty) {
+        "String".into()
+    } else if is_bytes(ty) {
+        "Uint8List".into()
+    } else if let Some(elem) = object_vec(ty) {
+        format!("List<{}>", dart_type(&elem, class))
+    } else {
+        opaque_name(ty, class)
+    }
+}
+
+/// Native and Dart types of a value passed through the C ABI
+fn ffi_types(ty: &Type, class: &str) -> (String, String) {
+    let ty = strip_reference(ty);
+
+    if is_string(ty) {
+        ("Pointer<Utf8>".into(), "Pointer<Utf8>".into())
+    } else if is_bytes(ty) {
+        ("_ByteBuffer".into(), "_ByteBuffer".into())
+    } else if let Some((native, dart)) = primitive(ty) {
+        (native.into(), dart.into())
+    } else {
+        let pointer = format!("Pointer<_{}>", opaque_name(ty, class));
+        (pointer.clone(), pointer)
+    }
+}
+
+/// Typedefs and lookup of the function exported for `method`
+fn lookup(method: &Method, class: &str) -> String {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(error)),
+        None => (output, None),
+    };
+
+    let mut params = vec![];
+    if let Some(FnArg::Receiver(_)) = sig.inputs.first() {
+        params.push(ffi_types(&parse_quote!(Self), class));
+    }
+    for (_, ty) in typed_args(sig) {
+        match is_bytes(ty) {
+            true => {
+                params.push(("Pointer<Uint8>".into(), "Pointer<Uint8>".into()));
+                params.push(("Size".into(), "int".into()));
+            }
+            false => params.push(ffi_types(ty, class)),
+        }
+    }
+    if let Some(elem) = object_vec(&ok) {
+        // array of pointers and its length
+        let array = format!("Pointer<Pointer<{}>>", ffi_types(&elem, class).0);
+        params.push((array.clone(), array));
+        params.push(("Pointer<Size>".into(), "Pointer<Size>".into()));
+    } else if by_reference(&ok) || (error.is_some() && ok != parse_quote!(())) {
+        let inner = option_type(&ok).unwrap_or_else(|| ok.clone());
+        let (native, _) = ffi_types(&inner, class);
+        params.push((
+            format!("Pointer<{}>", native),
+            format!("Pointer<{}>", native),
+        ));
+    }
+
+    let ret = if error.is_some() {
+        ("Int32".to_string(), "int".to_string())
+    } else if by_reference(&ok) || ok == parse_quote!(()) {
+        ("Void".into(), "void".into())
+    } else {
+        ffi_types(&ok, class)
+    };
+
+    let name = pascal_case(&method.symbol.to_string());
+    let (native_params, dart_params): (Vec<_>, Vec<_>) = params.into_iter().unzip();
+
+    format!(
+        "typedef _{0}C = {1} Function({2});\ntypedef _{0}Dart = {3} Function({4});\nfinal _{5} = _lib.lookupFunction<_{0}C, _{0}Dart>('{6}');\n",
+        name,
+        ret.0,
+        native_params.join(", "),
+        ret.1,
+        dart_params.join(", "),
+        camel_case(&method.symbol.to_string()),
+        method.symbol
+    )
+}
+
+/// Expression that takes the ownership of `value`, a native value of type `ty`
+fn take(ty: &Type, class: &str, value: &str) -> String {
+    if let Some(inner) = option_type(ty) {
+        return match is_primitive(&inner) {
+            true => take(&inner, class, value),
+            false => format!(
+                "{} == nullptr ? null : {}",
+                value,
+                take(&inner, class, value)
+            ),
+        };
+    }
+
+    if is_string(ty) {
+        format!("_takeString({})", value)
+    } else if is_bytes(ty) {
+        format!("_takeBytes({})", value)
+    } else if let Some(elem) = object_vec(ty) {
+        let name = opaque_name(&elem, class);
+        format!(
+            "_takeArray({}, outLen.value, {}._, _{})",
+            value,
+            name,
+            camel_case(&array_free(&name))
+        )
+    } else if is_opaque(ty) {
+        format!("{}._({})", opaque_name(ty, class), value)
+    } else {
+        value.into()
+    }
+}
+
+/// Statements calling the native function, the last one returning the converted value
+fn body(method: &Method, class: &str) -> Vec<String> {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(opaque_name(strip_reference(&error), class))),
+        None => (output, None),
+    };
+    let by_ref = by_reference(&ok) || (error.is_some() && ok != parse_quote!(()));
+
+    let mut arena = false;
+    let mut args = vec![];
+    if let Some(FnArg::Receiver(_)) = sig.inputs.first() {
+        args.push("_pointer".to_string());
+    }
+    for (name, ty) in typed_args(sig) {
+        let name = escape(&camel_case(&name));
+        let ty = strip_reference(ty);
+        args.push(if is_string(ty) {
+            arena = true;
+            format!("{}.toNativeUtf8(allocator: arena)", name)
+        } else if is_bytes(ty) {
+            arena = true;
+            format!("_toNativeBytes({0}, arena), {0}.length", name)
+        } else if is_opaque(ty) {
+            format!("{}._pointer", name)
+        } else {
+            name
+        });
+    }
+
+    let mut lines = vec![];
+    if let Some(elem) = object_vec(&ok) {
+        arena = true;
+        let (native, _) = ffi_types(&elem, class);
+        lines.push(format!("final out = arena<Pointer<{}>>();", native));
+        lines.push("final outLen = arena<Size>();".into());
+        args.push("out".into());
+        args.push("outLen".into());
+    } else if by_ref {
+        arena = true;
+        let inner = option_type(&ok).unwrap_or_else(|| ok.clone());
+        let (native, _) = ffi_types(&inner, class);
+        lines.push(format!("final out = arena<{}>();", native));
+        args.push("out".into());
+    }
+
+    let call = format!(
+        "_{}({})",
+        camel_case(&method.symbol.to_string()),
+        args.join(", ")
+    );
+    match &error {
+        Some(error) => {
+            lines.push(format!("final code = {};", call));
+            lines.push(format!("if (code != 0) throw {}(code);", error));
+        }
+        None if by_ref || ok == parse_quote!(()) => lines.push(format!("{};", call)),
+        None => {}
+    }
+
+    if ok != parse_quote!(()) {
+        // structs are read through `ref` instead of `value`
+        let out = match is_bytes(&ok) {
+            true => "out.ref",
+            false => "out.value",
+        };
+        lines.push(match by_ref {
+            true => format!("return {};", take(&ok, class, out)),
+            false => format!("return {};", take(&ok, class, &call)),
+        });
+    }
+
+    // strings and out-params are allocated in an arena, which is released when the call returns
+    match arena {
+        true => {
+            let mut wrapped = vec!["return using((arena) {".to_string()];
+            wrapped.extend(lines.into_iter().map(|line| format!("  {}", line)));
+            wrapped.push("});".into());
+            wrapped
+        }
+        false => lines,
+    }
+}
+
+fn params(sig: &Signature, class: &str) -> String {
+    typed_args(sig)
+        .into_iter()
+        .map(|(name, ty)| format!("{} {}", dart_type(ty, class), escape(&camel_case(&name))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn block(head: &str, lines: Vec<String>) -> String {
+    let body = lines
+        .into_iter()
+        .map(|line| format!("    {}\n", line))
+        .collect::<String>();
+
+    format!("  {} {{\n{}  }}\n", head, body)
+}
+
+/// Collect the class that owns the opaque pointer of an exposed struct, which is written to the
+/// library by [`library`]
+pub fn add_class(class: &Ident, methods: &[Method], skipped: &[Skipped]) {
+    let name = class.to_string();
+    let destroy = methods
+        .iter()
+        .find(|m| m.role == MethodRole::Destructor)
+        .map(|m| camel_case(&m.symbol.to_string()));
+
+    let mut source = format!("final class _{} extends Opaque {{}}\n\n", name);
+    for method in methods {
+        source.push_str(&lookup(method, &name));
+    }
+    let free = array_free(&name);
+    source.push_str(&format!(
+        "typedef _{0}C = Void Function(Pointer<Pointer<_{1}>>, Size);\ntypedef _{0}Dart = void Function(Pointer<Pointer<_{1}>>, int);\nfinal _{2} = _lib.lookupFunction<_{0}C, _{0}Dart>('{3}');\n",
+        pascal_case(&free),
+        name,
+        camel_case(&free),
+        free
+    ));
+
+    source.push_str(&format!(
+        "\n{}class {} {{\n",
+        skipped_note(skipped, "//"),
+        name
+    ));
+    match &destroy {
+        Some(destroy) => source.push_str(&format!(
+            "  static final _finalizer = Finalizer<Pointer<_{0}>>(_{1});
+
+  final Pointer<_{0}> _pointer;
+
+  {0}._(this._pointer) {{
+    _finalizer.attach(this, _pointer, detach: this);
+  }}
+
+  /// Release the native object now instead of waiting for the garbage collector
+  void dispose() {{
+    _finalizer.detach(this);
+    _{1}(_pointer);
+  }}
+",
+            name, destroy
+        )),
+        None => source.push_str(&format!(
+            "  final Pointer<_{0}> _pointer;\n\n  {0}._(this._pointer);\n",
+            name
+        )),
+    }
+
+    for method in methods {
+        let sig = &method.sig;
+        let ident = sig.ident.to_string();
+        let output = sig.output.as_type();
+        let ok = result_types(&output).map_or(output.clone(), |(ok, _)| ok);
+        let ret = dart_type(&output, &name);
+        let params = params(sig, &name);
+
+        let head = match method.role {
+            MethodRole::Destructor => continue,
+            MethodRole::Constructor if is_opaque(&ok) => {
+                format!("factory {}.{}({})", name, camel_case(&ident), params)
+            }
+            MethodRole::Getter => format!(
+                "{} get {}",
+                ret,
+                camel_case(ident.strip_prefix("get_").unwrap_or(&ident))
+            ),
+            _ => match sig.inputs.first() {
+                Some(FnArg::Receiver(_)) if ident == "to_string" && params.is_empty() => {
+                    "@override\n  String toString()".into()
+                }
+                Some(FnArg::Receiver(_)) => format!("{} {}({})", ret, camel_case(&ident), params),
+                _ => format!("static {} {}({})", ret, camel_case(&ident), params),
+            },
+        };
+
+        source.push('\n');
+        source.push_str(&block(&head, body(method, &name)));
+    }
+    source.push_str("}\n");
+
+    ERRORS.with(|errors| {
+        let mut errors = errors.borrow_mut();
+        for error in super::wrapper::errors(methods) {
+            let error = opaque_name(strip_reference(&error), &name);
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+    });
+    CLASSES.with(|classes| classes.borrow_mut().push(source));
+}
+
+/// Generate the library with all the classes collected so far
+pub fn library() -> String {
+    let classes = CLASSES.with(|classes| classes.take());
+    let errors = ERRORS.with(|errors| errors.take());
+
+    let mut source = format!(
+        "{}import 'dart:ffi';
+import 'dart:io';
+import 'dart:typed_data';
+
+import 'package:ffi/ffi.dart';
+
+DynamicLibrary _open() {{
+  final path = Platform.environment['BDK_FFI_LIB'];
+  if (path != null) return DynamicLibrary.open(path);
+  // the library is statically linked into the app on iOS
+  if (Platform.isIOS) return DynamicLibrary.process();
+  return DynamicLibrary.open('{}');
+}}
+
+final _lib = _open();
+
+typedef _StringFreeC = Void Function(Pointer<Utf8>);
+typedef _StringFreeDart = void Function(Pointer<Utf8>);
+final _stringFree = _lib.lookupFunction<_StringFreeC, _StringFreeDart>('string_free');
+
+/// Copy a string returned by the library and release it
+String _takeString(Pointer<Utf8> pointer) {{
+  try {{
+    return pointer.toDartString();
+  }} finally {{
+    _stringFree(pointer);
+  }}
+}}
+
+final class _ByteBuffer extends Struct {{
+  external Pointer<Uint8> data;
+  @Size()
+  external int len;
+  @Size()
+  external int cap;
+}}
+
+typedef _ByteBufferFreeC = Void Function(_ByteBuffer);
+typedef _ByteBufferFreeDart = void Function(_ByteBuffer);
+final _byteBufferFree =
+    _lib.lookupFunction<_ByteBufferFreeC, _ByteBufferFreeDart>('byte_buffer_free');
+
+/// Copy the bytes returned by the library and release them
+Uint8List _takeBytes(_ByteBuffer buffer) {{
+  try {{
+    return Uint8List.fromList(buffer.data.asTypedList(buffer.len));
+  }} finally {{
+    _byteBufferFree(buffer);
+  }}
+}}
+
+/// Take the ownership of the objects of an array returned by the library, and release the array
+List<T> _takeArray<T, P extends NativeType>(Pointer<Pointer<P>> array, int len,
+    T Function(Pointer<P>) take, void Function(Pointer<Pointer<P>>, int) free) {{
+  final values = <T>[];
+  for (var i = 0; i < len; i++) {{
+    values.add(take(array[i]));
+    array[i] = nullptr;
+  }}
+  free(array, len);
+  return values;
+}}
+
+/// Copy bytes to native memory, which is only borrowed by the library
+Pointer<Uint8> _toNativeBytes(Uint8List bytes, Allocator allocator) {{
+  final pointer = allocator<Uint8>(bytes.length);
+  pointer.asTypedList(bytes.length).setAll(0, bytes);
+  return pointer;
+}}
+",
+        HEADER, LIBRARY
+    );
+
+    for error in errors {
+        source.push_str(&format!(
+            "
+class {0} implements Exception {{
+  /// Error code returned by the library
+  final int code;
+
+  {0}(this.code);
+
+  @override
+  String toString() => '{0} (code $code)';
+}}
+",
+            error
+        ));
+    }
+
+    for class in classes {
+        source.push('\n');
+        source.push_str(&class);
+    }
+
+    source
+}
@@ -0,0 +1,480 @@
+use syn::{parse_quote, FnArg, Ident, Signature, Type, TypeReference};
+
+use super::super::{last_segment, pascal_case, MethodRole};
+use super::wrapper::*;
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n\n";
+
+/// Go keywords that can be valid Rust identifiers
+const KEYWORDS: &[&str] = &[
+    "chan",
+    "defer",
+    "fallthrough",
+    "func",
+    "go",
+    "goto",
+    "import",
+    "interface",
+    "map",
+    "package",
+    "range",
+    "select",
+    "switch",
+    "var",
+];
+
+/// Name of an argument, with a trailing underscore if it's a Go keyword or if it would shadow the
+/// receiver
+fn escape(ident: &str, receiver: &str) -> String {
+    match KEYWORDS.contains(&ident) || ident == receiver {
+        true => format!("{}_", ident),
+        false => ident.into(),
+    }
+}
+
+/// Go and C types of primitive values
+fn primitive(ty: &Type) -> Option<(&'static str, &'static str)> {
+    let types = match last_segment(ty)?.to_string().as_str() {
+        "bool" => ("bool", "bool"),
+        "i8" => ("int8", "int8_t"),
+        "i16" => ("int16", "int16_t"),
+        "i32" => ("int32", "int32_t"),
+        "i64" => ("int64", "int64_t"),
+        "u8" => ("uint8", "uint8_t"),
+        "u16" => ("uint16", "uint16_t"),
+        "u32" => ("uint32", "uint32_t"),
+        "u64" => ("uint64", "uint64_t"),
+        "usize" => ("uint", "size_t"),
+        "isize" => ("int", "ptrdiff_t"),
+        "f32" => ("float32", "float"),
+        "f64" => ("float64", "double"),
+        _ => return None,
+    };
+
+    Some(types)
+}
+
+fn strip_reference(ty: &Type) -> &Type {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => elem,
+        ty => ty,
+    }
+}
+
+fn opaque_name(ty: &Type, class: &str) -> String {
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") | None => class.into(),
+        Some(other) => other.into(),
+    }
+}
+
+/// Go type exposed to the users of the bindings
+fn go_type(ty: &Type, class: &str) -> String {
+    let ty = strip_reference(ty);
+
+    if let Some(inner) = option_type(ty) {
+        match is_opaque(&inner) {
+            // opaque structs are already returned as pointers
+            true => go_type(&inner, class),
+            false => format!("*{}", go_type(&inner, class)),
+        }
+    } else if let Some((ok, _)) = result_types(ty) {
+        go_type(&ok, class)
+    } else if let Some((go, _)) = primitive(ty) {
+        go.into()
+    } else if is_string(ty) {
+        "string".into()
+    } else if is_bytes(ty) {
+        "[]byte".into()
+    } else if let Some(elem) = object_vec(ty) {
+        format!("[]{}", go_type(&elem, class))
+    } else {
+        format!("*{}", opaque_name(ty, class))
+    }
+}
+
+/// Value returned alongside an error
+fn zero_value(ty: &Type, class: &str) -> &'static str {
+    match go_type(ty, class).as_str() {
+        "string" => "\"\"",
+        "bool" => "false",
+        ty if ty.starts_with('*') || ty.starts_with('[') => "nil",
+        _ => "0",
+    }
+}
+
+/// Declaration of the variable that receives an out-param
+fn out_var(ty: &Type, class: &str) -> String {
+    let ty = option_type(ty).unwrap_or_else(|| ty.clone());
+
+    if is_string(&ty) {
+        "var out *C.char".into()
+    } else if is_bytes(&ty) {
+        "var out C.ByteBuffer".into()
+    } else if let Some(elem) = object_vec(&ty) {
+        format!("var out **C.{}", opaque_name(&elem, class))
+    } else if let Some((_, c)) = primitive(&ty) {
+        format!("var out C.{}", c)
+    } else {
+        format!("var out *C.{}", opaque_name(&ty, class))
+    }
+}
+
+/// Expression that takes the ownership of `value`, a C value of type `ty`
+fn take(ty: &Type, class: &str, value: &str) -> String {
+    if is_string(ty) {
+        format!("takeString({})", value)
+    } else if is_bytes(ty) {
+        format!("takeBytes({})", value)
+    } else if let Some((go, _)) = primitive(ty) {
+        format!("{}({})", go, value)
+    } else {
+        format!("new{}({})", opaque_name(ty, class), value)
+    }
+}
+
+/// Whether the C function returns an error code, in which case the Go function returns an `error`
+fn error_type(sig: &Signature, class: &str) -> Option<String> {
+    result_types(&sig.output.as_type())
+        .map(|(_, error)| go_type(&error, class).trim_start_matches('*').to_string())
+}
+
+fn returns(sig: &Signature, class: &str) -> String {
+    let output = sig.output.as_type();
+    let ok = result_types(&output).map_or(output, |(ok, _)| ok);
+
+    match (ok == parse_quote!(()), error_type(sig, class).is_some()) {
+        (true, false) => String::new(),
+        (true, true) => " error".into(),
+        (false, false) => format!(" {}", go_type(&ok, class)),
+        (false, true) => format!(" ({}, error)", go_type(&ok, class)),
+    }
+}
+
+/// Converted function, with the Go imports it needs
+struct Function {
+    source: String,
+    unsafe_pointers: bool,
+    keep_alive: bool,
+}
+
+fn function(method: &Method, class: &str, receiver: &str, name: &str, is_method: bool) -> Function {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, _)) => (ok, error_type(sig, class)),
+        None => (output, None),
+    };
+    let by_ref = by_reference(&ok) || (error.is_some() && ok != parse_quote!(()));
+
+    let mut lines = vec![];
+    let mut args = vec![];
+    let mut keep_alive = vec![];
+    let mut unsafe_pointers = false;
+
+    if is_method {
+        args.push(format!("{}.pointer", receiver));
+        keep_alive.push(receiver.to_string());
+    }
+
+    let params = typed_args(sig)
+        .into_iter()
+        .map(|(name, ty)| {
+            let name = escape(&name, receiver);
+            let elem = strip_reference(ty);
+
+            if is_string(elem) {
+                unsafe_pointers = true;
+                lines.push(format!("c{} := C.CString({})", name, name));
+                lines.push(format!("defer C.free(unsafe.Pointer(c{}))", name));
+                args.push(format!("c{}", name));
+            } else if is_bytes(elem) {
+                // the slice is only borrowed for the duration of the call
+                args.push(format!("bytesPointer({})", name));
+                args.push(format!("C.size_t(len({}))", name));
+            } else if let Some((_, c)) = primitive(elem) {
+                args.push(format!("C.{}({})", c, name));
+            } else {
+                args.push(format!("{}.pointer", name));
+                keep_alive.push(name.clone());
+            }
+
+            format!("{} {}", name, go_type(ty, class))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if by_ref {
+        lines.push(out_var(&ok, class));
+        args.push("&out".into());
+    }
+    if object_vec(&ok).is_some() {
+        lines.push("var outLen C.size_t".into());
+        args.push("&outLen".into());
+    }
+
+    let call = format!("C.{}({})", method.symbol, args.join(", "));
+    let result = match &error {
+        Some(_) => Some("code"),
+        None if by_ref || ok == parse_quote!(()) => None,
+        None => Some("result"),
+    };
+    lines.push(match result {
+        Some(result) => format!("{} := {}", result, call),
+        None => call,
+    });
+    // the finalizers of the objects passed to the function must not run before it returns
+    lines.extend(
+        keep_alive
+            .iter()
+            .map(|name| format!("runtime.KeepAlive({})", name)),
+    );
+
+    if let Some(error) = &error {
+        let error = format!("&{}{{Code: int32(code)}}", error);
+        lines.push("if code != 0 {".into());
+        lines.push(match ok == parse_quote!(()) {
+            true => format!("\treturn {}", error),
+            false => format!("\treturn {}, {}", zero_value(&ok, class), error),
+        });
+        lines.push("}".into());
+    }
+
+    let value = if ok == parse_quote!(()) {
+        None
+    } else if let Some(elem) = object_vec(&ok) {
+        // the objects are taken out of the array before releasing it
+        let name = opaque_name(&elem, class);
+        unsafe_pointers = true;
+        lines.push("pointers := unsafe.Slice(out, outLen)".into());
+        lines.push(format!("values := make([]*{}, len(pointers))", name));
+        lines.push("for i := range pointers {".into());
+        lines.push(format!("	values[i] = new{}(pointers[i])", name));
+        lines.push("	pointers[i] = nil".into());
+        lines.push("}".into());
+        lines.push(format!("C.{}(out, outLen)", array_free(&name)));
+        Some("values".into())
+    } else if let Some(inner) = option_type(&ok) {
+        lines.push("if out == nil {".into());
+        lines.push(match error {
+            Some(_) => "\treturn nil, nil".into(),
+            None => "\treturn nil".into(),
+        });
+        lines.push("}".into());
+
+        match is_opaque(&inner) {
+            true => Some(take(&inner, class, "out")),
+            false => {
+                lines.push(format!("value := {}", take(&inner, class, "out")));
+                Some("&value".into())
+            }
+        }
+    } else if by_ref {
+        Some(take(&ok, class, "out"))
+    } else {
+        Some(take(&ok, class, "result"))
+    };
+    match (value, &error) {
+        (Some(value), Some(_)) => lines.push(format!("return {}, nil", value)),
+        (Some(value), None) => lines.push(format!("return {}", value)),
+        (None, Some(_)) => lines.push("return nil".into()),
+        (None, None) => {}
+    }
+
+    let head = match is_method {
+        true => format!(
+            "func ({} *{}) {}({}){}",
+            receiver,
+            class,
+            name,
+            params,
+            returns(sig, class)
+        ),
+        false => format!("func {}({}){}", name, params, returns(sig, class)),
+    };
+    let body = lines
+        .into_iter()
+        .map(|line| format!("\t{}\n", line))
+        .collect::<String>();
+
+    Function {
+        source: format!("{} {{\n{}}}\n", head, body),
+        unsafe_pointers,
+        keep_alive: !keep_alive.is_empty(),
+    }
+}
+
+/// Name of the Go package generated for a module
+pub fn package(mod_path: &[Ident]) -> String {
+    mod_path
+        .last()
+        .map(Ident::to_string)
+        .unwrap_or_else(|| "bdk".into())
+}
+
+/// Generate the struct that owns the opaque pointer of an exposed struct
+pub fn class(mod_path: &[Ident], class: &Ident, methods: &[Method], skipped: &[Skipped]) -> String {
+    let class = class.to_string();
+    let receiver = class[..1].to_lowercase();
+    let constructors = methods
+        .iter()
+        .filter(|m| m.role == MethodRole::Constructor)
+        .count();
+
+    let mut functions = vec![];
+    let mut destroy = None;
+    for method in methods {
+        let ident = method.sig.ident.to_string();
+        let is_method = matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_)));
+
+        let name = match method.role {
+            MethodRole::Destructor => {
+                destroy = Some(&method.symbol);
+                continue;
+            }
+            MethodRole::Constructor if constructors == 1 => format!("New{}", class),
+            MethodRole::Getter => pascal_case(ident.strip_prefix("get_").unwrap_or(&ident)),
+            _ if is_method => pascal_case(&ident),
+            _ => format!("{}{}", class, pascal_case(&ident)),
+        };
+        functions.push(function(method, &class, &receiver, &name, is_method));
+
+        if is_method && ident == "to_string" && method.sig.inputs.len() == 1 {
+            functions.push(Function {
+                source: format!(
+                    "// String implements fmt.Stringer\nfunc ({0} *{1}) String() string {{\n\treturn {0}.ToString()\n}}\n",
+                    receiver, class
+                ),
+                unsafe_pointers: false,
+                keep_alive: false,
+            });
+        }
+    }
+
+    let mut imports = vec![];
+    if destroy.is_some() || functions.iter().any(|f| f.keep_alive) {
+        imports.push("\"runtime\"");
+    }
+    if functions.iter().any(|f| f.unsafe_pointers) {
+        imports.push("\"unsafe\"");
+    }
+
+    let mut source = format!(
+        "{}package {}\n\n// #include <stdlib.h>\n// #include \"bindings.h\"\nimport \"C\"\n\n",
+        HEADER,
+        package(mod_path),
+    );
+    if !imports.is_empty() {
+        source.push_str(&format!(
+            "import (\n{})\n\n",
+            imports
+                .iter()
+                .map(|import| format!("\t{}\n", import))
+                .collect::<String>()
+        ));
+    }
+
+    source.push_str(&skipped_note(skipped, "//"));
+    source.push_str(&format!(
+        "type {0} struct {{\n\tpointer *C.{0}\n}}\n\n// new{0} takes the ownership of a pointer returned by the library\nfunc new{0}(pointer *C.{0}) *{0} {{\n\t{1} := &{0}{{pointer}}\n",
+        class, receiver
+    ));
+    match destroy {
+        Some(destroy) => source.push_str(&format!(
+            "\truntime.SetFinalizer({1}, func({1} *{0}) {{\n\t\tC.{2}({1}.pointer)\n\t}})\n\n\treturn {1}\n}}\n",
+            class, receiver, destroy
+        )),
+        None => source.push_str(&format!("\n\treturn {}\n}}\n", receiver)),
+    }
+
+    for function in functions {
+        source.push('\n');
+        source.push_str(&function.source);
+    }
+
+    source
+}
+
+/// Generate the helpers shared by all the files of a package
+pub fn support(mod_path: &[Ident]) -> String {
+    let root = "/..".repeat(mod_path.len() + 1);
+
+    format!(
+        "{}package {}
+
+// #cgo CFLAGS: -I${{SRCDIR}}{}/c
+// #cgo LDFLAGS: -lbdk_ffi
+// #include \"bindings.h\"
+import \"C\"
+
+import \"unsafe\"
+
+// takeString copies a string returned by the library and releases it
+func takeString(pointer *C.char) string {{
+\tdefer C.string_free(pointer)
+
+\treturn C.GoString(pointer)
+}}
+
+// takeBytes copies a byte buffer returned by the library and releases it
+func takeBytes(buffer C.ByteBuffer) []byte {{
+\tdefer C.byte_buffer_free(buffer)
+
+\treturn C.GoBytes(unsafe.Pointer(buffer.data), C.int(buffer.len))
+}}
+
+// bytesPointer returns a pointer to the first byte of a slice, or nil if it is empty
+func bytesPointer(bytes []byte) *C.uint8_t {{
+\tif len(bytes) == 0 {{
+\t\treturn nil
+\t}}
+
+\treturn (*C.uint8_t)(unsafe.Pointer(&bytes[0]))
+}}
+",
+        HEADER,
+        package(mod_path),
+        root
+    )
+}
+
+/// Name of the error returned when a function returns the error `ty`
+pub fn error_name(ty: &Type) -> String {
+    opaque_name(strip_reference(ty), "")
+}
+
+/// Generate the error returned when a function returns the error `name`
+pub fn error(mod_path: &[Ident], name: &str) -> String {
+    format!(
+        "{0}package {1}
+
+import \"fmt\"
+
+type {2} struct {{
+\t// Code is the error code returned by the library
+\tCode int32
+}}
+
+func (e *{2}) Error() string {{
+\treturn fmt.Sprintf(\"{2} (code %d)\", e.Code)
+}}
+",
+        HEADER,
+        package(mod_path),
+        name
+    )
+}
+
+/// Name of the file generated for the item `name`
+pub fn file_name(name: &str) -> String {
+    let mut file = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            file.push('_');
+        }
+        file.extend(c.to_lowercase());
+    }
+
+    format!("{}.go", file)
+}
@@ -0,0 +1,501 @@
+use std::cell::RefCell;
+
+use syn::{parse_quote, FnArg, Ident, Signature, Type, TypeReference};
+
+use super::super::{last_segment, pascal_case, MethodRole};
+use super::wrapper::*;
+use crate::types::*;
+
+const HEADER: &str = "# Automatically generated by the `derive` crate, do not edit\n\n";
+
+/// Module that contains everything, the path of the Rust modules is nested inside it
+const ROOT_MODULE: &str = "Bdk";
+
+/// Name of the library loaded by `ffi_lib`
+const LIBRARY: &str = "bdk_ffi";
+
+/// Ruby keywords that can be valid Rust identifiers
+const KEYWORDS: &[&str] = &[
+    "alias", "and", "begin", "case", "class", "def", "defined", "do", "end", "ensure", "false",
+    "for", "next", "nil", "not", "or", "redo", "rescue", "retry", "self", "then", "undef",
+    "unless", "until", "when", "yield",
+];
+
+thread_local! {
+    // `bdk.rb` requires every generated file, so their paths are collected while the impls are
+    // expanded and written out with the root module
+    static FILES: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Name of an argument, with a trailing underscore if it's a Ruby keyword
+fn escape(ident: &str) -> String {
+    match KEYWORDS.contains(&ident) {
+        true => format!("{}_", ident),
+        false => ident.into(),
+    }
+}
+
+/// Name of the type used by `attach_function` for primitive values
+fn primitive(ty: &Type) -> Option<&'static str> {
+    let ffi = match last_segment(ty)?.to_string().as_str() {
+        "bool" => ":bool",
+        "i8" => ":int8",
+        "i16" => ":int16",
+        "i32" => ":int32",
+        "i64" => ":int64",
+        "u8" => ":uint8",
+        "u16" => ":uint16",
+        "u32" => ":uint32",
+        "u64" => ":uint64",
+        "usize" => ":size_t",
+        "isize" => ":ssize_t",
+        "f32" => ":float",
+        "f64" => ":double",
+        _ => return None,
+    };
+
+    Some(ffi)
+}
+
+fn strip_reference(ty: &Type) -> &Type {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => elem,
+        ty => ty,
+    }
+}
+
+fn opaque_name(ty: &Type, class: &str) -> String {
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") | None => class.into(),
+        Some(other) => other.into(),
+    }
+}
+
+/// Snake case name of the file that defines `name`
+pub fn file_name(name: &str) -> String {
+    let mut file = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            file.push('_');
+        }
+        file.extend(c.to_lowercase());
+    }
+
+    file
+}
+
+/// Name of the Ruby modules of a Rust module path, starting from the root one
+fn modules(mod_path: &[Ident]) -> Vec<String> {
+    std::iter::once(ROOT_MODULE.to_string())
+        .chain(mod_path.iter().map(|m| pascal_case(&m.to_string())))
+        .collect()
+}
+
+/// Directory of the files generated for a module, relative to `lib`
+pub fn dir(mod_path: &[Ident]) -> String {
+    modules(mod_path)
+        .iter()
+        .map(|m| file_name(m))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Wrap `body` in the nested modules, indenting it accordingly
+fn nest(modules: &[String], body: &str) -> String {
+    let depth = modules.len();
+    let mut source = String::new();
+    for (i, module) in modules.iter().enumerate() {
+        source.push_str(&format!("{}module {}\n", "  ".repeat(i), module));
+    }
+    for line in body.lines() {
+        match line.is_empty() {
+            true => source.push('\n'),
+            false => source.push_str(&format!("{}{}\n", "  ".repeat(depth), line)),
+        }
+    }
+    for i in (0..depth).rev() {
+        source.push_str(&format!("{}end\n", "  ".repeat(i)));
+    }
+
+    source
+}
+
+/// `attach_function` declaration of the function exported for `method`
+fn attach(method: &Method) -> String {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(error)),
+        None => (output, None),
+    };
+
+    let mut params = vec![];
+    if let Some(FnArg::Receiver(_)) = sig.inputs.first() {
+        params.push(":pointer");
+    }
+    for (_, ty) in typed_args(sig) {
+        let ty = strip_reference(ty);
+        if is_bytes(ty) {
+            params.push(":pointer");
+        }
+        params.push(if is_string(ty) {
+            ":string"
+        } else if is_bytes(ty) {
+            ":size_t"
+        } else if let Some(primitive) = primitive(ty) {
+            primitive
+        } else {
+            ":pointer"
+        });
+    }
+    if by_reference(&ok) || (error.is_some() && ok != parse_quote!(())) {
+        params.push(":pointer");
+    }
+    if object_vec(&ok).is_some() {
+        // length of the array
+        params.push(":pointer");
+    }
+
+    let ret = if error.is_some() {
+        ":int32"
+    } else if is_string(&ok) {
+        ":pointer"
+    } else if is_bytes(&ok) {
+        "ByteBuffer.by_value"
+    } else if by_reference(&ok) || ok == parse_quote!(()) {
+        ":void"
+    } else {
+        primitive(&ok).unwrap_or(":pointer")
+    };
+
+    format!(
+        "attach_function :{}, [{}], {}\n",
+        method.symbol,
+        params.join(", "),
+        ret
+    )
+}
+
+/// Type read from an out-param with `FFI::Pointer#read`
+fn out_type(ty: &Type) -> &'static str {
+    primitive(ty).unwrap_or(":pointer")
+}
+
+/// Expression that takes the ownership of `value`, a native value of type `ty`
+fn take(ty: &Type, class: &str, value: &str) -> String {
+    if is_string(ty) {
+        format!("Native.take_string({})", value)
+    } else if is_bytes(ty) {
+        format!("Native.take_bytes({})", value)
+    } else if is_opaque(ty) {
+        format!("{}.new({})", opaque_name(ty, class), value)
+    } else {
+        value.into()
+    }
+}
+
+/// Statements calling the attached function, the last one being the returned value
+fn body(method: &Method, class: &str) -> Vec<String> {
+    let sig = &method.sig;
+    let output = sig.output.as_type();
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(opaque_name(strip_reference(&error), class))),
+        None => (output, None),
+    };
+    let by_ref = by_reference(&ok) || (error.is_some() && ok != parse_quote!(()));
+
+    let mut args = vec![];
+    if let Some(FnArg::Receiver(_)) = sig.inputs.first() {
+        args.push("@pointer".to_string());
+    }
+    for (name, ty) in typed_args(sig) {
+        let name = escape(&name);
+        let ty = strip_reference(ty);
+        if is_bytes(ty) {
+            args.push(format!("Native.bytes_pointer({})", name));
+            args.push(format!("{}.bytesize", name));
+        } else if is_opaque(ty) {
+            args.push(format!("{}.pointer", name));
+        } else {
+            args.push(name);
+        }
+    }
+
+    let mut lines = vec![];
+    let inner = option_type(&ok).unwrap_or_else(|| ok.clone());
+    if by_ref && is_bytes(&inner) {
+        lines.push("out = Native::ByteBuffer.new".into());
+        args.push("out".into());
+    } else if object_vec(&inner).is_some() {
+        lines.push("out = FFI::MemoryPointer.new(:pointer)".into());
+        lines.push("out_len = FFI::MemoryPointer.new(:size_t)".into());
+        args.push("out".into());
+        args.push("out_len".into());
+    } else if by_ref {
+        lines.push(format!(
+            "out = FFI::MemoryPointer.new({})",
+            out_type(&inner)
+        ));
+        args.push("out".into());
+    }
+
+    let call = format!("Native.{}({})", method.symbol, args.join(", "));
+    match &error {
+        Some(error) => {
+            lines.push(format!("code = {}", call));
+            lines.push(format!("raise {}.new(code) unless code.zero?", error));
+        }
+        None if by_ref || ok == parse_quote!(()) => lines.push(call.clone()),
+        None => {}
+    }
+
+    if ok == parse_quote!(()) {
+        lines.push("nil".into());
+    } else if by_ref && is_bytes(&inner) {
+        lines.push(take(&inner, class, "out"));
+    } else if let Some(elem) = object_vec(&inner) {
+        let name = opaque_name(&elem, class);
+        lines.push(format!(
+            "Native.take_array(out.read_pointer, out_len.read(:size_t), :{}) {{ |pointer| {}.new(pointer) }}",
+            array_free(&name),
+            name
+        ));
+    } else if by_ref {
+        lines.push(match is_primitive(&inner) {
+            true => format!("value = out.read({})", out_type(&inner)),
+            false => "value = out.read_pointer".into(),
+        });
+        match option_type(&ok) {
+            Some(_) if !is_primitive(&inner) => lines.push(format!(
+                "value.null? ? nil : {}",
+                take(&inner, class, "value")
+            )),
+            _ => lines.push(take(&inner, class, "value")),
+        }
+    } else {
+        lines.push(take(&ok, class, &call));
+    }
+
+    lines
+}
+
+fn def(name: &str, params: &[String], lines: Vec<String>) -> String {
+    let params = match params.is_empty() {
+        true => String::new(),
+        false => format!("({})", params.join(", ")),
+    };
+    let body = lines
+        .into_iter()
+        .map(|line| format!("  {}\n", line))
+        .collect::<String>();
+
+    format!("def {}{}\n{}end\n", name, params, body)
+}
+
+fn params(sig: &Signature) -> Vec<String> {
+    typed_args(sig)
+        .into_iter()
+        .map(|(name, _)| escape(&name))
+        .collect()
+}
+
+/// Generate the class that owns the opaque pointer of an exposed struct
+pub fn class(mod_path: &[Ident], class: &Ident, methods: &[Method], skipped: &[Skipped]) -> String {
+    let name = class.to_string();
+    let destroy = methods
+        .iter()
+        .find(|m| m.role == MethodRole::Destructor)
+        .map(|m| &m.symbol);
+
+    let attachments = methods
+        .iter()
+        .map(attach)
+        .chain(std::iter::once(format!(
+            "attach_function :{}, [:pointer, :size_t], :void\n",
+            array_free(&name)
+        )))
+        .collect::<String>();
+
+    let mut source = String::new();
+    match destroy {
+        Some(destroy) => source.push_str(&format!(
+            "# Releases the native object when it's garbage collected
+class Pointer < FFI::AutoPointer
+  def self.release(pointer)
+    Native.{}(pointer)
+  end
+end
+
+# @api private
+def initialize(pointer)
+  @pointer = Pointer.new(pointer)
+end
+",
+            destroy
+        )),
+        None => {
+            source.push_str("# @api private\ndef initialize(pointer)\n  @pointer = pointer\nend\n")
+        }
+    }
+    source.push_str("\n# Pointer to pass to the native functions, still owned by this object\nattr_reader :pointer\n");
+
+    for method in methods {
+        let sig = &method.sig;
+        let ident = sig.ident.to_string();
+
+        let definition = match method.role {
+            MethodRole::Destructor => continue,
+            MethodRole::Getter => def(
+                ident.strip_prefix("get_").unwrap_or(&ident),
+                &params(sig),
+                body(method, &name),
+            ),
+            _ => match sig.inputs.first() {
+                Some(FnArg::Receiver(_)) => def(&ident, &params(sig), body(method, &name)),
+                _ => def(
+                    &format!("self.{}", ident),
+                    &params(sig),
+                    body(method, &name),
+                ),
+            },
+        };
+        source.push('\n');
+        source.push_str(&definition);
+
+        if ident == "to_string" && sig.inputs.len() == 1 {
+            source.push_str("alias to_s to_string\n");
+        }
+    }
+
+    let indented = source
+        .lines()
+        .map(|line| match line.is_empty() {
+            true => "\n".to_string(),
+            false => format!("  {}\n", line),
+        })
+        .collect::<String>();
+    let class = format!(
+        "{}class {}\n{}end\n",
+        skipped_note(skipped, "#"),
+        name,
+        indented
+    );
+
+    let attachments = nest(
+        &[ROOT_MODULE.to_string()],
+        &format!("module Native\n{}end\n", indent(&attachments)),
+    );
+    let class = nest(&modules(mod_path), &class);
+
+    FILES.with(|files| {
+        let file = format!("{}/{}", dir(mod_path), file_name(&name));
+        let mut files = files.borrow_mut();
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    });
+
+    format!("{}{}\n{}", HEADER, attachments, class)
+}
+
+fn indent(source: &str) -> String {
+    source.lines().map(|line| format!("  {}\n", line)).collect()
+}
+
+/// Name of the file of the error `ty`, relative to `lib`
+pub fn error_file(ty: &Type) -> (String, String) {
+    let name = opaque_name(strip_reference(ty), "");
+    let file = format!("{}/{}", file_name(ROOT_MODULE), file_name(&name));
+
+    FILES.with(|files| {
+        let mut files = files.borrow_mut();
+        if !files.contains(&file) {
+            files.insert(0, file.clone());
+        }
+    });
+
+    (name, format!("{}.rb", file))
+}
+
+/// Generate the exception raised when a function returns the error `name`
+pub fn error(name: &str) -> String {
+    let class = format!(
+        "class {0} < StandardError
+  # Error code returned by the library
+  attr_reader :code
+
+  def initialize(code)
+    @code = code
+    super(\"{0} (code #{{code}})\")
+  end
+end
+",
+        name
+    );
+
+    format!("{}{}", HEADER, nest(&[ROOT_MODULE.to_string()], &class))
+}
+
+/// Generate the entry point of the gem, which loads the library and requires all the files
+/// collected so far
+pub fn entry_point() -> String {
+    let files = FILES.with(|files| files.take());
+
+    let native = format!(
+        "module Native
+  extend FFI::Library
+  ffi_lib ENV.fetch('BDK_FFI_LIB', '{}')
+
+  attach_function :string_free, [:pointer], :void
+
+  # Copy a string returned by the library and release it
+  def self.take_string(pointer)
+    pointer.read_string.force_encoding(Encoding::UTF_8)
+  ensure
+    string_free(pointer)
+  end
+
+  # Buffer of bytes returned by the library
+  class ByteBuffer < FFI::Struct
+    layout :data, :pointer, :len, :size_t, :cap, :size_t
+  end
+
+  attach_function :byte_buffer_free, [ByteBuffer.by_value], :void
+
+  # Copy the bytes returned by the library and release them
+  def self.take_bytes(buffer)
+    buffer[:data].read_bytes(buffer[:len])
+  ensure
+    byte_buffer_free(buffer)
+  end
+
+  # Take the ownership of the objects of an array returned by the library, and release the array
+  def self.take_array(array, len, free)
+    values = Array.new(len) do |i|
+      pointer = array.get_pointer(i * FFI::Pointer.size)
+      array.put_pointer(i * FFI::Pointer.size, nil)
+      yield pointer
+    end
+    send(free, array, len)
+    values
+  end
+
+  # Copy a binary string to native memory, which is only borrowed by the library
+  def self.bytes_pointer(bytes)
+    FFI::MemoryPointer.new(:uint8, bytes.bytesize).put_bytes(0, bytes)
+  end
+end
+",
+        LIBRARY
+    );
+
+    format!(
+        "{}require 'ffi'\n\n{}\n{}",
+        HEADER,
+        nest(&[ROOT_MODULE.to_string()], &native),
+        files
+            .iter()
+            .map(|file| format!("require_relative '{}'\n", file))
+            .collect::<String>()
+    )
+}
@@ -0,0 +1,337 @@
+use syn::{parse_quote, FnArg, Ident, Receiver, Signature, Type, TypeReference};
+
+use super::super::{camel_case, last_segment, MethodRole};
+use super::wrapper::*;
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n\n";
+
+/// Name of the Clang module that wraps the C header
+pub const FFI_MODULE: &str = "BdkFFI";
+
+/// Swift type of primitive values, which are passed unchanged through the C ABI
+fn primitive(ty: &Type) -> Option<&'static str> {
+    let swift = match last_segment(ty)?.to_string().as_str() {
+        "bool" => "Bool",
+        "i8" => "Int8",
+        "i16" => "Int16",
+        "i32" => "Int32",
+        "i64" => "Int64",
+        "u8" => "UInt8",
+        "u16" => "UInt16",
+        "u32" => "UInt32",
+        "u64" => "UInt64",
+        "usize" => "Int",
+        "isize" => "Int",
+        "f32" => "Float",
+        "f64" => "Double",
+        _ => return None,
+    };
+
+    Some(swift)
+}
+
+/// Swift type exposed to the users of the bindings
+fn swift_type(ty: &Type, class: &str) -> String {
+    if let Type::Reference(TypeReference { elem, .. }) = ty {
+        return swift_type(elem, class);
+    }
+    if *ty == parse_quote!(()) {
+        return "Void".into();
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+        return format!("{}?", swift_type(&inner[0], class));
+    }
+    if let Some((ok, _)) = result_types(ty) {
+        return swift_type(&ok, class);
+    }
+    if let Some(primitive) = primitive(ty) {
+        return primitive.into();
+    }
+    if is_string(ty) {
+        return "String".into();
+    }
+    if is_bytes(ty) {
+        return "[UInt8]".into();
+    }
+    if let Some(elem) = object_vec(ty) {
+        return format!("[{}]", swift_type(&elem, class));
+    }
+
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") | None => class.into(),
+        Some(other) => other.into(),
+    }
+}
+
+/// Expression that takes the ownership of the value written to `out` by the C function
+fn take_out(ty: &Type, class: &str, out: &str) -> String {
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+        let inner = &inner[0];
+        return match is_string(inner) {
+            true => format!("{}.map {{ takeString($0) }}", out),
+            false => format!(
+                "{}.map {{ {}(pointer: $0) }}",
+                out,
+                swift_type(inner, class)
+            ),
+        };
+    }
+
+    if is_string(ty) {
+        format!("takeString({}!)", out)
+    } else if is_bytes(ty) {
+        format!("takeBytes({})", out)
+    } else if let Some(elem) = object_vec(ty) {
+        let name = swift_type(&elem, class);
+        format!(
+            "takeArray({}!, {}Len, {}) {{ {}(pointer: $0) }}",
+            out,
+            out,
+            array_free(&name),
+            name
+        )
+    } else if primitive(ty).is_some() {
+        out.into()
+    } else {
+        format!("{}(pointer: {}!)", swift_type(ty, class), out)
+    }
+}
+
+/// Declaration of the variable that receives an out-param
+fn out_var(ty: &Type) -> String {
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+        return match is_string(&inner[0]) {
+            true => "var out: UnsafeMutablePointer<CChar>? = nil".into(),
+            false => "var out: OpaquePointer? = nil".into(),
+        };
+    }
+
+    if is_string(ty) {
+        "var out: UnsafeMutablePointer<CChar>? = nil".into()
+    } else if is_bytes(ty) {
+        "var out = ByteBuffer()".into()
+    } else if object_vec(ty).is_some() {
+        "var out: UnsafeMutablePointer<OpaquePointer?>? = nil".into()
+    } else if let Some(primitive) = primitive(ty) {
+        let zero = match primitive {
+            "Bool" => "false",
+            _ => "0",
+        };
+        format!("var out: {} = {}", primitive, zero)
+    } else {
+        "var out: OpaquePointer? = nil".into()
+    }
+}
+
+fn params(sig: &Signature, class: &str) -> String {
+    typed_args(sig)
+        .into_iter()
+        .map(|(name, ty)| format!("{}: {}", camel_case(&name), swift_type(ty, class)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Arguments passed to the C function, not including the out-params
+fn call_args(sig: &Signature) -> Vec<String> {
+    sig.inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Receiver(Receiver { .. }) => "pointer".to_string(),
+            FnArg::Typed(_) => String::new(),
+        })
+        .filter(|arg| !arg.is_empty())
+        .chain(typed_args(sig).into_iter().map(|(name, ty)| {
+            let elem = match ty {
+                Type::Reference(TypeReference { elem, .. }) => elem.as_ref(),
+                ty => ty,
+            };
+            if is_bytes(elem) {
+                // arrays are passed as pointers that are only valid for the duration of the call
+                format!("{0}, {0}.count", camel_case(&name))
+            } else if is_opaque(elem) {
+                format!("{}.pointer", camel_case(&name))
+            } else {
+                camel_case(&name)
+            }
+        }))
+        .collect()
+}
+
+/// Statements calling the C function, ending with a `return` of the converted result
+fn body(method: &Method, class: &str, indent: &str) -> String {
+    let output = method.sig.output.as_type();
+    let mut args = call_args(&method.sig);
+    let symbol = &method.symbol;
+
+    let (ok, error) = match result_types(&output) {
+        Some((ok, error)) => (ok, Some(swift_type(&error, class))),
+        None => (output, None),
+    };
+    let out = by_reference(&ok) || (error.is_some() && ok != parse_quote!(()));
+
+    let mut lines = vec![];
+    if out {
+        lines.push(out_var(&ok));
+        args.push("&out".into());
+    }
+    if object_vec(&ok).is_some() {
+        lines.push("var outLen = 0".into());
+        args.push("&outLen".into());
+    }
+
+    let call = format!("{}({})", symbol, args.join(", "));
+    match &error {
+        Some(error) => {
+            lines.push(format!("let code = {}", call));
+            lines.push(format!(
+                "guard code == 0 else {{ throw {}(code: code) }}",
+                error
+            ));
+        }
+        None if out || ok == parse_quote!(()) => lines.push(call.clone()),
+        None => {}
+    }
+
+    let ret = if out {
+        Some(take_out(&ok, class, "out"))
+    } else if ok == parse_quote!(()) || error.is_some() {
+        None
+    } else if is_string(&ok) {
+        Some(format!("takeString({}!)", call))
+    } else if is_bytes(&ok) {
+        Some(format!("takeBytes({})", call))
+    } else {
+        Some(call)
+    };
+    if let Some(ret) = ret {
+        lines.push(format!("return {}", ret));
+    }
+
+    lines
+        .into_iter()
+        .map(|line| format!("{}{}\n", indent, line))
+        .collect()
+}
+
+fn throws(sig: &Signature) -> &'static str {
+    match result_types(&sig.output.as_type()) {
+        Some(_) => " throws",
+        None => "",
+    }
+}
+
+fn returns(sig: &Signature, class: &str) -> String {
+    match swift_type(&sig.output.as_type(), class).as_str() {
+        "Void" => String::new(),
+        ty => format!(" -> {}", ty),
+    }
+}
+
+/// Generate the class that owns the opaque pointer of an exposed struct
+pub fn class(class: &Ident, methods: &[Method], skipped: &[Skipped]) -> String {
+    let class = class.to_string();
+
+    let mut source = format!(
+        "{}import {}\n\n{}public class {} {{\n    let pointer: OpaquePointer\n\n    init(pointer: OpaquePointer) {{\n        self.pointer = pointer\n    }}\n",
+        HEADER,
+        FFI_MODULE,
+        skipped_note(skipped, "//"),
+        class
+    );
+    let mut description = false;
+
+    for method in methods {
+        let sig = &method.sig;
+        let name = sig.ident.to_string();
+
+        source.push('\n');
+        match method.role {
+            MethodRole::Destructor => source.push_str(&format!(
+                "    deinit {{\n        {}(pointer)\n    }}\n",
+                method.symbol
+            )),
+            MethodRole::Constructor => {
+                let mut body = body(method, &class, "        ");
+                // the constructor initializes `self` with the pointer instead of returning it
+                body = body.replace(
+                    &format!("return {}(pointer: out!)", class),
+                    "self.init(pointer: out!)",
+                );
+                source.push_str(&format!(
+                    "    public convenience init({}){} {{\n{}    }}\n",
+                    params(sig, &class),
+                    throws(sig),
+                    body
+                ));
+            }
+            MethodRole::Getter => source.push_str(&format!(
+                "    public var {}: {} {{\n{}    }}\n",
+                camel_case(name.strip_prefix("get_").unwrap_or(&name)),
+                swift_type(&sig.output.as_type(), &class),
+                body(method, &class, "        ")
+            )),
+            // setters stay methods, since Swift property setters can't throw
+            MethodRole::Setter | MethodRole::Regular => {
+                let modifier = match sig.inputs.first() {
+                    Some(FnArg::Receiver(_)) => "",
+                    _ => "static ",
+                };
+                if name == "to_string" && sig.inputs.len() == 1 && modifier.is_empty() {
+                    description = true;
+                }
+
+                source.push_str(&format!(
+                    "    public {}func {}({}){}{} {{\n{}    }}\n",
+                    modifier,
+                    camel_case(&name),
+                    params(sig, &class),
+                    throws(sig),
+                    returns(sig, &class),
+                    body(method, &class, "        ")
+                ));
+            }
+        }
+    }
+    source.push_str("}\n");
+
+    if description {
+        source.push_str(&format!(
+            "\nextension {}: CustomStringConvertible {{\n    public var description: String {{\n        return toString()\n    }}\n}}\n",
+            class
+        ));
+    }
+
+    source.push_str(
+        "\n/// Take the ownership of a string returned by the library\nfileprivate func takeString(_ pointer: UnsafeMutablePointer<CChar>) -> String {\n    defer { string_free(pointer) }\n\n    return String(cString: pointer)\n}\n",
+    );
+    source.push_str(
+        "\n/// Take the ownership of a byte buffer returned by the library\nfileprivate func takeBytes(_ buffer: ByteBuffer) -> [UInt8] {\n    defer { byte_buffer_free(buffer) }\n\n    return Array(UnsafeBufferPointer(start: buffer.data, count: Int(buffer.len)))\n}\n",
+    );
+    source.push_str(
+        "\n/// Take the ownership of the objects of an array returned by the library, and release the array\nfileprivate func takeArray<T>(_ array: UnsafeMutablePointer<OpaquePointer?>, _ len: Int, _ free: (UnsafeMutablePointer<OpaquePointer?>?, Int) -> Void, _ take: (OpaquePointer) -> T) -> [T] {\n    let values = (0..<len).map { i -> T in\n        defer { array[i] = nil }\n        return take(array[i]!)\n    }\n    free(array, len)\n\n    return values\n}\n",
+    );
+
+    source
+}
+
+/// Name of the error thrown when a function returns the error `ty`
+pub fn error_name(ty: &Type) -> String {
+    swift_type(ty, "")
+}
+
+/// Generate the error thrown when a function returns the error `name`
+pub fn error(name: &str) -> String {
+    format!(
+        "{}public struct {}: Error {{\n    /// Error code returned by the library\n    public let code: Int32\n}}\n",
+        HEADER, name
+    )
+}
+
+/// Generate the module map that exposes the header written by cbindgen to Swift
+pub fn module_map(header: &str) -> String {
+    format!(
+        "{}module {} {{\n    header \"{}\"\n    link \"bdk_ffi\"\n    export *\n}}\n",
+        HEADER, FFI_MODULE, header
+    )
+}
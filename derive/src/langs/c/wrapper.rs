@@ -0,0 +1,162 @@
+//! Helpers shared by the languages that wrap the C ABI, which need to know the original signature
+//! of the exported functions
+
+use syn::{
+    parse_quote, FnArg, Ident, Pat, PatIdent, PatType, Signature, Type, TypePath, TypeReference,
+};
+
+use super::super::{struct_kind, MethodRole, StructKind};
+use crate::types::*;
+
+/// Method of an exposed `impl`, before it's converted to a C function
+pub struct Method {
+    pub role: MethodRole,
+    pub sig: Signature,
+    /// Name of the function exported by the C backend
+    pub symbol: Ident,
+}
+
+/// Method of an exposed `impl` that the wrappers don't cover yet, which is only available through
+/// its C function
+pub struct Skipped {
+    pub symbol: Ident,
+    pub reason: &'static str,
+}
+
+/// Comment listing the methods of a class that are not wrapped, with every line starting with
+/// `comment`
+pub fn skipped_note(skipped: &[Skipped], comment: &str) -> String {
+    if skipped.is_empty() {
+        return String::new();
+    }
+
+    let mut note = format!(
+        "{} Not wrapped yet, only available through the C API:\n",
+        comment
+    );
+    for method in skipped {
+        note.push_str(&format!(
+            "{} - {}: {}\n",
+            comment, method.symbol, method.reason
+        ));
+    }
+    note
+}
+
+/// Primitive types are passed unchanged through the C ABI
+#[cfg(any(feature = "ruby", feature = "dart"))]
+pub fn is_primitive(ty: &Type) -> bool {
+    matches!(
+        super::super::last_segment(ty)
+            .map(Ident::to_string)
+            .as_deref(),
+        Some(
+            "bool"
+                | "i8"
+                | "i16"
+                | "i32"
+                | "i64"
+                | "isize"
+                | "u8"
+                | "u16"
+                | "u32"
+                | "u64"
+                | "usize"
+                | "f32"
+                | "f64"
+        )
+    )
+}
+
+/// `String` and `&str`, which are passed as C strings
+pub fn is_string(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => is_string(elem),
+        ty => *ty == parse_quote!(String) || *ty == parse_quote!(str),
+    }
+}
+
+/// `Vec<u8>` and `&[u8]`, which are passed as a pointer and a length, and returned as a
+/// `ByteBuffer`
+pub fn is_bytes(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => is_bytes(elem),
+        ty => *ty == parse_quote!(Vec<u8>) || *ty == parse_quote!([u8]),
+    }
+}
+
+/// Structs exposed with the `"opaque"` option, shared or not, which are owned by a class of the
+/// target language
+///
+/// `Self` is the struct of the wrapped impl, only the impls of opaque structs are wrapped.
+pub fn is_opaque(ty: &Type) -> bool {
+    match ty {
+        Type::Path(TypePath { path, .. }) if path.is_ident("Self") => true,
+        _ => matches!(
+            struct_kind(ty),
+            Some(StructKind::Opaque | StructKind::Shared)
+        ),
+    }
+}
+
+/// Element of a `Vec` of opaque structs, returned as an array of pointers and a length that are
+/// written to two out-params. The array is released with [`array_free`] once the objects are taken
+/// out of it, by replacing their pointer with `NULL`.
+pub fn object_vec(ty: &Type) -> Option<Type> {
+    match_generic_type(ty, parse_quote!(Vec))
+        .map(|inner| inner[0].clone())
+        .filter(is_opaque)
+}
+
+/// C function releasing the arrays of the opaque struct `name`
+pub fn array_free(name: &str) -> String {
+    format!("{}_array_free", name.to_lowercase())
+}
+
+pub fn option_type(ty: &Type) -> Option<Type> {
+    match_generic_type(ty, parse_quote!(Option)).map(|inner| inner[0].clone())
+}
+
+pub fn result_types(ty: &Type) -> Option<(Type, Type)> {
+    match_generic_type(ty, parse_quote!(Result)).and_then(|inner| match inner.as_slice() {
+        [ok, err] => Some((ok.clone(), err.clone())),
+        _ => None,
+    })
+}
+
+/// Whether the C function writes its result to an out-param instead of returning it
+pub fn by_reference(ty: &Type) -> bool {
+    is_opaque(ty) || option_type(ty).is_some() || object_vec(ty).is_some()
+}
+
+/// Name and type of the arguments that are not the receiver
+pub fn typed_args(sig: &Signature) -> Vec<(String, &Type)> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.as_ref() {
+                Pat::Ident(PatIdent { ident, .. }) => Some((
+                    ident.to_string().trim_start_matches('_').to_string(),
+                    ty.as_ref(),
+                )),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Error types returned through a `Result` by `methods`
+pub fn errors(methods: &[Method]) -> Vec<Type> {
+    let mut errors: Vec<Type> = vec![];
+    for (_, error) in methods
+        .iter()
+        .filter_map(|m| result_types(&m.sig.output.as_type()))
+    {
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+
+    errors
+}
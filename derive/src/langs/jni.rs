@@ -0,0 +1,808 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemFn, ItemStruct, Pat, PatIdent,
+    Receiver, Signature, Stmt, Token, TypePath, TypeReference,
+};
+
+use super::*;
+use crate::idl;
+
+mod kotlin;
+
+/// Package of the generated Kotlin sources, exposed modules are nested inside it
+pub const PACKAGE: &str = "org.bitcoindevkit";
+/// Directory where the Kotlin sources are written, relative to the root of the crate
+const KOTLIN_DIR: &str = "jvm/src/main/kotlin";
+
+#[derive(Debug)]
+pub struct Jni;
+
+thread_local! {
+    /// Signatures of the functions exposed so far as seen from Kotlin, by path, since the original
+    /// functions lose their `#[default(...)]` attributes
+    static KOTLIN_SIGNATURES: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Signature seen from Kotlin, where the arguments marked with `#[default(...)]` are optional
+fn kotlin_signature(sig: &Signature) -> Result<Signature, JniError> {
+    let mut sig = collected_sig(sig).unwrap_or_else(|| sig.clone());
+    for input in sig.inputs.iter_mut() {
+        if let FnArg::Typed(arg) = input {
+            take_default(arg)?;
+        }
+    }
+
+    Ok(sig)
+}
+
+fn kotlin_package(mod_path: &[Ident]) -> String {
+    std::iter::once(PACKAGE.to_string())
+        .chain(mod_path.iter().map(Ident::to_string))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Escape a name as described in the "Resolving Native Method Names" section of the JNI spec
+fn jni_mangle(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '.' | '/' => "_".to_string(),
+            '_' => "_1".to_string(),
+            ';' => "_2".to_string(),
+            '[' => "_3".to_string(),
+            c if c.is_ascii_alphanumeric() => c.to_string(),
+            c => format!("_0{:04x}", c as u32),
+        })
+        .collect()
+}
+
+/// Name of the symbol the JVM looks up for `native` declared in the file facade `facade`
+fn export_ident(package: &str, facade: &str, native: &str) -> Ident {
+    format_ident!(
+        "Java_{}_{}",
+        jni_mangle(&format!("{}.{}", package, facade)),
+        jni_mangle(native)
+    )
+}
+
+/// Primitive types, with their JNI counterpart
+fn jni_primitive(ty: &Type) -> Option<Type> {
+    let ty = match last_segment(ty)?.to_string().as_str() {
+        "bool" => parse_quote!(::jni::sys::jboolean),
+        "i8" => parse_quote!(::jni::sys::jbyte),
+        "i16" => parse_quote!(::jni::sys::jshort),
+        "i32" | "u8" | "u16" => parse_quote!(::jni::sys::jint),
+        "i64" | "u32" | "u64" | "usize" => parse_quote!(::jni::sys::jlong),
+        "f32" => parse_quote!(::jni::sys::jfloat),
+        "f64" => parse_quote!(::jni::sys::jdouble),
+        _ => return None,
+    };
+
+    Some(ty)
+}
+
+/// Method and signature of the boxed class of a primitive type, to unbox it, with the `JValue`
+/// accessor of the unboxed value
+fn unboxing(ty: &Type) -> Option<(&'static str, &'static str, Ident)> {
+    let (method, signature, accessor) = match last_segment(ty)?.to_string().as_str() {
+        "bool" => ("booleanValue", "()Z", "z"),
+        "i8" => ("byteValue", "()B", "b"),
+        "i16" => ("shortValue", "()S", "s"),
+        "i32" | "u8" | "u16" => ("intValue", "()I", "i"),
+        "i64" | "u32" | "u64" | "usize" => ("longValue", "()J", "j"),
+        "f32" => ("floatValue", "()F", "f"),
+        "f64" => ("doubleValue", "()D", "d"),
+        _ => return None,
+    };
+
+    Some((method, signature, format_ident!("{}", accessor)))
+}
+
+/// `Vec<u8>` and `&[u8]`, exchanged as a `ByteArray`
+fn is_bytes(ty: &Type) -> bool {
+    *ty == parse_quote!(Vec<u8>) || *ty == parse_quote!(&[u8])
+}
+
+/// Element of a `Vec` of opaque structs, exchanged as an array of handles
+fn object_vec(ty: &Type) -> Option<Type> {
+    match_generic_type(ty, parse_quote!(Vec))
+        .map(|inner| inner[0].clone())
+        .filter(is_opaque)
+}
+
+/// Everything that is not a primitive, a string or a generic wrapper is assumed to be an exposed
+/// opaque struct, passed around as a handle
+fn is_opaque(ty: &Type) -> bool {
+    match ty {
+        Type::Path(TypePath { path, .. }) => {
+            jni_primitive(ty).is_none()
+                && !match_fixed_type(ty, parse_quote!(String))
+                && *ty != parse_quote!(str)
+                && path.segments.last().is_some_and(|s| s.arguments.is_empty())
+        }
+        _ => false,
+    }
+}
+
+impl Jni {
+    /// Build the function exported to the JVM, which converts the arguments, calls `call` and
+    /// converts the result back
+    fn export_fn(
+        sig: &Signature,
+        export: Ident,
+        prelude: TokenStream2,
+        call: TokenStream2,
+    ) -> Result<ItemFn, JniError> {
+        // iterators are returned as an array
+        let collected = collected_sig(sig);
+        let sig = collected.as_ref().unwrap_or(sig);
+
+        let mut args = Punctuated::<FnArg, Comma>::default();
+        let mut input_conversion = TokenStream2::default();
+        let mut call_args = Punctuated::<TokenStream2, Comma>::default();
+
+        for input in &sig.inputs {
+            match input {
+                FnArg::Receiver(Receiver {
+                    reference,
+                    mutability,
+                    ..
+                }) => {
+                    let this = format_ident!("__self");
+                    args.push(parse_quote!(#this: ::jni::sys::jlong));
+                    input_conversion.extend(match (reference, mutability) {
+                        (Some(_), Some(_)) => {
+                            quote! { let #this = unsafe { &mut *(#this as *mut Self) }; }
+                        }
+                        (Some(_), None) => {
+                            quote! { let #this = unsafe { &*(#this as *const Self) }; }
+                        }
+                        (None, _) => {
+                            quote! { let #this = unsafe { *Box::from_raw(#this as *mut Self) }; }
+                        }
+                    });
+                    call_args.push(quote! { #this });
+                }
+                FnArg::Typed(arg) => {
+                    let ident = match arg.pat.as_ref() {
+                        Pat::Ident(PatIdent { ident, .. }) => ident.clone(),
+                        pat => return Err(LangError::ComplexPatternFnArg(pat.span()).into()),
+                    };
+
+                    let (input, call_arg) = match optional_borrowed(&arg.ty) {
+                        Some(owned) => {
+                            let mut arg = arg.clone();
+                            arg.ty = Box::new(owned);
+                            (FnArg::Typed(arg), quote! { #ident.as_deref() })
+                        }
+                        None => (input.clone(), quote! { #ident }),
+                    };
+
+                    let ExpandedArgument {
+                        args: expanded,
+                        conv,
+                    } = Argument(input).expand(Self::convert_input)?;
+                    args.extend(expanded);
+                    input_conversion.extend(conv.into_inner());
+                    call_args.push(call_arg);
+                }
+            }
+        }
+
+        let ExpandedReturn {
+            ret,
+            conv: output_conversion,
+            ..
+        } = Return(sig.output.clone()).expand(
+            &format_ident!("__output"),
+            &format_ident!("__ptr_out"),
+            Self::convert_output,
+        )?;
+
+        // the JVM calls are blocking, so async functions are run to completion on the runtime
+        let mut call = match sig.asyncness {
+            Some(_) => quote! { crate::runtime::block_on(#call(#call_args)) },
+            None => quote! { #call(#call_args) },
+        };
+        if collected.is_some() {
+            call = quote! { #call.collect::<Vec<_>>() };
+        }
+
+        Ok(parse_quote! {
+            #[no_mangle]
+            #[allow(non_snake_case, unused_variables)]
+            pub extern "system" fn #export(__env: ::jni::JNIEnv, _: ::jni::objects::JClass, #args) #ret {
+                use crate::langs::*;
+
+                #prelude
+                #input_conversion
+
+                let __output = #call;
+                #output_conversion
+            }
+        })
+    }
+
+    /// Optional objects are passed as the handle `0` when they are missing, and the other values as
+    /// nullable references, with the primitives in their boxed class
+    fn convert_optional_input(ty: Type, inner: Type) -> Result<Input, JniError> {
+        let (elem, mutable) = match &inner {
+            Type::Reference(TypeReference {
+                elem, mutability, ..
+            }) => (elem.as_ref().clone(), Some(mutability.is_some())),
+            _ => (inner.clone(), None),
+        };
+
+        if is_opaque(&elem) {
+            return Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jlong)],
+                move |_, ident| {
+                    let object = match mutable {
+                        Some(true) => quote! { unsafe { &mut *(#ident as *mut #elem) } },
+                        Some(false) => quote! { unsafe { &*(#ident as *const #elem) } },
+                        None => quote! { unsafe { *Box::from_raw(#ident as *mut #elem) } },
+                    };
+                    let ts = quote! {
+                        match #ident {
+                            0 => None,
+                            #ident => Some(#object),
+                        }
+                    };
+                    ts.into()
+                },
+            ));
+        }
+
+        if let Some((method, signature, accessor)) = unboxing(&inner) {
+            let value = match match_fixed_type(&inner, parse_quote!(bool)) {
+                true => quote! { __value },
+                false => quote! { __value as #inner },
+            };
+            return Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jobject)],
+                move |_, ident| {
+                    let ts = quote! {
+                        match #ident.is_null() {
+                            true => None,
+                            false => {
+                                let __value = __env
+                                    .call_method(#ident, #method, #signature, &[])
+                                    .and_then(|value| value.#accessor())
+                                    .expect("Invalid incoming value");
+                                Some(#value)
+                            }
+                        }
+                    };
+                    ts.into()
+                },
+            ));
+        }
+
+        // borrowed strings and bytes are replaced by their owned version in `export_fn`
+        let span = ty.span();
+        match Self::convert_input(inner.clone())? {
+            Input::Custom {
+                sources, expand, ..
+            } if mutable.is_none() && sources.len() == 1 => {
+                Ok(Input::new_custom(ty, sources, move |_, ident| {
+                    let value = expand(&inner, ident);
+                    let ts = quote! {
+                        match #ident.is_null() {
+                            true => None,
+                            false => Some(#value),
+                        }
+                    };
+                    ts.into()
+                }))
+            }
+            _ => Err(JniError::UnsupportedType(span)),
+        }
+    }
+
+    /// Write the exception classes for the errors returned by `outputs`
+    fn write_exceptions<I: IntoIterator<Item = Type>>(outputs: I) -> Result<(), JniError> {
+        for error in outputs
+            .into_iter()
+            .filter_map(|ty| kotlin::result_error(&ty))
+        {
+            let name = kotlin::kotlin_type(&error, None);
+            write_generated(
+                &format!("{}/{}", KOTLIN_DIR, PACKAGE.replace('.', "/")),
+                &format!("{}.kt", name),
+                &kotlin::exception(&name),
+            )
+            .map_err(JniError::Kotlin)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Lang for Jni {
+    type Error = JniError;
+
+    fn namespace() -> Ident {
+        format_ident!("__jni")
+    }
+
+    fn expose_fn(function: &mut ItemFn, mod_path: &[Ident]) -> Result<Ident, Self::Error> {
+        let module = mod_path
+            .last()
+            .ok_or_else(|| JniError::NakedFunction(function.sig.ident.span()))?;
+
+        let mut original = function.clone();
+        MethodRole::take_from(&mut original.attrs);
+        strip_defaults(&mut original.sig);
+
+        let ident = &original.sig.ident;
+        let sig = kotlin_signature(&function.sig)?;
+        KOTLIN_SIGNATURES.with(|signatures| {
+            signatures.borrow_mut().insert(
+                format!("{}.{}", kotlin_package(mod_path), ident),
+                sig.to_token_stream().to_string(),
+            )
+        });
+
+        let native = camel_case(&format!("{}_{}", module, ident));
+        let export = export_ident(
+            &kotlin_package(mod_path),
+            &kotlin::facade(&pascal_case(&module.to_string())),
+            &native,
+        );
+
+        // keep the original function nested inside the exported one, so that it can be called
+        // with the converted arguments
+        *function = Self::export_fn(
+            &function.sig,
+            export,
+            quote! { #original },
+            quote! { #ident },
+        )?;
+        idl::record_export(ident, &function.sig.ident, &function.sig);
+
+        Ok(function.sig.ident.clone())
+    }
+
+    fn expose_mod(
+        module: &mut ItemMod,
+        mod_path: &[Ident],
+        sub_items: Vec<ModuleItem>,
+    ) -> Result<Ident, Self::Error> {
+        module.vis = parse_quote!(pub);
+
+        let content = &module.content.as_ref().expect("Empty module").1;
+        let package = kotlin_package(mod_path);
+        let functions = sub_items
+            .iter()
+            .filter_map(|sub_item| match sub_item {
+                ModuleItem::Function(ident) => Some(ident),
+                _ => None,
+            })
+            .filter_map(|ident| {
+                content.iter().find_map(|item| match item {
+                    Item::Fn(function) if &function.sig.ident == ident => {
+                        function.block.stmts.iter().find_map(|stmt| match stmt {
+                            Stmt::Item(Item::Fn(original)) => Some(original),
+                            _ => None,
+                        })
+                    }
+                    _ => None,
+                })
+            })
+            .map(|original| {
+                let mut function = original.clone();
+                let path = format!("{}.{}", package, function.sig.ident);
+                if let Some(sig) = KOTLIN_SIGNATURES.with(|s| s.borrow().get(&path).cloned()) {
+                    function.sig = syn::parse_str(&sig).expect("Invalid signature");
+                }
+
+                function
+            })
+            .collect::<Vec<_>>();
+
+        if !functions.is_empty() {
+            let facade = pascal_case(&module.ident.to_string());
+
+            Self::write_exceptions(functions.iter().map(|f| f.sig.output.as_type()))?;
+
+            write_generated(
+                &format!("{}/{}", KOTLIN_DIR, package.replace('.', "/")),
+                &format!("{}.kt", facade),
+                &kotlin::functions(
+                    &package,
+                    &module.ident,
+                    &functions.iter().collect::<Vec<_>>(),
+                ),
+            )
+            .map_err(JniError::Kotlin)?;
+        }
+
+        Ok(module.ident.clone())
+    }
+
+    fn expose_struct(
+        structure: &mut ItemStruct,
+        _opts: Punctuated<ExposeStructOpts, Token![,]>,
+        _mod_path: &[Ident],
+    ) -> Result<Ident, Self::Error> {
+        // structs are always passed to the JVM as opaque handles
+        Ok(structure.ident.clone())
+    }
+
+    fn expose_impl(implementation: &mut ItemImpl, mod_path: &[Ident]) -> Result<(), Self::Error> {
+        let class = last_segment(&implementation.self_ty)
+            .ok_or_else(|| JniError::InvalidSelfType(implementation.self_ty.span()))?
+            .clone();
+        let package = kotlin_package(mod_path);
+
+        let mut methods = vec![];
+        let mut exports = vec![];
+        for item in &mut implementation.items {
+            if let ImplItem::Method(ImplItemMethod { sig, attrs, .. }) = item {
+                let role = MethodRole::take_from(attrs);
+
+                let ident = &sig.ident;
+                let native = camel_case(&format!("{}_{}", class, ident));
+                let export = export_ident(&package, &kotlin::facade(&class.to_string()), &native);
+
+                let ItemFn {
+                    attrs,
+                    vis,
+                    sig: export_sig,
+                    block,
+                } = Self::export_fn(sig, export, quote! {}, quote! { Self::#ident })?;
+                idl::record_export(ident, &export_sig.ident, &export_sig);
+                exports.push(ImplItem::Method(ImplItemMethod {
+                    attrs,
+                    vis,
+                    defaultness: None,
+                    sig: export_sig,
+                    block: *block,
+                }));
+
+                methods.push(kotlin::Method {
+                    role,
+                    sig: kotlin_signature(sig)?,
+                    native,
+                });
+            }
+        }
+        implementation.items.extend(exports);
+
+        Self::write_exceptions(methods.iter().map(|m| m.sig.output.as_type()))?;
+        write_generated(
+            &format!("{}/{}", KOTLIN_DIR, package.replace('.', "/")),
+            &format!("{}.kt", class),
+            &kotlin::class(&package, &class, &methods),
+        )
+        .map_err(JniError::Kotlin)?;
+
+        Ok(())
+    }
+
+    fn convert_input(ty: Type) -> Result<Input, Self::Error> {
+        if let Some(inner) = match_generic_type(&ty, parse_quote!(Option)) {
+            Self::convert_optional_input(ty.clone(), inner[0].clone())
+        } else if match_fixed_type(&ty, parse_quote!(String)) {
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jstring)],
+                |_, ident| {
+                    let ts = quote! {
+                        String::from(
+                            __env
+                                .get_string(#ident.into())
+                                .expect("Invalid incoming string"),
+                        )
+                    };
+                    ts.into()
+                },
+            ))
+        } else if ty == parse_quote!(&str) {
+            // Java strings are encoded differently, so they are always copied
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jstring)],
+                |_, ident| {
+                    let ts = quote! {
+                        &*String::from(
+                            __env
+                                .get_string(#ident.into())
+                                .expect("Invalid incoming string"),
+                        )
+                    };
+                    ts.into()
+                },
+            ))
+        } else if match_fixed_type(&ty, parse_quote!(bool)) {
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jboolean)],
+                |_, ident| {
+                    let ts = quote! { #ident != 0 };
+                    ts.into()
+                },
+            ))
+        } else if let Some(primitive) = jni_primitive(&ty) {
+            Ok(Input::new_custom(ty, vec![primitive], |ty, ident| {
+                let ts = quote! { #ident as #ty };
+                ts.into()
+            }))
+        } else if is_bytes(&ty) {
+            let borrowed = matches!(ty, Type::Reference(_));
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jbyteArray)],
+                move |_, ident| {
+                    let bytes = quote! {
+                        __env
+                            .convert_byte_array(#ident)
+                            .expect("Invalid incoming byte array")
+                    };
+                    let ts = match borrowed {
+                        true => quote! { &*#bytes },
+                        false => bytes,
+                    };
+                    ts.into()
+                },
+            ))
+        } else if let Type::Reference(TypeReference {
+            elem, mutability, ..
+        }) = &ty
+        {
+            if !is_opaque(elem) {
+                return Err(JniError::UnsupportedType(ty.span()));
+            }
+
+            let elem = elem.clone();
+            let mutable = mutability.is_some();
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jlong)],
+                move |_, ident| {
+                    let ts = match mutable {
+                        true => quote! { unsafe { &mut *(#ident as *mut #elem) } },
+                        false => quote! { unsafe { &*(#ident as *const #elem) } },
+                    };
+                    ts.into()
+                },
+            ))
+        } else if is_opaque(&ty) {
+            // taking ownership of the object, the handle becomes invalid
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(::jni::sys::jlong)],
+                |ty, ident| {
+                    let ts = quote! { unsafe { *Box::from_raw(#ident as *mut #ty) } };
+                    ts.into()
+                },
+            ))
+        } else {
+            Err(JniError::UnsupportedType(ty.span()))
+        }
+    }
+
+    fn convert_output(output: Type) -> Result<Output, Self::Error> {
+        if output == parse_quote!(()) {
+            Ok(Output::new_unchanged(output))
+        } else if match_fixed_type(&output, parse_quote!(String)) {
+            Ok(Output::new_custom(
+                output,
+                parse_quote!(::jni::sys::jstring),
+                |ident| {
+                    let ts = quote! {
+                        let #ident = __env
+                            .new_string(#ident)
+                            .expect("Invalid outgoing string")
+                            .into_inner();
+                    };
+                    ts.into()
+                },
+            ))
+        } else if let Some(primitive) = jni_primitive(&output) {
+            let target = primitive.clone();
+            Ok(Output::new_custom(output, primitive, move |ident| {
+                let ts = quote! { let #ident = #ident as #target; };
+                ts.into()
+            }))
+        } else if is_bytes(&output) {
+            Ok(Output::new_custom(
+                output,
+                parse_quote!(::jni::sys::jbyteArray),
+                |ident| {
+                    let ts = quote! {
+                        let #ident = __env
+                            .byte_array_from_slice(&#ident)
+                            .expect("Invalid outgoing byte array");
+                    };
+                    ts.into()
+                },
+            ))
+        } else if let Some(elem) = object_vec(&output) {
+            // every object is owned by the handle in the array
+            Ok(Output::new_custom(
+                output,
+                parse_quote!(::jni::sys::jlongArray),
+                move |ident| {
+                    let ts = quote! {
+                        let #ident = #ident
+                            .into_iter()
+                            .map(|__elem: #elem| Box::into_raw(Box::new(__elem)) as ::jni::sys::jlong)
+                            .collect::<Vec<_>>();
+                        let #ident = {
+                            let __array = __env
+                                .new_long_array(#ident.len() as ::jni::sys::jsize)
+                                .expect("Invalid outgoing array");
+                            __env
+                                .set_long_array_region(__array, 0, &#ident)
+                                .expect("Invalid outgoing array");
+                            __array
+                        };
+                    };
+                    ts.into()
+                },
+            ))
+        } else if match_fixed_type(&output, parse_quote!(Vec<String>)) {
+            Ok(Output::new_custom(
+                output,
+                parse_quote!(::jni::sys::jobjectArray),
+                |ident| {
+                    let ts = quote! {
+                        let #ident = {
+                            let __array = __env
+                                .new_object_array(
+                                    #ident.len() as ::jni::sys::jsize,
+                                    "java/lang/String",
+                                    ::jni::objects::JObject::null(),
+                                )
+                                .expect("Invalid outgoing array");
+                            for (__i, __elem) in #ident.into_iter().enumerate() {
+                                let __elem = __env
+                                    .new_string(__elem)
+                                    .expect("Invalid outgoing string");
+                                __env
+                                    .set_object_array_element(__array, __i as ::jni::sys::jsize, __elem)
+                                    .expect("Invalid outgoing array");
+                            }
+                            __array
+                        };
+                    };
+                    ts.into()
+                },
+            ))
+        } else if let Some(inner) = match_generic_type(&output, parse_quote!(Option)) {
+            let inner = inner.into_iter().into_tuple();
+            if !is_opaque(&inner)
+                && !match_fixed_type(&inner, parse_quote!(String))
+                && !is_bytes(&inner)
+            {
+                return Err(JniError::UnsupportedType(output.span()));
+            }
+
+            let inner = Self::convert_output(inner)?;
+            let target = inner.get_targets().into_tuple();
+
+            Ok(Output::new_custom(output, target, move |ident| {
+                let inner_conv = inner.expand(&format_ident!("__inner")).conv;
+                let ts = quote! {
+                    let #ident = match #ident {
+                        Some(__inner) => {
+                            #inner_conv
+                            __inner
+                        }
+                        None => JniDefault::jni_default(),
+                    };
+                };
+                ts.into()
+            }))
+        } else if let Some(inner) = match_generic_type(&output, parse_quote!(Result)) {
+            let ok = inner
+                .into_iter()
+                .next()
+                .ok_or_else(|| JniError::InvalidResult(output.span()))?;
+            let ok = Self::convert_output(ok)?;
+            let target = ok.get_targets().into_tuple();
+
+            Ok(Output::new_custom(output, target, move |ident| {
+                let ok_conv = ok.expand(&format_ident!("__inner")).conv;
+                let ts = quote! {
+                    let #ident = match #ident {
+                        Ok(__inner) => {
+                            #ok_conv
+                            __inner
+                        }
+                        Err(e) => {
+                            IntoJavaException::throw(e, &__env);
+                            return JniDefault::jni_default();
+                        }
+                    };
+                };
+                ts.into()
+            }))
+        } else if is_opaque(&output) {
+            Ok(Output::new_custom(
+                output,
+                parse_quote!(::jni::sys::jlong),
+                |ident| {
+                    let ts = quote! {
+                        let #ident = Box::into_raw(Box::new(#ident)) as ::jni::sys::jlong;
+                    };
+                    ts.into()
+                },
+            ))
+        } else {
+            Err(JniError::UnsupportedType(output.span()))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum JniError {
+    /// Functions must be exposed in a module, which becomes their Kotlin class
+    NakedFunction(Span),
+    Kotlin(std::io::Error),
+
+    Lang(LangError),
+
+    UnsupportedType(Span),
+    InvalidSelfType(Span),
+    InvalidResult(Span),
+}
+
+impl JniError {
+    fn span(&self) -> Span {
+        match self {
+            JniError::NakedFunction(span)
+            | JniError::UnsupportedType(span)
+            | JniError::InvalidSelfType(span)
+            | JniError::InvalidResult(span) => *span,
+            JniError::Kotlin(_) => Span::call_site(),
+            JniError::Lang(e) => e.span(),
+        }
+    }
+}
+
+impl fmt::Display for JniError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JniError::NakedFunction(_) => write!(
+                f,
+                "functions must be exposed inside an `#[expose_mod]` module to be exposed to the JVM"
+            ),
+            JniError::Kotlin(e) => write!(f, "unable to write the Kotlin wrapper: {}", e),
+            JniError::Lang(e) => write!(f, "{}", e),
+            JniError::UnsupportedType(_) => {
+                write!(f, "this type is not supported by the JNI bindings")
+            }
+            JniError::InvalidSelfType(_) => {
+                write!(f, "only impls of a plain struct type can be exposed to the JVM")
+            }
+            JniError::InvalidResult(_) => {
+                write!(f, "`Result` must have an ok and an error type")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JniError {}
+
+impl From<LangError> for JniError {
+    fn from(e: LangError) -> Self {
+        JniError::Lang(e)
+    }
+}
+
+impl From<JniError> for syn::Error {
+    fn from(e: JniError) -> Self {
+        syn::Error::new(e.span(), e)
+    }
+}
@@ -0,0 +1,386 @@
+use syn::{
+    parse_quote, FnArg, Ident, ItemFn, Pat, PatIdent, PatType, Signature, Type, TypeReference,
+};
+
+use super::{camel_case, is_opaque, last_segment, MethodRole, PACKAGE};
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n\n";
+
+pub struct Method {
+    pub role: MethodRole,
+    pub sig: Signature,
+    /// Name of the `external` function declared for this method
+    pub native: String,
+}
+
+/// Name of the class generated by the Kotlin compiler for the top-level declarations of a file
+pub fn facade(file: &str) -> String {
+    format!("{}Kt", file)
+}
+
+/// Error type of a `Result`
+pub fn result_error(ty: &Type) -> Option<Type> {
+    match_generic_type(ty, parse_quote!(Result)).and_then(|inner| inner.get(1).cloned())
+}
+
+fn library_name() -> String {
+    std::env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| "bdk_ffi".into())
+}
+
+/// Kotlin type exposed to the users of the bindings
+pub fn kotlin_type(ty: &Type, class: Option<&Ident>) -> String {
+    if *ty == parse_quote!(Vec<u8>) || *ty == parse_quote!([u8]) {
+        return "ByteArray".into();
+    }
+    if let Type::Reference(TypeReference { elem, .. }) = ty {
+        return kotlin_type(elem, class);
+    }
+    if *ty == parse_quote!(()) {
+        return "Unit".into();
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+        return format!("{}?", kotlin_type(&inner[0], class));
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Result)) {
+        return kotlin_type(&inner[0], class);
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Vec)) {
+        return format!("List<{}>", kotlin_type(&inner[0], class));
+    }
+
+    match last_segment(ty).map(Ident::to_string).as_deref() {
+        Some("Self") => class.map(Ident::to_string).unwrap_or_else(|| "Any".into()),
+        Some("String") | Some("str") => "String".into(),
+        Some("bool") => "Boolean".into(),
+        Some("i8") => "Byte".into(),
+        Some("i16") => "Short".into(),
+        Some("i32") | Some("u8") | Some("u16") => "Int".into(),
+        Some("i64") | Some("u32") | Some("u64") | Some("usize") => "Long".into(),
+        Some("f32") => "Float".into(),
+        Some("f64") => "Double".into(),
+        Some(other) => other.into(),
+        None => "Any".into(),
+    }
+}
+
+/// Kotlin type used in the `external` declarations, where opaque objects are passed as handles
+fn native_type(ty: &Type) -> String {
+    if let Type::Reference(TypeReference { elem, .. }) = ty {
+        return native_type(elem);
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Result)) {
+        return native_type(&inner[0]);
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+        return match is_opaque(referenced(&inner[0])) {
+            true => "Long".into(),
+            false => format!("{}?", native_type(&inner[0])),
+        };
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Vec)) {
+        if is_opaque(&inner[0]) {
+            return "LongArray".into();
+        }
+        if inner[0] == parse_quote!(String) {
+            return "Array<String>".into();
+        }
+    }
+
+    match is_opaque(ty) {
+        true => "Long".into(),
+        false => kotlin_type(ty, None),
+    }
+}
+
+/// Convert the value returned by an `external` function to the type exposed to the users
+fn from_native(expr: String, ty: &Type, class: Option<&Ident>) -> String {
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Result)) {
+        return from_native(expr, &inner[0], class);
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+        return match is_opaque(&inner[0]) {
+            true => format!(
+                "{}.let {{ if (it == 0L) null else {}(it) }}",
+                expr,
+                kotlin_type(&inner[0], class)
+            ),
+            false => expr,
+        };
+    }
+    if let Some(inner) = match_generic_type(ty, parse_quote!(Vec)) {
+        if is_opaque(&inner[0]) {
+            return format!("{}.map {{ {}(it) }}", expr, kotlin_type(&inner[0], class));
+        }
+        if inner[0] == parse_quote!(String) {
+            return format!("{}.toList()", expr);
+        }
+    }
+
+    match is_opaque(ty) {
+        true => format!("{}({})", kotlin_type(ty, class), expr),
+        false => expr,
+    }
+}
+
+/// Type behind a reference, or the type itself
+fn referenced(ty: &Type) -> &Type {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => elem,
+        ty => ty,
+    }
+}
+
+fn arg_name(ident: &Ident) -> String {
+    camel_case(ident.to_string().trim_start_matches('_'))
+}
+
+/// Arguments of a function, as `(name, type)`. The receiver is not included
+fn typed_args(sig: &Signature) -> Vec<(String, &Type)> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.as_ref() {
+                Pat::Ident(PatIdent { ident, .. }) => Some((arg_name(ident), ty.as_ref())),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+fn has_receiver(sig: &Signature) -> bool {
+    matches!(sig.inputs.first(), Some(FnArg::Receiver(_)))
+}
+
+fn params(sig: &Signature, class: Option<&Ident>) -> String {
+    typed_args(sig)
+        .into_iter()
+        .map(
+            |(name, ty)| match match_generic_type(ty, parse_quote!(Option)) {
+                Some(_) => format!("{}: {} = null", name, kotlin_type(ty, class)),
+                None => format!("{}: {}", name, kotlin_type(ty, class)),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn native_call(native: &str, sig: &Signature) -> String {
+    let receiver = match has_receiver(sig) {
+        true => Some("handle".to_string()),
+        false => None,
+    };
+    let args = typed_args(sig).into_iter().map(|(name, ty)| {
+        if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+            return match is_opaque(referenced(&inner[0])) {
+                true => format!("{}?.handle ?: 0L", name),
+                false => name,
+            };
+        }
+        match is_opaque(referenced(ty)) {
+            true => format!("{}.handle", name),
+            false => name,
+        }
+    });
+
+    format!(
+        "{}({})",
+        native,
+        receiver
+            .into_iter()
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn external(native: &str, sig: &Signature) -> String {
+    let receiver = match has_receiver(sig) {
+        true => Some("handle: Long".to_string()),
+        false => None,
+    };
+    let args = typed_args(sig)
+        .into_iter()
+        .map(|(name, ty)| format!("{}: {}", name, native_type(ty)));
+
+    format!(
+        "private external fun {}({}): {}\n",
+        native,
+        receiver
+            .into_iter()
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(", "),
+        native_type(&sig.output.as_type())
+    )
+}
+
+fn throws(sig: &Signature, target: &str, indent: &str) -> String {
+    match result_error(&sig.output.as_type()) {
+        Some(error) => format!(
+            "{}@{}Throws({}::class)\n",
+            indent,
+            target,
+            kotlin_type(&error, None)
+        ),
+        None => String::new(),
+    }
+}
+
+fn imports<'a, I: IntoIterator<Item = &'a Signature>>(sigs: I) -> String {
+    let mut errors = sigs
+        .into_iter()
+        .filter_map(|sig| result_error(&sig.output.as_type()))
+        .map(|error| format!("import {}.{}\n", PACKAGE, kotlin_type(&error, None)))
+        .collect::<Vec<_>>();
+    errors.sort();
+    errors.dedup();
+
+    match errors.is_empty() {
+        true => String::new(),
+        false => format!("{}\n", errors.join("")),
+    }
+}
+
+fn externals<'a, I: IntoIterator<Item = (&'a str, &'a Signature)>>(natives: I) -> String {
+    let mut source = format!(
+        "@Suppress(\"unused\")\nprivate val library = System.loadLibrary(\"{}\")\n\n",
+        library_name()
+    );
+    for (native, sig) in natives {
+        source.push_str(&external(native, sig));
+    }
+
+    source
+}
+
+/// Generate a class wrapping the handle of an exposed struct
+pub fn class(package: &str, class: &Ident, methods: &[Method]) -> String {
+    let destructor = methods.iter().find(|m| m.role == MethodRole::Destructor);
+
+    let mut body = "    internal var handle: Long = handle\n        private set\n".to_string();
+    let mut companion = String::new();
+
+    for method in methods {
+        let sig = &method.sig;
+        let ret = kotlin_type(&sig.output.as_type(), Some(class));
+        let call = from_native(
+            native_call(&method.native, sig),
+            &sig.output.as_type(),
+            Some(class),
+        );
+
+        match method.role {
+            MethodRole::Destructor => continue,
+            MethodRole::Constructor => body.push_str(&format!(
+                "\n{}    constructor({}) : this({})\n",
+                throws(sig, "", "    "),
+                params(sig, Some(class)),
+                native_call(&method.native, sig)
+            )),
+            MethodRole::Getter => {
+                let name = sig.ident.to_string();
+                body.push_str(&format!(
+                    "\n{}    val {}: {}\n        get() = {}\n",
+                    throws(sig, "get:", "    "),
+                    camel_case(name.strip_prefix("get_").unwrap_or(&name)),
+                    ret,
+                    call
+                ))
+            }
+            MethodRole::Setter | MethodRole::Regular if has_receiver(sig) => {
+                let name = camel_case(&sig.ident.to_string());
+                let modifier = match (name.as_str(), sig.inputs.len(), ret.as_str()) {
+                    ("toString", 1, "String") => "override ",
+                    _ => "",
+                };
+
+                body.push_str(&format!(
+                    "\n{}    {}fun {}({}): {} = {}\n",
+                    throws(sig, "", "    "),
+                    modifier,
+                    name,
+                    params(sig, Some(class)),
+                    ret,
+                    call
+                ))
+            }
+            MethodRole::Setter | MethodRole::Regular => companion.push_str(&format!(
+                "\n{}        @JvmStatic\n        fun {}({}): {} = {}\n",
+                throws(sig, "", "        "),
+                camel_case(&sig.ident.to_string()),
+                params(sig, Some(class)),
+                ret,
+                call
+            )),
+        }
+    }
+
+    if let Some(destructor) = destructor {
+        body.push_str(&format!(
+            "\n    override fun close() {{\n        if (handle != 0L) {{\n            {}(handle)\n            handle = 0L\n        }}\n    }}\n",
+            destructor.native
+        ));
+    }
+    if !companion.is_empty() {
+        body.push_str(&format!("\n    companion object {{{}    }}\n", companion));
+    }
+
+    format!(
+        "{}package {}\n\n{}class {} internal constructor(handle: Long){} {{\n{}}}\n\n{}",
+        HEADER,
+        package,
+        imports(methods.iter().map(|m| &m.sig)),
+        class,
+        match destructor {
+            Some(_) => " : AutoCloseable",
+            None => "",
+        },
+        body,
+        externals(methods.iter().map(|m| (m.native.as_str(), &m.sig)))
+    )
+}
+
+/// Generate the top-level functions of a module
+pub fn functions(package: &str, module: &Ident, functions: &[&ItemFn]) -> String {
+    let natives = functions
+        .iter()
+        .map(|function| camel_case(&format!("{}_{}", module, function.sig.ident)))
+        .collect::<Vec<_>>();
+
+    let mut body = String::new();
+    for (function, native) in functions.iter().zip(natives.iter()) {
+        let sig = &function.sig;
+        body.push_str(&format!(
+            "{}fun {}({}): {} = {}\n\n",
+            throws(sig, "", ""),
+            camel_case(&sig.ident.to_string()),
+            params(sig, None),
+            kotlin_type(&sig.output.as_type(), None),
+            from_native(native_call(native, sig), &sig.output.as_type(), None)
+        ));
+    }
+
+    format!(
+        "{}package {}\n\n{}{}{}",
+        HEADER,
+        package,
+        imports(functions.iter().map(|f| &f.sig)),
+        body,
+        externals(
+            natives
+                .iter()
+                .map(String::as_str)
+                .zip(functions.iter().map(|f| &f.sig))
+        )
+    )
+}
+
+/// Generate the exception thrown when a function returns the error `name`
+pub fn exception(name: &str) -> String {
+    format!(
+        "{}package {}\n\nclass {}(message: String) : Exception(message)\n",
+        HEADER, PACKAGE, name
+    )
+}
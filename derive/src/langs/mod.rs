@@ -1,51 +1,78 @@
-use std::convert::TryFrom;
-use std::fmt;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::{fmt, fs, io};
 
-use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
-use syn::parse::{Parse, ParseStream};
+use proc_macro2::{Group, Span, TokenStream as TokenStream2, TokenTree};
+#[cfg(any(feature = "c", feature = "python", feature = "napi"))]
+use quote::{quote, quote_spanned};
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::token::Comma;
 use syn::{
-    parse_quote, FnArg, Ident, ItemFn, ItemImpl, ItemMod, ItemStruct, ItemTrait, Lit, LitStr,
-    ParenthesizedGenericArguments, Pat, PatIdent, PatType, Path, PathArguments, ReturnType, Token,
-    Type,
+    parse_quote, parse_quote_spanned, Attribute, Binding, GenericArgument, Ident, Item, ItemFn,
+    ItemImpl, ItemMod, ItemStruct, Lit, Meta, NestedMeta, PathArguments, Signature, Token,
+    TraitBound, Type, TypeImplTrait, TypeParamBound, TypePath, TypeReference,
 };
+#[cfg(any(feature = "c", feature = "python", feature = "napi", feature = "wasm"))]
+use syn::{FnArg, Receiver, ReturnType};
+#[cfg(any(feature = "c", feature = "python", feature = "napi"))]
+use syn::{Pat, PatIdent, PatType};
 
 use crate::types::*;
 
 #[cfg(feature = "c")]
 pub mod c;
+#[cfg(feature = "jni")]
+pub mod jni;
+#[cfg(feature = "napi")]
+pub mod napi;
 #[cfg(feature = "python")]
 pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub trait Lang {
-    type Error: From<LangError> + std::error::Error;
+    /// Reported as a compile error pointing to the item that couldn't be exposed
+    type Error: From<LangError> + std::error::Error + Into<syn::Error>;
 
-    fn expose_fn(function: &mut ItemFn, mod_path: &Vec<Ident>) -> Result<Ident, Self::Error>;
+    /// Name of the module wrapping the items generated for this language
+    fn namespace() -> Ident;
+
+    fn expose_fn(function: &mut ItemFn, mod_path: &[Ident]) -> Result<Ident, Self::Error>;
 
     fn expose_mod(
         module: &mut ItemMod,
-        mod_path: &Vec<Ident>,
+        mod_path: &[Ident],
         sub_items: Vec<ModuleItem>,
     ) -> Result<Ident, Self::Error>;
 
     fn expose_struct(
         structure: &mut ItemStruct,
         opts: Punctuated<ExposeStructOpts, Token![,]>,
-        mod_path: &Vec<Ident>,
+        mod_path: &[Ident],
     ) -> Result<Ident, Self::Error>;
 
-    fn expose_impl(implementation: &mut ItemImpl, mod_path: &Vec<Ident>)
-        -> Result<(), Self::Error>;
+    fn expose_impl(implementation: &mut ItemImpl, mod_path: &[Ident]) -> Result<(), Self::Error>;
+
+    /// Items declared right after an exposed struct, once the impls next to it are exposed
+    fn struct_items(_structure: &ItemStruct, _mod_path: &[Ident]) -> Vec<Item> {
+        vec![]
+    }
+
+    /// How the items of an `impl Iterator` reach the language: `"array"` when they are collected,
+    /// `"cursor"` or `"iterator"` when they are produced as they are requested
+    fn iterator_kind() -> &'static str {
+        "array"
+    }
 
-    //     fn expose_trait(tr: &mut ItemTrait, mod_path: &Vec<Ident>) -> Result<Ident, Self::Error>;
+    //     fn expose_trait(tr: &mut ItemTrait, mod_path: &[Ident]) -> Result<Ident, Self::Error>;
     //
     fn convert_input(ty: Type) -> Result<Input, Self::Error>;
 
     fn convert_output(output: Type) -> Result<Output, Self::Error>;
 
     // provided methods
+    #[cfg(any(feature = "c", feature = "python", feature = "napi", feature = "wasm"))]
     fn convert_fn_args<I: IntoIterator<Item = FnArg>>(
         args: I,
     ) -> Result<(Punctuated<FnArg, Comma>, TokenStream2), Self::Error> {
@@ -69,21 +96,452 @@ pub trait Lang {
     }
 }
 
+/// Write a source file generated for the target language to `<dir>/<name>`, relative to the
+/// directory pointed to by `BDK_FFI_GEN_DIR`
+///
+/// The file goes to the `OUT_DIR` of the crate being compiled when the variable is not set, so that
+/// `cargo check` or an IDE never touch the source tree. The file is left untouched if its content
+/// didn't change, to avoid triggering rebuilds of whatever depends on it.
+pub fn write_generated(dir: &str, name: &str, content: &str) -> io::Result<()> {
+    let root = match std::env::var("BDK_FFI_GEN_DIR").or_else(|_| std::env::var("OUT_DIR")) {
+        Ok(root) => root,
+        Err(_) => return Ok(()),
+    };
+
+    let path = std::path::Path::new(&root).join(dir).join(name);
+    if fs::read_to_string(&path).is_ok_and(|current| current == content) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, content)
+}
+
+/// Role of a method, as declared by the attributes on the Rust side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodRole {
+    Constructor,
+    Destructor,
+    Getter,
+    Setter,
+    Regular,
+}
+
+impl MethodRole {
+    /// Find the role of a method, removing the attributes that declared it along with the ones that
+    /// only matter to Python: the protocol methods and `#[blocking]`
+    pub fn take_from(attrs: &mut Vec<Attribute>) -> Self {
+        let mut role = MethodRole::Regular;
+        attrs.retain(|a| {
+            if a.path.is_ident("str") || a.path.is_ident("bytes") || a.path.is_ident("blocking") {
+                return false;
+            }
+
+            if a.path.is_ident("constructor") {
+                role = MethodRole::Constructor;
+            } else if a.path.is_ident("destructor") {
+                role = MethodRole::Destructor;
+            } else if a.path.is_ident("getter") {
+                role = MethodRole::Getter;
+            } else if a.path.is_ident("setter") {
+                role = MethodRole::Setter;
+            } else {
+                return true;
+            }
+
+            false
+        });
+
+        role
+    }
+}
+
+/// Reference taken by an argument, directly or in an `Option`, and whether it's optional
+pub fn borrowed_arg(ty: &Type) -> Option<(TypeReference, bool)> {
+    match ty {
+        Type::Reference(reference) => Some((reference.clone(), false)),
+        _ => match match_generic_type(ty, parse_quote!(Option))?.as_slice() {
+            [Type::Reference(reference)] => Some((reference.clone(), true)),
+            _ => None,
+        },
+    }
+}
+
+/// Owned counterpart of `Option<&str>` and `Option<&[u8]>`, for the languages that can only
+/// receive them by value
+#[cfg(any(feature = "jni", feature = "napi", feature = "wasm"))]
+pub fn optional_borrowed(ty: &Type) -> Option<Type> {
+    match borrowed_arg(ty) {
+        Some((TypeReference { elem, .. }, true)) if *elem == parse_quote!(str) => {
+            Some(parse_quote!(Option<String>))
+        }
+        Some((TypeReference { elem, .. }, true)) if *elem == parse_quote!([u8]) => {
+            Some(parse_quote!(Option<Vec<u8>>))
+        }
+        _ => None,
+    }
+}
+
+/// Copy what an async function borrows before its future is spawned, so that the future doesn't
+/// depend on objects the other language may change or release in the meantime
+///
+/// Returns the statements that make the copies, to run before spawning the future, and the ones
+/// that borrow them again inside of it. The receiver is copied to `__self`. The borrowed types
+/// must be `Clone`, or `ToOwned` like `str` and `[u8]`, and mutable references are rejected.
+#[cfg(any(feature = "c", feature = "python", feature = "napi"))]
+pub fn copy_borrowed_args(
+    inputs: &Punctuated<FnArg, Comma>,
+) -> Result<(TokenStream2, TokenStream2), LangError> {
+    let mut copies = TokenStream2::default();
+    let mut borrows = TokenStream2::default();
+
+    for input in inputs {
+        match input {
+            FnArg::Receiver(Receiver {
+                reference: Some(_),
+                mutability: Some(_),
+                ..
+            }) => return Err(LangError::MutableAsync(input.span())),
+            FnArg::Receiver(Receiver {
+                reference: Some(_), ..
+            }) => {
+                copies.extend(quote_spanned! {input.span()=>
+                    let __self = <Self as std::clone::Clone>::clone(self);
+                });
+                borrows.extend(quote! {
+                    let __self = &__self;
+                });
+            }
+            FnArg::Receiver(_) => copies.extend(quote! {
+                let __self = self;
+            }),
+            FnArg::Typed(PatType { pat, ty, .. }) => {
+                let ident = match pat.as_ref() {
+                    Pat::Ident(PatIdent { ident, .. }) => ident,
+                    pat => return Err(LangError::ComplexPatternFnArg(pat.span())),
+                };
+                let (reference, optional) = match borrowed_arg(ty) {
+                    Some(borrowed) => borrowed,
+                    None => continue,
+                };
+                if reference.mutability.is_some() {
+                    return Err(LangError::MutableAsync(reference.span()));
+                }
+
+                let elem = &reference.elem;
+                let (copy, borrow) = match optional {
+                    true => (
+                        quote!(#ident.map(<#elem as std::borrow::ToOwned>::to_owned)),
+                        quote!(#ident.as_ref().map(std::borrow::Borrow::<#elem>::borrow)),
+                    ),
+                    false => (
+                        quote!(<#elem as std::borrow::ToOwned>::to_owned(#ident)),
+                        quote!(std::borrow::Borrow::<#elem>::borrow(&#ident)),
+                    ),
+                };
+                copies.extend(quote_spanned! {ty.span()=>
+                    let #ident = #copy;
+                });
+                borrows.extend(quote! {
+                    let #ident = #borrow;
+                });
+            }
+        }
+    }
+
+    Ok((copies, borrows))
+}
+
+/// Make sure the signature of a `#[setter]` can be exposed as a property setter
+#[cfg(any(feature = "c", feature = "python", feature = "napi", feature = "wasm"))]
+pub fn check_setter(sig: &Signature) -> Result<(), LangError> {
+    let returns_unit = match &sig.output {
+        ReturnType::Default => true,
+        ReturnType::Type(_, ty) => match_generic_type(ty, parse_quote!(Result))
+            .is_some_and(|inner| inner[0] == parse_quote!(())),
+    };
+
+    match (sig.inputs.first(), sig.inputs.len(), returns_unit) {
+        (
+            Some(FnArg::Receiver(Receiver {
+                reference: Some(_),
+                mutability: Some(_),
+                ..
+            })),
+            2,
+            true,
+        ) => Ok(()),
+        _ => Err(LangError::InvalidSetter(sig.span())),
+    }
+}
+
+/// Name of the property accessed by a `#[getter]` or `#[setter]` method: the one passed to the
+/// attribute, or the method name without its `get_`/`set_` prefix
+pub fn accessor_name(attrs: &[Attribute], ident: &Ident) -> Option<String> {
+    let attr = attrs
+        .iter()
+        .find(|a| a.path.is_ident("getter") || a.path.is_ident("setter"))?;
+
+    if let Ok(Meta::List(list)) = attr.parse_meta() {
+        match list.nested.first() {
+            Some(NestedMeta::Meta(Meta::Path(path))) => {
+                return Some(path.get_ident().expect("Invalid accessor name").to_string())
+            }
+            Some(NestedMeta::Lit(Lit::Str(name))) => return Some(name.value()),
+            _ => {}
+        }
+    }
+
+    let name = ident.to_string();
+    let prefix = if attr.path.is_ident("getter") {
+        "get_"
+    } else {
+        "set_"
+    };
+    Some(name.strip_prefix(prefix).unwrap_or(&name).to_string())
+}
+
+#[cfg(any(
+    feature = "jni",
+    feature = "napi",
+    feature = "wasm",
+    feature = "swift",
+    feature = "dart"
+))]
+pub fn camel_case(s: &str) -> String {
+    let pascal = pascal_case(s);
+    let mut chars = pascal.chars();
+
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(any(
+    feature = "jni",
+    feature = "napi",
+    feature = "wasm",
+    feature = "swift",
+    feature = "go",
+    feature = "csharp",
+    feature = "ruby",
+    feature = "dart"
+))]
+pub fn pascal_case(s: &str) -> String {
+    s.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap();
+            first.to_uppercase().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+pub fn last_segment(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(TypePath { path, .. }) => path.segments.last().map(|s| &s.ident),
+        _ => None,
+    }
+}
+
+/// Item of an `impl Iterator<Item = T>`, which is returned as a cursor instead of being collected
+pub fn iterator_item(ty: &Type) -> Option<Type> {
+    let bounds = match ty {
+        Type::ImplTrait(TypeImplTrait { bounds, .. }) => bounds,
+        _ => return None,
+    };
+
+    bounds.iter().find_map(|bound| match bound {
+        TypeParamBound::Trait(TraitBound { path, .. }) => {
+            let segment = path.segments.last()?;
+            match &segment.arguments {
+                PathArguments::AngleBracketed(args) if segment.ident == "Iterator" => {
+                    args.args.iter().find_map(|arg| match arg {
+                        GenericArgument::Binding(Binding { ident, ty, .. }) if ident == "Item" => {
+                            Some(ty.clone())
+                        }
+                        _ => None,
+                    })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Signature of a function returning an `impl Iterator` once its items are collected in a `Vec`,
+/// for the languages that don't expose cursors
+#[cfg(any(feature = "jni", feature = "napi", feature = "wasm"))]
+pub fn collected_sig(sig: &Signature) -> Option<Signature> {
+    let item = iterator_item(&sig.output.as_type())?;
+
+    let mut sig = sig.clone();
+    sig.output = parse_quote_spanned!(sig.output.span()=> -> Vec<#item>);
+    Some(sig)
+}
+
+/// Replace the `self` keyword with `ident`, for bodies that are moved out of their method
+#[cfg(any(feature = "c", feature = "python", feature = "napi"))]
+pub fn rename_self(tokens: TokenStream2, ident: &Ident) -> TokenStream2 {
+    rename_ident(tokens, "self", ident)
+}
+
+/// Replace every occurrence of the identifier `from` with `to`
+pub fn rename_ident(tokens: TokenStream2, from: &str, to: &Ident) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(i) if i == from => TokenTree::Ident(to.clone()),
+            TokenTree::Group(group) => {
+                let mut renamed =
+                    Group::new(group.delimiter(), rename_ident(group.stream(), from, to));
+                renamed.set_span(group.span());
+                TokenTree::Group(renamed)
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// How a struct is exposed, depending on the options of `#[expose_struct]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructKind {
+    Plain,
+    Opaque,
+    Shared,
+}
+
+thread_local! {
+    /// Structs exposed so far, by name
+    static EXPOSED_STRUCTS: RefCell<BTreeMap<String, StructKind>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Remember how `structure` is exposed, so that the impls and the functions using it can handle it.
+/// Must be called before they are processed.
+pub fn register_struct(structure: &Ident, opts: &Punctuated<ExposeStructOpts, Token![,]>) {
+    let kind = if opts.iter().any(|o| *o == ExposeStructOpts::Shared) {
+        StructKind::Shared
+    } else if opts.iter().any(|o| *o == ExposeStructOpts::Opaque) {
+        StructKind::Opaque
+    } else {
+        StructKind::Plain
+    };
+
+    EXPOSED_STRUCTS.with(|structs| structs.borrow_mut().insert(structure.to_string(), kind));
+}
+
+/// How `ty` is exposed, if it's one of the structs exposed so far
+#[cfg(any(feature = "c", feature = "python", feature = "wasm"))]
+pub fn struct_kind(ty: &Type) -> Option<StructKind> {
+    let ident = last_segment(ty)?;
+    EXPOSED_STRUCTS.with(|structs| structs.borrow().get(&ident.to_string()).copied())
+}
+
+/// Whether `ty` is a struct exposed with the `"shared"` option
+#[cfg(any(feature = "c", feature = "python"))]
+pub fn is_shared(ty: &Type) -> bool {
+    struct_kind(ty) == Some(StructKind::Shared)
+}
+
+/// Check that a shared struct is `Send`, since its lock is used from multiple threads
+///
+/// Spanned on the struct, so that the error points to it rather than to the generated code.
+#[cfg(any(feature = "c", feature = "python"))]
+pub fn assert_send(ident: &Ident) -> Item {
+    parse_quote_spanned! {ident.span()=>
+        const _: fn() = || {
+            fn assert_send<T: Send>() {}
+            assert_send::<#ident>();
+        };
+    }
+}
+
 #[derive(Debug)]
 pub enum LangError {
     /// Complex pattern in function argument.
     ///
     /// Only basic patterns like `foo: u32` are supported
-    ComplexPatternFnArg,
+    ComplexPatternFnArg(Span),
 
     /// Trying to return multiple different types by reference
-    MultipleTypesByReference,
+    MultipleTypesByReference(Span),
+
+    /// Setters must take `&mut self` and the new value, and return nothing or a `Result<(), E>`
+    InvalidSetter(Span),
+
+    /// The argument of `#[default(...)]` must be an expression
+    InvalidDefault(Span),
+
+    /// Async functions copy what they borrow, so they can't take mutable references
+    MutableAsync(Span),
+
+    /// Constructors and accessors are called synchronously by the other languages
+    AsyncRole(Span),
+
+    /// The arguments of callbacks are named in the other languages
+    UnnamedCallbackArguments(Span),
+}
+
+impl LangError {
+    pub fn span(&self) -> Span {
+        match self {
+            LangError::ComplexPatternFnArg(span)
+            | LangError::MultipleTypesByReference(span)
+            | LangError::InvalidSetter(span)
+            | LangError::InvalidDefault(span)
+            | LangError::MutableAsync(span)
+            | LangError::AsyncRole(span)
+            | LangError::UnnamedCallbackArguments(span) => *span,
+        }
+    }
 }
 
 impl fmt::Display for LangError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            LangError::ComplexPatternFnArg(_) => write!(
+                f,
+                "arguments must be plain identifiers, like `value: u32`, patterns are not supported"
+            ),
+            LangError::MultipleTypesByReference(_) => write!(
+                f,
+                "this type is converted to multiple values, which can't be returned by reference"
+            ),
+            LangError::InvalidSetter(_) => write!(
+                f,
+                "setters must take `&mut self` and the new value, and return nothing or a \
+                 `Result<(), E>`"
+            ),
+            LangError::InvalidDefault(_) => write!(
+                f,
+                "default values must be an expression of the type of the argument, like \
+                 `#[default(1.0)]`"
+            ),
+            LangError::MutableAsync(_) => write!(
+                f,
+                "async functions can only take shared references, which are copied for the \
+                 duration of the call"
+            ),
+            LangError::AsyncRole(_) => {
+                write!(f, "constructors, getters and setters can't be async")
+            }
+            LangError::UnnamedCallbackArguments(_) => write!(
+                f,
+                "the arguments of callbacks must be named, like `fn(value: u32)`"
+            ),
+        }
     }
 }
 
 impl std::error::Error for LangError {}
+
+impl From<LangError> for syn::Error {
+    fn from(e: LangError) -> Self {
+        syn::Error::new(e.span(), e)
+    }
+}
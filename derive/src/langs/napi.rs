@@ -0,0 +1,571 @@
+use std::fmt;
+
+use proc_macro2::{Group, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Attribute, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemFn, ItemStruct, Pat,
+    PatIdent, PatType, Signature, Token,
+};
+
+use super::*;
+use crate::idl;
+
+mod typings;
+
+/// Directory where `index.d.ts` is written, relative to the root of the crate
+const TYPINGS_DIR: &str = "node";
+
+/// Prefix of the methods generated to wrap the original ones in an exposed `impl`
+const WRAPPER_PREFIX: &str = "__napi_";
+
+#[derive(Debug)]
+pub struct Napi;
+
+/// Name of the object that holds the items of a module in the JS exports
+///
+/// N-API namespaces can't be nested, so the path of submodules is flattened
+fn js_namespace(mod_path: &[Ident]) -> String {
+    mod_path
+        .iter()
+        .map(Ident::to_string)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Replace `Self` with `ty` in `tokens`, because napi-derive only resolves it at the top level of
+/// the return type
+fn replace_self(tokens: TokenStream2, ty: &Type) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Ident(ident) if ident == "Self" => quote! { #ty },
+            TokenTree::Group(group) => {
+                let mut replaced = Group::new(group.delimiter(), replace_self(group.stream(), ty));
+                replaced.set_span(group.span());
+
+                TokenTree::Group(replaced).into()
+            }
+            token => token.into(),
+        })
+        .collect()
+}
+
+/// `Vec<u8>` and `&[u8]`, exchanged as a `Buffer`
+fn is_bytes(ty: &Type) -> bool {
+    *ty == parse_quote!(Vec<u8>) || *ty == parse_quote!(&[u8])
+}
+
+impl Napi {
+    /// Build a function with the converted signature of `sig`, which calls `call` and converts
+    /// the result back
+    fn wrap_fn(
+        sig: &Signature,
+        self_ty: Option<&Type>,
+        ident: Ident,
+        attr: Attribute,
+        prelude: TokenStream2,
+        call: TokenStream2,
+    ) -> Result<ItemFn, NapiError> {
+        // iterators are returned as an array
+        let collected = collected_sig(sig);
+        let sig = collected.as_ref().unwrap_or(sig);
+
+        let (mut args, input_conversion) = Self::convert_fn_args(sig.inputs.clone())?;
+        let call_args = sig
+            .inputs
+            .iter()
+            .map(|input| match input {
+                FnArg::Receiver(_) => Ok(format_ident!("self")),
+                FnArg::Typed(PatType { pat, .. }) => match pat.as_ref() {
+                    Pat::Ident(PatIdent { ident, .. }) => Ok(ident.clone()),
+                    pat => Err(LangError::ComplexPatternFnArg(pat.span())),
+                },
+            })
+            .collect::<Result<Punctuated<Ident, Comma>, _>>()?;
+
+        let ExpandedReturn {
+            ret,
+            conv: output_conversion,
+            ..
+        } = Return(sig.output.clone()).expand(
+            &format_ident!("__output"),
+            &format_ident!("__ptr_out"),
+            Self::convert_output,
+        )?;
+
+        let collect = match collected {
+            Some(_) => quote! { .collect::<Vec<_>>() },
+            None => quote! {},
+        };
+
+        let callbacks = sig.inputs.iter().any(|input| {
+            matches!(input, FnArg::Typed(PatType { ty, .. }) if matches!(**ty, Type::BareFn(_)))
+        });
+
+        if let Some(asyncness) = sig.asyncness {
+            if callbacks {
+                return Err(NapiError::AsyncCallback(asyncness.span()));
+            }
+            return Self::wrap_async_fn(
+                sig,
+                self_ty,
+                ident,
+                attr,
+                args,
+                quote! { #prelude #input_conversion },
+                quote! { #call(#call_args).await #collect },
+                ret.as_type(),
+                output_conversion,
+            );
+        }
+
+        // the JS functions are called through closures that keep the first error they throw, which
+        // is thrown again by the exported function, so it has to return a `Result`
+        let (ret, output_conversion, callback_error, rethrow) = match callbacks {
+            false => (ret, quote! { #output_conversion }, quote! {}, quote! {}),
+            true => {
+                args.push(parse_quote!(__env: Env));
+                let (ret, output_conversion) =
+                    match match_generic_type(&ret.as_type(), parse_quote!(napi::Result)) {
+                        Some(_) => (ret, quote! { #output_conversion }),
+                        None => {
+                            let ty = ret.as_type();
+                            (
+                                parse_quote!(-> napi::Result<#ty, String>),
+                                quote! { Ok({ #output_conversion }) },
+                            )
+                        }
+                    };
+                (
+                    ret,
+                    output_conversion,
+                    quote! { let __callback_error = std::cell::RefCell::new(None::<napi::Error>); },
+                    quote! {
+                        if let Some(e) = __callback_error.take() {
+                            // the original exception stays pending, and isn't replaced by the
+                            // error returned here
+                            unsafe { napi::JsError::from(e).throw_into(__env.raw()) };
+                            return Err(napi::Error::new(
+                                napi::Status::PendingException.as_ref().to_string(),
+                                String::new(),
+                            ));
+                        }
+                    },
+                )
+            }
+        };
+
+        let ret = match self_ty {
+            Some(ty) => replace_self(ret.into_token_stream(), ty),
+            None => ret.into_token_stream(),
+        };
+
+        Ok(parse_quote! {
+            #attr
+            #[allow(unused_variables)]
+            pub fn #ident(#args) #ret {
+                use crate::mapping::{MapTo, MapFrom};
+
+                #prelude
+                #callback_error
+                #input_conversion
+
+                let __output = #call(#call_args) #collect;
+                #rethrow
+                #output_conversion
+            }
+        })
+    }
+}
+
+impl Napi {
+    /// Build a function that copies what `sig` borrows, and returns a `Promise` of the output of
+    /// `call`, converted back, awaited on the library runtime
+    #[allow(clippy::too_many_arguments)]
+    fn wrap_async_fn(
+        sig: &Signature,
+        self_ty: Option<&Type>,
+        ident: Ident,
+        attr: Attribute,
+        args: Punctuated<FnArg, Comma>,
+        input_conversion: TokenStream2,
+        call: TokenStream2,
+        ret: Type,
+        output_conversion: ExpandedReturnConversion,
+    ) -> Result<ItemFn, NapiError> {
+        let (copies, borrows) = copy_borrowed_args(&sig.inputs)?;
+        let call = rename_self(call, &format_ident!("__self"));
+
+        let (output, result) = match match_generic_type(&ret, parse_quote!(napi::Result)) {
+            Some(inner) => (inner[0].clone(), quote! { __output }),
+            None => (ret, quote! { Ok(__output) }),
+        };
+        let output = match self_ty {
+            Some(ty) => replace_self(output.into_token_stream(), ty),
+            None => output.into_token_stream(),
+        };
+
+        Ok(parse_quote! {
+            #attr
+            #[allow(unused_variables)]
+            pub fn #ident(#args) -> napi::bindgen_prelude::AsyncTask<crate::langs::FutureTask<#output>> {
+                use crate::mapping::{MapTo, MapFrom};
+
+                #input_conversion
+                #copies
+
+                crate::langs::FutureTask::spawn(async move {
+                    #borrows
+
+                    let __output = #call;
+                    let __output = { #output_conversion };
+                    #result
+                })
+            }
+        })
+    }
+}
+
+impl Lang for Napi {
+    type Error = NapiError;
+
+    fn namespace() -> Ident {
+        format_ident!("__napi")
+    }
+
+    fn expose_fn(function: &mut ItemFn, mod_path: &[Ident]) -> Result<Ident, Self::Error> {
+        if mod_path.is_empty() {
+            return Err(NapiError::NakedFunction(function.sig.ident.span()));
+        }
+
+        let mut original = function.clone();
+        MethodRole::take_from(&mut original.attrs);
+        strip_defaults(&mut original.sig);
+
+        let ident = original.sig.ident.clone();
+        let namespace = js_namespace(mod_path);
+        typings::register_callbacks(&namespace, None, &original.sig);
+        accept_closures(&mut original.sig);
+        let js_name = camel_case(&ident.to_string());
+
+        // keep the original function nested inside the exported one, so that it can be called
+        // with the converted arguments
+        *function = Self::wrap_fn(
+            &function.sig,
+            None,
+            ident.clone(),
+            parse_quote!( #[napi_derive::napi(namespace = #namespace, js_name = #js_name)] ),
+            quote! { #original },
+            quote! { #ident },
+        )?;
+        idl::record_export(&ident, &js_name, &function.sig);
+
+        Ok(function.sig.ident.clone())
+    }
+
+    fn expose_mod(
+        module: &mut ItemMod,
+        mod_path: &[Ident],
+        _sub_items: Vec<ModuleItem>,
+    ) -> Result<Ident, Self::Error> {
+        // napi-rs only passes the `Env` to the arguments declared with that exact type, which the
+        // functions taking callbacks use to throw again what they threw
+        let takes_env = |sig: &Signature| {
+            sig.inputs.iter().any(|input| {
+                matches!(input, FnArg::Typed(PatType { ty, .. }) if **ty == parse_quote!(Env))
+            })
+        };
+        let content = &mut module.content.as_mut().expect("Empty module").1;
+        if content.iter().any(|item| match item {
+            Item::Fn(function) => takes_env(&function.sig),
+            Item::Impl(implementation) => implementation
+                .items
+                .iter()
+                .any(|item| matches!(item, ImplItem::Method(method) if takes_env(&method.sig))),
+            _ => false,
+        }) {
+            content.push(parse_quote!(
+                use napi::Env;
+            ));
+        }
+
+        // submodules are already expanded at this point, so the root module can describe all of
+        // them in a single file
+        if mod_path.len() == 1 {
+            write_generated(TYPINGS_DIR, "index.d.ts", &typings::generate(module))
+                .map_err(NapiError::Typings)?;
+        }
+
+        Ok(module.ident.clone())
+    }
+
+    fn expose_struct(
+        structure: &mut ItemStruct,
+        _opts: Punctuated<ExposeStructOpts, Token![,]>,
+        mod_path: &[Ident],
+    ) -> Result<Ident, Self::Error> {
+        let namespace = js_namespace(mod_path);
+        structure
+            .attrs
+            .push(parse_quote!( #[napi_derive::napi(namespace = #namespace)] ));
+
+        Ok(structure.ident.clone())
+    }
+
+    fn expose_impl(implementation: &mut ItemImpl, mod_path: &[Ident]) -> Result<(), Self::Error> {
+        let namespace = js_namespace(mod_path);
+        implementation
+            .attrs
+            .push(parse_quote!( #[napi_derive::napi(namespace = #namespace)] ));
+
+        // objects are released by the garbage collector, so destructors are removed
+        implementation.items.retain(|item| match item {
+            ImplItem::Method(ImplItemMethod { attrs, .. }) => {
+                !attrs.iter().any(|a| a.path.is_ident("destructor"))
+            }
+            _ => true,
+        });
+
+        let class = last_segment(&implementation.self_ty).cloned();
+        let mut wrappers = vec![];
+        for item in &mut implementation.items {
+            if let ImplItem::Method(ImplItemMethod { sig, attrs, .. }) = item {
+                let ident = &sig.ident;
+                let wrapper = format_ident!("{}{}", WRAPPER_PREFIX, ident);
+                typings::register_callbacks(&namespace, class.as_ref(), sig);
+
+                let property = accessor_name(attrs, ident).map(|name| camel_case(&name));
+                let role = MethodRole::take_from(attrs);
+                if let (Some(asyncness), false) = (sig.asyncness, role == MethodRole::Regular) {
+                    return Err(LangError::AsyncRole(asyncness.span()).into());
+                }
+                let (js_name, attr): (String, Attribute) = match role {
+                    MethodRole::Destructor => continue,
+                    MethodRole::Constructor => {
+                        ("constructor".into(), parse_quote!( #[napi(constructor)] ))
+                    }
+                    MethodRole::Getter => {
+                        let js_name = property.expect("Getter without a name");
+                        let attr = parse_quote!( #[napi(getter, js_name = #js_name)] );
+                        (js_name, attr)
+                    }
+                    MethodRole::Setter => {
+                        check_setter(sig)?;
+                        let js_name = property.expect("Setter without a name");
+                        let attr = parse_quote!( #[napi(setter, js_name = #js_name)] );
+                        (js_name, attr)
+                    }
+                    MethodRole::Regular => {
+                        let js_name = camel_case(&ident.to_string());
+                        let attr = parse_quote!( #[napi(js_name = #js_name)] );
+                        (js_name, attr)
+                    }
+                };
+
+                let ItemFn {
+                    attrs,
+                    vis,
+                    sig: wrapper_sig,
+                    block,
+                } = Self::wrap_fn(
+                    sig,
+                    Some(&implementation.self_ty),
+                    wrapper,
+                    attr,
+                    quote! {},
+                    quote! { Self::#ident },
+                )?;
+                idl::record_export(ident, &js_name, &wrapper_sig);
+                wrappers.push(ImplItem::Method(ImplItemMethod {
+                    attrs,
+                    vis,
+                    defaultness: None,
+                    sig: wrapper_sig,
+                    block: *block,
+                }));
+            }
+        }
+        implementation.items.extend(wrappers);
+
+        Ok(())
+    }
+
+    fn convert_input(ty: Type) -> Result<Input, Self::Error> {
+        if let Type::BareFn(ref bare_fn) = ty {
+            let inputs = bare_fn.inputs.clone();
+            let output = bare_fn.output.clone();
+
+            let args_types = bare_fn.inputs.iter().map(|arg| &arg.ty);
+            let ret_type = output.as_type();
+            let args_names = bare_fn
+                .inputs
+                .iter()
+                .map(|arg| match &arg.name {
+                    Some((name, _)) => Ok(name.clone()),
+                    None => Err(LangError::UnnamedCallbackArguments(bare_fn.span())),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Input::new_custom(
+                ty.clone(),
+                vec![parse_quote!(
+                    napi::bindgen_prelude::Function<'_, (#(#args_types,)*), #ret_type>
+                )],
+                move |_, ident| {
+                    // once the JS function throws it isn't called anymore, and the Rust function
+                    // receives the default value of the output until it returns
+                    let ts = quote! {
+                        |#inputs| #output {
+                            if __callback_error.borrow().is_none() {
+                                match #ident.call( (#(#args_names,)*) ) {
+                                    Ok(output) => return output,
+                                    Err(e) => *__callback_error.borrow_mut() = Some(e),
+                                }
+                            }
+                            Default::default()
+                        }
+                    };
+                    ts.into()
+                },
+            ))
+        } else if ty == parse_quote!(&str) {
+            // JavaScript strings are encoded differently, so they are always copied
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(String)],
+                |_, ident| {
+                    let ts = quote! { &*#ident };
+                    ts.into()
+                },
+            ))
+        } else if is_bytes(&ty) {
+            let borrowed = matches!(ty, Type::Reference(_));
+            Ok(Input::new_custom(
+                ty,
+                vec![parse_quote!(napi::bindgen_prelude::Buffer)],
+                move |_, ident| {
+                    let ts = match borrowed {
+                        true => quote! { &*#ident },
+                        false => quote! { Vec::from(#ident) },
+                    };
+                    ts.into()
+                },
+            ))
+        } else if let Some(owned) = optional_borrowed(&ty) {
+            // optional strings and buffers are copied like the non-optional ones
+            let owned = match owned == parse_quote!(Option<Vec<u8>>) {
+                true => parse_quote!(Option<napi::bindgen_prelude::Buffer>),
+                false => owned,
+            };
+            Ok(Input::new_custom(ty, vec![owned], |_, ident| {
+                let ts = quote! { #ident.as_deref() };
+                ts.into()
+            }))
+        } else {
+            Ok(Input::new_unchanged(ty))
+        }
+    }
+
+    fn convert_output(output: Type) -> Result<Output, Self::Error> {
+        match match_generic_type(&output, parse_quote!(Result)) {
+            Some(inner) => {
+                let ok = inner
+                    .first()
+                    .ok_or_else(|| NapiError::InvalidResult(output.span()))?;
+
+                let (ok, map_ok) = match is_bytes(ok) {
+                    true => (
+                        parse_quote!(napi::bindgen_prelude::Buffer),
+                        quote! { .map(napi::bindgen_prelude::Buffer::from) },
+                    ),
+                    false => (ok.clone(), quote! {}),
+                };
+                Ok(Output::new_custom(
+                    output.clone(),
+                    parse_quote!(napi::Result<#ok, String>),
+                    move |ident| {
+                        let ts = quote! {
+                            let #ident = #ident
+                                #map_ok
+                                .map_err(crate::langs::IntoJsError::into_js_error);
+                        };
+                        ts.into()
+                    },
+                ))
+            }
+            None if is_bytes(&output) => Ok(Output::new_custom(
+                output,
+                parse_quote!(napi::bindgen_prelude::Buffer),
+                |ident| {
+                    let ts = quote! {
+                        let #ident = napi::bindgen_prelude::Buffer::from(#ident);
+                    };
+                    ts.into()
+                },
+            )),
+            None => Ok(Output::new_unchanged(output)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NapiError {
+    /// Functions must be exposed in a module, which prefixes their name in JavaScript
+    NakedFunction(Span),
+    Typings(std::io::Error),
+
+    Lang(LangError),
+
+    InvalidResult(Span),
+    /// JavaScript functions can only be called from the main thread, not from the library runtime
+    AsyncCallback(Span),
+}
+
+impl NapiError {
+    fn span(&self) -> Span {
+        match self {
+            NapiError::NakedFunction(span)
+            | NapiError::InvalidResult(span)
+            | NapiError::AsyncCallback(span) => *span,
+            NapiError::Typings(_) => Span::call_site(),
+            NapiError::Lang(e) => e.span(),
+        }
+    }
+}
+
+impl fmt::Display for NapiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NapiError::NakedFunction(_) => write!(
+                f,
+                "functions must be exposed inside an `#[expose_mod]` module to be exposed to Node.js"
+            ),
+            NapiError::Typings(e) => write!(f, "unable to write the TypeScript typings: {}", e),
+            NapiError::Lang(e) => write!(f, "{}", e),
+            NapiError::InvalidResult(_) => {
+                write!(f, "`Result` must have an ok and an error type")
+            }
+            NapiError::AsyncCallback(_) => write!(
+                f,
+                "async functions can't take callbacks, which can only be called from the \
+                 JavaScript thread"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NapiError {}
+
+impl From<LangError> for NapiError {
+    fn from(e: LangError) -> Self {
+        NapiError::Lang(e)
+    }
+}
+
+impl From<NapiError> for syn::Error {
+    fn from(e: NapiError) -> Self {
+        syn::Error::new(e.span(), e)
+    }
+}
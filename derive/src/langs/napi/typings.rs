@@ -0,0 +1,357 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use quote::format_ident;
+use syn::{
+    parse_quote, Attribute, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemMod, Lit, Meta,
+    NestedMeta, Pat, PatIdent, PatType, Signature, Stmt, Type, TypeBareFn, TypePath, TypeReference,
+    TypeTuple,
+};
+
+use super::{camel_case, iterator_item, last_segment, WRAPPER_PREFIX};
+use crate::types::*;
+
+const HEADER: &str = "// Automatically generated by the `derive` crate, do not edit\n";
+
+thread_local! {
+    /// Declarations of the root modules processed so far, by name, which all go to the same file
+    static MODULES: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+
+    /// JS type of the callbacks, by `<namespace>.<class>.<function>.<argument>`, since the
+    /// functions kept behind the exported ones take closures instead
+    static CALLBACKS: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+fn callback_key(namespace: &str, class: Option<&Ident>, function: &Ident, arg: &Ident) -> String {
+    let class = class.map(Ident::to_string).unwrap_or_default();
+    format!("{}.{}.{}.{}", namespace, class, function, arg)
+}
+
+/// Register the type of the callbacks taken by `sig`, exported in `namespace`, before they are
+/// turned into closures
+pub fn register_callbacks(namespace: &str, class: Option<&Ident>, sig: &Signature) {
+    for input in &sig.inputs {
+        if let FnArg::Typed(PatType { pat, ty, .. }) = input {
+            if let (Pat::Ident(PatIdent { ident, .. }), Type::BareFn(_)) = (pat.as_ref(), &**ty) {
+                let key = callback_key(namespace, class, &sig.ident, ident);
+                let ts = ts_type(ty, class);
+                CALLBACKS.with(|callbacks| callbacks.borrow_mut().insert(key, ts));
+            }
+        }
+    }
+}
+
+/// Role of an exported function, read back from its `#[napi]` attribute
+enum Role {
+    Constructor,
+    Getter,
+    Setter,
+    Regular,
+}
+
+/// Options of the `#[napi]` attribute of an item, if it has one
+fn napi_attr(attrs: &[Attribute]) -> Option<(Role, Option<String>)> {
+    let attr = attrs.iter().find(|a| {
+        a.path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "napi")
+    })?;
+
+    let mut role = Role::Regular;
+    let mut js_name = None;
+    if let Ok(Meta::List(list)) = attr.parse_meta() {
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("constructor") => {
+                    role = Role::Constructor
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("getter") => {
+                    role = Role::Getter
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("setter") => {
+                    role = Role::Setter
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("js_name") => {
+                    if let Lit::Str(name) = nv.lit {
+                        js_name = Some(name.value());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Some((role, js_name))
+}
+
+/// TypeScript type that corresponds to a Rust type
+///
+/// Types that are not recognized are assumed to be other exposed classes and are referenced by
+/// the last segment of their path
+fn ts_type(ty: &Type, class: Option<&Ident>) -> String {
+    if *ty == parse_quote!(Vec<u8>) || *ty == parse_quote!([u8]) {
+        return "Buffer".into();
+    }
+    if let Some(item) = iterator_item(ty) {
+        return format!("Array<{}>", ts_type(&item, class));
+    }
+
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => ts_type(elem, class),
+        Type::Tuple(TypeTuple { elems, .. }) if elems.is_empty() => "void".into(),
+        Type::BareFn(TypeBareFn { inputs, output, .. }) => format!(
+            "({}) => {}",
+            inputs
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| format!(
+                    "{}: {}",
+                    arg.name
+                        .as_ref()
+                        .map(|(name, _)| camel_case(&name.to_string()))
+                        .unwrap_or_else(|| format!("arg{}", i)),
+                    ts_type(&arg.ty, class)
+                ))
+                .collect::<Vec<_>>()
+                .join(", "),
+            ts_type(&output.as_type(), class)
+        ),
+        Type::Path(TypePath { .. }) => {
+            if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+                format!("{} | null", ts_type(&inner[0], class))
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(Vec)) {
+                format!("Array<{}>", ts_type(&inner[0], class))
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(Result)) {
+                ts_type(&inner[0], class)
+            } else {
+                match last_segment(ty).map(Ident::to_string).as_deref() {
+                    Some("Self") => class.map(Ident::to_string).unwrap_or_else(|| "any".into()),
+                    Some("String") | Some("str") => "string".into(),
+                    Some("bool") => "boolean".into(),
+                    Some("f32") | Some("f64") | Some("u8") | Some("u16") | Some("u32")
+                    | Some("i8") | Some("i16") | Some("i32") | Some("i64") => "number".into(),
+                    Some(other) => other.into(),
+                    None => "any".into(),
+                }
+            }
+        }
+        _ => "any".into(),
+    }
+}
+
+/// Whether an argument of the original function was marked with `#[default(...)]`, which turned
+/// it into an `Option` in the exported `wrapper`
+fn is_defaulted(ident: &Ident, ty: &Type, wrapper: &Signature) -> bool {
+    let arg = format_ident!("__{}_0", ident);
+
+    match_generic_type(ty, parse_quote!(Option)).is_none()
+        && wrapper.inputs.iter().any(|input| match input {
+            FnArg::Typed(PatType { pat, ty, .. }) => {
+                matches!(pat.as_ref(), Pat::Ident(PatIdent { ident, .. }) if *ident == arg)
+                    && match_generic_type(ty, parse_quote!(Option)).is_some()
+            }
+            FnArg::Receiver(_) => false,
+        })
+}
+
+/// Parameters of the original function, the trailing ones that are optional or have a default
+/// value can be left out like with the keyword arguments in Python. A default value that isn't
+/// last can also be skipped with `null`, to pass the following ones.
+fn params(sig: &Signature, wrapper: &Signature, namespace: &str, class: Option<&Ident>) -> String {
+    let args = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.as_ref() {
+                Pat::Ident(PatIdent { ident, .. }) => {
+                    Some((ident, ty, is_defaulted(ident, ty, wrapper)))
+                }
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let required = args.len()
+        - args
+            .iter()
+            .rev()
+            .take_while(|(_, ty, defaulted)| {
+                *defaulted || match_generic_type(ty, parse_quote!(Option)).is_some()
+            })
+            .count();
+
+    args.iter()
+        .enumerate()
+        .map(|(i, (ident, ty, defaulted))| {
+            let name = camel_case(ident.to_string().trim_start_matches('_'));
+            let key = callback_key(namespace, class, &sig.ident, ident);
+            let ty = match CALLBACKS.with(|callbacks| callbacks.borrow().get(&key).cloned()) {
+                Some(callback) => callback,
+                None => ts_type(ty, class),
+            };
+            let ty = match *defaulted && i + 1 < args.len() {
+                true => format!("{} | null", ty),
+                false => ty,
+            };
+
+            match i < required {
+                true => format!("{}: {}", name, ty),
+                false => format!("{}?: {}", name, ty),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Type returned by a function, a `Promise` for the async ones
+fn ret_type(sig: &Signature, class: Option<&Ident>) -> String {
+    let ret = ts_type(&sig.output.as_type(), class);
+    match sig.asyncness {
+        Some(_) => format!("Promise<{}>", ret),
+        None => ret,
+    }
+}
+
+/// Doc comment describing the error thrown by a function returning a `Result`
+fn throws(sig: &Signature, indent: &str) -> String {
+    match match_generic_type(&sig.output.as_type(), parse_quote!(Result))
+        .and_then(|inner| inner.get(1).cloned())
+    {
+        Some(error) if sig.asyncness.is_some() => format!(
+            "{}/** Rejects with an `Error` with `code` set to `\"{}\"` */\n",
+            indent,
+            ts_type(&error, None)
+        ),
+        Some(error) => format!(
+            "{}/** @throws {{Error}} with `code` set to `\"{}\"` */\n",
+            indent,
+            ts_type(&error, None)
+        ),
+        None => String::new(),
+    }
+}
+
+fn class(namespace: &str, class: &Ident, content: &[Item]) -> String {
+    let methods = content
+        .iter()
+        .filter_map(|item| match item {
+            Item::Impl(implementation)
+                if napi_attr(&implementation.attrs).is_some()
+                    && last_segment(&implementation.self_ty) == Some(class) =>
+            {
+                Some(implementation)
+            }
+            _ => None,
+        })
+        .flat_map(|implementation| {
+            let originals = implementation
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    ImplItem::Method(method) => Some(method),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            originals.clone().into_iter().filter_map(move |wrapper| {
+                let name = wrapper.sig.ident.to_string();
+                let original = name.strip_prefix(WRAPPER_PREFIX)?;
+                let (role, js_name) = napi_attr(&wrapper.attrs)?;
+                let original = originals.iter().find(|m| m.sig.ident == original)?;
+
+                Some((role, js_name, *original, wrapper))
+            })
+        });
+
+    let mut body = String::new();
+    for (role, js_name, ImplItemMethod { sig, .. }, wrapper) in methods {
+        let js_name = js_name.unwrap_or_else(|| camel_case(&sig.ident.to_string()));
+        let ret = ret_type(sig, Some(class));
+        let params = params(sig, &wrapper.sig, namespace, Some(class));
+
+        body.push_str(&throws(sig, "    "));
+        body.push_str(&match role {
+            Role::Constructor => format!("    constructor({})\n", params),
+            Role::Getter => format!("    get {}(): {}\n", js_name, ret),
+            Role::Setter => format!("    set {}({})\n", js_name, params),
+            Role::Regular => match sig.inputs.first() {
+                Some(FnArg::Receiver(_)) => format!("    {}({}): {}\n", js_name, params, ret),
+                _ => format!("    static {}({}): {}\n", js_name, params, ret),
+            },
+        });
+    }
+
+    format!("  export class {} {{\n{}  }}\n", class, body)
+}
+
+fn namespace(path: &[Ident], module: &ItemMod) -> String {
+    let content = &module.content.as_ref().expect("Empty module").1;
+    let name = path
+        .iter()
+        .map(Ident::to_string)
+        .collect::<Vec<_>>()
+        .join("_");
+
+    let mut body = String::new();
+    let mut nested = String::new();
+    for item in content {
+        match item {
+            Item::Struct(structure) if napi_attr(&structure.attrs).is_some() => {
+                body.push_str(&class(&name, &structure.ident, content));
+            }
+            Item::Fn(function) => {
+                let js_name = match napi_attr(&function.attrs) {
+                    Some((_, Some(js_name))) => js_name,
+                    _ => continue,
+                };
+                let original = function.block.stmts.iter().find_map(|stmt| match stmt {
+                    Stmt::Item(Item::Fn(original)) => Some(original),
+                    _ => None,
+                });
+
+                if let Some(original) = original {
+                    let sig = &original.sig;
+                    body.push_str(&throws(sig, "  "));
+                    body.push_str(&format!(
+                        "  export function {}({}): {}\n",
+                        js_name,
+                        params(sig, &function.sig, &name, None),
+                        ret_type(sig, None)
+                    ));
+                }
+            }
+            Item::Mod(submodule) if submodule.content.is_some() => {
+                let mut path = path.to_vec();
+                path.push(submodule.ident.clone());
+
+                nested.push_str(&namespace(&path, submodule));
+            }
+            _ => {}
+        }
+    }
+
+    match body.is_empty() {
+        true => nested,
+        false => format!(
+            "\nexport declare namespace {} {{\n{}}}\n{}",
+            name, body, nested
+        ),
+    }
+}
+
+/// Generate the TypeScript declarations for a root module and all of its submodules, after they
+/// have been processed by the N-API backend, along with the ones of the root modules before it
+pub fn generate(module: &ItemMod) -> String {
+    let declarations = namespace(std::slice::from_ref(&module.ident), module);
+    MODULES.with(|modules| {
+        let mut modules = modules.borrow_mut();
+        modules.insert(module.ident.to_string(), declarations);
+
+        format!(
+            "{}{}",
+            HEADER,
+            modules.values().cloned().collect::<String>()
+        )
+    })
+}
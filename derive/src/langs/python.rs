@@ -1,17 +1,48 @@
-use std::convert::TryFrom;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
 
-use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::{
-    parse_quote, Attribute, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemFn, Pat, PatIdent,
-    PatType, Token,
+    parse_quote, Attribute, Block, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemFn,
+    MetaNameValue, Pat, PatIdent, PatType, Signature, Token, TypeReference, TypeSlice,
 };
 
 use super::*;
-use crate::types::*;
+use crate::idl;
+
+mod stub;
+
+pub use stub::register_error_variants;
+
+/// Directory where the `.pyi` stubs are written, relative to the root of the crate
+const STUB_DIR: &str = "python/bdk";
+
+/// Protocol methods of a class, generated from the options of its struct and the attributes of its
+/// methods
+#[derive(Debug, Default)]
+struct Protocols {
+    /// Method marked with `#[str]`, returning a `String`
+    str: Option<Ident>,
+    /// Method marked with `#[bytes]`, returning a `Vec<u8>`
+    bytes: Option<Ident>,
+    eq: bool,
+    hash: bool,
+    clone: bool,
+}
+
+thread_local! {
+    /// Protocol methods of the classes exposed so far, by name
+    static PROTOCOLS: RefCell<BTreeMap<String, Protocols>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+fn update_protocols(class: &Ident, update: impl FnOnce(&mut Protocols)) {
+    PROTOCOLS
+        .with(|protocols| update(protocols.borrow_mut().entry(class.to_string()).or_default()));
+}
 
 #[derive(Debug)]
 pub struct Python;
@@ -19,14 +50,22 @@ pub struct Python;
 impl Lang for Python {
     type Error = PythonError;
 
-    fn expose_fn(function: &mut ItemFn, mod_path: &Vec<Ident>) -> Result<Ident, Self::Error> {
+    fn namespace() -> Ident {
+        format_ident!("__python")
+    }
+
+    fn expose_fn(function: &mut ItemFn, mod_path: &[Ident]) -> Result<Ident, Self::Error> {
         if mod_path.is_empty() {
-            return Err(PythonError::NakedFunction);
+            return Err(PythonError::NakedFunction(function.sig.ident.span()));
         }
 
+        buffers(&mut function.sig, &mut function.block);
+        lock_shared_args(&mut function.sig, &mut function.block);
+
         let ident = &function.sig.ident;
 
-        let (mut args, input_conversion) = Self::convert_fn_args(function.sig.inputs.clone())?;
+        let (mut args, mut input_conversion) = Self::convert_fn_args(function.sig.inputs.clone())?;
+        keyword_names(&mut args, &mut input_conversion);
 
         let ExpandedReturn {
             ret,
@@ -41,9 +80,14 @@ impl Lang for Python {
 
         let block = &function.block;
 
-        let ident_str = ident.to_string();
+        let keywords = keyword_args(&args);
+        let attr: Attribute = match keywords.is_empty() {
+            true => parse_quote!( #[pyo3::prelude::pyfunction] ),
+            false => parse_quote!( #[pyo3::prelude::pyfunction(#keywords)] ),
+        };
+
         *function = parse_quote! {
-            #[pyo3::prelude::pyfunction]
+            #attr
             fn #ident(#args) #ret {
                 use crate::mapping::{MapTo, MapFrom};
 
@@ -54,22 +98,65 @@ impl Lang for Python {
                 #output_conversion
             }
         };
+        idl::record_export(&function.sig.ident, &function.sig.ident, &function.sig);
 
         Ok(function.sig.ident.clone())
     }
 
     fn expose_mod(
         module: &mut ItemMod,
-        mod_path: &Vec<Ident>,
+        mod_path: &[Ident],
         sub_items: Vec<ModuleItem>,
     ) -> Result<Ident, Self::Error> {
         let ident = &module.ident;
         let content = &mut module.content.as_mut().expect("Empty module").1;
 
+        // the shared structs are wrapped by another class, declared right after them, and so are
+        // the protocol methods of the other ones
+        let mut index = 0;
+        while index < content.len() {
+            if let Item::Struct(structure) = &content[index] {
+                let items = Self::struct_items(structure, mod_path);
+                let count = items.len();
+                content.splice(index + 1..index + 1, items);
+                index += count;
+            }
+            index += 1;
+        }
+
         let mut content_tokens = TokenStream2::default();
-        content_tokens.append_all(content);
+        content_tokens.append_all(content.iter());
+
+        let submodules = sub_items
+            .iter()
+            .filter_map(|sub_item| match sub_item {
+                ModuleItem::Module(ident) => Some(ident.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let stub_path = mod_path
+            .iter()
+            .map(Ident::to_string)
+            .collect::<Vec<_>>()
+            .join("/");
+        let stub_name = match submodules.is_empty() {
+            true => format!("{}.pyi", stub_path),
+            false => format!("{}/__init__.pyi", stub_path),
+        };
+        write_generated(STUB_DIR, &stub_name, &stub::generate(content, &submodules))
+            .map_err(PythonError::Stub)?;
 
         let mut export_tokens = TokenStream2::default();
+        for error in stub::result_errors(content) {
+            let error_str = stub::py_type(&error, None);
+            export_tokens.extend(quote! {
+                m.add(#error_str, <#error as crate::langs::PyErrorType>::type_object(py))?;
+                for variant in <#error as crate::langs::PyErrorType>::variant_type_objects(py) {
+                    m.add(variant.name()?, variant)?;
+                }
+            });
+        }
         for sub_item in sub_items {
             let tokens = match sub_item {
                 ModuleItem::Function(ident) => {
@@ -90,7 +177,6 @@ impl Lang for Python {
                         m.add_submodule(submod)?;
                     }
                 }
-                ModuleItem::Trait(ident) => TokenStream2::default(),
             };
 
             export_tokens.extend(tokens);
@@ -116,30 +202,79 @@ impl Lang for Python {
         Ok(module.ident.clone())
     }
 
+    fn iterator_kind() -> &'static str {
+        "iterator"
+    }
+
+    fn struct_items(structure: &ItemStruct, mod_path: &[Ident]) -> Vec<Item> {
+        let ty = &structure.ident;
+        match is_shared(&parse_quote!(#ty)) {
+            true => shared_class_items(structure, mod_path),
+            false => protocol_impl(ty),
+        }
+    }
+
     fn expose_struct(
         structure: &mut ItemStruct,
         opts: Punctuated<ExposeStructOpts, Token![,]>,
-        mod_path: &Vec<Ident>,
+        mod_path: &[Ident],
     ) -> Result<Ident, Self::Error> {
-        structure
-            .attrs
-            .push(parse_quote!( #[pyo3::prelude::pyclass] ));
+        register_struct(&structure.ident, &opts);
+        let eq = opts.iter().any(|o| *o == ExposeStructOpts::Eq);
+        let hash = opts.iter().any(|o| *o == ExposeStructOpts::Hash);
+        let clone = opts.iter().any(|o| *o == ExposeStructOpts::Clone);
+        if eq || hash || clone {
+            update_protocols(&structure.ident, |protocols| {
+                protocols.eq = eq;
+                protocols.hash = hash;
+                protocols.clone = clone;
+            });
+        }
+
+        if opts.iter().any(|o| *o == ExposeStructOpts::Shared) {
+            if eq || hash || clone {
+                return Err(PythonError::SharedProtocol(structure.span()));
+            }
+
+            // the class wrapping it is added next to it by `expose_mod`
+            return Ok(shared_class(&structure.ident));
+        }
+
+        structure.attrs.push(match mod_path.is_empty() {
+            true => parse_quote!( #[pyo3::prelude::pyclass] ),
+            false => {
+                let module = class_module(mod_path);
+                parse_quote!( #[pyo3::prelude::pyclass(module = #module)] )
+            }
+        });
 
         Ok(structure.ident.clone())
     }
 
-    fn expose_impl(
-        implementation: &mut ItemImpl,
-        mod_path: &Vec<Ident>,
-    ) -> Result<(), Self::Error> {
+    fn expose_impl(implementation: &mut ItemImpl, _mod_path: &[Ident]) -> Result<(), Self::Error> {
         implementation
             .attrs
             .push(parse_quote!( #[pyo3::prelude::pymethods] ));
 
+        let exposed = implementation
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ImplItem::Method(method) => Some(method.sig.ident.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(class) = last_segment(&implementation.self_ty).cloned() {
+            take_protocol_methods(implementation, &class)?;
+            let copy_methods = copy_methods(implementation, &class);
+            implementation.items.extend(copy_methods);
+        }
+
         // remove items marked as "destructors" because pyo3 handles them automatically
         implementation.items.retain(|item| {
-            if let ImplItem::Method(ImplItemMethod { sig, attrs, .. }) = item {
-                if let Some(pos) = attrs.iter().position(|a| a.path.is_ident("destructor")) {
+            if let ImplItem::Method(ImplItemMethod { attrs, .. }) = item {
+                if attrs.iter().any(|a| a.path.is_ident("destructor")) {
                     return false;
                 }
             }
@@ -148,13 +283,25 @@ impl Lang for Python {
         });
 
         for item in &mut implementation.items {
-            if let ImplItem::Method(ImplItemMethod { sig, attrs, .. }) = item {
+            if let ImplItem::Method(ImplItemMethod {
+                sig, attrs, block, ..
+            }) = item
+            {
+                fill_defaults(sig, block)?;
+                let keywords = keyword_args(&sig.inputs);
+                if !keywords.is_empty() {
+                    attrs.push(parse_quote!( #[args(#keywords)] ));
+                }
+
                 if let Some(pos) = attrs.iter().position(|a| a.path.is_ident("constructor")) {
                     attrs.remove(pos);
                     attrs.push(parse_quote!( #[new] ));
 
                     continue;
                 }
+                if attrs.iter().any(|a| a.path.is_ident("setter")) {
+                    check_setter(sig)?;
+                }
 
                 match sig.inputs.first() {
                     // the first argument is not some kind of "self", so this is a static method
@@ -164,6 +311,68 @@ impl Lang for Python {
             }
         }
 
+        let original = last_segment(&implementation.self_ty).cloned();
+        let shared = is_shared(&implementation.self_ty);
+        for item in &mut implementation.items {
+            if let ImplItem::Method(method) = item {
+                buffers(&mut method.sig, &mut method.block);
+                iterator(&mut method.sig, &mut method.block);
+
+                if let (Some(original), true) = (&original, shared) {
+                    // `Self` is about to become the wrapping class
+                    method.sig =
+                        syn::parse2(rename_ident(method.sig.to_token_stream(), "Self", original))
+                            .expect("Invalid signature");
+                    method.block = syn::parse2(rename_ident(
+                        method.block.to_token_stream(),
+                        "Self",
+                        original,
+                    ))
+                    .expect("Invalid block");
+                }
+
+                let blocking = take_blocking(method)?;
+                let locked = lock_shared_args(&mut method.sig, &mut method.block);
+                if (shared || locked) && method.sig.asyncness.is_some() {
+                    return Err(PythonError::SharedAsync(method.sig.span()));
+                }
+
+                if let (Some(original), true) = (&original, shared) {
+                    release_gil(method, original);
+                } else if blocking {
+                    allow_threads(method);
+                }
+            }
+        }
+        if let (Some(original), true) = (&original, shared) {
+            let class = shared_class(original);
+            implementation.self_ty = parse_quote!(#class);
+        }
+
+        // done last, since the receiver is replaced by an argument
+        for item in &mut implementation.items {
+            if let ImplItem::Method(method) = item {
+                if method.sig.asyncness.is_some() {
+                    expose_async_method(method)?;
+                }
+            }
+        }
+
+        for item in &implementation.items {
+            if let ImplItem::Method(ImplItemMethod { sig, attrs, .. }) = item {
+                if !exposed.contains(&sig.ident) {
+                    continue;
+                }
+
+                let symbol = match accessor_name(attrs, &sig.ident) {
+                    Some(property) => property,
+                    None if attrs.iter().any(|a| a.path.is_ident("new")) => "__new__".into(),
+                    None => sig.ident.to_string(),
+                };
+                idl::record_export(&sig.ident, symbol, sig);
+            }
+        }
+
         Ok(())
     }
 
@@ -175,8 +384,11 @@ impl Lang for Python {
             let args_names = bare_fn
                 .inputs
                 .iter()
-                .map(|arg| arg.name.clone().unwrap().0)
-                .collect::<Punctuated<Ident, Comma>>();
+                .map(|arg| match &arg.name {
+                    Some((name, _)) => Ok(name.clone()),
+                    None => Err(LangError::UnnamedCallbackArguments(bare_fn.span())),
+                })
+                .collect::<Result<Punctuated<Ident, Comma>, _>>()?;
 
             Ok(Input::new_custom(
                 ty,
@@ -200,16 +412,640 @@ impl Lang for Python {
     }
 }
 
+/// Make the arguments marked with `#[default(...)]` optional, and fill in their default at the
+/// start of `block` when they are `None`
+fn fill_defaults(sig: &mut Signature, block: &mut Block) -> Result<(), LangError> {
+    let mut defaults = TokenStream2::default();
+    for input in sig.inputs.iter_mut() {
+        if let FnArg::Typed(arg) = input {
+            let default = take_default(arg)?;
+            if let (Some(default), Pat::Ident(PatIdent { ident, .. })) = (default, arg.pat.as_ref())
+            {
+                defaults.extend(quote! {
+                    let #ident = #ident.unwrap_or_else(|| #default);
+                });
+            }
+        }
+    }
+
+    if !defaults.is_empty() {
+        *block = parse_quote!({
+            #defaults
+            #block
+        });
+    }
+
+    Ok(())
+}
+
+/// Give back their name to the arguments converted to a single one, which `convert_fn_args` names
+/// `__<name>_0`, so that they can be passed by keyword
+fn keyword_names(args: &mut Punctuated<FnArg, Token![,]>, conversion: &mut TokenStream2) {
+    let names = args
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { pat, .. }) => match pat.as_ref() {
+                Pat::Ident(PatIdent { ident, .. }) => Some(ident.to_string()),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    for arg in args.iter_mut() {
+        if let FnArg::Typed(PatType { pat, .. }) = arg {
+            if let Pat::Ident(PatIdent { ident, .. }) = pat.as_mut() {
+                let renamed = ident.to_string();
+                let name = match renamed
+                    .strip_prefix("__")
+                    .and_then(|name| name.strip_suffix("_0"))
+                {
+                    Some(name) if !names.contains(&format!("__{}_1", name)) => name,
+                    _ => continue,
+                };
+
+                let name = format_ident!("{}", name, span = ident.span());
+                *conversion = rename_ident(std::mem::take(conversion), &renamed, &name);
+                *ident = name;
+            }
+        }
+    }
+}
+
+/// Defaults of the trailing `Option` arguments, which can be omitted or passed by keyword, in the
+/// format of `#[args(...)]`
+fn keyword_args(inputs: &Punctuated<FnArg, Token![,]>) -> Punctuated<MetaNameValue, Token![,]> {
+    let mut keywords = inputs
+        .iter()
+        .rev()
+        .map_while(|input| match input {
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.as_ref() {
+                Pat::Ident(PatIdent { ident, .. })
+                    if match_generic_type(ty, parse_quote!(Option)).is_some() =>
+                {
+                    Some(parse_quote!(#ident = "None"))
+                }
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<MetaNameValue>>();
+    keywords.reverse();
+
+    keywords.into_iter().collect()
+}
+
+/// Python module of the classes exposed in `mod_path`, so that `pickle` can import them
+fn class_module(mod_path: &[Ident]) -> String {
+    std::iter::once("bdk".to_string())
+        .chain(mod_path.iter().map(Ident::to_string))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Name of the class wrapping a struct exposed with the `"shared"` option
+fn shared_class(structure: &Ident) -> Ident {
+    format_ident!("Shared{}", structure)
+}
+
+/// Class wrapping a shared struct, which keeps it behind a reference counted lock and is exposed
+/// to Python with the name of the struct
+fn shared_class_items(structure: &ItemStruct, mod_path: &[Ident]) -> Vec<Item> {
+    let ident = &structure.ident;
+    let vis = &structure.vis;
+    let class = shared_class(ident);
+    let name = ident.to_string();
+    let module = class_module(mod_path);
+
+    vec![
+        parse_quote! {
+            #[pyo3::prelude::pyclass(name = #name, module = #module)]
+            #vis struct #class {
+                inner: std::sync::Arc<std::sync::Mutex<#ident>>,
+            }
+        },
+        parse_quote! {
+            impl From<#ident> for #class {
+                fn from(inner: #ident) -> Self {
+                    #class {
+                        inner: std::sync::Arc::new(std::sync::Mutex::new(inner)),
+                    }
+                }
+            }
+        },
+        parse_quote! {
+            impl pyo3::IntoPy<pyo3::PyObject> for #ident {
+                fn into_py(self, py: pyo3::Python) -> pyo3::PyObject {
+                    #class::from(self).into_py(py)
+                }
+            }
+        },
+        assert_send(ident),
+    ]
+}
+
+/// Exchange the `Vec<u8>` of a function as Python bytes instead of lists of integers, and borrow
+/// its `&[T]` arguments from Python buffers
+fn buffers(sig: &mut Signature, block: &mut Block) {
+    let mut conversions = TokenStream2::default();
+
+    for input in sig.inputs.iter_mut() {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            _ => continue,
+        };
+        let ident = match &*arg.pat {
+            Pat::Ident(pat) => pat.ident.clone(),
+            _ => continue,
+        };
+        if *arg.ty == parse_quote!(Vec<u8>) {
+            conversions.extend(quote! {
+                let #ident: Vec<u8> = #ident.0;
+            });
+            arg.ty = parse_quote!(crate::langs::PyByteBuffer);
+        } else if let Type::Reference(TypeReference {
+            elem,
+            mutability: None,
+            ..
+        }) = &*arg.ty
+        {
+            let elem = match elem.as_ref() {
+                Type::Slice(TypeSlice { elem, .. }) => elem.clone(),
+                _ => continue,
+            };
+            // read in place when possible
+            conversions.extend(quote! {
+                let #ident: &[#elem] = &#ident;
+            });
+            arg.ty = parse_quote!(crate::langs::PySlice<#elem>);
+        }
+    }
+
+    let output = sig.output.as_type();
+    let bytes: Type = parse_quote!(Vec<u8>);
+    let conv = if output == bytes {
+        sig.output = parse_quote!(-> crate::langs::PyByteBuffer);
+        quote!(crate::langs::PyByteBuffer(__output))
+    } else {
+        match match_generic_type(&output, parse_quote!(Result)) {
+            Some(inner) if inner.len() == 2 && inner[0] == bytes => {
+                let err = &inner[1];
+                sig.output = parse_quote!(-> Result<crate::langs::PyByteBuffer, #err>);
+                quote!(__output.map(crate::langs::PyByteBuffer))
+            }
+            _ if conversions.is_empty() => return,
+            _ => quote!(__output),
+        }
+    };
+
+    *block = parse_quote!({
+        #conversions
+
+        let __output: #output = #block;
+        #conv
+    });
+}
+
+/// Remember the methods marked with `#[str]` and `#[bytes]`, removing the attributes
+fn take_protocol_methods(implementation: &mut ItemImpl, class: &Ident) -> Result<(), PythonError> {
+    let shared = is_shared(&implementation.self_ty);
+
+    for item in &mut implementation.items {
+        if let ImplItem::Method(method) = item {
+            let ident = method.sig.ident.clone();
+            let mut found = false;
+            method.attrs.retain(|a| {
+                if a.path.is_ident("str") {
+                    update_protocols(class, |protocols| protocols.str = Some(ident.clone()));
+                } else if a.path.is_ident("bytes") {
+                    update_protocols(class, |protocols| protocols.bytes = Some(ident.clone()));
+                } else {
+                    return true;
+                }
+
+                found = true;
+                false
+            });
+
+            if found && shared {
+                return Err(PythonError::SharedProtocol(method.sig.span()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Methods used by `pickle` and `copy`: `__reduce__` rebuilds the object by passing the output of
+/// its `#[str]` or `#[bytes]` method to its constructor, when it takes a single argument of that
+/// kind, and `__copy__` and `__deepcopy__` clone the structs exposed with the `"clone"` option
+fn copy_methods(implementation: &ItemImpl, class: &Ident) -> Vec<ImplItem> {
+    let (str_method, bytes_method, clone) = PROTOCOLS.with(|protocols| {
+        protocols
+            .borrow()
+            .get(&class.to_string())
+            .map(|p| (p.str.clone(), p.bytes.clone(), p.clone))
+            .unwrap_or_default()
+    });
+    let mut methods = vec![];
+
+    let constructor_arg = implementation.items.iter().find_map(|item| match item {
+        ImplItem::Method(method) if method.attrs.iter().any(|a| a.path.is_ident("constructor")) => {
+            match method.sig.inputs.iter().collect::<Vec<_>>().as_slice() {
+                [FnArg::Typed(PatType { ty, .. })] => Some(ty.as_ref().clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    });
+    let state_method = constructor_arg.and_then(|ty| {
+        let ty = match ty {
+            Type::Reference(TypeReference { elem, .. }) => *elem,
+            ty => ty,
+        };
+        if ty == parse_quote!(str) || ty == parse_quote!(String) {
+            str_method
+        } else if ty == parse_quote!([u8]) || ty == parse_quote!(Vec<u8>) {
+            bytes_method
+        } else {
+            None
+        }
+    });
+    if let Some(state_method) = state_method {
+        methods.push(parse_quote! {
+            fn __reduce__(&self, __py: pyo3::Python) -> (pyo3::PyObject, (pyo3::PyObject,)) {
+                let state = pyo3::IntoPy::into_py(self.#state_method(), __py);
+
+                (pyo3::IntoPy::into_py(__py.get_type::<Self>(), __py), (state,))
+            }
+        });
+    }
+
+    if clone {
+        methods.push(parse_quote! {
+            fn __copy__(&self) -> Self {
+                self.clone()
+            }
+        });
+        methods.push(parse_quote! {
+            fn __deepcopy__(&self, _memo: &pyo3::PyAny) -> Self {
+                self.clone()
+            }
+        });
+    }
+
+    methods
+}
+
+/// Implementation of `PyObjectProtocol` for a class that has protocol methods
+fn protocol_impl(class: &Ident) -> Vec<Item> {
+    let name = class.to_string();
+    let mut methods = TokenStream2::default();
+
+    PROTOCOLS.with(|protocols| {
+        let protocols = protocols.borrow();
+        let protocols = match protocols.get(&name) {
+            Some(protocols) => protocols,
+            None => return vec![],
+        };
+
+        if let Some(str_method) = &protocols.str {
+            methods.extend(quote! {
+                fn __str__(&self) -> String {
+                    self.#str_method()
+                }
+
+                // quoted the way Python quotes strings
+                fn __repr__(&self) -> pyo3::PyResult<String> {
+                    pyo3::Python::with_gil(|py| {
+                        let value = pyo3::types::PyString::new(py, &self.#str_method());
+                        Ok(format!("{}({})", #name, value.repr()?.to_str()?))
+                    })
+                }
+            });
+        }
+        if let Some(bytes_method) = &protocols.bytes {
+            methods.extend(quote! {
+                fn __bytes__(&self) -> crate::langs::PyByteBuffer {
+                    self.#bytes_method()
+                }
+            });
+        }
+        if protocols.eq {
+            methods.extend(quote! {
+                fn __richcmp__(
+                    &self,
+                    other: &pyo3::PyAny,
+                    op: pyo3::class::basic::CompareOp,
+                ) -> pyo3::PyObject {
+                    use pyo3::class::basic::CompareOp;
+
+                    let py = pyo3::PyNativeType::py(other);
+                    let other = match other.extract::<pyo3::PyRef<Self>>() {
+                        Ok(other) => other,
+                        Err(_) => return py.NotImplemented(),
+                    };
+                    match op {
+                        CompareOp::Eq => pyo3::IntoPy::into_py(*self == *other, py),
+                        CompareOp::Ne => pyo3::IntoPy::into_py(*self != *other, py),
+                        _ => py.NotImplemented(),
+                    }
+                }
+            });
+        }
+        if protocols.hash {
+            methods.extend(quote! {
+                fn __hash__(&self) -> u64 {
+                    use std::hash::{Hash, Hasher};
+
+                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                    self.hash(&mut hasher);
+                    hasher.finish()
+                }
+            });
+        }
+
+        vec![
+            // the methods that are not slots are looked up through the trait
+            parse_quote!(
+                use pyo3::class::PyObjectProtocol as _;
+            ),
+            parse_quote! {
+                #[pyo3::prelude::pyproto]
+                impl pyo3::class::PyObjectProtocol for #class {
+                    #methods
+                }
+            },
+        ]
+    })
+}
+
+/// Return an `impl Iterator` as a Python iterator, which produces its items as they are requested
+fn iterator(sig: &mut Signature, block: &mut Block) {
+    if let Some(item) = iterator_item(&sig.output.as_type()) {
+        sig.output = parse_quote!(-> crate::langs::PyIter<#item>);
+        *block = parse_quote!({ crate::langs::PyIter::new(#block) });
+    }
+}
+
+/// Lock the shared structs borrowed by a function, which receives their wrapping class instead.
+/// Returns whether any was found.
+fn lock_shared_args(sig: &mut Signature, block: &mut Block) -> bool {
+    let mut locks = TokenStream2::default();
+
+    for input in sig.inputs.iter_mut() {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            _ => continue,
+        };
+        let (ident, elem, mutable, optional) = match (&*arg.pat, borrowed_arg(&arg.ty)) {
+            (Pat::Ident(pat), Some((reference, optional))) if is_shared(&reference.elem) => (
+                pat.ident.clone(),
+                reference.elem.clone(),
+                reference.mutability.is_some(),
+                optional,
+            ),
+            _ => continue,
+        };
+        let class = shared_class(last_segment(&elem).expect("Shared struct without a name"));
+
+        let guard = format_ident!("{}_guard", ident);
+        match optional {
+            true => arg.ty = parse_quote!(Option<&#class>),
+            false => arg.ty = parse_quote!(&#class),
+        }
+        locks.extend(match (mutable, optional) {
+            (true, false) => quote! {
+                let mut #guard = #ident.inner.lock().expect("Poisoned shared object");
+                let #ident: &mut #elem = &mut *#guard;
+            },
+            (false, false) => quote! {
+                let #guard = #ident.inner.lock().expect("Poisoned shared object");
+                let #ident: &#elem = &*#guard;
+            },
+            (true, true) => quote! {
+                let mut #guard = #ident.map(|o| o.inner.lock().expect("Poisoned shared object"));
+                let #ident: Option<&mut #elem> = #guard.as_deref_mut();
+            },
+            (false, true) => quote! {
+                let #guard = #ident.map(|o| o.inner.lock().expect("Poisoned shared object"));
+                let #ident: Option<&#elem> = #guard.as_deref();
+            },
+        });
+    }
+
+    if locks.is_empty() {
+        return false;
+    }
+    *block = parse_quote!({
+        #locks
+
+        let __output = #block;
+        __output
+    });
+
+    true
+}
+
+/// Run the body of a method of a shared struct without holding the GIL, after locking the object
+///
+/// Constructors return the wrapping class, the other methods return the struct itself, which is
+/// wrapped when converted to a Python object.
+fn release_gil(method: &mut ImplItemMethod, original: &Ident) {
+    let class = shared_class(original);
+    let block = rename_self(method.block.to_token_stream(), &format_ident!("__self"));
+    let py: FnArg = parse_quote!(__py: pyo3::Python);
+
+    match method.sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) => {
+            let borrow = match receiver.mutability {
+                Some(_) => quote! {
+                    let mut __guard = __inner.lock().expect("Poisoned shared object");
+                    let __self: &mut #original = &mut *__guard;
+                },
+                None => quote! {
+                    let __guard = __inner.lock().expect("Poisoned shared object");
+                    let __self: &#original = &*__guard;
+                },
+            };
+
+            method.sig.inputs[0] = parse_quote!(&self);
+            method.sig.inputs.insert(1, py);
+            method.block = parse_quote!({
+                let __inner = &self.inner;
+                __py.allow_threads(move || {
+                    #borrow
+
+                    let __output = #block;
+                    __output
+                })
+            });
+        }
+        _ if method.attrs.iter().any(|a| a.path.is_ident("new")) => {
+            let output = method.sig.output.as_type();
+            let (ret, conv) = match match_generic_type(&output, parse_quote!(Result)) {
+                Some(inner) => {
+                    let err = &inner[1];
+                    (
+                        quote!(Result<#class, #err>),
+                        quote!(__output.map(#class::from)),
+                    )
+                }
+                None => (quote!(#class), quote!(#class::from(__output))),
+            };
+
+            method.sig.inputs.insert(0, py);
+            method.sig.output = parse_quote!(-> #ret);
+            method.block = parse_quote!({
+                let __output: #output = __py.allow_threads(move || #block);
+                #conv
+            });
+        }
+        _ => {
+            method.sig.inputs.insert(0, py);
+            method.block = parse_quote!({
+                __py.allow_threads(move || #block)
+            });
+        }
+    }
+}
+
+/// Whether a method is marked with `#[blocking]`, removing the attribute
+///
+/// Blocking methods run without holding the GIL, so that the other threads can go on, for example
+/// to cancel them. Async methods already don't block.
+fn take_blocking(method: &mut ImplItemMethod) -> Result<bool, PythonError> {
+    let pos = match method
+        .attrs
+        .iter()
+        .position(|a| a.path.is_ident("blocking"))
+    {
+        Some(pos) => pos,
+        None => return Ok(false),
+    };
+    let attr = method.attrs.remove(pos);
+    if method.sig.asyncness.is_some() {
+        return Err(PythonError::BlockingAsync(attr.span()));
+    }
+
+    Ok(true)
+}
+
+/// Run the body of a blocking method without holding the GIL
+///
+/// The receiver and the arguments are moved into a closure run by `allow_threads`, so they must be
+/// `Send`.
+fn allow_threads(method: &mut ImplItemMethod) {
+    let block = &method.block;
+    let pos = match method.sig.inputs.first() {
+        Some(FnArg::Receiver(_)) => 1,
+        _ => 0,
+    };
+
+    method
+        .sig
+        .inputs
+        .insert(pos, parse_quote!(__py: pyo3::Python));
+    method.block = parse_quote!({
+        __py.allow_threads(move || #block)
+    });
+}
+
+/// Turn an `async fn` into a method that spawns its body on the library runtime and returns an
+/// awaitable
+///
+/// Python may change or release the objects while the future runs, so the future works on copies
+/// of the receiver and of the arguments passed by reference, made before it's spawned.
+fn expose_async_method(method: &mut ImplItemMethod) -> Result<(), PythonError> {
+    if method.attrs.iter().any(|a| a.path.is_ident("new")) {
+        return Err(PythonError::InvalidAsync(method.sig.span()));
+    }
+    if let Some(FnArg::Receiver(receiver)) = method.sig.inputs.first() {
+        if receiver.reference.is_none() {
+            return Err(PythonError::InvalidAsync(receiver.span()));
+        }
+    }
+
+    let sig = &mut method.sig;
+    let output = sig.output.as_type();
+    let block = rename_self(method.block.to_token_stream(), &format_ident!("__self"));
+    let (copies, borrows) = copy_borrowed_args(&sig.inputs)?;
+
+    sig.asyncness = None;
+    sig.output = parse_quote!(-> pyo3::PyResult<crate::langs::Awaitable<#output>>);
+    method.block = parse_quote!({
+        #copies
+
+        crate::langs::Awaitable::spawn(async move {
+            #borrows
+
+            let __output: #output = async move #block.await;
+            __output
+        })
+    });
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub enum PythonError {
-    NakedFunction,
+    /// Functions must be exposed in a module, which becomes the Python module
+    NakedFunction(Span),
+    /// Async methods can't be constructors or take `self` by value
+    InvalidAsync(Span),
+    /// Async methods can't use shared structs, which stay locked during the call
+    SharedAsync(Span),
+    /// Protocol methods are not generated for shared structs
+    SharedProtocol(Span),
+    /// Async methods don't block, they can't be marked with `#[blocking]`
+    BlockingAsync(Span),
+    Stub(std::io::Error),
 
     Lang(LangError),
 }
 
+impl PythonError {
+    fn span(&self) -> Span {
+        match self {
+            PythonError::NakedFunction(span)
+            | PythonError::InvalidAsync(span)
+            | PythonError::SharedAsync(span)
+            | PythonError::SharedProtocol(span)
+            | PythonError::BlockingAsync(span) => *span,
+            PythonError::Stub(_) => Span::call_site(),
+            PythonError::Lang(e) => e.span(),
+        }
+    }
+}
+
 impl fmt::Display for PythonError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            PythonError::NakedFunction(_) => write!(
+                f,
+                "functions must be exposed inside an `#[expose_mod]` module to be exposed to Python"
+            ),
+            PythonError::InvalidAsync(_) => write!(
+                f,
+                "async methods can't be constructors or take `self` by value"
+            ),
+            PythonError::SharedAsync(_) => write!(
+                f,
+                "async methods can't use shared structs, which would stay locked for the whole call"
+            ),
+            PythonError::SharedProtocol(_) => write!(
+                f,
+                "protocol methods like `__str__` or `__eq__` are not supported on shared structs"
+            ),
+            PythonError::BlockingAsync(_) => {
+                write!(
+                    f,
+                    "async methods don't block, they can't be marked `#[blocking]`"
+                )
+            }
+            PythonError::Stub(e) => write!(f, "unable to write the Python stub: {}", e),
+            PythonError::Lang(e) => write!(f, "{}", e),
+        }
     }
 }
 
@@ -220,3 +1056,9 @@ impl From<LangError> for PythonError {
         PythonError::Lang(e)
     }
 }
+
+impl From<PythonError> for syn::Error {
+    fn from(e: PythonError) -> Self {
+        syn::Error::new(e.span(), e)
+    }
+}
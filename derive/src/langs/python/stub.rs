@@ -0,0 +1,410 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use proc_macro2::TokenStream;
+
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    braced, parse_quote, Attribute, FnArg, Ident, ImplItem, ImplItemMethod, Item, ItemFn,
+    ItemStruct, Lit, Meta, MetaNameValue, NestedMeta, Pat, PatIdent, PatType, ReturnType,
+    Signature, Token, Type, TypeBareFn, TypePath, TypeReference, TypeSlice, TypeTuple,
+};
+
+use crate::types::*;
+
+const HEADER: &str = "# Automatically generated by the `derive` crate, do not edit\n\n";
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|a| a.path.is_ident(name))
+}
+
+fn has_pyo3_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|a| {
+        a.path.segments.len() == 3
+            && a.path.segments.first().unwrap().ident == "pyo3"
+            && a.path.segments.last().unwrap().ident == name
+    })
+}
+
+/// Name of the Python class of a struct, which can be renamed by `#[pyclass(name = "...")]`
+fn class_name(structure: &ItemStruct) -> Ident {
+    let attr = structure
+        .attrs
+        .iter()
+        .find(|a| has_pyo3_attr(std::slice::from_ref(a), "pyclass"));
+    if let Some(Ok(Meta::List(list))) = attr.map(Attribute::parse_meta) {
+        for nested in list.nested {
+            if let NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(name),
+                ..
+            })) = nested
+            {
+                if path.is_ident("name") {
+                    return Ident::new(&name.value(), name.span());
+                }
+            }
+        }
+    }
+
+    structure.ident.clone()
+}
+
+/// Name of the Python type that corresponds to a Rust type
+///
+/// Types that are not recognized are assumed to be other exposed classes and are referenced by
+/// the last segment of their path
+pub fn py_type(ty: &Type, class: Option<&Ident>) -> String {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => py_type(elem, class),
+        Type::Slice(TypeSlice { elem, .. }) if **elem == parse_quote!(u8) => "bytes".into(),
+        Type::Tuple(TypeTuple { elems, .. }) if elems.is_empty() => "None".into(),
+        Type::Tuple(TypeTuple { elems, .. }) => format!(
+            "Tuple[{}]",
+            elems
+                .iter()
+                .map(|t| py_type(t, class))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Type::BareFn(TypeBareFn { inputs, output, .. }) => format!(
+            "Callable[[{}], {}]",
+            inputs
+                .iter()
+                .map(|arg| py_type(&arg.ty, class))
+                .collect::<Vec<_>>()
+                .join(", "),
+            py_type(&output.as_type(), class)
+        ),
+        Type::Path(TypePath { path, .. }) => {
+            if let Some(inner) = match_generic_type(ty, parse_quote!(Option)) {
+                format!("Optional[{}]", py_type(&inner[0], class))
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(Vec)) {
+                format!("List[{}]", py_type(&inner[0], class))
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(Result)) {
+                py_type(&inner[0], class)
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(pyo3::PyResult)) {
+                py_type(&inner[0], class)
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(crate::langs::PySlice))
+            {
+                match inner[0] == parse_quote!(u8) {
+                    true => "bytes".into(),
+                    false => format!("Sequence[{}]", py_type(&inner[0], class)),
+                }
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(crate::langs::PyIter)) {
+                format!("Iterator[{}]", py_type(&inner[0], class))
+            } else if let Some(inner) = match_generic_type(ty, parse_quote!(pyo3::PyCell)) {
+                py_type(&inner[0], class)
+            } else {
+                let last = &path.segments.last().expect("Empty type path").ident;
+                match last.to_string().as_str() {
+                    "Self" => class.map(Ident::to_string).unwrap_or_else(|| "Any".into()),
+                    "String" | "str" => "str".into(),
+                    "bool" => "bool".into(),
+                    "f32" | "f64" => "float".into(),
+                    "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32"
+                    | "i64" | "i128" | "isize" => "int".into(),
+                    "PyCb" => "Callable[..., Any]".into(),
+                    "PyObject" | "PyAny" => "Any".into(),
+                    // also accepts `bytearray` and the other objects implementing the buffer
+                    // protocol, which type checkers treat as `bytes`
+                    "PyByteBuffer" => "bytes".into(),
+                    // class wrapping a shared struct, which has the name of the struct
+                    other => match other.strip_prefix("Shared") {
+                        Some(name)
+                            if syn::parse_str(name).is_ok_and(|t| crate::langs::is_shared(&t)) =>
+                        {
+                            name.into()
+                        }
+                        _ => other.into(),
+                    },
+                }
+            }
+        }
+        _ => "Any".into(),
+    }
+}
+
+/// Return the error type if `output` is a `Result`
+fn result_error(output: &ReturnType) -> Option<Type> {
+    match_generic_type(&output.as_type(), parse_quote!(Result))
+        .and_then(|inner| inner.get(1).cloned())
+}
+
+/// Arguments that can be omitted, listed by `#[args(...)]` or `#[pyfunction(...)]`
+fn keyword_names(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("args") || has_pyo3_attr(std::slice::from_ref(a), "pyfunction"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, .. })) => {
+                path.get_ident().map(Ident::to_string)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn stub_args(sig: &Signature, class: Option<&Ident>, keywords: &[String]) -> Vec<String> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Receiver(_) => Some("self".to_string()),
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.as_ref() {
+                // token passed to the methods that release the GIL
+                Pat::Ident(PatIdent { ident, .. }) if ident == "__py" => None,
+                Pat::Ident(PatIdent { ident, .. }) => {
+                    let default = match keywords.contains(&ident.to_string()) {
+                        true => " = ...",
+                        false => "",
+                    };
+                    Some(format!("{}: {}{}", ident, py_type(ty, class), default))
+                }
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+fn stub_def(name: &str, args: &[String], ret: &str, error: Option<Type>, indent: &str) -> String {
+    let raises = error
+        .map(|e| format!("  # raises {}", py_type(&e, None)))
+        .unwrap_or_default();
+
+    format!(
+        "{}def {}({}) -> {}: ...{}\n",
+        indent,
+        name,
+        args.join(", "),
+        ret,
+        raises
+    )
+}
+
+/// Name of the Python property generated by pyo3 for a `#[getter]` or a `#[setter]`
+fn property_name(method: &ImplItemMethod) -> String {
+    crate::langs::accessor_name(&method.attrs, &method.sig.ident).expect("Not an accessor")
+}
+
+/// Output of an async method, which returns an `Awaitable` once exposed
+fn awaited(output: &ReturnType) -> Option<Type> {
+    match_generic_type(&output.as_type(), parse_quote!(pyo3::PyResult))
+        .and_then(|inner| match_generic_type(&inner[0], parse_quote!(crate::langs::Awaitable)))
+        .map(|inner| inner[0].clone())
+}
+
+fn stub_method(method: &ImplItemMethod, class: &Ident) -> String {
+    let sig = &method.sig;
+    if let Some(output) = awaited(&sig.output) {
+        let mut sig = sig.clone();
+        sig.output = parse_quote!(-> #output);
+        let method = ImplItemMethod {
+            sig,
+            ..method.clone()
+        };
+
+        return stub_method(&method, class).replacen("def ", "async def ", 1);
+    }
+
+    let error = result_error(&sig.output);
+    let ret = py_type(&sig.output.as_type(), Some(class));
+    let args = stub_args(sig, Some(class), &keyword_names(&method.attrs));
+
+    if sig.ident == "__richcmp__" {
+        // only equality is supported
+        stub_def(
+            "__eq__",
+            &["self".to_string(), "other: object".to_string()],
+            "bool",
+            None,
+            "    ",
+        )
+    } else if has_attr(&method.attrs, "new") {
+        let mut init_args = vec!["self".to_string()];
+        init_args.extend(args);
+
+        stub_def("__init__", &init_args, "None", error, "    ")
+    } else if has_attr(&method.attrs, "getter") {
+        format!(
+            "    @property\n{}",
+            stub_def(&property_name(method), &args, &ret, error, "    ")
+        )
+    } else if has_attr(&method.attrs, "setter") {
+        let name = property_name(method);
+        format!(
+            "    @{}.setter\n{}",
+            name,
+            stub_def(&name, &args, "None", error, "    ")
+        )
+    } else if has_attr(&method.attrs, "staticmethod") {
+        format!(
+            "    @staticmethod\n{}",
+            stub_def(&sig.ident.to_string(), &args, &ret, error, "    ")
+        )
+    } else {
+        stub_def(&sig.ident.to_string(), &args, &ret, error, "    ")
+    }
+}
+
+fn stub_function(function: &ItemFn) -> String {
+    let sig = &function.sig;
+
+    stub_def(
+        &sig.ident.to_string(),
+        &stub_args(sig, None, &keyword_names(&function.attrs)),
+        &py_type(&sig.output.as_type(), None),
+        result_error(&sig.output),
+        "",
+    )
+}
+
+fn exposed_methods(content: &[Item]) -> impl Iterator<Item = (&Ident, &ImplItemMethod)> {
+    content
+        .iter()
+        .filter_map(|item| match item {
+            Item::Impl(implementation)
+                if has_pyo3_attr(&implementation.attrs, "pymethods")
+                    || has_pyo3_attr(&implementation.attrs, "pyproto") =>
+            {
+                match implementation.self_ty.as_ref() {
+                    Type::Path(TypePath { path, .. }) => Some((
+                        &path.segments.last().expect("Empty type path").ident,
+                        implementation,
+                    )),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .flat_map(|(class, implementation)| {
+            implementation
+                .items
+                .iter()
+                .filter_map(move |item| match item {
+                    ImplItem::Method(method) => Some((class, method)),
+                    _ => None,
+                })
+        })
+}
+
+fn exposed_functions(content: &[Item]) -> impl Iterator<Item = &ItemFn> {
+    content.iter().filter_map(|item| match item {
+        Item::Fn(function) if has_pyo3_attr(&function.attrs, "pyfunction") => Some(function),
+        _ => None,
+    })
+}
+
+/// Error types returned through a `Result` by the items exposed in a module
+pub fn result_errors(content: &[Item]) -> Vec<Type> {
+    let mut seen = BTreeSet::new();
+
+    exposed_methods(content)
+        .map(|(_, method)| match awaited(&method.sig.output) {
+            Some(output) => parse_quote!(-> #output),
+            None => method.sig.output.clone(),
+        })
+        .chain(exposed_functions(content).map(|function| function.sig.output.clone()))
+        .filter_map(|output| result_error(&output))
+        .filter(|e| seen.insert(py_type(e, None)))
+        .collect()
+}
+
+/// Error and the variants raised as its own subclasses, as listed by `impl_py_error!`
+struct ErrorVariants {
+    error: Ident,
+    variants: Vec<Ident>,
+}
+
+impl Parse for ErrorVariants {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let error = input.parse()?;
+        let content;
+        braced!(content in input);
+        let variants = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+
+        Ok(ErrorVariants { error, variants })
+    }
+}
+
+thread_local! {
+    /// Variants raised as subclasses of the errors registered so far, by name
+    static ERROR_VARIANTS: RefCell<BTreeMap<String, Vec<String>>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Register the variants of an error raised as subclasses, from the input of `impl_py_error!`
+pub fn register_error_variants(input: TokenStream) -> syn::Result<()> {
+    let ErrorVariants { error, variants } = syn::parse2(input)?;
+    let variants = variants.iter().map(Ident::to_string).collect();
+    ERROR_VARIANTS.with(|errors| errors.borrow_mut().insert(error.to_string(), variants));
+
+    Ok(())
+}
+
+/// Variants raised as subclasses of an error, registered by its `impl_py_error!`
+fn error_variants(error: &str) -> Vec<String> {
+    ERROR_VARIANTS.with(|errors| errors.borrow().get(error).cloned().unwrap_or_default())
+}
+
+/// Generate the `.pyi` stub for a module, after it has been processed by the Python backend
+pub fn generate(content: &[Item], submodules: &[Ident]) -> String {
+    let mut stub = HEADER.to_string();
+    stub.push_str(
+        "from typing import Any, Callable, Iterator, List, Optional, Sequence, Tuple\n\n",
+    );
+
+    for submodule in submodules {
+        stub.push_str(&format!("from . import {} as {}\n", submodule, submodule));
+    }
+    if !submodules.is_empty() {
+        stub.push('\n');
+    }
+
+    for error in result_errors(content) {
+        let error = py_type(&error, None);
+        stub.push_str(&format!("class {}(Exception): ...\n\n", error));
+        for variant in error_variants(&error) {
+            stub.push_str(&format!("class {}({}): ...\n\n", variant, error));
+        }
+    }
+
+    for item in content {
+        if let Item::Struct(structure) = item {
+            if !has_pyo3_attr(&structure.attrs, "pyclass") {
+                continue;
+            }
+
+            let class = class_name(structure);
+            // setters come after the getters, `@<name>.setter` refers to the property they declare
+            let (setters, others): (Vec<_>, Vec<_>) = exposed_methods(content)
+                .filter(|(c, _)| **c == structure.ident)
+                .map(|(_, method)| method)
+                .partition(|method| has_attr(&method.attrs, "setter"));
+            let methods = others
+                .into_iter()
+                .chain(setters)
+                .map(|method| stub_method(method, &class))
+                .collect::<String>();
+
+            stub.push_str(&format!("class {}:\n", class));
+            match methods.is_empty() {
+                true => stub.push_str("    ...\n"),
+                false => stub.push_str(&methods),
+            }
+            stub.push('\n');
+        }
+    }
+
+    for function in exposed_functions(content) {
+        stub.push_str(&stub_function(function));
+    }
+
+    format!("{}\n", stub.trim_end())
+}
@@ -0,0 +1,313 @@
+use std::fmt;
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Attribute, FnArg, Ident, ImplItem, ImplItemMethod, ItemFn, ItemStruct, Pat,
+    PatIdent, PatType, Signature, Token,
+};
+
+use super::*;
+use crate::idl;
+
+/// Prefix of the methods generated to wrap the original ones in an exposed `impl`
+const WRAPPER_PREFIX: &str = "__wasm_";
+
+#[derive(Debug)]
+pub struct Wasm;
+
+impl Wasm {
+    /// Build a function with the converted signature of `sig`, which calls `call` and converts
+    /// the result back
+    fn wrap_fn(
+        sig: &Signature,
+        ident: Ident,
+        attr: Attribute,
+        prelude: TokenStream2,
+        call: TokenStream2,
+    ) -> Result<ItemFn, WasmError> {
+        // iterators are returned as an array
+        let collected = collected_sig(sig);
+        let sig = collected.as_ref().unwrap_or(sig);
+
+        let (args, input_conversion) = Self::convert_fn_args(sig.inputs.clone())?;
+        let call_args = sig
+            .inputs
+            .iter()
+            .map(|input| match input {
+                FnArg::Receiver(_) => Ok(format_ident!("self")),
+                FnArg::Typed(PatType { pat, .. }) => match pat.as_ref() {
+                    Pat::Ident(PatIdent { ident, .. }) => Ok(ident.clone()),
+                    pat => Err(LangError::ComplexPatternFnArg(pat.span())),
+                },
+            })
+            .collect::<Result<Punctuated<Ident, Comma>, _>>()?;
+
+        let ExpandedReturn {
+            ret,
+            conv: output_conversion,
+            ..
+        } = Return(sig.output.clone()).expand(
+            &format_ident!("__output"),
+            &format_ident!("__ptr_out"),
+            Self::convert_output,
+        )?;
+
+        // wasm-bindgen turns async functions into a `Promise`, keeping the objects they borrow
+        // alive and locked until it's settled
+        let asyncness = sig.asyncness;
+        let mut call = match asyncness {
+            Some(_) => quote! { #call(#call_args).await },
+            None => quote! { #call(#call_args) },
+        };
+        if collected.is_some() {
+            call = quote! { #call.collect::<Vec<_>>() };
+        }
+
+        Ok(parse_quote! {
+            #attr
+            #[allow(unused_variables)]
+            pub #asyncness fn #ident(#args) #ret {
+                use crate::mapping::{MapTo, MapFrom};
+
+                #prelude
+                #input_conversion
+
+                let __output = #call;
+                #output_conversion
+            }
+        })
+    }
+}
+
+impl Lang for Wasm {
+    type Error = WasmError;
+
+    fn namespace() -> Ident {
+        format_ident!("__wasm")
+    }
+
+    fn expose_fn(function: &mut ItemFn, mod_path: &[Ident]) -> Result<Ident, Self::Error> {
+        if mod_path.is_empty() {
+            return Err(WasmError::NakedFunction(function.sig.ident.span()));
+        }
+
+        let mut original = function.clone();
+        MethodRole::take_from(&mut original.attrs);
+        strip_defaults(&mut original.sig);
+
+        // wasm-bindgen exports everything at the top level, so functions are prefixed with the
+        // name of their module
+        let ident = original.sig.ident.clone();
+        let js_name = camel_case(&format!(
+            "{}_{}",
+            mod_path.last().expect("Empty module path"),
+            ident
+        ));
+
+        // keep the original function nested inside the exported one, so that it can be called
+        // with the converted arguments
+        *function = Self::wrap_fn(
+            &function.sig,
+            ident.clone(),
+            parse_quote!( #[wasm_bindgen::prelude::wasm_bindgen(js_name = #js_name)] ),
+            quote! { #original },
+            quote! { #ident },
+        )?;
+        idl::record_export(&ident, &js_name, &function.sig);
+
+        Ok(function.sig.ident.clone())
+    }
+
+    fn expose_mod(
+        module: &mut ItemMod,
+        _mod_path: &[Ident],
+        _sub_items: Vec<ModuleItem>,
+    ) -> Result<Ident, Self::Error> {
+        // the TypeScript declarations are generated by the `wasm-bindgen` CLI
+        Ok(module.ident.clone())
+    }
+
+    fn expose_struct(
+        structure: &mut ItemStruct,
+        _opts: Punctuated<ExposeStructOpts, Token![,]>,
+        _mod_path: &[Ident],
+    ) -> Result<Ident, Self::Error> {
+        structure
+            .attrs
+            .push(parse_quote!( #[wasm_bindgen::prelude::wasm_bindgen] ));
+
+        Ok(structure.ident.clone())
+    }
+
+    fn expose_impl(implementation: &mut ItemImpl, _mod_path: &[Ident]) -> Result<(), Self::Error> {
+        implementation
+            .attrs
+            .push(parse_quote!( #[wasm_bindgen::prelude::wasm_bindgen] ));
+
+        // wasm-bindgen generates a `free()` method for every class, so destructors are
+        // removed
+        implementation.items.retain(|item| match item {
+            ImplItem::Method(ImplItemMethod { attrs, .. }) => {
+                !attrs.iter().any(|a| a.path.is_ident("destructor"))
+            }
+            _ => true,
+        });
+
+        let mut wrappers = vec![];
+        for item in &mut implementation.items {
+            if let ImplItem::Method(ImplItemMethod { sig, attrs, .. }) = item {
+                let ident = &sig.ident;
+                let wrapper = format_ident!("{}{}", WRAPPER_PREFIX, ident);
+
+                let property =
+                    accessor_name(attrs, ident).map(|name| format_ident!("{}", camel_case(&name)));
+                let role = MethodRole::take_from(attrs);
+                if let (Some(asyncness), false) = (sig.asyncness, role == MethodRole::Regular) {
+                    return Err(LangError::AsyncRole(asyncness.span()).into());
+                }
+                let (js_name, attr): (String, Attribute) = match role {
+                    MethodRole::Destructor => continue,
+                    MethodRole::Constructor => (
+                        "constructor".into(),
+                        parse_quote!( #[wasm_bindgen(constructor)] ),
+                    ),
+                    MethodRole::Getter => {
+                        let js_name = property.expect("Getter without a name");
+                        let attr = parse_quote!( #[wasm_bindgen(getter = #js_name)] );
+                        (js_name.to_string(), attr)
+                    }
+                    MethodRole::Setter => {
+                        check_setter(sig)?;
+                        let js_name = property.expect("Setter without a name");
+                        let attr = parse_quote!( #[wasm_bindgen(setter = #js_name)] );
+                        (js_name.to_string(), attr)
+                    }
+                    MethodRole::Regular => {
+                        let js_name = camel_case(&ident.to_string());
+                        let attr = parse_quote!( #[wasm_bindgen(js_name = #js_name)] );
+                        (js_name, attr)
+                    }
+                };
+
+                let ItemFn {
+                    attrs,
+                    vis,
+                    sig: wrapper_sig,
+                    block,
+                } = Self::wrap_fn(sig, wrapper, attr, quote! {}, quote! { Self::#ident })?;
+                idl::record_export(ident, &js_name, &wrapper_sig);
+                wrappers.push(ImplItem::Method(ImplItemMethod {
+                    attrs,
+                    vis,
+                    defaultness: None,
+                    sig: wrapper_sig,
+                    block: *block,
+                }));
+            }
+        }
+        implementation.items.extend(wrappers);
+
+        Ok(())
+    }
+
+    fn convert_input(ty: Type) -> Result<Input, Self::Error> {
+        match ty {
+            // there's no synchronous way to call back into JS from an exported function that
+            // would work for any signature
+            Type::BareFn(_) => Err(WasmError::UnsupportedType(ty.span())),
+            // wasm-bindgen can only take optional objects by value, which would consume them
+            ty if matches!(borrowed_arg(&ty), Some((r, true)) if struct_kind(&r.elem).is_some()) => {
+                Err(WasmError::UnsupportedType(ty.span()))
+            }
+            // wasm-bindgen can't borrow optional strings and slices, they are received owned
+            ty => match optional_borrowed(&ty) {
+                Some(owned) => Ok(Input::new_custom(ty, vec![owned], |_, ident| {
+                    let ts = quote! { #ident.as_deref() };
+                    ts.into()
+                })),
+                // `Vec<u8>` and `&[u8]` are passed as an `Uint8Array` by wasm-bindgen
+                None => Ok(Input::new_unchanged(ty)),
+            },
+        }
+    }
+
+    fn convert_output(output: Type) -> Result<Output, Self::Error> {
+        match match_generic_type(&output, parse_quote!(Result)) {
+            Some(inner) => {
+                let ok = inner
+                    .first()
+                    .ok_or_else(|| WasmError::InvalidResult(output.span()))?;
+
+                Ok(Output::new_custom(
+                    output.clone(),
+                    parse_quote!(Result<#ok, wasm_bindgen::JsValue>),
+                    |ident| {
+                        let ts = quote! {
+                            let #ident = #ident.map_err(crate::langs::IntoJsException::into_js_exception);
+                        };
+                        ts.into()
+                    },
+                ))
+            }
+            None => Ok(Output::new_unchanged(output)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WasmError {
+    /// Functions must be exposed in a module, which prefixes their name in JavaScript
+    NakedFunction(Span),
+
+    Lang(LangError),
+
+    UnsupportedType(Span),
+    InvalidResult(Span),
+}
+
+impl WasmError {
+    fn span(&self) -> Span {
+        match self {
+            WasmError::NakedFunction(span)
+            | WasmError::UnsupportedType(span)
+            | WasmError::InvalidResult(span) => *span,
+            WasmError::Lang(e) => e.span(),
+        }
+    }
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmError::NakedFunction(_) => write!(
+                f,
+                "functions must be exposed inside an `#[expose_mod]` module to be exposed to \
+                 WebAssembly"
+            ),
+            WasmError::Lang(e) => write!(f, "{}", e),
+            WasmError::UnsupportedType(_) => {
+                write!(f, "this type is not supported by the WebAssembly bindings")
+            }
+            WasmError::InvalidResult(_) => {
+                write!(f, "`Result` must have an ok and an error type")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WasmError {}
+
+impl From<LangError> for WasmError {
+    fn from(e: LangError) -> Self {
+        WasmError::Lang(e)
+    }
+}
+
+impl From<WasmError> for syn::Error {
+    fn from(e: WasmError) -> Self {
+        syn::Error::new(e.span(), e)
+    }
+}
@@ -1,38 +1,226 @@
-#![allow(unused_imports)]
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{quote, ToTokens};
-use syn::parse::Parser;
+use quote::{format_ident, quote, ToTokens};
 use syn::punctuated::Punctuated;
 use syn::{
-    parse_macro_input, parse_quote, Attribute, Fields, Ident, ImplItem, ImplItemMethod, Item,
-    ItemFn, ItemImpl, ItemMod, ItemStruct, ItemTrait, Token, Type, TypePath,
+    parse_macro_input, parse_quote, Attribute, Fields, Ident, ImplItem, Item, ItemFn, ItemImpl,
+    ItemMod, ItemStruct, ItemTrait, Token, Visibility,
 };
 
+mod idl;
 mod langs;
+// conversions shared by the languages, each of them only uses some
+#[allow(dead_code)]
 mod types;
 
 use langs::Lang;
 use types::*;
 
-#[cfg(feature = "c")]
-type CurrentLang = langs::c::C;
-#[cfg(feature = "python")]
-type CurrentLang = langs::python::Python;
+/// Evaluate `$body` once for every enabled language, with `$lang` being its `Lang` type
+macro_rules! for_each_lang {
+    ($lang:ident => $body:expr) => {{
+        let mut expanded = Vec::<TokenStream2>::new();
+
+        #[cfg(feature = "c")]
+        {
+            type $lang = langs::c::C;
+            expanded.push($body);
+        }
+        #[cfg(feature = "python")]
+        {
+            type $lang = langs::python::Python;
+            expanded.push($body);
+        }
+        #[cfg(feature = "jni")]
+        {
+            type $lang = langs::jni::Jni;
+            expanded.push($body);
+        }
+        #[cfg(feature = "napi")]
+        {
+            type $lang = langs::napi::Napi;
+            expanded.push($body);
+        }
+        #[cfg(feature = "wasm")]
+        {
+            type $lang = langs::wasm::Wasm;
+            expanded.push($body);
+        }
+
+        expanded
+    }};
+}
+
+thread_local! {
+    /// Structs exposed outside of an `#[expose_mod]` module so far
+    static STANDALONE_STRUCTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// Number of impls exposed outside of an `#[expose_mod]` module so far, by type
+    static STANDALONE_IMPLS: RefCell<BTreeMap<String, usize>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+fn enabled_langs() -> usize {
+    [
+        cfg!(feature = "c"),
+        cfg!(feature = "python"),
+        cfg!(feature = "jni"),
+        cfg!(feature = "napi"),
+        cfg!(feature = "wasm"),
+    ]
+    .iter()
+    .filter(|enabled| **enabled)
+    .count()
+}
+
+/// Items exposed outside of an `#[expose_mod]` module are left in place when a single language is
+/// enabled. Otherwise every language works on its own copy of the item, in a `<namespace>_<name>`
+/// module where the structs exposed the same way before it are in scope.
+///
+/// The private items are made visible to the parent module, so that they can still be used like
+/// the original ones. The self type of an impl must be one of these structs.
+fn expand_standalone<L: Lang>(name: &Ident, expanded: TokenStream2) -> TokenStream2 {
+    if enabled_langs() == 1 {
+        return expanded;
+    }
+
+    let namespace = format_ident!("{}_{}", L::namespace(), name);
+    let structs = STANDALONE_STRUCTS.with(|structs| {
+        structs
+            .borrow()
+            .iter()
+            .filter(|s| name != s)
+            .map(|s| {
+                let s = format_ident!("{}", s);
+                let module = format_ident!("{}_{}", L::namespace(), s);
+                quote! {
+                    #[allow(unused_imports)]
+                    use super::#module::#s;
+                }
+            })
+            .collect::<TokenStream2>()
+    });
+
+    quote! {
+        pub mod #namespace {
+            use super::*;
+            #structs
 
-fn check_struct(s: &ItemStruct) {
-    if !matches!(s.fields, Fields::Named(_)) {
-        panic!("Only named structs are supported");
+            #expanded
+        }
+    }
+}
+
+/// Make an item visible outside of the namespace it's moved to by `expand_standalone`
+fn widen_visibility(vis: &mut Visibility) {
+    if let Visibility::Inherited = vis {
+        *vis = parse_quote!(pub(super));
+    }
+}
+
+/// Turn the result of exposing an item into its tokens, or a compile error pointing to the part
+/// that couldn't be exposed
+fn expanded_or_error(result: syn::Result<TokenStream2>) -> TokenStream2 {
+    result.unwrap_or_else(|e| e.to_compile_error())
+}
+
+/// Compile error reported by a language
+fn lang_error<E: Into<syn::Error>>(e: E) -> syn::Error {
+    e.into()
+}
+
+fn check_struct(s: &ItemStruct) -> syn::Result<()> {
+    match s.fields {
+        Fields::Named(_) => Ok(()),
+        _ => Err(syn::Error::new_spanned(
+            &s.fields,
+            "only structs with named fields can be exposed",
+        )),
+    }
+}
+
+/// Every `#[setter]` needs a `#[getter]` for the same property in the impl, write-only properties
+/// can't be declared in most languages
+fn check_impl(implementation: &ItemImpl) -> syn::Result<()> {
+    let accessors = |name: &'static str| {
+        implementation
+            .items
+            .iter()
+            .filter_map(move |item| match item {
+                ImplItem::Method(method) if method.attrs.iter().any(|a| a.path.is_ident(name)) => {
+                    Some((
+                        method,
+                        langs::accessor_name(&method.attrs, &method.sig.ident),
+                    ))
+                }
+                _ => None,
+            })
+    };
+    let getters = accessors("getter")
+        .filter_map(|(_, property)| property)
+        .collect::<Vec<_>>();
+
+    for (setter, property) in accessors("setter") {
+        if let Some(property) = property.filter(|p| !getters.contains(p)) {
+            return Err(syn::Error::new_spanned(
+                &setter.sig.ident,
+                format!(
+                    "the property `{}` has a setter but no getter, add a `#[getter]` for it",
+                    property
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_struct_opts(attr: &Attribute) -> syn::Result<Punctuated<ExposeStructOpts, Token![,]>> {
+    attr.parse_args_with(Punctuated::<ExposeStructOpts, Token![,]>::parse_terminated)
+}
+
+/// Register every struct exposed in `module` and its submodules, so that they are known to the
+/// items that come before their declaration
+fn register_structs(module: &ItemMod) -> syn::Result<()> {
+    for item in module.content.iter().flat_map(|(_, items)| items) {
+        match item {
+            Item::Mod(inner_module) => register_structs(inner_module)?,
+            Item::Struct(structure) => {
+                if let Some(attr) = structure
+                    .attrs
+                    .iter()
+                    .find(|a| a.path.is_ident("expose_struct"))
+                {
+                    langs::register_struct(&structure.ident, &parse_struct_opts(attr)?);
+                }
+            }
+            _ => {}
+        }
     }
+
+    Ok(())
 }
 
-fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
+/// Expose the items of `module` and its submodules, returning their description
+fn analyze_module<L: Lang>(module: &mut ItemMod, mut path: Vec<Ident>) -> syn::Result<idl::Module> {
     path.push(module.ident.clone());
 
     let mut sub_items = vec![];
+    let mut description = idl::Module::new(&module.ident);
+
+    let span = module.ident.span();
+    let items = match module.content.as_mut() {
+        Some((_, items)) => items,
+        None => {
+            return Err(syn::Error::new(
+                span,
+                "exposed modules must be declared inline, with their items",
+            ))
+        }
+    };
 
-    for item in &mut module.content.as_mut().expect("Empty module").1 {
+    for item in items {
         match item {
             Item::Mod(inner_module) => {
                 if let Some(pos) = inner_module
@@ -41,7 +229,7 @@ fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
                     .position(|a| a.path.is_ident("expose_mod"))
                 {
                     inner_module.attrs.remove(pos);
-                    analyze_module(inner_module, path.clone());
+                    description.add_module(analyze_module::<L>(inner_module, path.clone())?);
 
                     sub_items.push(ModuleItem::Module(inner_module.ident.clone()));
                 }
@@ -53,9 +241,16 @@ fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
                     .position(|a| a.path.is_ident("expose_fn"))
                 {
                     function.attrs.remove(pos);
-                    sub_items.push(ModuleItem::Function(
-                        CurrentLang::expose_fn(function, &path).unwrap(),
+                    let original = function.sig.clone();
+
+                    let symbol = L::expose_fn(function, &path).map_err(lang_error)?;
+                    description.add_function(idl::Function::new::<L>(
+                        &original,
+                        None,
+                        None,
+                        &mut idl::take_exports(),
                     ));
+                    sub_items.push(ModuleItem::Function(symbol));
                 }
             }
             Item::Struct(structure) => {
@@ -64,14 +259,17 @@ fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
                     .iter()
                     .position(|a| a.path.is_ident("expose_struct"))
                 {
-                    let parser = Punctuated::<ExposeStructOpts, Token![,]>::parse_terminated;
-                    let opts = structure.attrs[pos].parse_args_with(parser).unwrap();
+                    let opts = parse_struct_opts(&structure.attrs[pos])?;
 
                     structure.attrs.remove(pos);
-                    check_struct(structure);
+                    check_struct(structure)?;
+                    description.add_struct(idl::Struct::new(
+                        structure,
+                        &opts.iter().cloned().collect::<Vec<_>>(),
+                    ));
 
                     sub_items.push(ModuleItem::Structure(
-                        CurrentLang::expose_struct(structure, opts, &path).unwrap(),
+                        L::expose_struct(structure, opts, &path).map_err(lang_error)?,
                     ));
                 }
             }
@@ -82,7 +280,18 @@ fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
                     .position(|a| a.path.is_ident("expose_impl"))
                 {
                     implementation.attrs.remove(pos);
-                    CurrentLang::expose_impl(implementation, &path).unwrap();
+                    check_impl(implementation)?;
+                    let original = implementation.clone();
+                    L::expose_impl(implementation, &path).map_err(lang_error)?;
+                    description.add_impl::<L>(&original, idl::take_exports());
+
+                    // the methods that are kept behind the exposed ones still have their defaults
+                    for item in &mut implementation.items {
+                        if let ImplItem::Method(method) = item {
+                            strip_defaults(&mut method.sig);
+                            accept_closures(&mut method.sig);
+                        }
+                    }
                 }
             }
             Item::Trait(tr) => {
@@ -94,7 +303,7 @@ fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
                     tr.attrs.remove(pos);
 
                     // sub_items.push(ModuleItem::Trait(
-                    //     CurrentLang::expose_trait(tr, &path).unwrap(),
+                    //     L::expose_trait(tr, &path).unwrap(),
                     // ));
                 }
             }
@@ -102,65 +311,151 @@ fn analyze_module(module: &mut ItemMod, mut path: Vec<Ident>) {
         }
     }
 
-    CurrentLang::expose_mod(module, &path, sub_items).unwrap();
+    L::expose_mod(module, &path, sub_items).map_err(lang_error)?;
+
+    Ok(description)
 }
 
 #[proc_macro_attribute]
 pub fn expose_mod(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut input = parse_macro_input!(item as ItemMod);
-    analyze_module(&mut input, vec![]);
+    let input = parse_macro_input!(item as ItemMod);
+    if let Err(e) = register_structs(&input) {
+        return e.to_compile_error().into();
+    }
 
-    (quote! {
-        #input
-    })
+    // every language works on its own copy of the module, wrapped in a namespace to avoid clashes
+    for_each_lang!(L => expanded_or_error((|| -> syn::Result<TokenStream2> {
+        let mut module = input.clone();
+        analyze_module::<L>(&mut module, vec![])?
+            .write::<L>()
+            .map_err(|e| {
+                syn::Error::new(
+                    module.ident.span(),
+                    format!("unable to write the interface description: {}", e),
+                )
+            })?;
+
+        // named after the module, so that multiple modules can be exposed side by side
+        let namespace = format_ident!("{}_{}", L::namespace(), module.ident);
+        Ok(quote! {
+            pub mod #namespace {
+                use super::*;
+
+                #module
+            }
+        })
+    })()))
+    .into_iter()
+    .collect::<TokenStream2>()
     .into()
 }
 
 #[proc_macro_attribute]
 pub fn expose_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut input = parse_macro_input!(item as ItemFn);
-    CurrentLang::expose_fn(&mut input, &vec![]).unwrap();
+    if enabled_langs() > 1 {
+        widen_visibility(&mut input.vis);
+    }
 
-    (quote! {
-        #input
-    })
+    for_each_lang!(L => expanded_or_error((|| -> syn::Result<TokenStream2> {
+        let mut exposed = input.clone();
+        L::expose_fn(&mut exposed, &[]).map_err(lang_error)?;
+
+        Ok(expand_standalone::<L>(&input.sig.ident, exposed.into_token_stream()))
+    })()))
+    .into_iter()
+    .collect::<TokenStream2>()
     .into()
 }
 
 #[proc_macro_attribute]
 pub fn expose_struct(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut input = parse_macro_input!(item as ItemStruct);
-    check_struct(&input);
-
-    let attr: TokenStream2 = attr.into();
-    let attr: Attribute = parse_quote! { #attr };
+    if let Err(e) = check_struct(&input) {
+        return e.to_compile_error().into();
+    }
 
     let parser = Punctuated::<ExposeStructOpts, Token![,]>::parse_terminated;
-    let opts = attr.parse_args_with(parser).unwrap();
+    let opts = parse_macro_input!(attr with parser);
 
-    CurrentLang::expose_struct(&mut input, opts, &vec![]).unwrap();
+    if enabled_langs() > 1 {
+        widen_visibility(&mut input.vis);
+        for field in &mut input.fields {
+            widen_visibility(&mut field.vis);
+        }
+    }
 
-    (quote! {
-        #input
-    })
-    .into()
+    let expanded = for_each_lang!(L => expanded_or_error((|| -> syn::Result<TokenStream2> {
+        let mut exposed = input.clone();
+        L::expose_struct(&mut exposed, opts.clone(), &[]).map_err(lang_error)?;
+        let items = L::struct_items(&exposed, &[]);
+
+        Ok(expand_standalone::<L>(&input.ident, quote!(#exposed #(#items)*)))
+    })()));
+    STANDALONE_STRUCTS.with(|structs| structs.borrow_mut().push(input.ident.to_string()));
+
+    expanded.into_iter().collect::<TokenStream2>().into()
 }
 
 #[proc_macro_attribute]
 pub fn expose_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut input = parse_macro_input!(item as ItemImpl);
-    CurrentLang::expose_impl(&mut input, &vec![]).unwrap();
+    if let Err(e) = check_impl(&input) {
+        return e.to_compile_error().into();
+    }
 
-    (quote! {
-        #input
-    })
+    // there can be multiple impls for the same type, each one needs its own namespace
+    let name = match langs::last_segment(&input.self_ty) {
+        Some(ty) => {
+            let count = STANDALONE_IMPLS.with(|impls| {
+                let mut impls = impls.borrow_mut();
+                let count = impls.entry(ty.to_string()).or_default();
+                *count += 1;
+                *count
+            });
+            format_ident!("{}_impl{}", ty, count)
+        }
+        None => {
+            return syn::Error::new_spanned(&input.self_ty, "only impls of a struct can be exposed")
+                .to_compile_error()
+                .into()
+        }
+    };
+    if enabled_langs() > 1 {
+        for item in &mut input.items {
+            if let ImplItem::Method(method) = item {
+                widen_visibility(&mut method.vis);
+            }
+        }
+    }
+
+    for_each_lang!(L => expanded_or_error((|| -> syn::Result<TokenStream2> {
+        let mut input = input.clone();
+        L::expose_impl(&mut input, &[]).map_err(lang_error)?;
+
+        Ok(expand_standalone::<L>(&name, input.into_token_stream()))
+    })()))
+    .into_iter()
+    .collect::<TokenStream2>()
     .into()
 }
 
+/// Register the variants of an error raised as their own subclasses in Python, for the stubs of the
+/// modules raising it. Expanded by `impl_py_error!`, which can't be called in an `#[expose_mod]`
+/// module since every language gets its own copy of it.
+#[cfg(feature = "python")]
+#[proc_macro]
+pub fn py_error_variants(input: TokenStream) -> TokenStream {
+    match langs::python::register_error_variants(input.into()) {
+        Ok(()) => TokenStream::new(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[proc_macro_attribute]
 pub fn expose_trait(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut input = parse_macro_input!(item as ItemTrait);
-    // CurrentLang::expose_trait(&mut input, &vec![]).unwrap();
+    let input = parse_macro_input!(item as ItemTrait);
+    // L::expose_trait(&mut input, &[]).unwrap();
 
     (quote! {
         #input
@@ -5,10 +5,11 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote, ToTokens};
 
 use syn::punctuated::Punctuated;
-use syn::token::{Comma, RArrow};
+use syn::spanned::Spanned;
+use syn::token::Comma;
 use syn::{
-    parse_quote, BareFnArg, FnArg, GenericArgument, Ident, Pat, PatIdent, PatType, Path,
-    PathArguments, PathSegment, ReturnType, Type, TypePath,
+    parse_quote, BareFnArg, Expr, FnArg, GenericArgument, Ident, Pat, PatIdent, PatType, Path,
+    PathArguments, PathSegment, ReturnType, Signature, Type, TypeBareFn, TypePath,
 };
 
 use crate::langs::LangError;
@@ -19,12 +20,12 @@ use crate::langs::LangError;
 /// - `()` if the list is empty
 /// - `T` if the list only contains one element
 /// - `(T1, T2, ...)` otherwise
-pub trait AsTuple {
-    fn as_tuple(self) -> Type;
+pub trait IntoTuple {
+    fn into_tuple(self) -> Type;
 }
 
-impl<T: IntoIterator<Item = Type>> AsTuple for T {
-    fn as_tuple(self) -> Type {
+impl<T: IntoIterator<Item = Type>> IntoTuple for T {
+    fn into_tuple(self) -> Type {
         let punctuated = self.into_iter().collect::<Punctuated<_, Comma>>();
 
         match punctuated.len() {
@@ -35,11 +36,12 @@ impl<T: IntoIterator<Item = Type>> AsTuple for T {
     }
 }
 
+fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())
+}
+
 pub fn match_fixed_type(ty: &Type, type_path: Path) -> bool {
-    match ty {
-        Type::Path(TypePath { path, .. }) if path == &type_path => true,
-        _ => false,
-    }
+    matches!(ty, Type::Path(TypePath { path, .. }) if *path == type_path)
 }
 
 pub fn match_generic_type(ty: &Type, type_path: Path) -> Option<Vec<Type>> {
@@ -49,14 +51,14 @@ pub fn match_generic_type(ty: &Type, type_path: Path) -> Option<Vec<Type>> {
             .iter_mut()
             .last()
             // Remove the generic from the last path segment and return it
-            .and_then(
+            .map(
                 |PathSegment {
                      ref mut arguments, ..
                  }| {
                     let original_arguments = arguments.clone();
                     *arguments = PathArguments::None;
 
-                    Some(original_arguments)
+                    original_arguments
                 },
             )
             // Compare the path without generic to the required one
@@ -97,6 +99,9 @@ impl ReturnTypeTyped for ReturnType {
     }
 }
 
+/// Conversion of a custom input, from the original type and the name of the argument
+pub type ExpandInput = Box<dyn Fn(&Type, &Ident) -> ExpandedInputConversion>;
+
 // #[derive(Debug)]
 pub enum Input {
     /// Leave the type unchanged, doesn't perform any conversion
@@ -104,14 +109,14 @@ pub enum Input {
     /// Map from one or more different types
     MapFrom {
         target: Box<Type>,
-        sources: Vec<Box<Type>>,
+        sources: Vec<Type>,
     },
     /// Custom mapping
     Custom {
         target: Box<Type>,
-        sources: Vec<Box<Type>>,
+        sources: Vec<Type>,
 
-        expand: Box<dyn Fn(&Type, &Ident) -> ExpandedInputConversion>,
+        expand: ExpandInput,
     },
 }
 
@@ -126,9 +131,11 @@ impl ExpandedInputConversion {
         ts.into()
     }
 
+    /// Conversion with `MapFrom`, where the caller on the other side of the FFI guarantees that
+    /// the pointers it passed are valid
     pub fn map_from(ty: &Type, ident: &Ident) -> Self {
         let ts = quote! {
-            <#ty>::map_from(#ident)
+            unsafe { <#ty>::map_from(#ident) }
         };
         ts.into()
     }
@@ -136,7 +143,7 @@ impl ExpandedInputConversion {
 
 #[derive(Debug)]
 pub struct ExpandedInput {
-    pub types: Vec<Box<Type>>,
+    pub types: Vec<Type>,
     pub conv: ExpandedInputConversion,
 }
 
@@ -148,7 +155,7 @@ impl Input {
     pub fn new_map_from(target: Type, sources: Vec<Type>) -> Self {
         Input::MapFrom {
             target: Box::new(target),
-            sources: sources.into_iter().map(Box::new).collect(),
+            sources,
         }
     }
 
@@ -159,12 +166,12 @@ impl Input {
     ) -> Self {
         Input::Custom {
             target: Box::new(target),
-            sources: sources.into_iter().map(Box::new).collect(),
+            sources,
             expand: Box::new(expand),
         }
     }
 
-    pub fn get_sources(&self) -> Vec<&Box<Type>> {
+    pub fn get_sources(&self) -> Vec<&Type> {
         match self {
             Input::Unchanged(ty) => vec![ty],
             Input::MapFrom { sources, .. } | Input::Custom { sources, .. } => {
@@ -177,7 +184,7 @@ impl Input {
         match self {
             Input::Unchanged(ty) => ExpandedInput {
                 conv: ExpandedInputConversion::pass_through(ident),
-                types: vec![ty],
+                types: vec![*ty],
             },
             Input::MapFrom { target, sources } => ExpandedInput {
                 conv: ExpandedInputConversion::map_from(&target, ident),
@@ -195,6 +202,55 @@ impl Input {
     }
 }
 
+/// Value of an argument marked with `#[default(...)]` when it's missing, removing the attribute
+///
+/// The argument becomes an `Option` on the other side, and the default is filled in on the Rust
+/// side when it's `None`.
+pub fn take_default(arg: &mut PatType) -> Result<Option<Expr>, LangError> {
+    let pos = match arg.attrs.iter().position(|a| a.path.is_ident("default")) {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let attr = arg.attrs.remove(pos);
+    let default = attr
+        .parse_args()
+        .map_err(|_| LangError::InvalidDefault(attr.span()))?;
+    let ty = &arg.ty;
+    arg.ty = parse_quote!(Option<#ty>);
+
+    Ok(Some(default))
+}
+
+/// Remove the `#[default(...)]` attributes of a function that is kept as it is, behind the exposed
+/// one
+pub fn strip_defaults(sig: &mut Signature) {
+    for input in sig.inputs.iter_mut() {
+        if let FnArg::Typed(arg) = input {
+            arg.attrs.retain(|a| !a.path.is_ident("default"));
+        }
+    }
+}
+
+/// Turn the callbacks of a function that is kept as it is, behind the exposed one, into `impl Fn`
+/// arguments, so that it can be called with the closures that wrap the foreign ones
+pub fn accept_closures(sig: &mut Signature) {
+    for input in sig.inputs.iter_mut() {
+        if let FnArg::Typed(PatType { ty, .. }) = input {
+            // the `extern` ones are C function pointers added by the C backend, not callbacks
+            if let Type::BareFn(TypeBareFn {
+                inputs,
+                output,
+                abi: None,
+                ..
+            }) = ty.as_ref()
+            {
+                let (inputs, output) = (inputs.iter().map(|arg| arg.ty.clone()), output.clone());
+                *ty = parse_quote!(impl Fn(#(#inputs),*) #output);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Argument(pub FnArg);
 
@@ -219,17 +275,20 @@ impl Argument {
         E: From<LangError>,
         F: Fn(Type) -> Result<Input, E>,
     {
-        let (ident, ty) = match self.0 {
+        let (ident, ty, default) = match self.0 {
             r @ FnArg::Receiver(_) => {
                 return Ok(ExpandedArgument {
                     args: vec![r].into_iter().collect(),
                     conv: ExpandedArgumentConversion::empty(),
                 });
             }
-            FnArg::Typed(PatType { pat, ty, .. }) => match *pat {
-                Pat::Ident(PatIdent { ident, .. }) => (ident, ty),
-                _ => return Err(LangError::ComplexPatternFnArg.into()),
-            },
+            FnArg::Typed(mut arg) => {
+                let default = take_default(&mut arg)?;
+                match *arg.pat {
+                    Pat::Ident(PatIdent { ident, .. }) => (ident, arg.ty, default),
+                    pat => return Err(LangError::ComplexPatternFnArg(pat.span()).into()),
+                }
+            }
         };
 
         let temp_ident = format_ident!("_temp_{}", ident);
@@ -243,15 +302,21 @@ impl Argument {
             .map(|(i, _)| Ident::new(&format!("__{}_{}", ident, i), ident.span()))
             .collect::<Punctuated<_, Comma>>();
 
-        let conv = quote! {
-            let #temp_ident = (#idents);
-            let #ident = #input_conv;
+        let conv = match default {
+            Some(default) => quote! {
+                let #temp_ident = (#idents);
+                let #ident = #input_conv.unwrap_or_else(|| #default);
+            },
+            None => quote! {
+                let #temp_ident = (#idents);
+                let #ident = #input_conv;
+            },
         };
 
         let args = expanded
             .types
             .into_iter()
-            .zip(idents.into_iter())
+            .zip(idents)
             .map::<FnArg, _>(|(ty, ident)| parse_quote!(#ident: #ty))
             .collect();
 
@@ -262,14 +327,14 @@ impl Argument {
     }
 }
 
-#[derive(Debug)]
+// #[derive(Debug)]
 pub enum Output {
     /// Leave the type unchanged, doesn't perform any conversion
     Unchanged(Box<Type>),
     /// Map to another one or more types. This will call `MapTo::map_to()`
     MapTo {
         original: Box<Type>,
-        targets: Vec<(Box<Type>, String)>,
+        targets: Vec<(Type, String)>,
     },
     /// Move the value to the heap and return a pointer
     ByReference(Box<Type>),
@@ -278,11 +343,18 @@ pub enum Output {
         original_ok: Box<Type>,
         original_err: Box<Type>,
 
-        ok: Vec<Box<Type>>,
+        ok: Vec<Type>,
         err: Box<Type>,
     },
     /// Return by reference, NULL if the Option is `None`
     Option { original: Box<Type>, ty: Box<Type> },
+    /// Custom mapping
+    Custom {
+        original: Box<Type>,
+        target: Box<Type>,
+
+        expand: Box<dyn Fn(&Ident) -> ExpandedOutputConversion>,
+    },
 }
 
 #[derive(Debug)]
@@ -306,7 +378,15 @@ impl ExpandedOutputConversion {
 
     pub fn by_reference(ident: &Ident) -> Self {
         let ts = quote! {
-            let #ident = Box::into_raw(Box::new(#ident));
+            let #ident = crate::langs::Handle::into_handle(#ident);
+        };
+        ts.into()
+    }
+
+    pub fn custom(ident: &Ident, original: &Type, conv: ExpandedOutputConversion) -> Self {
+        let ts = quote! {
+            let #ident: #original = #ident;
+            #conv
         };
         ts.into()
     }
@@ -315,8 +395,8 @@ impl ExpandedOutputConversion {
         let ts = quote! {
             let #ident: Option<#original> = #ident;
             let #ident: #ty = match #ident {
-                Some(inner) => Box::into_raw(Box::new(inner)),
-                None => std::ptr::null_mut(),
+                Some(inner) => crate::langs::Handle::into_handle(inner),
+                None => crate::langs::null_handle(),
             };
         };
         ts.into()
@@ -336,7 +416,7 @@ impl ExpandedOutputConversion {
 
 #[derive(Debug)]
 pub struct ExpandedOutput {
-    pub ty: Vec<Box<Type>>,
+    pub ty: Vec<Type>,
     pub suffix: Vec<String>,
     pub conv: ExpandedOutputConversion,
 }
@@ -349,21 +429,10 @@ impl Output {
     pub fn new_map_to_suffix(original: Type, targets: Vec<(Type, String)>) -> Self {
         Output::MapTo {
             original: Box::new(original),
-            targets: targets.into_iter().map(|(t, s)| (Box::new(t), s)).collect(),
+            targets,
         }
     }
 
-    pub fn new_map_to(original: Type, targets: Vec<Type>) -> Self {
-        Self::new_map_to_suffix(
-            original,
-            targets
-                .into_iter()
-                .enumerate()
-                .map(|(i, t)| (t, i.to_string()))
-                .collect(),
-        )
-    }
-
     pub fn new_map_to_single(original: Type, target: Type) -> Self {
         Self::new_map_to_suffix(original, vec![(target, String::new())])
     }
@@ -372,7 +441,7 @@ impl Output {
         Output::Result {
             original_ok: Box::new(original_ok),
             original_err: Box::new(original_err),
-            ok: ok.into_iter().map(Box::new).collect(),
+            ok,
             err: Box::new(err),
         }
     }
@@ -384,20 +453,33 @@ impl Output {
         }
     }
 
-    pub fn get_targets(&self) -> Vec<Box<Type>> {
+    pub fn new_custom<F: 'static + Fn(&Ident) -> ExpandedOutputConversion>(
+        original: Type,
+        target: Type,
+        expand: F,
+    ) -> Self {
+        Output::Custom {
+            original: Box::new(original),
+            target: Box::new(target),
+            expand: Box::new(expand),
+        }
+    }
+
+    pub fn get_targets(&self) -> Vec<Type> {
         match self {
-            Output::Unchanged(ty) | Output::ByReference(ty) | Output::Option { ty, .. } => {
-                vec![ty.clone()]
-            }
+            Output::Unchanged(ty)
+            | Output::ByReference(ty)
+            | Output::Option { ty, .. }
+            | Output::Custom { target: ty, .. } => vec![(**ty).clone()],
             Output::MapTo { targets, .. } => targets.iter().map(|(t, _)| t.clone()).collect(),
-            Output::Result { ok, .. } => ok.iter().cloned().collect(),
+            Output::Result { ok, .. } => ok.to_vec(),
         }
     }
 
     pub fn expand(&self, ident: &Ident) -> ExpandedOutput {
         match self {
             Output::Unchanged(ty) => ExpandedOutput {
-                ty: vec![ty.clone()],
+                ty: vec![(**ty).clone()],
                 suffix: vec![String::new()],
                 conv: ExpandedOutputConversion::pass_through(ident),
             },
@@ -407,7 +489,7 @@ impl Output {
                 ExpandedOutput {
                     ty: targets,
                     suffix,
-                    conv: ExpandedOutputConversion::map_to(ident, &original),
+                    conv: ExpandedOutputConversion::map_to(ident, original),
                 }
             }
             Output::ByReference(ty) => ExpandedOutput {
@@ -421,22 +503,40 @@ impl Output {
                 original_err,
                 ..
             } => ExpandedOutput {
-                ty: ok.iter().map(|t| parse_quote! { *mut #t }).collect(),
-                suffix: ok.iter().map(|_| String::new()).collect(),
+                // nothing is written for `Result<(), _>`
+                ty: ok
+                    .iter()
+                    .filter(|t| !is_unit(t))
+                    .map(|t| parse_quote! { *mut #t })
+                    .collect(),
+                suffix: ok
+                    .iter()
+                    .filter(|t| !is_unit(t))
+                    .map(|_| String::new())
+                    .collect(),
                 conv: ExpandedOutputConversion::result(
                     ident,
-                    &ok.into_iter()
-                        .map(|t| (**t).clone())
+                    &ok.iter()
+                        .cloned()
                         .collect::<Punctuated<_, Comma>>()
-                        .as_tuple(),
-                    &original_ok,
-                    &original_err,
+                        .into_tuple(),
+                    original_ok,
+                    original_err,
                 ),
             },
             Output::Option { original, ty } => ExpandedOutput {
                 ty: vec![parse_quote! { *mut #ty }],
                 suffix: vec!["opt".to_string()],
-                conv: ExpandedOutputConversion::option(ident, &ty, &original),
+                conv: ExpandedOutputConversion::option(ident, ty, original),
+            },
+            Output::Custom {
+                original,
+                target,
+                expand,
+            } => ExpandedOutput {
+                ty: vec![(**target).clone()],
+                suffix: vec![String::new()],
+                conv: ExpandedOutputConversion::custom(ident, original, expand(ident)),
             },
         }
     }
@@ -478,14 +578,15 @@ impl Return {
         F: Fn(Type) -> Result<Output, E>,
     {
         let ty = self.0.as_type();
+        let span = ty.span();
         let converted = convert_output(ty)?;
 
-        let ExpandedOutput { ty, conv, .. } = converted.expand(&ident);
+        let ExpandedOutput { ty, conv, .. } = converted.expand(ident);
 
         match converted {
             Output::ByReference(_) | Output::Option { .. } => {
                 if ty.len() > 1 {
-                    return Err(LangError::MultipleTypesByReference.into());
+                    return Err(LangError::MultipleTypesByReference(span).into());
                 }
                 let ty = &ty[0];
 
@@ -532,14 +633,39 @@ impl Return {
                     }),
                 })
             }
+            // tuples can't cross the C ABI, multiple values are written to out-params instead
+            _ if ty.len() > 1 => {
+                let (extra_args, assign_args): (Vec<_>, Vec<_>) = ty
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| {
+                        let arg_name_num = format_ident!("{}_{}", arg_name, i);
+                        let index = syn::Index::from(i);
+
+                        (
+                            parse_quote!(#arg_name_num: *mut #t),
+                            quote! { unsafe { *#arg_name_num = #ident.#index; } },
+                        )
+                    })
+                    .unzip();
+
+                Ok(ExpandedReturn {
+                    ret: ReturnType::Default,
+                    extra_args,
+                    conv: ExpandedReturnConversion::from(quote! {
+                        #conv
+                        let #ident: (#(#ty),*) = #ident;
+                        #(#assign_args)*
+                    }),
+                })
+            }
             _ => Ok(ExpandedReturn {
                 ret: ReturnType::Type(
                     Default::default(),
                     Box::new(
                         ty.into_iter()
-                            .map(|t| *t)
                             .collect::<Punctuated<_, Comma>>()
-                            .as_tuple(),
+                            .into_tuple(),
                     ),
                 ),
                 extra_args: vec![],
@@ -590,10 +716,10 @@ impl CallbackArgument {
     {
         let converted = convert_output(self.0.ty)?;
 
-        let ExpandedOutput { ty, suffix, conv } = converted.expand(&ident);
+        let ExpandedOutput { ty, suffix, conv } = converted.expand(ident);
         let (args, arg_names): (Vec<_>, Vec<_>) = ty
             .into_iter()
-            .zip(suffix.into_iter())
+            .zip(suffix)
             .map(|(t, s)| {
                 let arg_name = match s.is_empty() {
                     true => ident.clone(),
@@ -629,14 +755,11 @@ impl CallbackReturn {
         E: From<LangError>,
         F: Fn(Type) -> Result<Input, E>,
     {
-        let expanded = convert_input(self.0.as_type())?.expand(&ident);
+        let expanded = convert_input(self.0.as_type())?.expand(ident);
 
         let ret = match expanded.types.is_empty() {
             true => ReturnType::Default,
-            false => ReturnType::Type(
-                Default::default(),
-                Box::new(expanded.types.into_iter().map(|t| *t).as_tuple()),
-            ),
+            false => ReturnType::Type(Default::default(), Box::new(expanded.types.into_tuple())),
         };
 
         Ok(ExpandedCallbackReturn {
@@ -5,5 +5,4 @@ pub enum ModuleItem {
     Function(Ident),
     Structure(Ident),
     Module(Ident),
-    Trait(Ident),
 }
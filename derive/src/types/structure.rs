@@ -1,21 +1,37 @@
 use syn::parse::{Parse, ParseStream};
-use syn::{Lit, LitStr};
+use syn::LitStr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExposeStructOpts {
     Opaque,
+    /// Objects that can be used from multiple threads at once, stored behind a lock
+    Shared,
+    /// Compared with `PartialEq` by the languages that support it
+    Eq,
+    /// Hashed with `Hash` by the languages that support it
+    Hash,
+    /// Copied with `Clone` by the languages that support it
+    Clone,
 }
 
 impl Parse for ExposeStructOpts {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let lookahead = input.lookahead1();
-        if lookahead.peek(Lit)
-            && input.peek(LitStr)
-            && input.parse::<LitStr>().unwrap().value() == "opaque"
-        {
-            Ok(ExposeStructOpts::Opaque)
-        } else {
-            Err(lookahead.error())
+        if !lookahead.peek(LitStr) {
+            return Err(lookahead.error());
+        }
+
+        let option = input.parse::<LitStr>()?;
+        match option.value().as_str() {
+            "opaque" => Ok(ExposeStructOpts::Opaque),
+            "shared" => Ok(ExposeStructOpts::Shared),
+            "eq" => Ok(ExposeStructOpts::Eq),
+            "hash" => Ok(ExposeStructOpts::Hash),
+            "clone" => Ok(ExposeStructOpts::Clone),
+            _ => Err(syn::Error::new(
+                option.span(),
+                "expected `\"opaque\"`, `\"shared\"`, `\"eq\"`, `\"hash\"` or `\"clone\"`",
+            )),
         }
     }
 }
@@ -0,0 +1,27 @@
+//! Compile errors reported for the items that can't be exposed, with a single language enabled
+//! since every language reports its own errors
+
+#[cfg(all(
+    feature = "c",
+    not(any(
+        feature = "python",
+        feature = "jni",
+        feature = "napi",
+        feature = "wasm"
+    ))
+))]
+#[test]
+fn c() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/c/*.rs");
+}
+
+#[cfg(all(
+    feature = "python",
+    not(any(feature = "c", feature = "jni", feature = "napi", feature = "wasm"))
+))]
+#[test]
+fn python() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/python/*.rs");
+}
@@ -0,0 +1,11 @@
+use derive::expose_mod;
+
+#[expose_mod]
+mod bitcoin {
+    #[expose_fn]
+    fn fee(vbytes: u32, #[default(let)] sat_per_vb: u32) -> u32 {
+        vbytes * sat_per_vb
+    }
+}
+
+fn main() {}
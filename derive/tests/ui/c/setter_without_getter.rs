@@ -0,0 +1,19 @@
+use derive::expose_mod;
+
+#[expose_mod]
+mod bitcoin {
+    #[expose_struct("opaque")]
+    pub struct FeeRate {
+        sat_per_vb: u32,
+    }
+
+    #[expose_impl]
+    impl FeeRate {
+        #[setter]
+        fn set_sat_per_vb(&mut self, sat_per_vb: u32) {
+            self.sat_per_vb = sat_per_vb;
+        }
+    }
+}
+
+fn main() {}
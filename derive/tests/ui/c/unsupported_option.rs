@@ -0,0 +1,15 @@
+use derive::expose_mod;
+
+#[expose_mod]
+mod bitcoin {
+    pub struct Amount {
+        sat: u64,
+    }
+
+    #[expose_fn]
+    fn is_dust(amount: Option<Amount>) -> bool {
+        amount.map_or(true, |amount| amount.sat < 546)
+    }
+}
+
+fn main() {}
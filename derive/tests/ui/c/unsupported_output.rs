@@ -0,0 +1,15 @@
+use derive::expose_mod;
+
+#[expose_mod]
+mod bitcoin {
+    pub struct Amount {
+        sat: u64,
+    }
+
+    #[expose_fn]
+    fn dust() -> Amount {
+        Amount { sat: 546 }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use derive::expose_mod;
+
+#[expose_mod]
+mod bitcoin {
+    #[expose_struct("opaque")]
+    pub struct FeeRate {
+        sat_per_vb: u32,
+    }
+
+    #[expose_impl]
+    impl FeeRate {
+        #[getter]
+        fn get_sat_per_vb(&self) -> u32 {
+            self.sat_per_vb
+        }
+        #[setter]
+        fn set_sat_per_vb(&self, _sat_per_vb: u32) {}
+    }
+}
+
+fn main() {}
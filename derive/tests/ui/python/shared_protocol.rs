@@ -0,0 +1,19 @@
+use derive::expose_mod;
+
+#[expose_mod]
+mod bitcoin {
+    #[expose_struct("opaque", "shared")]
+    pub struct FeeRate {
+        sat_per_vb: u32,
+    }
+
+    #[expose_impl]
+    impl FeeRate {
+        #[str]
+        fn to_string(&self) -> String {
+            format!("{} sat/vB", self.sat_per_vb)
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,503 @@
+// Automatically generated by the `derive` crate, do not edit
+
+#nullable enable
+
+using System;
+using System.Runtime.InteropServices;
+
+namespace Bdk
+{
+    internal static class NativeString
+    {
+        [DllImport("bdk_ffi")]
+        private static extern void string_free(IntPtr s);
+
+        /// Copy a string returned by the library and release it
+        internal static string Take(IntPtr pointer)
+        {
+            try
+            {
+                return Marshal.PtrToStringUTF8(pointer)!;
+            }
+            finally
+            {
+                string_free(pointer);
+            }
+        }
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct ByteBuffer
+    {
+        public IntPtr Data;
+        public UIntPtr Len;
+        public UIntPtr Cap;
+    }
+
+    internal static class NativeBytes
+    {
+        [DllImport("bdk_ffi")]
+        private static extern void byte_buffer_free(ByteBuffer buffer);
+
+        /// Copy the bytes returned by the library and release them
+        internal static byte[] Take(ByteBuffer buffer)
+        {
+            try
+            {
+                var bytes = new byte[(int)buffer.Len];
+                Marshal.Copy(buffer.Data, bytes, 0, bytes.Length);
+                return bytes;
+            }
+            finally
+            {
+                byte_buffer_free(buffer);
+            }
+        }
+    }
+
+    internal static class NativeArray
+    {
+        /// Take the ownership of the objects of an array returned by the library, and release the
+        /// array
+        internal static T[] Take<T>(IntPtr array, UIntPtr len, Func<IntPtr, T> take, Action<IntPtr, UIntPtr> free)
+        {
+            var values = new T[(int)len];
+            for (var i = 0; i < values.Length; i++)
+            {
+                var offset = i * IntPtr.Size;
+                values[i] = take(Marshal.ReadIntPtr(array, offset));
+                Marshal.WriteIntPtr(array, offset, IntPtr.Zero);
+            }
+            free(array, len);
+            return values;
+        }
+    }
+
+    public class BitcoinError : Exception
+    {
+        public BitcoinError(int code) : base($"BitcoinError (code {code})")
+        {
+            Code = code;
+        }
+
+        /// Error code returned by the library
+        public int Code { get; }
+    }
+}
+
+namespace Bdk.Bitcoin
+{
+    internal static class Native
+    {
+        private const string Library = "bdk_ffi";
+
+        [DllImport(Library)]
+        internal static extern int script_from_hex([MarshalAs(UnmanagedType.LPUTF8Str)] string hex, out ScriptHandle output);
+
+        [DllImport(Library)]
+        internal static extern void script_from_bytes(byte[] bytes, UIntPtr bytesLen, out ScriptHandle output);
+
+        [DllImport(Library)]
+        internal static extern void script_destroy(IntPtr s);
+
+        [DllImport(Library)]
+        internal static extern ByteBuffer script_to_bytes(ScriptHandle self);
+
+        [DllImport(Library)]
+        internal static extern IntPtr script_to_hex(ScriptHandle self);
+
+        [DllImport(Library)]
+        internal static extern IntPtr script_asm(ScriptHandle self);
+
+        [DllImport(Library)]
+        internal static extern int script_find_nonce(ScriptHandle self, uint zero_bits, CancellationTokenHandle token, out uint output);
+
+        [DllImport(Library)]
+        internal static extern uint script_spend_fee(ScriptHandle self, FeeRateHandle fee_rate);
+
+        [DllImport(Library)]
+        internal static extern void script_array_free(IntPtr array, UIntPtr len);
+
+        [DllImport(Library)]
+        internal static extern int network_from_string([MarshalAs(UnmanagedType.LPUTF8Str)] string s, out NetworkHandle output);
+
+        [DllImport(Library)]
+        internal static extern void network_destroy(IntPtr s);
+
+        [DllImport(Library)]
+        internal static extern void network_bitcoin(out NetworkHandle output);
+
+        [DllImport(Library)]
+        internal static extern void network_testnet(out NetworkHandle output);
+
+        [DllImport(Library)]
+        internal static extern void network_all(out IntPtr output, out UIntPtr outputLen);
+
+        [DllImport(Library)]
+        internal static extern IntPtr network_to_string(NetworkHandle self);
+
+        [DllImport(Library)]
+        internal static extern void network_array_free(IntPtr array, UIntPtr len);
+
+        [DllImport(Library)]
+        internal static extern void address_from_script(ScriptHandle script, NetworkHandle network, out AddressHandle output);
+
+        [DllImport(Library)]
+        internal static extern int address_from_string([MarshalAs(UnmanagedType.LPUTF8Str)] string s, out AddressHandle output);
+
+        [DllImport(Library)]
+        internal static extern void address_destroy(IntPtr s);
+
+        [DllImport(Library)]
+        internal static extern IntPtr address_to_string(AddressHandle self);
+
+        [DllImport(Library)]
+        internal static extern void address_get_script(AddressHandle self, out ScriptHandle output);
+
+        [DllImport(Library)]
+        internal static extern void address_network(AddressHandle self, out NetworkHandle output);
+
+        [DllImport(Library)]
+        internal static extern void address_array_free(IntPtr array, UIntPtr len);
+
+        [DllImport(Library)]
+        internal static extern void cancellationtoken_new(out CancellationTokenHandle output);
+
+        [DllImport(Library)]
+        internal static extern void cancellationtoken_destroy(IntPtr s);
+
+        [DllImport(Library)]
+        internal static extern void cancellationtoken_cancel(CancellationTokenHandle self);
+
+        [DllImport(Library)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool cancellationtoken_is_cancelled(CancellationTokenHandle self);
+
+        [DllImport(Library)]
+        internal static extern void cancellationtoken_array_free(IntPtr array, UIntPtr len);
+
+        [DllImport(Library)]
+        internal static extern void feerate_from_sat_per_vb(uint sat_per_vb, out FeeRateHandle output);
+
+        [DllImport(Library)]
+        internal static extern void feerate_destroy(IntPtr f);
+
+        [DllImport(Library)]
+        internal static extern uint feerate_get_sat_per_vb(FeeRateHandle self);
+
+        [DllImport(Library)]
+        internal static extern void feerate_set_sat_per_vb(FeeRateHandle self, uint sat_per_vb);
+
+        [DllImport(Library)]
+        internal static extern void feerate_array_free(IntPtr array, UIntPtr len);
+    }
+
+    // Not wrapped yet, only available through the C API:
+    // - script_instructions: returns a cursor
+    // - script_find_nonce_async: async
+    // - script_short_hex: takes optional arguments
+    /// Owns the pointer to the native `Script`, released by its destructor
+    public sealed class ScriptHandle : SafeHandle
+    {
+        public ScriptHandle() : base(IntPtr.Zero, true) { }
+
+        internal ScriptHandle(IntPtr pointer) : base(IntPtr.Zero, true)
+        {
+            SetHandle(pointer);
+        }
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            Native.script_destroy(handle);
+            return true;
+        }
+    }
+
+    public sealed class Script : IDisposable
+    {
+        private readonly ScriptHandle handle;
+
+        internal Script(ScriptHandle handle)
+        {
+            this.handle = handle;
+        }
+
+        /// Handle of the native object, still owned by this object
+        public ScriptHandle Handle => handle;
+
+        public void Dispose() => handle.Dispose();
+
+        public Script(string hex)
+        {
+            int code = Native.script_from_hex(hex, out handle);
+            if (code != 0) throw new BitcoinError(code);
+        }
+
+        public static Script FromBytes(byte[] bytes)
+        {
+            Native.script_from_bytes(bytes, (UIntPtr)bytes.Length, out ScriptHandle output);
+            return new Script(output);
+        }
+
+        public byte[] ToBytes()
+        {
+            return NativeBytes.Take(Native.script_to_bytes(handle));
+        }
+
+        public string ToHex()
+        {
+            return NativeString.Take(Native.script_to_hex(handle));
+        }
+
+        public string Asm()
+        {
+            return NativeString.Take(Native.script_asm(handle));
+        }
+
+        public uint FindNonce(uint zero_bits, CancellationToken token)
+        {
+            int code = Native.script_find_nonce(handle, zero_bits, token.Handle, out uint output);
+            if (code != 0) throw new BitcoinError(code);
+            return output;
+        }
+
+        public uint SpendFee(FeeRate fee_rate)
+        {
+            return Native.script_spend_fee(handle, fee_rate.Handle);
+        }
+    }
+
+    /// Owns the pointer to the native `Network`, released by its destructor
+    public sealed class NetworkHandle : SafeHandle
+    {
+        public NetworkHandle() : base(IntPtr.Zero, true) { }
+
+        internal NetworkHandle(IntPtr pointer) : base(IntPtr.Zero, true)
+        {
+            SetHandle(pointer);
+        }
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            Native.network_destroy(handle);
+            return true;
+        }
+    }
+
+    public sealed class Network : IDisposable
+    {
+        private readonly NetworkHandle handle;
+
+        internal Network(NetworkHandle handle)
+        {
+            this.handle = handle;
+        }
+
+        /// Handle of the native object, still owned by this object
+        public NetworkHandle Handle => handle;
+
+        public void Dispose() => handle.Dispose();
+
+        public Network(string s)
+        {
+            int code = Native.network_from_string(s, out handle);
+            if (code != 0) throw new BitcoinError(code);
+        }
+
+        public static Network Bitcoin()
+        {
+            Native.network_bitcoin(out NetworkHandle output);
+            return new Network(output);
+        }
+
+        public static Network Testnet()
+        {
+            Native.network_testnet(out NetworkHandle output);
+            return new Network(output);
+        }
+
+        public static Network[] All()
+        {
+            Native.network_all(out IntPtr output, out UIntPtr outputLen);
+            return NativeArray.Take(output, outputLen, p => new Network(new NetworkHandle(p)), Native.network_array_free);
+        }
+
+        public override string ToString()
+        {
+            return NativeString.Take(Native.network_to_string(handle));
+        }
+    }
+
+    /// Owns the pointer to the native `Address`, released by its destructor
+    public sealed class AddressHandle : SafeHandle
+    {
+        public AddressHandle() : base(IntPtr.Zero, true) { }
+
+        internal AddressHandle(IntPtr pointer) : base(IntPtr.Zero, true)
+        {
+            SetHandle(pointer);
+        }
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            Native.address_destroy(handle);
+            return true;
+        }
+    }
+
+    public sealed class Address : IDisposable
+    {
+        private readonly AddressHandle handle;
+
+        internal Address(AddressHandle handle)
+        {
+            this.handle = handle;
+        }
+
+        /// Handle of the native object, still owned by this object
+        public AddressHandle Handle => handle;
+
+        public void Dispose() => handle.Dispose();
+
+        public static Address? FromScript(Script script, Network network)
+        {
+            Native.address_from_script(script.Handle, network.Handle, out AddressHandle output);
+            return output.IsInvalid ? null : new Address(output);
+        }
+
+        public Address(string s)
+        {
+            int code = Native.address_from_string(s, out handle);
+            if (code != 0) throw new BitcoinError(code);
+        }
+
+        public override string ToString()
+        {
+            return NativeString.Take(Native.address_to_string(handle));
+        }
+
+        public Script Script
+        {
+            get
+            {
+                Native.address_get_script(handle, out ScriptHandle output);
+                return new Script(output);
+            }
+        }
+
+        public Network Network()
+        {
+            Native.address_network(handle, out NetworkHandle output);
+            return new Network(output);
+        }
+    }
+
+    /// Owns the pointer to the native `CancellationToken`, released by its destructor
+    public sealed class CancellationTokenHandle : SafeHandle
+    {
+        public CancellationTokenHandle() : base(IntPtr.Zero, true) { }
+
+        internal CancellationTokenHandle(IntPtr pointer) : base(IntPtr.Zero, true)
+        {
+            SetHandle(pointer);
+        }
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            Native.cancellationtoken_destroy(handle);
+            return true;
+        }
+    }
+
+    public sealed class CancellationToken : IDisposable
+    {
+        private readonly CancellationTokenHandle handle;
+
+        internal CancellationToken(CancellationTokenHandle handle)
+        {
+            this.handle = handle;
+        }
+
+        /// Handle of the native object, still owned by this object
+        public CancellationTokenHandle Handle => handle;
+
+        public void Dispose() => handle.Dispose();
+
+        public CancellationToken()
+        {
+            Native.cancellationtoken_new(out handle);
+        }
+
+        public void Cancel()
+        {
+            Native.cancellationtoken_cancel(handle);
+        }
+
+        public bool IsCancelled()
+        {
+            return Native.cancellationtoken_is_cancelled(handle);
+        }
+    }
+
+    // Not wrapped yet, only available through the C API:
+    // - feerate_fee: takes optional arguments
+    /// Owns the pointer to the native `FeeRate`, released by its destructor
+    public sealed class FeeRateHandle : SafeHandle
+    {
+        public FeeRateHandle() : base(IntPtr.Zero, true) { }
+
+        internal FeeRateHandle(IntPtr pointer) : base(IntPtr.Zero, true)
+        {
+            SetHandle(pointer);
+        }
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            Native.feerate_destroy(handle);
+            return true;
+        }
+    }
+
+    public sealed class FeeRate : IDisposable
+    {
+        private readonly FeeRateHandle handle;
+
+        internal FeeRate(FeeRateHandle handle)
+        {
+            this.handle = handle;
+        }
+
+        /// Handle of the native object, still owned by this object
+        public FeeRateHandle Handle => handle;
+
+        public void Dispose() => handle.Dispose();
+
+        public FeeRate(uint sat_per_vb)
+        {
+            Native.feerate_from_sat_per_vb(sat_per_vb, out handle);
+        }
+
+        public uint SatPerVb
+        {
+            get
+            {
+                return Native.feerate_get_sat_per_vb(handle);
+            }
+        }
+
+        public void SetSatPerVb(uint sat_per_vb)
+        {
+            Native.feerate_set_sat_per_vb(handle, sat_per_vb);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+using System;
+
+using Bdk;
+using Bdk.Bitcoin;
+
+using var script = new Script("a91457d6b4ded38193013643b03b4472e15f80bc465787");
+using var network = Network.Testnet();
+
+using (var address = Address.FromScript(script, network))
+{
+    if (address != null)
+    {
+        Console.WriteLine($"Address: {address}");
+        Console.WriteLine($"Script: {address.Script.ToHex()}");
+    }
+}
+
+try
+{
+    new Address("invalid");
+}
+catch (BitcoinError e)
+{
+    Console.WriteLine($"Error: {e.Message}");
+}
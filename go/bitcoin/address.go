@@ -0,0 +1,73 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+// #include <stdlib.h>
+// #include "bindings.h"
+import "C"
+
+import (
+	"runtime"
+	"unsafe"
+)
+
+type Address struct {
+	pointer *C.Address
+}
+
+// newAddress takes the ownership of a pointer returned by the library
+func newAddress(pointer *C.Address) *Address {
+	a := &Address{pointer}
+	runtime.SetFinalizer(a, func(a *Address) {
+		C.address_destroy(a.pointer)
+	})
+
+	return a
+}
+
+func AddressFromScript(script *Script, network *Network) *Address {
+	var out *C.Address
+	C.address_from_script(script.pointer, network.pointer, &out)
+	runtime.KeepAlive(script)
+	runtime.KeepAlive(network)
+	if out == nil {
+		return nil
+	}
+	return newAddress(out)
+}
+
+func NewAddress(s string) (*Address, error) {
+	cs := C.CString(s)
+	defer C.free(unsafe.Pointer(cs))
+	var out *C.Address
+	code := C.address_from_string(cs, &out)
+	if code != 0 {
+		return nil, &BitcoinError{Code: int32(code)}
+	}
+	return newAddress(out), nil
+}
+
+func (a *Address) ToString() string {
+	result := C.address_to_string(a.pointer)
+	runtime.KeepAlive(a)
+	return takeString(result)
+}
+
+// String implements fmt.Stringer
+func (a *Address) String() string {
+	return a.ToString()
+}
+
+func (a *Address) Script() *Script {
+	var out *C.Script
+	C.address_get_script(a.pointer, &out)
+	runtime.KeepAlive(a)
+	return newScript(out)
+}
+
+func (a *Address) Network() *Network {
+	var out *C.Network
+	C.address_network(a.pointer, &out)
+	runtime.KeepAlive(a)
+	return newNetwork(out)
+}
@@ -0,0 +1,14 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+import "fmt"
+
+type BitcoinError struct {
+	// Code is the error code returned by the library
+	Code int32
+}
+
+func (e *BitcoinError) Error() string {
+	return fmt.Sprintf("BitcoinError (code %d)", e.Code)
+}
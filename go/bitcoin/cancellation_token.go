@@ -0,0 +1,42 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+// #include <stdlib.h>
+// #include "bindings.h"
+import "C"
+
+import (
+	"runtime"
+)
+
+type CancellationToken struct {
+	pointer *C.CancellationToken
+}
+
+// newCancellationToken takes the ownership of a pointer returned by the library
+func newCancellationToken(pointer *C.CancellationToken) *CancellationToken {
+	c := &CancellationToken{pointer}
+	runtime.SetFinalizer(c, func(c *CancellationToken) {
+		C.cancellationtoken_destroy(c.pointer)
+	})
+
+	return c
+}
+
+func NewCancellationToken() *CancellationToken {
+	var out *C.CancellationToken
+	C.cancellationtoken_new(&out)
+	return newCancellationToken(out)
+}
+
+func (c *CancellationToken) Cancel() {
+	C.cancellationtoken_cancel(c.pointer)
+	runtime.KeepAlive(c)
+}
+
+func (c *CancellationToken) IsCancelled() bool {
+	result := C.cancellationtoken_is_cancelled(c.pointer)
+	runtime.KeepAlive(c)
+	return bool(result)
+}
@@ -0,0 +1,44 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+// #include <stdlib.h>
+// #include "bindings.h"
+import "C"
+
+import (
+	"runtime"
+)
+
+// Not wrapped yet, only available through the C API:
+// - feerate_fee: takes optional arguments
+type FeeRate struct {
+	pointer *C.FeeRate
+}
+
+// newFeeRate takes the ownership of a pointer returned by the library
+func newFeeRate(pointer *C.FeeRate) *FeeRate {
+	f := &FeeRate{pointer}
+	runtime.SetFinalizer(f, func(f *FeeRate) {
+		C.feerate_destroy(f.pointer)
+	})
+
+	return f
+}
+
+func NewFeeRate(sat_per_vb uint32) *FeeRate {
+	var out *C.FeeRate
+	C.feerate_from_sat_per_vb(C.uint32_t(sat_per_vb), &out)
+	return newFeeRate(out)
+}
+
+func (f *FeeRate) SatPerVb() uint32 {
+	result := C.feerate_get_sat_per_vb(f.pointer)
+	runtime.KeepAlive(f)
+	return uint32(result)
+}
+
+func (f *FeeRate) SetSatPerVb(sat_per_vb uint32) {
+	C.feerate_set_sat_per_vb(f.pointer, C.uint32_t(sat_per_vb))
+	runtime.KeepAlive(f)
+}
@@ -0,0 +1,33 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+// #cgo CFLAGS: -I${SRCDIR}/../../c
+// #cgo LDFLAGS: -lbdk_ffi
+// #include "bindings.h"
+import "C"
+
+import "unsafe"
+
+// takeString copies a string returned by the library and releases it
+func takeString(pointer *C.char) string {
+	defer C.string_free(pointer)
+
+	return C.GoString(pointer)
+}
+
+// takeBytes copies a byte buffer returned by the library and releases it
+func takeBytes(buffer C.ByteBuffer) []byte {
+	defer C.byte_buffer_free(buffer)
+
+	return C.GoBytes(unsafe.Pointer(buffer.data), C.int(buffer.len))
+}
+
+// bytesPointer returns a pointer to the first byte of a slice, or nil if it is empty
+func bytesPointer(bytes []byte) *C.uint8_t {
+	if len(bytes) == 0 {
+		return nil
+	}
+
+	return (*C.uint8_t)(unsafe.Pointer(&bytes[0]))
+}
@@ -0,0 +1,74 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+// #include <stdlib.h>
+// #include "bindings.h"
+import "C"
+
+import (
+	"runtime"
+	"unsafe"
+)
+
+type Network struct {
+	pointer *C.Network
+}
+
+// newNetwork takes the ownership of a pointer returned by the library
+func newNetwork(pointer *C.Network) *Network {
+	n := &Network{pointer}
+	runtime.SetFinalizer(n, func(n *Network) {
+		C.network_destroy(n.pointer)
+	})
+
+	return n
+}
+
+func NewNetwork(s string) (*Network, error) {
+	cs := C.CString(s)
+	defer C.free(unsafe.Pointer(cs))
+	var out *C.Network
+	code := C.network_from_string(cs, &out)
+	if code != 0 {
+		return nil, &BitcoinError{Code: int32(code)}
+	}
+	return newNetwork(out), nil
+}
+
+func NetworkBitcoin() *Network {
+	var out *C.Network
+	C.network_bitcoin(&out)
+	return newNetwork(out)
+}
+
+func NetworkTestnet() *Network {
+	var out *C.Network
+	C.network_testnet(&out)
+	return newNetwork(out)
+}
+
+func NetworkAll() []*Network {
+	var out **C.Network
+	var outLen C.size_t
+	C.network_all(&out, &outLen)
+	pointers := unsafe.Slice(out, outLen)
+	values := make([]*Network, len(pointers))
+	for i := range pointers {
+		values[i] = newNetwork(pointers[i])
+		pointers[i] = nil
+	}
+	C.network_array_free(out, outLen)
+	return values
+}
+
+func (n *Network) ToString() string {
+	result := C.network_to_string(n.pointer)
+	runtime.KeepAlive(n)
+	return takeString(result)
+}
+
+// String implements fmt.Stringer
+func (n *Network) String() string {
+	return n.ToString()
+}
@@ -0,0 +1,83 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package bitcoin
+
+// #include <stdlib.h>
+// #include "bindings.h"
+import "C"
+
+import (
+	"runtime"
+	"unsafe"
+)
+
+// Not wrapped yet, only available through the C API:
+// - script_instructions: returns a cursor
+// - script_find_nonce_async: async
+// - script_short_hex: takes optional arguments
+type Script struct {
+	pointer *C.Script
+}
+
+// newScript takes the ownership of a pointer returned by the library
+func newScript(pointer *C.Script) *Script {
+	s := &Script{pointer}
+	runtime.SetFinalizer(s, func(s *Script) {
+		C.script_destroy(s.pointer)
+	})
+
+	return s
+}
+
+func NewScript(hex string) (*Script, error) {
+	chex := C.CString(hex)
+	defer C.free(unsafe.Pointer(chex))
+	var out *C.Script
+	code := C.script_from_hex(chex, &out)
+	if code != 0 {
+		return nil, &BitcoinError{Code: int32(code)}
+	}
+	return newScript(out), nil
+}
+
+func ScriptFromBytes(bytes []byte) *Script {
+	var out *C.Script
+	C.script_from_bytes(bytesPointer(bytes), C.size_t(len(bytes)), &out)
+	return newScript(out)
+}
+
+func (s *Script) ToBytes() []byte {
+	result := C.script_to_bytes(s.pointer)
+	runtime.KeepAlive(s)
+	return takeBytes(result)
+}
+
+func (s *Script) ToHex() string {
+	result := C.script_to_hex(s.pointer)
+	runtime.KeepAlive(s)
+	return takeString(result)
+}
+
+func (s *Script) Asm() string {
+	result := C.script_asm(s.pointer)
+	runtime.KeepAlive(s)
+	return takeString(result)
+}
+
+func (s *Script) FindNonce(zero_bits uint32, token *CancellationToken) (uint32, error) {
+	var out C.uint32_t
+	code := C.script_find_nonce(s.pointer, C.uint32_t(zero_bits), token.pointer, &out)
+	runtime.KeepAlive(s)
+	runtime.KeepAlive(token)
+	if code != 0 {
+		return 0, &BitcoinError{Code: int32(code)}
+	}
+	return uint32(out), nil
+}
+
+func (s *Script) SpendFee(fee_rate *FeeRate) uint32 {
+	result := C.script_spend_fee(s.pointer, fee_rate.pointer)
+	runtime.KeepAlive(s)
+	runtime.KeepAlive(fee_rate)
+	return uint32(result)
+}
@@ -0,0 +1,26 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+
+	"bdk/bitcoin"
+)
+
+func main() {
+	script, err := bitcoin.NewScript("a91457d6b4ded38193013643b03b4472e15f80bc465787")
+	if err != nil {
+		panic(err)
+	}
+	network := bitcoin.NetworkTestnet()
+
+	if address := bitcoin.AddressFromScript(script, network); address != nil {
+		fmt.Println("Address:", address)
+		fmt.Println("Script:", address.Script().ToHex())
+	}
+
+	var bitcoinErr *bitcoin.BitcoinError
+	if _, err := bitcoin.NewAddress("invalid"); errors.As(err, &bitcoinErr) {
+		fmt.Println("Error:", bitcoinErr)
+	}
+}
@@ -0,0 +1,3 @@
+module bdk
+
+go 1.17
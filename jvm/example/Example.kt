@@ -0,0 +1,14 @@
+import org.bitcoindevkit.bitcoin.Address
+import org.bitcoindevkit.bitcoin.Network
+import org.bitcoindevkit.bitcoin.Script
+
+fun main() {
+    Script("a91457d6b4ded38193013643b03b4472e15f80bc465787").use { s ->
+        Network("testnet").use { n ->
+            Address.fromScript(s, n)!!.use { a ->
+                println("Address: $a")
+                a.script.use { println("Script: ${it.toHex()}") }
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package org.bitcoindevkit
+
+class BitcoinError(message: String) : Exception(message)
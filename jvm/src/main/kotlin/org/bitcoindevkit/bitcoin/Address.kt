@@ -0,0 +1,42 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package org.bitcoindevkit.bitcoin
+
+import org.bitcoindevkit.BitcoinError
+
+class Address internal constructor(handle: Long) : AutoCloseable {
+    internal var handle: Long = handle
+        private set
+
+    @Throws(BitcoinError::class)
+    constructor(s: String) : this(addressFromString(s))
+
+    override fun toString(): String = addressToString(handle)
+
+    val script: Script
+        get() = Script(addressGetScript(handle))
+
+    fun network(): Network = Network(addressNetwork(handle))
+
+    override fun close() {
+        if (handle != 0L) {
+            addressDestroy(handle)
+            handle = 0L
+        }
+    }
+
+    companion object {
+        @JvmStatic
+        fun fromScript(script: Script, network: Network): Address? = addressFromScript(script.handle, network.handle).let { if (it == 0L) null else Address(it) }
+    }
+}
+
+@Suppress("unused")
+private val library = System.loadLibrary("bdk_ffi")
+
+private external fun addressFromScript(script: Long, network: Long): Long
+private external fun addressFromString(s: String): Long
+private external fun addressDestroy(s: Long): Unit
+private external fun addressToString(handle: Long): String
+private external fun addressGetScript(handle: Long): Long
+private external fun addressNetwork(handle: Long): Long
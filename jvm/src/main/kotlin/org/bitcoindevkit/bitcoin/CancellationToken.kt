@@ -0,0 +1,29 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package org.bitcoindevkit.bitcoin
+
+class CancellationToken internal constructor(handle: Long) : AutoCloseable {
+    internal var handle: Long = handle
+        private set
+
+    constructor() : this(cancellationTokenNew())
+
+    fun cancel(): Unit = cancellationTokenCancel(handle)
+
+    fun isCancelled(): Boolean = cancellationTokenIsCancelled(handle)
+
+    override fun close() {
+        if (handle != 0L) {
+            cancellationTokenDestroy(handle)
+            handle = 0L
+        }
+    }
+}
+
+@Suppress("unused")
+private val library = System.loadLibrary("bdk_ffi")
+
+private external fun cancellationTokenNew(): Long
+private external fun cancellationTokenDestroy(s: Long): Unit
+private external fun cancellationTokenCancel(handle: Long): Unit
+private external fun cancellationTokenIsCancelled(handle: Long): Boolean
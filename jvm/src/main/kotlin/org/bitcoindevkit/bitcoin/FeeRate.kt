@@ -0,0 +1,33 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package org.bitcoindevkit.bitcoin
+
+class FeeRate internal constructor(handle: Long) : AutoCloseable {
+    internal var handle: Long = handle
+        private set
+
+    constructor(satPerVb: Long) : this(feeRateFromSatPerVb(satPerVb))
+
+    val satPerVb: Long
+        get() = feeRateGetSatPerVb(handle)
+
+    fun setSatPerVb(satPerVb: Long): Unit = feeRateSetSatPerVb(handle, satPerVb)
+
+    fun fee(vbytes: Long, minFee: Long? = null): Long = feeRateFee(handle, vbytes, minFee)
+
+    override fun close() {
+        if (handle != 0L) {
+            feeRateDestroy(handle)
+            handle = 0L
+        }
+    }
+}
+
+@Suppress("unused")
+private val library = System.loadLibrary("bdk_ffi")
+
+private external fun feeRateFromSatPerVb(satPerVb: Long): Long
+private external fun feeRateDestroy(f: Long): Unit
+private external fun feeRateGetSatPerVb(handle: Long): Long
+private external fun feeRateSetSatPerVb(handle: Long, satPerVb: Long): Unit
+private external fun feeRateFee(handle: Long, vbytes: Long, minFee: Long?): Long
@@ -0,0 +1,43 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package org.bitcoindevkit.bitcoin
+
+import org.bitcoindevkit.BitcoinError
+
+class Network internal constructor(handle: Long) : AutoCloseable {
+    internal var handle: Long = handle
+        private set
+
+    @Throws(BitcoinError::class)
+    constructor(s: String) : this(networkFromString(s))
+
+    override fun toString(): String = networkToString(handle)
+
+    override fun close() {
+        if (handle != 0L) {
+            networkDestroy(handle)
+            handle = 0L
+        }
+    }
+
+    companion object {
+        @JvmStatic
+        fun bitcoin(): Network = Network(networkBitcoin())
+
+        @JvmStatic
+        fun testnet(): Network = Network(networkTestnet())
+
+        @JvmStatic
+        fun all(): List<Network> = networkAll().map { Network(it) }
+    }
+}
+
+@Suppress("unused")
+private val library = System.loadLibrary("bdk_ffi")
+
+private external fun networkFromString(s: String): Long
+private external fun networkDestroy(s: Long): Unit
+private external fun networkBitcoin(): Long
+private external fun networkTestnet(): Long
+private external fun networkAll(): LongArray
+private external fun networkToString(handle: Long): String
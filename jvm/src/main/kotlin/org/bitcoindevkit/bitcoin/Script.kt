@@ -0,0 +1,58 @@
+// Automatically generated by the `derive` crate, do not edit
+
+package org.bitcoindevkit.bitcoin
+
+import org.bitcoindevkit.BitcoinError
+
+class Script internal constructor(handle: Long) : AutoCloseable {
+    internal var handle: Long = handle
+        private set
+
+    @Throws(BitcoinError::class)
+    constructor(hex: String) : this(scriptFromHex(hex))
+
+    fun toBytes(): ByteArray = scriptToBytes(handle)
+
+    fun toHex(): String = scriptToHex(handle)
+
+    fun asm(): String = scriptAsm(handle)
+
+    fun instructions(): List<String> = scriptInstructions(handle).toList()
+
+    @Throws(BitcoinError::class)
+    fun findNonce(zeroBits: Long, token: CancellationToken): Long = scriptFindNonce(handle, zeroBits, token.handle)
+
+    @Throws(BitcoinError::class)
+    fun findNonceAsync(zeroBits: Long, token: CancellationToken): Long = scriptFindNonceAsync(handle, zeroBits, token.handle)
+
+    fun shortHex(maxChars: Long? = null, ellipsis: String? = null): String = scriptShortHex(handle, maxChars, ellipsis)
+
+    fun spendFee(feeRate: FeeRate): Long = scriptSpendFee(handle, feeRate.handle)
+
+    override fun close() {
+        if (handle != 0L) {
+            scriptDestroy(handle)
+            handle = 0L
+        }
+    }
+
+    companion object {
+        @JvmStatic
+        fun fromBytes(bytes: ByteArray): Script = Script(scriptFromBytes(bytes))
+    }
+}
+
+@Suppress("unused")
+private val library = System.loadLibrary("bdk_ffi")
+
+private external fun scriptFromHex(hex: String): Long
+private external fun scriptFromBytes(bytes: ByteArray): Long
+private external fun scriptDestroy(s: Long): Unit
+private external fun scriptToBytes(handle: Long): ByteArray
+private external fun scriptToHex(handle: Long): String
+private external fun scriptAsm(handle: Long): String
+private external fun scriptInstructions(handle: Long): Array<String>
+private external fun scriptFindNonce(handle: Long, zeroBits: Long, token: Long): Long
+private external fun scriptFindNonceAsync(handle: Long, zeroBits: Long, token: Long): Long
+private external fun scriptShortHex(handle: Long, maxChars: Long?, ellipsis: String?): String
+private external fun scriptSpendFee(handle: Long, feeRate: Long): Long
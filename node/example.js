@@ -0,0 +1,13 @@
+const { bitcoin } = require('.');
+
+const s = new bitcoin.Script('a91457d6b4ded38193013643b03b4472e15f80bc465787');
+const a = bitcoin.Address.fromScript(s, new bitcoin.Network('testnet'));
+
+console.log(`Address: ${a}`);
+console.log(`Script: ${a.script.toHex()}`);
+
+try {
+    new bitcoin.Script('not hex');
+} catch (e) {
+    console.log(`Error: ${e.code}: ${e.message}`);
+}
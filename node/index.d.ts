@@ -0,0 +1,51 @@
+// Automatically generated by the `derive` crate, do not edit
+
+export declare namespace bitcoin {
+  export class Script {
+    /** @throws {Error} with `code` set to `"BitcoinError"` */
+    constructor(hex: string)
+    static fromBytes(bytes: Buffer): Script
+    toBytes(): Buffer
+    toHex(): string
+    asm(): string
+    instructions(): Array<string>
+    /** @throws {Error} with `code` set to `"BitcoinError"` */
+    findNonce(zeroBits: number, token: CancellationToken): number
+    /** Rejects with an `Error` with `code` set to `"BitcoinError"` */
+    findNonceAsync(zeroBits: number, token: CancellationToken): Promise<number>
+    shortHex(maxChars?: number | null, ellipsis?: string | null): string
+    spendFee(feeRate: FeeRate): number
+  }
+  export class Network {
+    /** @throws {Error} with `code` set to `"BitcoinError"` */
+    constructor(s: string)
+    static bitcoin(): Network
+    static testnet(): Network
+    static all(): Array<Network>
+    toString(): string
+  }
+  export class Address {
+    static fromScript(script: Script, network: Network): Address | null
+    /** @throws {Error} with `code` set to `"BitcoinError"` */
+    constructor(s: string)
+    toString(): string
+    get script(): Script
+    network(): Network
+  }
+  export class CancellationToken {
+    constructor()
+    cancel(): void
+    isCancelled(): boolean
+  }
+  export class FeeRate {
+    constructor(satPerVb: number)
+    get satPerVb(): number
+    set satPerVb(satPerVb: number)
+    fee(vbytes: number, minFee?: number): number
+  }
+}
+
+export declare namespace callbacks {
+  /** @throws {Error} with `code` set to `"BitcoinError"` */
+  export function visitInstructions(hex: string, callback: (instruction: string) => boolean): number
+}
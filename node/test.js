@@ -0,0 +1,53 @@
+const assert = require('node:assert');
+const test = require('node:test');
+
+const { callbacks } = require('.');
+
+const SCRIPT_HEX = 'a91457d6b4ded38193013643b03b4472e15f80bc465787';
+
+test('callbacks receive every item', () => {
+    const instructions = [];
+    const visited = callbacks.visitInstructions(SCRIPT_HEX, (instruction) => {
+        instructions.push(instruction);
+        return true;
+    });
+
+    assert.strictEqual(visited, 3);
+    assert.deepStrictEqual(instructions, [
+        'OP_HASH160',
+        '57d6b4ded38193013643b03b4472e15f80bc4657',
+        'OP_EQUAL',
+    ]);
+});
+
+test('callbacks return values to Rust', () => {
+    assert.strictEqual(callbacks.visitInstructions(SCRIPT_HEX, () => false), 1);
+});
+
+test('exceptions thrown by callbacks are thrown again', () => {
+    const error = new Error('stop');
+    let calls = 0;
+    assert.throws(
+        () => callbacks.visitInstructions(SCRIPT_HEX, () => {
+            calls++;
+            throw error;
+        }),
+        (e) => e === error,
+    );
+    // the callback isn't called anymore once it threw
+    assert.strictEqual(calls, 1);
+});
+
+test('invalid values returned by callbacks are thrown', () => {
+    assert.throws(
+        () => callbacks.visitInstructions(SCRIPT_HEX, () => 'not a boolean'),
+        { code: 'BooleanExpected' },
+    );
+});
+
+test('errors of the Rust function are thrown', () => {
+    assert.throws(
+        () => callbacks.visitInstructions('not hex', () => true),
+        { code: 'BitcoinError' },
+    );
+});
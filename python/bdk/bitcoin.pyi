@@ -0,0 +1,74 @@
+# Automatically generated by the `derive` crate, do not edit
+
+from typing import Any, Callable, Iterator, List, Optional, Sequence, Tuple
+
+class BitcoinError(Exception): ...
+
+class Cancelled(BitcoinError): ...
+
+class Script:
+    def __str__(self) -> str: ...
+    def __repr__(self) -> str: ...
+    def __bytes__(self) -> bytes: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def __init__(self, hex: str) -> None: ...  # raises BitcoinError
+    @staticmethod
+    def from_bytes(bytes: bytes) -> Script: ...
+    def to_bytes(self) -> bytes: ...
+    def to_hex(self) -> str: ...
+    def asm(self) -> str: ...
+    def instructions(self) -> Iterator[str]: ...
+    def find_nonce(self, zero_bits: int, token: CancellationToken) -> int: ...  # raises BitcoinError
+    async def find_nonce_async(self, zero_bits: int, token: CancellationToken) -> int: ...  # raises BitcoinError
+    def short_hex(self, max_chars: Optional[int] = ..., ellipsis: Optional[str] = ...) -> str: ...
+    def spend_fee(self, fee_rate: FeeRate) -> int: ...
+    def __reduce__(self) -> Tuple[Any, Tuple[Any]]: ...
+    def __copy__(self) -> Script: ...
+    def __deepcopy__(self, _memo: Any) -> Script: ...
+
+class Network:
+    def __str__(self) -> str: ...
+    def __repr__(self) -> str: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def __init__(self, s: str) -> None: ...  # raises BitcoinError
+    @staticmethod
+    def bitcoin() -> Network: ...
+    @staticmethod
+    def testnet() -> Network: ...
+    @staticmethod
+    def all() -> List[Network]: ...
+    def to_string(self) -> str: ...
+    def __reduce__(self) -> Tuple[Any, Tuple[Any]]: ...
+    def __copy__(self) -> Network: ...
+    def __deepcopy__(self, _memo: Any) -> Network: ...
+
+class Address:
+    def __str__(self) -> str: ...
+    def __repr__(self) -> str: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    @staticmethod
+    def from_script(script: Script, network: Network) -> Optional[Address]: ...
+    def __init__(self, s: str) -> None: ...  # raises BitcoinError
+    def to_string(self) -> str: ...
+    @property
+    def script(self) -> Script: ...
+    def network(self) -> Network: ...
+    def __reduce__(self) -> Tuple[Any, Tuple[Any]]: ...
+    def __copy__(self) -> Address: ...
+    def __deepcopy__(self, _memo: Any) -> Address: ...
+
+class CancellationToken:
+    def __init__(self) -> None: ...
+    def cancel(self) -> None: ...
+    def is_cancelled(self) -> bool: ...
+
+class FeeRate:
+    def __init__(self, sat_per_vb: int) -> None: ...
+    @property
+    def sat_per_vb(self) -> int: ...
+    def fee(self, vbytes: int, min_fee: Optional[int] = ...) -> int: ...
+    @sat_per_vb.setter
+    def sat_per_vb(self, sat_per_vb: int) -> None: ...
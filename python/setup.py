@@ -1,11 +1,18 @@
+import os
+
 from setuptools import setup
 from setuptools_rust import Binding, RustExtension
 
+# the type stubs are only written to the source tree when asked to
+os.environ.setdefault("BDK_FFI_GEN_DIR", os.path.abspath(os.path.join(os.path.dirname(__file__), "..")))
+
 setup(
     name="bdk",
     version="1.0",
     rust_extensions=[RustExtension("bdk.bitcoin", path="../Cargo.toml", features=["python"], binding=Binding.PyO3)],
     packages=["bdk"],
+    # type stubs generated by the derive crate while building the extension
+    package_data={"bdk": ["*.pyi", "py.typed"]},
     # rust extensions are not zip safe, just like C-extensions.
     zip_safe=False,
 )
@@ -0,0 +1,106 @@
+import asyncio
+import copy
+import pickle
+import threading
+
+import pytest
+
+from bdk.bitcoin import Address, BitcoinError, CancellationToken, FeeRate, Network, Script
+
+SCRIPT_HEX = "a91457d6b4ded38193013643b03b4472e15f80bc465787"
+ADDRESS = "2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX"
+
+
+def test_constructor_error():
+    with pytest.raises(BitcoinError):
+        Address("invalid")
+
+
+def test_str_and_repr():
+    address = Address(ADDRESS)
+    assert str(address) == ADDRESS
+    assert repr(address) == "Address('{}')".format(ADDRESS)
+    assert eval(repr(address), {"Address": Address}) == address
+    assert str(Script(SCRIPT_HEX)) == SCRIPT_HEX
+
+
+def test_bytes():
+    script = Script(SCRIPT_HEX)
+    assert bytes(script) == bytes.fromhex(SCRIPT_HEX)
+    assert Script.from_bytes(bytearray.fromhex(SCRIPT_HEX)) == script
+
+
+def test_eq_and_hash():
+    assert Network("testnet") == Network.testnet()
+    assert Network("testnet") != Network.bitcoin()
+    assert len({Network("testnet"), Network.testnet(), Network.bitcoin()}) == 2
+    assert {Address(ADDRESS): 1}[Address(ADDRESS)] == 1
+
+
+@pytest.mark.parametrize(
+    "value", [Script(SCRIPT_HEX), Network.testnet(), Address(ADDRESS)], ids=type
+)
+def test_pickle_and_copy(value):
+    for other in (pickle.loads(pickle.dumps(value)), copy.copy(value), copy.deepcopy(value)):
+        assert other == value
+        assert other is not value
+
+
+def test_getter():
+    address = Address.from_script(Script(SCRIPT_HEX), Network.testnet())
+    assert address == Address(ADDRESS)
+    assert address.script == Script(SCRIPT_HEX)
+
+
+def test_setter():
+    fee_rate = FeeRate(2)
+    fee_rate.sat_per_vb = 3
+    assert fee_rate.sat_per_vb == 3
+    assert Script(SCRIPT_HEX).spend_fee(fee_rate) == 3 * 23
+
+
+def test_default_and_optional_arguments():
+    script = Script(SCRIPT_HEX)
+    assert script.short_hex() == SCRIPT_HEX[:16] + "..."
+    assert script.short_hex(4) == SCRIPT_HEX[:4] + "..."
+    assert script.short_hex(None, "~") == SCRIPT_HEX[:16] + "~"
+    assert script.short_hex(ellipsis="~", max_chars=100) == SCRIPT_HEX
+
+    fee_rate = FeeRate(2)
+    assert fee_rate.fee(10) == 20
+    assert fee_rate.fee(1, min_fee=5) == 5
+
+
+def test_iterator():
+    instructions = Script(SCRIPT_HEX).instructions()
+    assert iter(instructions) is instructions
+    assert list(instructions) == [
+        "OP_HASH160",
+        "57d6b4ded38193013643b03b4472e15f80bc4657",
+        "OP_EQUAL",
+    ]
+
+
+def test_async():
+    async def find():
+        return await Script(SCRIPT_HEX).find_nonce_async(4, CancellationToken())
+
+    nonce = asyncio.run(find())
+    assert nonce == Script(SCRIPT_HEX).find_nonce(4, CancellationToken())
+
+
+def test_shared_between_threads():
+    fee_rate = FeeRate(0)
+
+    def bump():
+        for _ in range(100):
+            fee_rate.sat_per_vb = fee_rate.fee(1) + 1
+
+    threads = [threading.Thread(target=bump) for _ in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    # the increments can interleave, but every value written is a valid one
+    assert 100 <= fee_rate.sat_per_vb <= 400
@@ -0,0 +1,69 @@
+"""Check the type stubs generated by the derive crate against the extension module"""
+
+import ast
+import builtins
+import inspect
+from pathlib import Path
+
+import pytest
+
+from bdk import bitcoin
+
+STUB = ast.parse((Path(bitcoin.__file__).parent / "bitcoin.pyi").read_text())
+CLASSES = [node for node in STUB.body if isinstance(node, ast.ClassDef)]
+
+
+def functions(stub):
+    return [
+        node
+        for node in stub.body
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef))
+    ]
+
+
+def decorators(function):
+    return {ast.unparse(decorator) for decorator in function.decorator_list}
+
+
+def test_module_matches_stub():
+    names = {node.name for node in CLASSES}
+    exported = {
+        name
+        for name, value in vars(bitcoin).items()
+        if isinstance(value, type) and value.__module__.startswith("bdk")
+    }
+    assert names == exported
+
+
+@pytest.mark.parametrize("stub", CLASSES, ids=lambda stub: stub.name)
+def test_class_matches_stub(stub):
+    cls = getattr(bitcoin, stub.name)
+    for base in stub.bases:
+        name = ast.unparse(base)
+        assert issubclass(cls, getattr(bitcoin, name, None) or getattr(builtins, name))
+
+    for function in functions(stub):
+        assert hasattr(cls, function.name), "{}.{}".format(stub.name, function.name)
+        value = inspect.getattr_static(cls, function.name)
+
+        kind = decorators(function)
+        if "property" in kind:
+            assert isinstance(value, property) or inspect.isdatadescriptor(value)
+        elif "{}.setter".format(function.name) in kind:
+            assert inspect.isdatadescriptor(value)
+            assert getattr(value, "__set__", None) is not None
+        elif "staticmethod" in kind:
+            assert callable(getattr(cls, function.name))
+        else:
+            assert callable(value)
+
+
+def test_setters_follow_their_property():
+    for stub in CLASSES:
+        properties = set()
+        for function in functions(stub):
+            kind = decorators(function)
+            if "property" in kind:
+                properties.add(function.name)
+            elif "{}.setter".format(function.name) in kind:
+                assert function.name in properties
@@ -0,0 +1,14 @@
+require_relative 'lib/bdk'
+
+script = Bdk::Bitcoin::Script.from_hex('a91457d6b4ded38193013643b03b4472e15f80bc465787')
+network = Bdk::Bitcoin::Network.testnet
+
+address = Bdk::Bitcoin::Address.from_script(script, network)
+puts "Address: #{address}"
+puts "Script: #{address.script.to_hex}"
+
+begin
+  Bdk::Bitcoin::Address.from_string('invalid')
+rescue Bdk::BitcoinError => e
+  puts "Error: #{e.message}"
+end
@@ -0,0 +1,56 @@
+# Automatically generated by the `derive` crate, do not edit
+
+require 'ffi'
+
+module Bdk
+  module Native
+    extend FFI::Library
+    ffi_lib ENV.fetch('BDK_FFI_LIB', 'bdk_ffi')
+
+    attach_function :string_free, [:pointer], :void
+
+    # Copy a string returned by the library and release it
+    def self.take_string(pointer)
+      pointer.read_string.force_encoding(Encoding::UTF_8)
+    ensure
+      string_free(pointer)
+    end
+
+    # Buffer of bytes returned by the library
+    class ByteBuffer < FFI::Struct
+      layout :data, :pointer, :len, :size_t, :cap, :size_t
+    end
+
+    attach_function :byte_buffer_free, [ByteBuffer.by_value], :void
+
+    # Copy the bytes returned by the library and release them
+    def self.take_bytes(buffer)
+      buffer[:data].read_bytes(buffer[:len])
+    ensure
+      byte_buffer_free(buffer)
+    end
+
+    # Take the ownership of the objects of an array returned by the library, and release the array
+    def self.take_array(array, len, free)
+      values = Array.new(len) do |i|
+        pointer = array.get_pointer(i * FFI::Pointer.size)
+        array.put_pointer(i * FFI::Pointer.size, nil)
+        yield pointer
+      end
+      send(free, array, len)
+      values
+    end
+
+    # Copy a binary string to native memory, which is only borrowed by the library
+    def self.bytes_pointer(bytes)
+      FFI::MemoryPointer.new(:uint8, bytes.bytesize).put_bytes(0, bytes)
+    end
+  end
+end
+
+require_relative 'bdk/bitcoin_error'
+require_relative 'bdk/bitcoin/script'
+require_relative 'bdk/bitcoin/network'
+require_relative 'bdk/bitcoin/address'
+require_relative 'bdk/bitcoin/cancellation_token'
+require_relative 'bdk/bitcoin/fee_rate'
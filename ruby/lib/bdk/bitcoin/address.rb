@@ -0,0 +1,68 @@
+# Automatically generated by the `derive` crate, do not edit
+
+module Bdk
+  module Native
+    attach_function :address_from_script, [:pointer, :pointer, :pointer], :void
+    attach_function :address_from_string, [:string, :pointer], :int32
+    attach_function :address_destroy, [:pointer], :void
+    attach_function :address_to_string, [:pointer], :pointer
+    attach_function :address_get_script, [:pointer, :pointer], :void
+    attach_function :address_network, [:pointer, :pointer], :void
+    attach_function :address_array_free, [:pointer, :size_t], :void
+  end
+end
+
+module Bdk
+  module Bitcoin
+    class Address
+      # Releases the native object when it's garbage collected
+      class Pointer < FFI::AutoPointer
+        def self.release(pointer)
+          Native.address_destroy(pointer)
+        end
+      end
+
+      # @api private
+      def initialize(pointer)
+        @pointer = Pointer.new(pointer)
+      end
+
+      # Pointer to pass to the native functions, still owned by this object
+      attr_reader :pointer
+
+      def self.from_script(script, network)
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.address_from_script(script.pointer, network.pointer, out)
+        value = out.read_pointer
+        value.null? ? nil : Address.new(value)
+      end
+
+      def self.from_string(s)
+        out = FFI::MemoryPointer.new(:pointer)
+        code = Native.address_from_string(s, out)
+        raise BitcoinError.new(code) unless code.zero?
+        value = out.read_pointer
+        Address.new(value)
+      end
+
+      def to_string
+        Native.take_string(Native.address_to_string(@pointer))
+      end
+      alias to_s to_string
+
+      def script
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.address_get_script(@pointer, out)
+        value = out.read_pointer
+        Script.new(value)
+      end
+
+      def network
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.address_network(@pointer, out)
+        value = out.read_pointer
+        Network.new(value)
+      end
+    end
+  end
+end
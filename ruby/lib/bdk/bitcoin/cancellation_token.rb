@@ -0,0 +1,48 @@
+# Automatically generated by the `derive` crate, do not edit
+
+module Bdk
+  module Native
+    attach_function :cancellationtoken_new, [:pointer], :void
+    attach_function :cancellationtoken_destroy, [:pointer], :void
+    attach_function :cancellationtoken_cancel, [:pointer], :void
+    attach_function :cancellationtoken_is_cancelled, [:pointer], :bool
+    attach_function :cancellationtoken_array_free, [:pointer, :size_t], :void
+  end
+end
+
+module Bdk
+  module Bitcoin
+    class CancellationToken
+      # Releases the native object when it's garbage collected
+      class Pointer < FFI::AutoPointer
+        def self.release(pointer)
+          Native.cancellationtoken_destroy(pointer)
+        end
+      end
+
+      # @api private
+      def initialize(pointer)
+        @pointer = Pointer.new(pointer)
+      end
+
+      # Pointer to pass to the native functions, still owned by this object
+      attr_reader :pointer
+
+      def self.new
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.cancellationtoken_new(out)
+        value = out.read_pointer
+        CancellationToken.new(value)
+      end
+
+      def cancel
+        Native.cancellationtoken_cancel(@pointer)
+        nil
+      end
+
+      def is_cancelled
+        Native.cancellationtoken_is_cancelled(@pointer)
+      end
+    end
+  end
+end
@@ -0,0 +1,50 @@
+# Automatically generated by the `derive` crate, do not edit
+
+module Bdk
+  module Native
+    attach_function :feerate_from_sat_per_vb, [:uint32, :pointer], :void
+    attach_function :feerate_destroy, [:pointer], :void
+    attach_function :feerate_get_sat_per_vb, [:pointer], :uint32
+    attach_function :feerate_set_sat_per_vb, [:pointer, :uint32], :void
+    attach_function :feerate_array_free, [:pointer, :size_t], :void
+  end
+end
+
+module Bdk
+  module Bitcoin
+    # Not wrapped yet, only available through the C API:
+    # - feerate_fee: takes optional arguments
+    class FeeRate
+      # Releases the native object when it's garbage collected
+      class Pointer < FFI::AutoPointer
+        def self.release(pointer)
+          Native.feerate_destroy(pointer)
+        end
+      end
+
+      # @api private
+      def initialize(pointer)
+        @pointer = Pointer.new(pointer)
+      end
+
+      # Pointer to pass to the native functions, still owned by this object
+      attr_reader :pointer
+
+      def self.from_sat_per_vb(sat_per_vb)
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.feerate_from_sat_per_vb(sat_per_vb, out)
+        value = out.read_pointer
+        FeeRate.new(value)
+      end
+
+      def sat_per_vb
+        Native.feerate_get_sat_per_vb(@pointer)
+      end
+
+      def set_sat_per_vb(sat_per_vb)
+        Native.feerate_set_sat_per_vb(@pointer, sat_per_vb)
+        nil
+      end
+    end
+  end
+end
@@ -0,0 +1,68 @@
+# Automatically generated by the `derive` crate, do not edit
+
+module Bdk
+  module Native
+    attach_function :network_from_string, [:string, :pointer], :int32
+    attach_function :network_destroy, [:pointer], :void
+    attach_function :network_bitcoin, [:pointer], :void
+    attach_function :network_testnet, [:pointer], :void
+    attach_function :network_all, [:pointer, :pointer], :void
+    attach_function :network_to_string, [:pointer], :pointer
+    attach_function :network_array_free, [:pointer, :size_t], :void
+  end
+end
+
+module Bdk
+  module Bitcoin
+    class Network
+      # Releases the native object when it's garbage collected
+      class Pointer < FFI::AutoPointer
+        def self.release(pointer)
+          Native.network_destroy(pointer)
+        end
+      end
+
+      # @api private
+      def initialize(pointer)
+        @pointer = Pointer.new(pointer)
+      end
+
+      # Pointer to pass to the native functions, still owned by this object
+      attr_reader :pointer
+
+      def self.from_string(s)
+        out = FFI::MemoryPointer.new(:pointer)
+        code = Native.network_from_string(s, out)
+        raise BitcoinError.new(code) unless code.zero?
+        value = out.read_pointer
+        Network.new(value)
+      end
+
+      def self.bitcoin
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.network_bitcoin(out)
+        value = out.read_pointer
+        Network.new(value)
+      end
+
+      def self.testnet
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.network_testnet(out)
+        value = out.read_pointer
+        Network.new(value)
+      end
+
+      def self.all
+        out = FFI::MemoryPointer.new(:pointer)
+        out_len = FFI::MemoryPointer.new(:size_t)
+        Native.network_all(out, out_len)
+        Native.take_array(out.read_pointer, out_len.read(:size_t), :network_array_free) { |pointer| Network.new(pointer) }
+      end
+
+      def to_string
+        Native.take_string(Native.network_to_string(@pointer))
+      end
+      alias to_s to_string
+    end
+  end
+end
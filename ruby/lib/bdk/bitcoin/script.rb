@@ -0,0 +1,79 @@
+# Automatically generated by the `derive` crate, do not edit
+
+module Bdk
+  module Native
+    attach_function :script_from_hex, [:string, :pointer], :int32
+    attach_function :script_from_bytes, [:pointer, :size_t, :pointer], :void
+    attach_function :script_destroy, [:pointer], :void
+    attach_function :script_to_bytes, [:pointer], ByteBuffer.by_value
+    attach_function :script_to_hex, [:pointer], :pointer
+    attach_function :script_asm, [:pointer], :pointer
+    attach_function :script_find_nonce, [:pointer, :uint32, :pointer, :pointer], :int32
+    attach_function :script_spend_fee, [:pointer, :pointer], :uint32
+    attach_function :script_array_free, [:pointer, :size_t], :void
+  end
+end
+
+module Bdk
+  module Bitcoin
+    # Not wrapped yet, only available through the C API:
+    # - script_instructions: returns a cursor
+    # - script_find_nonce_async: async
+    # - script_short_hex: takes optional arguments
+    class Script
+      # Releases the native object when it's garbage collected
+      class Pointer < FFI::AutoPointer
+        def self.release(pointer)
+          Native.script_destroy(pointer)
+        end
+      end
+
+      # @api private
+      def initialize(pointer)
+        @pointer = Pointer.new(pointer)
+      end
+
+      # Pointer to pass to the native functions, still owned by this object
+      attr_reader :pointer
+
+      def self.from_hex(hex)
+        out = FFI::MemoryPointer.new(:pointer)
+        code = Native.script_from_hex(hex, out)
+        raise BitcoinError.new(code) unless code.zero?
+        value = out.read_pointer
+        Script.new(value)
+      end
+
+      def self.from_bytes(bytes)
+        out = FFI::MemoryPointer.new(:pointer)
+        Native.script_from_bytes(Native.bytes_pointer(bytes), bytes.bytesize, out)
+        value = out.read_pointer
+        Script.new(value)
+      end
+
+      def to_bytes
+        Native.take_bytes(Native.script_to_bytes(@pointer))
+      end
+
+      def to_hex
+        Native.take_string(Native.script_to_hex(@pointer))
+      end
+
+      def asm
+        Native.take_string(Native.script_asm(@pointer))
+      end
+
+      def find_nonce(zero_bits, token)
+        out = FFI::MemoryPointer.new(:uint32)
+        code = Native.script_find_nonce(@pointer, zero_bits, token.pointer, out)
+        raise BitcoinError.new(code) unless code.zero?
+        value = out.read(:uint32)
+        value
+      end
+
+      def spend_fee(fee_rate)
+        Native.script_spend_fee(@pointer, fee_rate.pointer)
+      end
+    end
+  end
+end
@@ -0,0 +1,13 @@
+# Automatically generated by the `derive` crate, do not edit
+
+module Bdk
+  class BitcoinError < StandardError
+    # Error code returned by the library
+    attr_reader :code
+
+    def initialize(code)
+      @code = code
+      super("BitcoinError (code #{code})")
+    end
+  end
+end
@@ -0,0 +1,132 @@
+//! Cancellation of long-running operations
+//!
+//! A [`Token`] is shared between the caller, which can cancel it from any thread, and the
+//! operation, which either checks it between steps or races its future against it.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Error returned by an operation that was cancelled before completing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    // futures waiting for the token, woken up when it's cancelled, by the id given by `next_id`
+    wakers: Mutex<HashMap<usize, Waker>>,
+    next_id: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Token(Arc<Inner>);
+
+impl Token {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+
+        let wakers = std::mem::take(&mut *self.0.wakers.lock().unwrap());
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fail if the token was cancelled, for blocking operations to call between their steps
+    pub fn check(&self) -> Result<(), Cancelled> {
+        match self.is_cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Run `future` until it completes or the token is cancelled, whichever happens first
+    pub fn run<F: Future>(&self, future: F) -> Cancellable<F> {
+        Cancellable {
+            token: self.clone(),
+            id: self.0.next_id.fetch_add(1, Ordering::Relaxed),
+            future: Box::pin(future),
+        }
+    }
+}
+
+/// Let the other tasks run, for async operations to call between their steps so that they can be
+/// cancelled
+pub async fn yield_now() {
+    YieldNow(false).await
+}
+
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Future returned by [`Token::run`]
+///
+/// It keeps a single waker registered with the token while it's pending, removed once it
+/// completes or is dropped, so a token can be reused for any number of operations.
+pub struct Cancellable<F> {
+    token: Token,
+    id: usize,
+    future: Pin<Box<F>>,
+}
+
+impl<F> Cancellable<F> {
+    fn unregister(&self) {
+        self.token.0.wakers.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl<F> Drop for Cancellable<F> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+impl<F: Future> Future for Cancellable<F> {
+    type Output = Result<F::Output, Cancelled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(Err(Cancelled));
+        }
+
+        {
+            let mut wakers = self.token.0.wakers.lock().unwrap();
+            match wakers.get(&self.id) {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                _ => {
+                    wakers.insert(self.id, cx.waker().clone());
+                }
+            }
+        }
+        // cancelled while registering the waker, which may have missed the wake up
+        if self.token.is_cancelled() {
+            return Poll::Ready(Err(Cancelled));
+        }
+
+        let output = self.future.as_mut().poll(cx);
+        if output.is_ready() {
+            self.unregister();
+        }
+        output.map(Ok)
+    }
+}
@@ -1,20 +1,281 @@
+#[cfg(not(feature = "c-handles"))]
 use crate::mapping::MapFrom;
 
-pub struct Destroy<T>(*mut T);
+/// Storage of the exposed objects behind the pointers handed to C, implemented for every exposed
+/// struct
+#[cfg(not(feature = "c-handles"))]
+pub trait Handle: Sized {
+    /// Move `self` to the heap, returning a pointer owned by the caller
+    fn into_handle(self) -> *mut Self;
 
-impl<T> std::ops::Drop for Destroy<T> {
+    /// Release what is owned by a pointer returned by `into_handle`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_handle` and not released or taken since, it's
+    /// invalid afterwards.
+    unsafe fn release_handle(ptr: *mut Self);
+
+    /// Move the object out of a pointer returned by `into_handle`, which becomes invalid
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_handle` and not released or taken since.
+    unsafe fn take_handle(ptr: *mut Self) -> Self;
+}
+
+/// Storage of the exposed objects behind the handles given to C, implemented for every exposed
+/// struct
+#[cfg(feature = "c-handles")]
+pub trait Handle: Sized + Send + 'static {
+    /// Move `self` to the registry, returning the handle that owns it
+    fn into_handle(self) -> u64 {
+        crate::registry::insert(self)
+    }
+}
+
+/// Value returned to C for a missing object
+#[cfg(not(feature = "c-handles"))]
+pub fn null_handle<T>() -> *mut T {
+    std::ptr::null_mut()
+}
+
+/// Value returned to C for a missing object, a handle that is never issued
+#[cfg(feature = "c-handles")]
+pub fn null_handle() -> u64 {
+    0
+}
+
+/// Release an array of objects returned by one of the exported functions, along with the objects
+/// it still owns. The caller can keep some of them by replacing their pointer with `NULL`.
+///
+/// # Safety
+///
+/// `arr` and `len` must have been returned together for an array of `T`, which isn't used
+/// afterwards. The pointers left in it must not have been released.
+#[cfg(not(feature = "c-handles"))]
+pub unsafe fn release_handle_array<T: Handle>(arr: *mut *mut T, len: usize) {
+    if arr.is_null() {
+        return;
+    }
+
+    let arr = Box::from_raw(std::ptr::slice_from_raw_parts_mut(arr, len));
+    for ptr in arr.iter().filter(|ptr| !ptr.is_null()) {
+        T::release_handle(*ptr);
+    }
+}
+
+/// Release an array of handles returned by one of the exported functions, along with the objects
+/// it still owns. The caller can keep some of them by replacing their handle with `0`.
+///
+/// # Safety
+///
+/// `arr` and `len` must have been returned together for an array of `T`, which isn't used
+/// afterwards.
+#[cfg(feature = "c-handles")]
+pub unsafe fn release_handle_array<T: Handle>(arr: *mut u64, len: usize) {
+    if arr.is_null() {
+        return;
+    }
+
+    let arr = Box::from_raw(std::ptr::slice_from_raw_parts_mut(arr, len));
+    for handle in arr.iter().filter(|handle| **handle != 0) {
+        // handles that were already released are skipped
+        let _ = crate::registry::remove::<T>(*handle);
+    }
+}
+
+/// Handles of the objects exposed with `#[expose_struct("opaque", "shared")]`
+///
+/// They point to a reference counted `Mutex<T>`, so they can be cloned and used from multiple
+/// threads. Each clone must be released separately.
+pub mod shared {
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    /// Move `value` behind a new handle, owned by the caller
+    pub fn into_handle<T>(value: T) -> *mut T {
+        Arc::into_raw(Arc::new(Mutex::new(value))) as *mut T
+    }
+
+    /// Lock the object, blocking until no other thread is using it
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_handle` or `clone_handle` and not released, and it
+    /// must stay that way as long as the guard is alive.
+    pub unsafe fn lock<'a, T>(ptr: *const T) -> MutexGuard<'a, T> {
+        (*(ptr as *const Mutex<T>))
+            .lock()
+            .expect("Poisoned shared object")
+    }
+
+    /// Add a handle to the object, which must be released on its own
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_handle` or `clone_handle` and not released.
+    pub unsafe fn clone_handle<T>(ptr: *const T) -> *mut T {
+        Arc::increment_strong_count(ptr as *const Mutex<T>);
+        ptr as *mut T
+    }
+
+    /// Release a handle to the object, which is dropped with the last one
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_handle` or `clone_handle` and not released, it's
+    /// invalid afterwards.
+    pub unsafe fn release_handle<T>(ptr: *mut T) {
+        drop(Arc::from_raw(ptr as *const Mutex<T>));
+    }
+}
+
+#[cfg(not(feature = "c-handles"))]
+pub struct Destroy<T: Handle>(*mut T);
+
+#[cfg(not(feature = "c-handles"))]
+impl<T: Handle> std::ops::Drop for Destroy<T> {
     fn drop(&mut self) {
-        let _inner = unsafe { Box::from_raw(self.0) };
+        unsafe { T::release_handle(self.0) };
     }
 }
 
-impl<T> MapFrom<*mut T> for Destroy<T> {
+#[cfg(not(feature = "c-handles"))]
+impl<T: Handle> MapFrom<*mut T> for Destroy<T> {
     #[inline]
-    fn map_from(ptr: *mut T) -> Self {
+    unsafe fn map_from(ptr: *mut T) -> Self {
         Destroy(ptr)
     }
 }
 
+/// Items of an `impl Iterator` returned to C, which are produced one at a time by the `_next`
+/// function of the method. The cursor is owned by the caller and released with its `_destroy`
+/// function.
+pub struct Cursor<T>(Box<dyn Iterator<Item = T> + Send>);
+
+impl<T> Cursor<T> {
+    pub fn new<I: Iterator<Item = T> + Send + 'static>(iter: I) -> Self {
+        Cursor(Box::new(iter))
+    }
+
+    pub fn next_item(&mut self) -> Result<T, CursorEnd> {
+        self.0.next().ok_or(CursorEnd)
+    }
+
+    /// Release a cursor returned to C, unless it's `NULL`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned for a `Cursor<T>` and not released, it's invalid afterwards.
+    #[cfg(not(feature = "c-handles"))]
+    pub unsafe fn release(ptr: *mut Self) {
+        if !ptr.is_null() {
+            drop(Box::from_raw(ptr));
+        }
+    }
+}
+
+/// Value returned by the `_next` function of a cursor that has no items left
+pub const CURSOR_END: i32 = 1;
+
+#[derive(Debug)]
+pub struct CursorEnd;
+
+impl IntoPlatformError for CursorEnd {
+    type TargetType = i32;
+
+    fn into_platform_error(self) -> i32 {
+        CURSOR_END
+    }
+
+    fn ok() -> i32 {
+        0
+    }
+}
+
+/// Error of the `_next` function of a cursor behind a handle, which can also be invalid
+#[cfg(feature = "c-handles")]
+#[derive(Debug)]
+pub enum CursorError {
+    End,
+    Handle(crate::registry::HandleError),
+}
+
+#[cfg(feature = "c-handles")]
+impl From<CursorEnd> for CursorError {
+    fn from(_: CursorEnd) -> Self {
+        CursorError::End
+    }
+}
+
+#[cfg(feature = "c-handles")]
+impl From<crate::registry::HandleError> for CursorError {
+    fn from(e: crate::registry::HandleError) -> Self {
+        CursorError::Handle(e)
+    }
+}
+
+#[cfg(feature = "c-handles")]
+impl IntoPlatformError for CursorError {
+    type TargetType = i32;
+
+    fn into_platform_error(self) -> i32 {
+        match self {
+            CursorError::End => CURSOR_END,
+            CursorError::Handle(e) => e.into_platform_error(),
+        }
+    }
+
+    fn ok() -> i32 {
+        0
+    }
+}
+
+/// Free a string returned by one of the exported functions
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn string_free(s: *mut libc::c_char) {
+    if !s.is_null() {
+        let _inner = unsafe { std::ffi::CString::from_raw(s) };
+    }
+}
+
+/// Bytes returned by one of the exported functions, owned by the caller and released with
+/// `byte_buffer_free`
+#[repr(C)]
+pub struct ByteBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+    pub cap: usize,
+}
+
+impl From<Vec<u8>> for ByteBuffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        let mut bytes = std::mem::ManuallyDrop::new(bytes);
+
+        ByteBuffer {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            cap: bytes.capacity(),
+        }
+    }
+}
+
+/// Free a byte buffer returned by one of the exported functions
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn byte_buffer_free(buffer: ByteBuffer) {
+    if !buffer.data.is_null() {
+        let _inner = unsafe { Vec::from_raw_parts(buffer.data, buffer.len, buffer.cap) };
+    }
+}
+
+/// Pointer passed back to the completion callback of an async function, which is only used by the
+/// caller
+pub struct UserData(pub *mut libc::c_void);
+
+unsafe impl Send for UserData {}
+
 pub trait IntoPlatformError {
     type TargetType: std::fmt::Debug;
 
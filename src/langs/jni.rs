@@ -0,0 +1,55 @@
+/// Value returned to the JVM when an exception has been thrown or an `Option` is `None`
+pub trait JniDefault {
+    fn jni_default() -> Self;
+}
+
+macro_rules! impl_jni_default {
+    ($($type:ty => $value:expr),*) => {
+        $(
+            impl JniDefault for $type {
+                #[inline]
+                fn jni_default() -> Self {
+                    $value
+                }
+            }
+        )*
+    };
+}
+
+impl_jni_default!(
+    () => (),
+    i8 => 0,
+    u8 => 0,
+    i16 => 0,
+    u16 => 0,
+    i32 => 0,
+    i64 => 0,
+    f32 => 0.0,
+    f64 => 0.0
+);
+
+impl<T> JniDefault for *mut T {
+    #[inline]
+    fn jni_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+/// Error types that are thrown as a dedicated Java exception
+pub trait IntoJavaException {
+    fn throw(self, env: &jni::JNIEnv);
+}
+
+#[macro_export]
+macro_rules! impl_jni_error {
+    ($type:ident) => {
+        impl $crate::langs::IntoJavaException for $type {
+            fn throw(self, env: &jni::JNIEnv) {
+                // the Kotlin class is generated alongside the bindings, in the root package
+                let class = concat!("org/bitcoindevkit/", stringify!($type));
+                env.throw_new(class, format!("{:?}", self))
+                    .expect("Unable to throw the exception");
+            }
+        }
+    };
+}
@@ -8,3 +8,21 @@ pub use c::*;
 pub mod python;
 #[cfg(feature = "python")]
 pub use python::*;
+
+#[cfg(feature = "jni")]
+#[macro_use]
+pub mod jni;
+#[cfg(feature = "jni")]
+pub use self::jni::*;
+
+#[cfg(feature = "napi")]
+#[macro_use]
+pub mod napi;
+#[cfg(feature = "napi")]
+pub use self::napi::*;
+
+#[cfg(feature = "wasm")]
+#[macro_use]
+pub mod wasm;
+#[cfg(feature = "wasm")]
+pub use self::wasm::*;
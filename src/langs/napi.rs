@@ -0,0 +1,49 @@
+/// Error types that are thrown as a JS `Error`, whose `code` is the name of the type
+pub trait IntoJsError {
+    fn into_js_error(self) -> napi::Error<String>;
+}
+
+#[macro_export]
+macro_rules! impl_napi_error {
+    ($type:ident) => {
+        impl $crate::langs::IntoJsError for $type {
+            fn into_js_error(self) -> napi::Error<String> {
+                napi::Error::new(stringify!($type).to_string(), format!("{:?}", self))
+            }
+        }
+    };
+}
+
+/// Future of an async function, run on the library runtime from the libuv thread pool so that
+/// JavaScript receives a `Promise`
+pub struct FutureTask<T>(Option<crate::runtime::BoxFuture<'static, napi::Result<T, String>>>);
+
+impl<T> FutureTask<T>
+where
+    T: napi::bindgen_prelude::ToNapiValue + napi::bindgen_prelude::TypeName + Send + 'static,
+{
+    pub fn spawn<F>(future: F) -> napi::bindgen_prelude::AsyncTask<Self>
+    where
+        F: std::future::Future<Output = napi::Result<T, String>> + Send + 'static,
+    {
+        napi::bindgen_prelude::AsyncTask::new(FutureTask(Some(Box::pin(future))))
+    }
+}
+
+impl<T> napi::Task for FutureTask<T>
+where
+    T: napi::bindgen_prelude::ToNapiValue + napi::bindgen_prelude::TypeName + Send + 'static,
+{
+    type Output = napi::Result<T, String>;
+    type JsValue = T;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let future = self.0.take().expect("Task computed twice");
+        Ok(crate::runtime::block_on(future))
+    }
+
+    fn resolve(&mut self, env: napi::Env, output: Self::Output) -> napi::Result<T> {
+        // the error is created here, where it can keep its `code`, and rejects the promise as it is
+        output.map_err(|e| napi::JsError::from(e).into_unknown(env).into())
+    }
+}
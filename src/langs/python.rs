@@ -1,3 +1,5 @@
+pub use derive::py_error_variants;
+
 pub struct PyCb<'source>(&'source pyo3::PyAny);
 
 impl<'source> std::ops::Deref for PyCb<'source> {
@@ -20,24 +22,300 @@ impl<'source> pyo3::conversion::FromPyObject<'source> for PyCb<'source> {
     }
 }
 
-// #[pyo3::prelude::pyproto]
-// impl<T> pyo3::class::PyObjectProtocol for T
-// where
-//     T: ToString
-// {
-//     fn __str__(&self) -> String {
-//         self.to_string()
-//     }
-// }
+/// Bytes exchanged with Python: read from any object implementing the buffer protocol, like
+/// `bytes` and `bytearray`, and returned as `bytes`
+pub struct PyByteBuffer(pub Vec<u8>);
+
+impl<'source> pyo3::conversion::FromPyObject<'source> for PyByteBuffer {
+    fn extract(ob: &'source pyo3::PyAny) -> pyo3::PyResult<Self> {
+        let buffer = pyo3::buffer::PyBuffer::<u8>::get(ob)?;
+
+        Ok(PyByteBuffer(buffer.to_vec(pyo3::PyNativeType::py(ob))?))
+    }
+}
+
+impl pyo3::IntoPy<pyo3::PyObject> for PyByteBuffer {
+    fn into_py(self, py: pyo3::Python) -> pyo3::PyObject {
+        pyo3::types::PyBytes::new(py, &self.0).into()
+    }
+}
+
+/// Slice borrowed from Python for the duration of a call: read in place from the read-only and
+/// contiguous objects implementing the buffer protocol, like `bytes`, and copied from the other
+/// buffers and sequences
+pub enum PySlice<T: pyo3::buffer::Element> {
+    Borrowed(pyo3::buffer::PyBuffer<T>),
+    Copied(Vec<T>),
+}
+
+impl<'source, T> pyo3::conversion::FromPyObject<'source> for PySlice<T>
+where
+    T: pyo3::buffer::Element + pyo3::conversion::FromPyObject<'source>,
+{
+    fn extract(ob: &'source pyo3::PyAny) -> pyo3::PyResult<Self> {
+        match pyo3::buffer::PyBuffer::<T>::get(ob) {
+            // writable buffers could be modified by another thread while the GIL is released
+            Ok(buffer) if buffer.readonly() && buffer.is_c_contiguous() => {
+                Ok(PySlice::Borrowed(buffer))
+            }
+            Ok(buffer) => Ok(PySlice::Copied(buffer.to_vec(pyo3::PyNativeType::py(ob))?)),
+            Err(_) => Ok(PySlice::Copied(ob.extract()?)),
+        }
+    }
+}
+
+impl<T: pyo3::buffer::Element> std::ops::Deref for PySlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            PySlice::Borrowed(buffer) if buffer.item_count() == 0 => &[],
+            // the buffer is kept alive and can't be modified until it's released
+            PySlice::Borrowed(buffer) => unsafe {
+                std::slice::from_raw_parts(buffer.buf_ptr() as *const T, buffer.item_count())
+            },
+            PySlice::Copied(vec) => vec,
+        }
+    }
+}
+
+/// Items of an `impl Iterator` returned to Python, converted lazily as they are requested
+pub struct PyIter<T>(Box<dyn Iterator<Item = T> + Send>);
+
+impl<T> PyIter<T> {
+    pub fn new<I: Iterator<Item = T> + Send + 'static>(iter: I) -> Self {
+        PyIter(Box::new(iter))
+    }
+}
+
+impl<T: pyo3::IntoPy<pyo3::PyObject> + 'static> pyo3::IntoPy<pyo3::PyObject> for PyIter<T> {
+    fn into_py(self, py: pyo3::Python) -> pyo3::PyObject {
+        let iter = PyIterator {
+            inner: Box::new(
+                self.0
+                    .map(|item| pyo3::Python::with_gil(|py| item.into_py(py))),
+            ),
+        };
+
+        pyo3::Py::new(py, iter)
+            .expect("Unable to create the iterator")
+            .into_py(py)
+    }
+}
+
+/// Python iterator wrapping a `PyIter`, usable in a `for` loop
+#[pyo3::prelude::pyclass(name = "Iterator")]
+pub struct PyIterator {
+    inner: Box<dyn Iterator<Item = pyo3::PyObject> + Send>,
+}
+
+#[pyo3::prelude::pyproto]
+impl pyo3::class::PyIterProtocol for PyIterator {
+    fn __iter__(slf: pyo3::PyRef<Self>) -> pyo3::PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: pyo3::PyRefMut<Self>) -> Option<pyo3::PyObject> {
+        slf.inner.next()
+    }
+}
+
+/// Error types that are raised as a dedicated Python exception
+pub trait PyErrorType {
+    fn type_object(py: pyo3::Python<'_>) -> &pyo3::types::PyType;
+
+    /// Subclasses of the exception raised for specific variants, so they can be caught separately
+    fn variant_type_objects(_py: pyo3::Python<'_>) -> Vec<&pyo3::types::PyType> {
+        vec![]
+    }
+}
 
 #[macro_export]
 macro_rules! impl_py_error {
     ($type:ident) => {
-        impl Into<pyo3::PyErr> for $type {
-            fn into(self) -> pyo3::PyErr {
-                // TODO: proper errors
-                pyo3::exceptions::PyTypeError::new_err(format!("{:?}", self))
+        impl $crate::langs::PyErrorType for $type {
+            fn type_object(py: pyo3::Python<'_>) -> &pyo3::types::PyType {
+                // shadows the Rust type in here, the exception has the same name in Python
+                pyo3::create_exception!(bdk, $type, pyo3::exceptions::PyException);
+
+                py.get_type::<$type>()
+            }
+        }
+
+        impl From<$type> for pyo3::PyErr {
+            fn from(error: $type) -> pyo3::PyErr {
+                pyo3::Python::with_gil(|py| {
+                    pyo3::PyErr::from_type(
+                        <$type as $crate::langs::PyErrorType>::type_object(py),
+                        format!("{:?}", error),
+                    )
+                })
+            }
+        }
+    };
+    ($type:ident { $($variant:ident),* $(,)? }) => {
+        // the stubs list the subclasses of the exception
+        $crate::langs::py_error_variants!($type { $($variant),* });
+
+        impl $crate::langs::PyErrorType for $type {
+            fn type_object(py: pyo3::Python<'_>) -> &pyo3::types::PyType {
+                pyo3::create_exception!(bdk, $type, pyo3::exceptions::PyException);
+
+                py.get_type::<$type>()
+            }
+
+            fn variant_type_objects(py: pyo3::Python<'_>) -> Vec<&pyo3::types::PyType> {
+                static TYPE_OBJECTS: pyo3::once_cell::GILOnceCell<Vec<pyo3::Py<pyo3::types::PyType>>> =
+                    pyo3::once_cell::GILOnceCell::new();
+
+                TYPE_OBJECTS
+                    .get_or_init(py, || {
+                        let base = <$type as $crate::langs::PyErrorType>::type_object(py);
+                        vec![$(
+                            unsafe {
+                                pyo3::Py::from_owned_ptr(
+                                    py,
+                                    pyo3::PyErr::new_type(
+                                        py,
+                                        concat!("bdk.", stringify!($variant)),
+                                        Some(base),
+                                        None,
+                                    )
+                                    .as_ptr() as *mut pyo3::ffi::PyObject,
+                                )
+                            }
+                        ),*]
+                    })
+                    .iter()
+                    .map(|t| t.as_ref(py))
+                    .collect()
+            }
+        }
+
+        impl From<$type> for pyo3::PyErr {
+            fn from(error: $type) -> pyo3::PyErr {
+                pyo3::Python::with_gil(|py| {
+                    let variant = match &error {
+                        $( $type::$variant { .. } => Some(stringify!($variant)), )*
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    };
+                    let type_object = <$type as $crate::langs::PyErrorType>::variant_type_objects(py)
+                        .into_iter()
+                        .find(|t| t.name().ok() == variant)
+                        .unwrap_or_else(|| <$type as $crate::langs::PyErrorType>::type_object(py));
+
+                    pyo3::PyErr::from_type(type_object, format!("{:?}", error))
+                })
             }
         }
     };
 }
+
+/// Futures of the runtime resolving their `asyncio.Future` with the GIL, and whether the
+/// interpreter is exiting
+///
+/// The runtime threads aren't known to Python, which kills any thread taking the GIL once it's
+/// finalizing: it has to wait for the ones already holding it, and the others give up instead.
+static RESOLVING: std::sync::Mutex<(usize, bool)> = std::sync::Mutex::new((0, false));
+static RESOLVED: std::sync::Condvar = std::sync::Condvar::new();
+
+#[pyo3::prelude::pyfunction]
+fn wait_for_runtime(py: pyo3::Python) {
+    py.allow_threads(|| {
+        let mut resolving = RESOLVING.lock().unwrap();
+        resolving.1 = true;
+        while resolving.0 > 0 {
+            resolving = RESOLVED.wait(resolving).unwrap();
+        }
+    })
+}
+
+/// Run `f` with the GIL from a thread of the runtime, unless the interpreter is exiting
+fn with_gil_from_runtime(f: impl FnOnce(pyo3::Python)) {
+    {
+        let mut resolving = RESOLVING.lock().unwrap();
+        if resolving.1 {
+            return;
+        }
+        resolving.0 += 1;
+    }
+
+    pyo3::Python::with_gil(f);
+
+    RESOLVING.lock().unwrap().0 -= 1;
+    RESOLVED.notify_all();
+}
+
+/// Object returned by the `async fn` exposed to Python: an `asyncio.Future` of the running event
+/// loop, resolved with the `T` produced by a future running on the library runtime
+pub struct Awaitable<T> {
+    future: pyo3::PyObject,
+    output: std::marker::PhantomData<T>,
+}
+
+impl<T> Awaitable<T>
+where
+    T: pyo3::callback::IntoPyCallbackOutput<*mut pyo3::ffi::PyObject> + Send + 'static,
+{
+    pub fn spawn<F>(future: F) -> pyo3::PyResult<Self>
+    where
+        F: std::future::Future<Output = T> + Send + 'static,
+    {
+        pyo3::Python::with_gil(|py| {
+            static AT_EXIT: pyo3::once_cell::GILOnceCell<()> = pyo3::once_cell::GILOnceCell::new();
+            if AT_EXIT.get(py).is_none() {
+                let wait = pyo3::wrap_pyfunction!(wait_for_runtime)(py)?;
+                py.import("atexit")?.call1("register", (wait,))?;
+                let _ = AT_EXIT.set(py, ());
+            }
+
+            let event_loop = py.import("asyncio")?.call0("get_running_loop")?;
+            let py_future = event_loop.call_method0("create_future")?;
+
+            let event_loop: pyo3::PyObject = event_loop.into();
+            let resolved: pyo3::PyObject = py_future.into();
+            crate::runtime::spawn(async move {
+                let output = future.await;
+
+                with_gil_from_runtime(|py| {
+                    let (method, value) = match output.convert(py) {
+                        Ok(ptr) => ("set_result", unsafe {
+                            pyo3::PyObject::from_owned_ptr(py, ptr)
+                        }),
+                        Err(e) => ("set_exception", e.into_instance(py).into()),
+                    };
+
+                    // the future may have been cancelled while waiting
+                    let resolve = py.eval(
+                        "lambda future, method, value: None if future.done() else getattr(future, method)(value)",
+                        None,
+                        None,
+                    );
+                    let scheduled = resolve.and_then(|resolve| {
+                        event_loop.call_method1(
+                            py,
+                            "call_soon_threadsafe",
+                            (resolve, resolved, method, value),
+                        )
+                    });
+                    if let Err(e) = scheduled {
+                        // the event loop is gone, nobody is waiting for the result anymore
+                        e.print(py);
+                    }
+                });
+            });
+
+            Ok(Awaitable {
+                future: py_future.into(),
+                output: std::marker::PhantomData,
+            })
+        })
+    }
+}
+
+impl<T> pyo3::IntoPy<pyo3::PyObject> for Awaitable<T> {
+    fn into_py(self, _py: pyo3::Python) -> pyo3::PyObject {
+        self.future
+    }
+}
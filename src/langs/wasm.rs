@@ -0,0 +1,18 @@
+/// Error types that are thrown as a JS `Error`, whose `name` is the name of the type
+pub trait IntoJsException {
+    fn into_js_exception(self) -> wasm_bindgen::JsValue;
+}
+
+#[macro_export]
+macro_rules! impl_wasm_error {
+    ($type:ident) => {
+        impl $crate::langs::IntoJsException for $type {
+            fn into_js_exception(self) -> wasm_bindgen::JsValue {
+                let error = js_sys::Error::new(&format!("{:?}", self));
+                error.set_name(stringify!($type));
+
+                error.into()
+            }
+        }
+    };
+}
@@ -1,16 +1,38 @@
 use derive::expose_mod;
 
+pub mod cancel;
 pub mod mapping;
 #[macro_use]
 pub mod langs;
+#[cfg(feature = "c-handles")]
+pub mod registry;
+#[cfg(any(feature = "c", feature = "python", feature = "jni", feature = "napi"))]
+pub mod runtime;
 
-#[cfg(not(any(feature = "c", feature = "python")))]
+#[cfg(not(any(
+    feature = "c",
+    feature = "python",
+    feature = "jni",
+    feature = "napi",
+    feature = "wasm"
+)))]
 compile_error!("No language enabled");
-
-#[cfg(all(feature = "c", any(feature = "python")))]
-compile_error!("Enable at most one language");
-#[cfg(all(feature = "python", any(feature = "c")))]
-compile_error!("Enable at most one language");
+#[cfg(all(
+    feature = "c-handles",
+    any(
+        feature = "swift",
+        feature = "cpp",
+        feature = "go",
+        feature = "csharp",
+        feature = "ruby",
+        feature = "dart"
+    )
+))]
+compile_error!("The languages wrapping the C ABI don't support the `c-handles` feature");
+#[cfg(all(target_arch = "wasm32", feature = "network"))]
+compile_error!(
+    "The `network` feature is not supported on wasm, build with `--no-default-features`"
+);
 
 #[derive(Debug)]
 pub enum BitcoinError {
@@ -18,6 +40,9 @@ pub enum BitcoinError {
     Hex(bdk::bitcoin::hashes::hex::Error),
     Address(bdk::bitcoin::util::address::Error),
     IO(std::io::Error),
+    Cancelled,
+    #[cfg(feature = "c-handles")]
+    Handle(registry::HandleError),
 }
 impl From<bdk::bitcoin::Error> for BitcoinError {
     fn from(e: bdk::bitcoin::Error) -> Self {
@@ -39,16 +64,39 @@ impl From<std::io::Error> for BitcoinError {
         BitcoinError::IO(e)
     }
 }
+impl From<cancel::Cancelled> for BitcoinError {
+    fn from(_: cancel::Cancelled) -> Self {
+        BitcoinError::Cancelled
+    }
+}
+#[cfg(feature = "c-handles")]
+impl From<registry::HandleError> for BitcoinError {
+    fn from(e: registry::HandleError) -> Self {
+        BitcoinError::Handle(e)
+    }
+}
 #[cfg(feature = "python")]
-impl_py_error!(BitcoinError);
+impl_py_error!(BitcoinError { Cancelled });
+#[cfg(feature = "jni")]
+impl_jni_error!(BitcoinError);
+#[cfg(feature = "napi")]
+impl_napi_error!(BitcoinError);
+#[cfg(feature = "wasm")]
+impl_wasm_error!(BitcoinError);
+/// Error code returned to C by the operations that were cancelled
+#[cfg(feature = "c")]
+pub const BITCOIN_ERROR_CANCELLED: i32 = -2;
 #[cfg(feature = "c")]
 impl langs::IntoPlatformError for BitcoinError {
     type TargetType = i32;
 
     fn into_platform_error(self) -> Self::TargetType {
-        // match self {
-        // }
-        -1
+        match self {
+            BitcoinError::Cancelled => BITCOIN_ERROR_CANCELLED,
+            #[cfg(feature = "c-handles")]
+            BitcoinError::Handle(e) => langs::IntoPlatformError::into_platform_error(e),
+            _ => -1,
+        }
     }
 
     fn ok() -> Self::TargetType {
@@ -62,7 +110,8 @@ mod bitcoin {
 
     use super::BitcoinError;
 
-    #[expose_struct("opaque")]
+    #[expose_struct("opaque", "eq", "hash", "clone")]
+    #[derive(Clone, PartialEq, Eq, Hash)]
     pub struct Script {
         script: bitcoin_orig::Script,
     }
@@ -71,22 +120,98 @@ mod bitcoin {
             Script { script }
         }
     }
-    impl Into<bitcoin_orig::Script> for Script {
-        fn into(self) -> bitcoin_orig::Script {
-            self.script
+    impl From<Script> for bitcoin_orig::Script {
+        fn from(script: Script) -> Self {
+            script.script
+        }
+    }
+    impl Script {
+        /// Number of leading zero bits of the double SHA-256 of the script followed by `nonce`
+        fn work(&self, nonce: u32) -> u32 {
+            use bitcoin_orig::hashes::{sha256d, Hash, HashEngine};
+
+            let mut engine = sha256d::Hash::engine();
+            engine.input(self.script.as_bytes());
+            engine.input(&nonce.to_le_bytes());
+
+            let mut zeros = 0;
+            for byte in sha256d::Hash::from_engine(engine).into_inner().iter() {
+                zeros += byte.leading_zeros();
+                if *byte != 0 {
+                    break;
+                }
+            }
+            zeros
         }
     }
+    /// Instructions of a script, decoded one at a time from a copy of it so that they don't
+    /// borrow the object they come from
+    struct Instructions {
+        script: bitcoin_orig::Script,
+        offset: usize,
+    }
+    impl Iterator for Instructions {
+        type Item = String;
+
+        fn next(&mut self) -> Option<String> {
+            use bitcoin_orig::blockdata::opcodes::{self, Class, Ordinary};
+            use bitcoin_orig::blockdata::script::Error;
+            use bitcoin_orig::hashes::hex::ToHex;
+
+            let bytes = &self.script.as_bytes()[self.offset..];
+            let opcode = opcodes::All::from(*bytes.first()?);
+            let (header, len) = match opcode.classify() {
+                Class::PushBytes(len) => (1, Some(len as usize)),
+                Class::Ordinary(Ordinary::OP_PUSHDATA1) => (2, little_endian(bytes.get(1..2))),
+                Class::Ordinary(Ordinary::OP_PUSHDATA2) => (3, little_endian(bytes.get(1..3))),
+                Class::Ordinary(Ordinary::OP_PUSHDATA4) => (5, little_endian(bytes.get(1..5))),
+                _ => {
+                    self.offset += 1;
+                    return Some(format!("{:?}", opcode));
+                }
+            };
+
+            match len.and_then(|len| bytes.get(header..header + len)) {
+                Some(data) => {
+                    self.offset += header + data.len();
+                    Some(data.to_hex())
+                }
+                None => {
+                    self.offset = self.script.len();
+                    Some(format!("<{}>", Error::EarlyEndOfScript))
+                }
+            }
+        }
+    }
+    /// Length of a push encoded in `bytes`, if there are enough of them
+    fn little_endian(bytes: Option<&[u8]>) -> Option<usize> {
+        bytes.map(|bytes| {
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |len, byte| len << 8 | *byte as usize)
+        })
+    }
     #[expose_impl]
     impl Script {
         #[constructor]
-        fn from_hex(hex: String) -> Result<Self, BitcoinError> {
+        fn from_hex(hex: &str) -> Result<Self, BitcoinError> {
             use bitcoin_orig::hashes::hex::FromHex;
 
-            Ok(bitcoin_orig::Script::from_hex(&hex)?.into())
+            Ok(bitcoin_orig::Script::from_hex(hex)?.into())
+        }
+        fn from_bytes(bytes: &[u8]) -> Self {
+            bitcoin_orig::Script::from(bytes.to_vec()).into()
         }
         #[destructor]
         fn destroy(_s: Self) {}
 
+        #[bytes]
+        fn to_bytes(&self) -> Vec<u8> {
+            self.script.to_bytes()
+        }
+
+        #[str]
         fn to_hex(&self) -> String {
             use bitcoin_orig::hashes::hex::ToHex;
 
@@ -96,9 +221,79 @@ mod bitcoin {
         fn asm(&self) -> String {
             self.script.asm()
         }
+
+        /// Instructions of the script, as the opcodes and the hex of the data pushes
+        fn instructions(&self) -> impl Iterator<Item = String> {
+            Instructions {
+                script: self.script.clone(),
+                offset: 0,
+            }
+        }
+
+        /// Find a nonce that makes the double SHA-256 of the script followed by the nonce start
+        /// with `zero_bits` zero bits, until `token` is cancelled
+        #[blocking]
+        fn find_nonce(
+            &self,
+            zero_bits: u32,
+            token: &CancellationToken,
+        ) -> Result<u32, BitcoinError> {
+            let mut nonce: u32 = 0;
+            loop {
+                if self.work(nonce) >= zero_bits {
+                    return Ok(nonce);
+                }
+                nonce = nonce.wrapping_add(1);
+                if nonce.is_multiple_of(1024) {
+                    token.token.check()?;
+                }
+            }
+        }
+
+        /// Same as `find_nonce`, without blocking
+        async fn find_nonce_async(
+            &self,
+            zero_bits: u32,
+            token: &CancellationToken,
+        ) -> Result<u32, BitcoinError> {
+            let search = async {
+                let mut nonce: u32 = 0;
+                loop {
+                    if self.work(nonce) >= zero_bits {
+                        return nonce;
+                    }
+                    nonce = nonce.wrapping_add(1);
+                    if nonce.is_multiple_of(1024) {
+                        crate::cancel::yield_now().await;
+                    }
+                }
+            };
+
+            Ok(token.token.run(search).await?)
+        }
+
+        /// Hex of the script cut to `max_chars` characters, followed by `ellipsis` (`...` by
+        /// default) when it's longer
+        fn short_hex(&self, #[default(16)] max_chars: u32, ellipsis: Option<&str>) -> String {
+            use bitcoin_orig::hashes::hex::ToHex;
+
+            let hex = self.script.to_hex();
+            match hex.get(..max_chars as usize) {
+                Some(short) if short.len() < hex.len() => {
+                    format!("{}{}", short, ellipsis.unwrap_or("..."))
+                }
+                _ => hex,
+            }
+        }
+
+        /// Fee to spend an output locked by the script at `fee_rate`, counting only the script
+        fn spend_fee(&self, fee_rate: &FeeRate) -> u32 {
+            fee_rate.sat_per_vb.saturating_mul(self.script.len() as u32)
+        }
     }
 
-    #[expose_struct("opaque")]
+    #[expose_struct("opaque", "eq", "hash", "clone")]
+    #[derive(Clone, PartialEq, Eq, Hash)]
     pub struct Network {
         network: bitcoin_orig::Network,
     }
@@ -107,18 +302,18 @@ mod bitcoin {
             Network { network }
         }
     }
-    impl Into<bitcoin_orig::Network> for Network {
-        fn into(self) -> bitcoin_orig::Network {
-            self.network
+    impl From<Network> for bitcoin_orig::Network {
+        fn from(network: Network) -> Self {
+            network.network
         }
     }
     #[expose_impl]
     impl Network {
         #[constructor]
-        fn from_string(s: String) -> Result<Self, BitcoinError> {
+        fn from_string(s: &str) -> Result<Self, BitcoinError> {
             use std::str::FromStr;
 
-            Ok(bitcoin_orig::Network::from_str(&s)?.into())
+            Ok(bitcoin_orig::Network::from_str(s)?.into())
         }
         #[destructor]
         fn destroy(_s: Self) {}
@@ -129,13 +324,24 @@ mod bitcoin {
         fn testnet() -> Self {
             bitcoin_orig::Network::Testnet.into()
         }
+        fn all() -> Vec<Self> {
+            vec![
+                bitcoin_orig::Network::Bitcoin.into(),
+                bitcoin_orig::Network::Testnet.into(),
+                bitcoin_orig::Network::Signet.into(),
+                bitcoin_orig::Network::Regtest.into(),
+            ]
+        }
 
+        #[allow(clippy::inherent_to_string)]
+        #[str]
         fn to_string(&self) -> String {
             self.network.to_string()
         }
     }
 
-    #[expose_struct("opaque")]
+    #[expose_struct("opaque", "eq", "hash", "clone")]
+    #[derive(Clone, PartialEq, Eq, Hash)]
     pub struct Address {
         address: bitcoin_orig::Address,
     }
@@ -144,9 +350,9 @@ mod bitcoin {
             Address { address }
         }
     }
-    impl Into<bitcoin_orig::Address> for Address {
-        fn into(self) -> bitcoin_orig::Address {
-            self.address
+    impl From<Address> for bitcoin_orig::Address {
+        fn from(address: Address) -> Self {
+            address.address
         }
     }
     #[expose_impl]
@@ -157,14 +363,16 @@ mod bitcoin {
         }
 
         #[constructor]
-        fn from_string(s: String) -> Result<Self, BitcoinError> {
+        fn from_string(s: &str) -> Result<Self, BitcoinError> {
             use std::str::FromStr;
 
-            Ok(bitcoin_orig::Address::from_str(&s)?.into())
+            Ok(bitcoin_orig::Address::from_str(s)?.into())
         }
         #[destructor]
         fn destroy(_s: Self) {}
 
+        #[allow(clippy::inherent_to_string)]
+        #[str]
         fn to_string(&self) -> String {
             self.address.to_string()
         }
@@ -178,4 +386,97 @@ mod bitcoin {
             self.address.network.into()
         }
     }
+
+    /// Passed to long-running operations to stop them, from any thread
+    #[expose_struct("opaque")]
+    #[derive(Clone)]
+    pub struct CancellationToken {
+        token: crate::cancel::Token,
+    }
+    #[expose_impl]
+    impl CancellationToken {
+        #[constructor]
+        fn new() -> Self {
+            CancellationToken {
+                token: Default::default(),
+            }
+        }
+        #[destructor]
+        fn destroy(_s: Self) {}
+
+        fn cancel(&self) {
+            self.token.cancel()
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.token.is_cancelled()
+        }
+    }
+
+    /// Fee rate in satoshis per virtual byte, which can be shared and changed between threads
+    #[expose_struct("opaque", "shared")]
+    pub struct FeeRate {
+        sat_per_vb: u32,
+    }
+    #[expose_impl]
+    impl FeeRate {
+        #[constructor]
+        fn from_sat_per_vb(sat_per_vb: u32) -> Self {
+            FeeRate { sat_per_vb }
+        }
+        #[destructor]
+        fn destroy(_f: Self) {}
+
+        #[getter]
+        fn get_sat_per_vb(&self) -> u32 {
+            self.sat_per_vb
+        }
+        #[setter]
+        fn set_sat_per_vb(&mut self, sat_per_vb: u32) {
+            self.sat_per_vb = sat_per_vb;
+        }
+
+        /// Fee of `vbytes` virtual bytes, and at least `min_fee`
+        fn fee(&self, vbytes: u32, #[default(0)] min_fee: u32) -> u32 {
+            std::cmp::max(self.sat_per_vb.saturating_mul(vbytes), min_fee)
+        }
+    }
+}
+
+/// Functions taking a callback, which are only exposed to Node.js for now
+#[cfg(all(
+    feature = "napi",
+    not(any(feature = "c", feature = "python", feature = "jni", feature = "wasm"))
+))]
+#[expose_mod]
+mod callbacks {
+    use bdk::bitcoin as bitcoin_orig;
+
+    use super::BitcoinError;
+
+    /// Call `callback` with the instructions of the script `hex`, formatted like the ones of
+    /// `Script::instructions()`, until it returns `false`, and return how many it received
+    #[expose_fn]
+    fn visit_instructions(
+        hex: &str,
+        callback: fn(instruction: String) -> bool,
+    ) -> Result<u32, BitcoinError> {
+        use bitcoin_orig::blockdata::script::Instruction;
+        use bitcoin_orig::hashes::hex::{FromHex, ToHex};
+
+        let script = bitcoin_orig::Script::from_hex(hex)?;
+        let mut visited = 0;
+        for instruction in script.instructions() {
+            let instruction = match instruction {
+                Ok(Instruction::Op(op)) => format!("{:?}", op),
+                Ok(Instruction::PushBytes(data)) => data.to_hex(),
+                Err(e) => format!("<{}>", e),
+            };
+            visited += 1;
+            if !callback(instruction) {
+                break;
+            }
+        }
+        Ok(visited)
+    }
 }
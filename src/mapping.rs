@@ -1,10 +1,14 @@
 pub trait MapFrom<Source> {
-    fn map_from(s: Source) -> Self;
+    /// # Safety
+    ///
+    /// The raw pointers received from C must be valid as described by each implementation, and
+    /// what's borrowed from them must not outlive the call they were passed to.
+    unsafe fn map_from(s: Source) -> Self;
 }
 
 impl<T> MapFrom<T> for T {
     #[inline]
-    fn map_from(s: T) -> Self {
+    unsafe fn map_from(s: T) -> Self {
         s
     }
 }
@@ -23,61 +27,191 @@ impl<T> MapTo<T> for T {
 #[cfg(feature = "c")]
 mod c_mapping {
     use super::{MapFrom, MapTo};
+    use crate::langs::{ByteBuffer, Cursor, Handle};
 
+    /// `s` must point to a nul-terminated string
     impl MapFrom<*const libc::c_char> for String {
-        fn map_from(s: *const libc::c_char) -> Self {
-            unsafe {
-                std::ffi::CStr::from_ptr(s)
-                    .to_str()
-                    .expect("Invalid incoming string")
-                    .to_string()
-            }
+        unsafe fn map_from(s: *const libc::c_char) -> Self {
+            <&str>::map_from(s).to_string()
+        }
+    }
+
+    /// `s` must point to a nul-terminated string, which isn't changed or released while it's
+    /// borrowed
+    impl MapFrom<*const libc::c_char> for &str {
+        unsafe fn map_from(s: *const libc::c_char) -> Self {
+            // borrowed for the duration of the call, without copying
+            std::ffi::CStr::from_ptr(s)
+                .to_str()
+                .expect("Invalid incoming string")
         }
     }
 
+    /// `ptr` must point to `len` items, each one valid as a source of `T`
     impl<F: Clone, T: MapFrom<F>> MapFrom<(*const F, usize)> for Vec<T> {
-        fn map_from((ptr, len): (*const F, usize)) -> Self {
-            let slice = unsafe { std::slice::from_raw_parts(ptr, len) };
-            slice.to_vec().into_iter().map(T::map_from).collect()
+        unsafe fn map_from((ptr, len): (*const F, usize)) -> Self {
+            <&[F]>::map_from((ptr, len))
+                .iter()
+                .cloned()
+                .map(|item| T::map_from(item))
+                .collect()
+        }
+    }
+
+    /// `ptr` must point to `len` items, which aren't changed or released while they're borrowed.
+    /// It can be `NULL` when `len` is 0.
+    impl<T> MapFrom<(*const T, usize)> for &[T] {
+        unsafe fn map_from((ptr, len): (*const T, usize)) -> Self {
+            // C callers can pass `NULL` for an empty slice
+            match len {
+                0 => &[],
+                len => std::slice::from_raw_parts(ptr, len),
+            }
+        }
+    }
+
+    // optional arguments are `NULL` when they are `None`, otherwise they must be valid like the
+    // non-optional ones
+
+    /// `ptr` must be `NULL` or point to a `T`
+    impl<T: Copy> MapFrom<*const T> for Option<T> {
+        unsafe fn map_from(ptr: *const T) -> Self {
+            ptr.as_ref().copied()
+        }
+    }
+
+    impl MapFrom<*const libc::c_char> for Option<String> {
+        unsafe fn map_from(s: *const libc::c_char) -> Self {
+            match s.is_null() {
+                true => None,
+                false => Some(String::map_from(s)),
+            }
+        }
+    }
+
+    impl MapFrom<*const libc::c_char> for Option<&str> {
+        unsafe fn map_from(s: *const libc::c_char) -> Self {
+            match s.is_null() {
+                true => None,
+                false => Some(<&str>::map_from(s)),
+            }
+        }
+    }
+
+    impl<F: Clone, T: MapFrom<F>> MapFrom<(*const F, usize)> for Option<Vec<T>> {
+        unsafe fn map_from((ptr, len): (*const F, usize)) -> Self {
+            match ptr.is_null() {
+                true => None,
+                false => Some(Vec::map_from((ptr, len))),
+            }
+        }
+    }
+
+    impl<T> MapFrom<(*const T, usize)> for Option<&[T]> {
+        unsafe fn map_from((ptr, len): (*const T, usize)) -> Self {
+            match ptr.is_null() {
+                true => None,
+                false => Some(<&[T]>::map_from((ptr, len))),
+            }
         }
     }
 
     impl MapTo<*mut libc::c_char> for String {
         fn map_to(self) -> *mut libc::c_char {
-            let cstring = std::ffi::CString::new(self).expect("Invalid outgoing string");
-            let ptr = cstring.as_ptr();
-            std::mem::forget(cstring);
-
-            ptr as *mut libc::c_char
+            // released by `string_free`
+            std::ffi::CString::new(self)
+                .expect("Invalid outgoing string")
+                .into_raw()
         }
     }
 
     impl<F: Clone, T: MapTo<F>> MapTo<(*mut F, usize)> for Vec<T> {
         fn map_to(self) -> (*mut F, usize) {
-            let mut mapped: Vec<F> = self.into_iter().map(T::map_to).collect();
-            mapped.shrink_to_fit();
+            // a boxed slice has no spare capacity, so it can be released knowing only its length
+            let mapped: Box<[F]> = self.into_iter().map(T::map_to).collect();
+            let len = mapped.len();
+
+            (Box::into_raw(mapped) as *mut F, len)
+        }
+    }
 
-            let result = (mapped.as_mut_ptr(), mapped.len());
-            std::mem::forget(mapped);
+    #[cfg(not(feature = "c-handles"))]
+    impl<T> MapTo<*mut Cursor<T>> for Cursor<T> {
+        #[inline]
+        fn map_to(self) -> *mut Cursor<T> {
+            // released by the `_destroy` function of the method
+            Box::into_raw(Box::new(self))
+        }
+    }
 
-            result
+    impl MapTo<ByteBuffer> for Vec<u8> {
+        #[inline]
+        fn map_to(self) -> ByteBuffer {
+            self.into()
+        }
+    }
+
+    /// `ptr` must have been returned for a `T` and not released, it becomes invalid as if the
+    /// object was destroyed
+    #[cfg(not(feature = "c-handles"))]
+    impl<T: Handle> MapFrom<*mut T> for T {
+        #[inline]
+        unsafe fn map_from(ptr: *mut T) -> Self {
+            T::take_handle(ptr)
+        }
+    }
+
+    /// `ptr` must be `NULL` or valid like for a `T`
+    #[cfg(not(feature = "c-handles"))]
+    impl<T: Handle> MapFrom<*mut T> for Option<T> {
+        #[inline]
+        unsafe fn map_from(ptr: *mut T) -> Self {
+            match ptr.is_null() {
+                true => None,
+                false => Some(T::map_from(ptr)),
+            }
         }
     }
 
-    impl<T> MapTo<*mut T> for T {
+    #[cfg(not(feature = "c-handles"))]
+    impl<T: Handle> MapTo<*mut T> for T {
         #[inline]
         fn map_to(self) -> *mut T {
-            Box::into_raw(Box::new(self))
+            self.into_handle()
         }
     }
 
-    impl<T> MapTo<*mut T> for Option<T> {
+    #[cfg(not(feature = "c-handles"))]
+    impl<T: Handle> MapTo<*mut T> for Option<T> {
         #[inline]
         fn map_to(self) -> *mut T {
             self.map(MapTo::map_to)
                 .unwrap_or_else(|| std::ptr::null_mut())
         }
     }
+
+    #[cfg(feature = "c-handles")]
+    impl<T: Send + 'static> MapTo<u64> for Cursor<T> {
+        #[inline]
+        fn map_to(self) -> u64 {
+            // released by the `_destroy` function of the method
+            crate::registry::insert(self)
+        }
+    }
+
+    #[cfg(feature = "c-handles")]
+    impl<T: Handle> MapTo<u64> for T {
+        #[inline]
+        fn map_to(self) -> u64 {
+            self.into_handle()
+        }
+    }
+
+    #[cfg(feature = "c-handles")]
+    impl<T: Handle> MapTo<u64> for Option<T> {
+        #[inline]
+        fn map_to(self) -> u64 {
+            self.map(MapTo::map_to).unwrap_or(0)
+        }
+    }
 }
-#[cfg(feature = "c")]
-pub use c_mapping::*;
@@ -0,0 +1,212 @@
+//! Registry of the objects handed to C as integer handles, with the `c-handles` feature
+//!
+//! A handle packs the index of a slot with its generation, which is incremented every time the
+//! slot is released, so a handle that was already released no longer matches its slot and is
+//! rejected instead of reaching freed memory.
+
+use std::any::Any;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::langs::IntoPlatformError;
+
+/// Error code returned for a handle that was already released
+pub const HANDLE_ERROR_RELEASED: i32 = -3;
+/// Error code returned for a handle that was never issued, or that belongs to another type
+pub const HANDLE_ERROR_INVALID: i32 = -4;
+/// Error code returned when an object can't be taken by value, because other handles or running
+/// calls still use it
+pub const HANDLE_ERROR_IN_USE: i32 = -5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    Released,
+    Invalid,
+    InUse,
+}
+
+impl IntoPlatformError for HandleError {
+    type TargetType = i32;
+
+    fn into_platform_error(self) -> Self::TargetType {
+        match self {
+            HandleError::Released => HANDLE_ERROR_RELEASED,
+            HandleError::Invalid => HANDLE_ERROR_INVALID,
+            HandleError::InUse => HANDLE_ERROR_IN_USE,
+        }
+    }
+
+    fn ok() -> Self::TargetType {
+        0
+    }
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    // only read by the leak report
+    #[cfg_attr(not(feature = "c-handles-debug"), allow(dead_code))]
+    type_name: &'static str,
+}
+
+#[derive(Default)]
+struct Slot {
+    generation: u32,
+    entry: Option<Entry>,
+}
+
+struct Registry {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    slots: Vec::new(),
+    free: Vec::new(),
+});
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().expect("Poisoned handle registry")
+}
+
+// the index is offset by one so that `0` is never a valid handle
+fn pack(index: u32, generation: u32) -> u64 {
+    ((generation as u64) << 32) | (index as u64 + 1)
+}
+
+fn unpack(handle: u64) -> Option<(usize, u32)> {
+    let index = (handle & 0xffff_ffff) as usize;
+    match index {
+        0 => None,
+        _ => Some((index - 1, (handle >> 32) as u32)),
+    }
+}
+
+impl Registry {
+    fn insert(&mut self, entry: Entry) -> u64 {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot::default());
+                (self.slots.len() - 1) as u32
+            }
+        };
+
+        let slot = &mut self.slots[index as usize];
+        slot.entry = Some(entry);
+        pack(index, slot.generation)
+    }
+
+    fn slot(&mut self, handle: u64) -> Result<&mut Slot, HandleError> {
+        let (index, generation) = unpack(handle).ok_or(HandleError::Invalid)?;
+        let slot = self.slots.get_mut(index).ok_or(HandleError::Invalid)?;
+
+        if generation > slot.generation {
+            Err(HandleError::Invalid)
+        } else if generation < slot.generation || slot.entry.is_none() {
+            Err(HandleError::Released)
+        } else {
+            Ok(slot)
+        }
+    }
+
+    fn get<T: Send + 'static>(&mut self, handle: u64) -> Result<Arc<Mutex<T>>, HandleError> {
+        let entry = self.slot(handle)?.entry.as_ref().expect("Empty slot");
+
+        Arc::clone(&entry.value)
+            .downcast::<Mutex<T>>()
+            .map_err(|_| HandleError::Invalid)
+    }
+
+    fn release(&mut self, handle: u64) {
+        let (index, _) = unpack(handle).expect("Handle already checked");
+        let slot = &mut self.slots[index];
+        slot.entry = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index as u32);
+    }
+}
+
+/// Store `value`, returning the handle that owns it
+pub fn insert<T: Send + 'static>(value: T) -> u64 {
+    #[cfg(feature = "c-handles-debug")]
+    debug::report_leaks_at_exit();
+
+    registry().insert(Entry {
+        value: Arc::new(Mutex::new(value)),
+        type_name: std::any::type_name::<T>(),
+    })
+}
+
+/// Object owned by `handle`, which stays alive until the returned value is dropped even if the
+/// handle is released in the meantime
+pub fn get<T: Send + 'static>(handle: u64) -> Result<Arc<Mutex<T>>, HandleError> {
+    registry().get(handle)
+}
+
+/// Issue a new handle for the object owned by `handle`, which must be released separately
+pub fn clone<T: Send + 'static>(handle: u64) -> Result<u64, HandleError> {
+    let mut registry = registry();
+    let value = registry.get::<T>(handle)?;
+
+    Ok(registry.insert(Entry {
+        value,
+        type_name: std::any::type_name::<T>(),
+    }))
+}
+
+/// Release `handle`, returning the object it owned
+pub fn remove<T: Send + 'static>(handle: u64) -> Result<Arc<Mutex<T>>, HandleError> {
+    let mut registry = registry();
+    let value = registry.get::<T>(handle)?;
+    registry.release(handle);
+
+    Ok(value)
+}
+
+/// Release `handle` and move the object out of it, failing without releasing it when the object is
+/// still used through another handle or by a running call
+pub fn take<T: Send + 'static>(handle: u64) -> Result<T, HandleError> {
+    let mut registry = registry();
+    let value = registry.get::<T>(handle)?;
+    // the registry is locked, so no other reference can be made while checking: only the slot
+    // and `value` may hold one
+    if Arc::strong_count(&value) > 2 {
+        return Err(HandleError::InUse);
+    }
+    registry.release(handle);
+
+    let value = Arc::try_unwrap(value).map_err(|_| HandleError::InUse)?;
+    Ok(value.into_inner().expect("Poisoned object"))
+}
+
+#[cfg(feature = "c-handles-debug")]
+mod debug {
+    use std::sync::Once;
+
+    use super::{pack, REGISTRY};
+
+    static REGISTER: Once = Once::new();
+
+    pub fn report_leaks_at_exit() {
+        REGISTER.call_once(|| unsafe {
+            libc::atexit(report_leaks);
+        });
+    }
+
+    extern "C" fn report_leaks() {
+        // the registry could be locked by a thread that is still running
+        let registry = match REGISTRY.try_lock() {
+            Ok(registry) => registry,
+            Err(_) => return,
+        };
+
+        for (index, slot) in registry.slots.iter().enumerate() {
+            if let Some(entry) = &slot.entry {
+                eprintln!(
+                    "bdk-ffi: leaked handle {:#x} ({})",
+                    pack(index as u32, slot.generation),
+                    entry.type_name
+                );
+            }
+        }
+    }
+}
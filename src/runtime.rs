@@ -0,0 +1,35 @@
+//! Runtime managed by the library, which drives the `async fn` exposed to the other languages
+//!
+//! It's started the first time an async function is called, so that the languages that only use
+//! blocking functions don't pay for its threads.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+
+use tokio::runtime::{Builder, Runtime};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .thread_name("bdk-ffi-runtime")
+            .enable_all()
+            .build()
+            .expect("Unable to start the runtime")
+    })
+}
+
+/// Run `future` in the background, its output is delivered by the future itself
+pub fn spawn<F: Future<Output = ()> + Send + 'static>(future: F) {
+    runtime().spawn(future);
+}
+
+/// Run `future` to completion, blocking the current thread, for the languages that can't wait
+/// for it asynchronously
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    runtime().block_on(future)
+}
@@ -0,0 +1,15 @@
+// swift-tools-version:5.5
+import PackageDescription
+
+let package = Package(
+    name: "Bdk",
+    products: [
+        .library(name: "Bdk", targets: ["Bdk"]),
+    ],
+    targets: [
+        // module map of the header generated by cbindgen, linking `libbdk_ffi`
+        .systemLibrary(name: "BdkFFI", path: "Sources/BdkFFI"),
+        .target(name: "Bdk", dependencies: ["BdkFFI"]),
+        .executableTarget(name: "Example", dependencies: ["Bdk"]),
+    ]
+)
@@ -0,0 +1,75 @@
+// Automatically generated by the `derive` crate, do not edit
+
+import BdkFFI
+
+public class Address {
+    let pointer: OpaquePointer
+
+    init(pointer: OpaquePointer) {
+        self.pointer = pointer
+    }
+
+    public static func fromScript(script: Script, network: Network) -> Address? {
+        var out: OpaquePointer? = nil
+        address_from_script(script.pointer, network.pointer, &out)
+        return out.map { Address(pointer: $0) }
+    }
+
+    public convenience init(s: String) throws {
+        var out: OpaquePointer? = nil
+        let code = address_from_string(s, &out)
+        guard code == 0 else { throw BitcoinError(code: code) }
+        self.init(pointer: out!)
+    }
+
+    deinit {
+        address_destroy(pointer)
+    }
+
+    public func toString() -> String {
+        return takeString(address_to_string(pointer)!)
+    }
+
+    public var script: Script {
+        var out: OpaquePointer? = nil
+        address_get_script(pointer, &out)
+        return Script(pointer: out!)
+    }
+
+    public func network() -> Network {
+        var out: OpaquePointer? = nil
+        address_network(pointer, &out)
+        return Network(pointer: out!)
+    }
+}
+
+extension Address: CustomStringConvertible {
+    public var description: String {
+        return toString()
+    }
+}
+
+/// Take the ownership of a string returned by the library
+fileprivate func takeString(_ pointer: UnsafeMutablePointer<CChar>) -> String {
+    defer { string_free(pointer) }
+
+    return String(cString: pointer)
+}
+
+/// Take the ownership of a byte buffer returned by the library
+fileprivate func takeBytes(_ buffer: ByteBuffer) -> [UInt8] {
+    defer { byte_buffer_free(buffer) }
+
+    return Array(UnsafeBufferPointer(start: buffer.data, count: Int(buffer.len)))
+}
+
+/// Take the ownership of the objects of an array returned by the library, and release the array
+fileprivate func takeArray<T>(_ array: UnsafeMutablePointer<OpaquePointer?>, _ len: Int, _ free: (UnsafeMutablePointer<OpaquePointer?>?, Int) -> Void, _ take: (OpaquePointer) -> T) -> [T] {
+    let values = (0..<len).map { i -> T in
+        defer { array[i] = nil }
+        return take(array[i]!)
+    }
+    free(array, len)
+
+    return values
+}
@@ -0,0 +1,6 @@
+// Automatically generated by the `derive` crate, do not edit
+
+public struct BitcoinError: Error {
+    /// Error code returned by the library
+    public let code: Int32
+}
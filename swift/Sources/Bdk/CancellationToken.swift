@@ -0,0 +1,54 @@
+// Automatically generated by the `derive` crate, do not edit
+
+import BdkFFI
+
+public class CancellationToken {
+    let pointer: OpaquePointer
+
+    init(pointer: OpaquePointer) {
+        self.pointer = pointer
+    }
+
+    public convenience init() {
+        var out: OpaquePointer? = nil
+        cancellationtoken_new(&out)
+        self.init(pointer: out!)
+    }
+
+    deinit {
+        cancellationtoken_destroy(pointer)
+    }
+
+    public func cancel() {
+        cancellationtoken_cancel(pointer)
+    }
+
+    public func isCancelled() -> Bool {
+        return cancellationtoken_is_cancelled(pointer)
+    }
+}
+
+/// Take the ownership of a string returned by the library
+fileprivate func takeString(_ pointer: UnsafeMutablePointer<CChar>) -> String {
+    defer { string_free(pointer) }
+
+    return String(cString: pointer)
+}
+
+/// Take the ownership of a byte buffer returned by the library
+fileprivate func takeBytes(_ buffer: ByteBuffer) -> [UInt8] {
+    defer { byte_buffer_free(buffer) }
+
+    return Array(UnsafeBufferPointer(start: buffer.data, count: Int(buffer.len)))
+}
+
+/// Take the ownership of the objects of an array returned by the library, and release the array
+fileprivate func takeArray<T>(_ array: UnsafeMutablePointer<OpaquePointer?>, _ len: Int, _ free: (UnsafeMutablePointer<OpaquePointer?>?, Int) -> Void, _ take: (OpaquePointer) -> T) -> [T] {
+    let values = (0..<len).map { i -> T in
+        defer { array[i] = nil }
+        return take(array[i]!)
+    }
+    free(array, len)
+
+    return values
+}
@@ -0,0 +1,76 @@
+// Automatically generated by the `derive` crate, do not edit
+
+import BdkFFI
+
+public class Network {
+    let pointer: OpaquePointer
+
+    init(pointer: OpaquePointer) {
+        self.pointer = pointer
+    }
+
+    public convenience init(s: String) throws {
+        var out: OpaquePointer? = nil
+        let code = network_from_string(s, &out)
+        guard code == 0 else { throw BitcoinError(code: code) }
+        self.init(pointer: out!)
+    }
+
+    deinit {
+        network_destroy(pointer)
+    }
+
+    public static func bitcoin() -> Network {
+        var out: OpaquePointer? = nil
+        network_bitcoin(&out)
+        return Network(pointer: out!)
+    }
+
+    public static func testnet() -> Network {
+        var out: OpaquePointer? = nil
+        network_testnet(&out)
+        return Network(pointer: out!)
+    }
+
+    public static func all() -> [Network] {
+        var out: UnsafeMutablePointer<OpaquePointer?>? = nil
+        var outLen = 0
+        network_all(&out, &outLen)
+        return takeArray(out!, outLen, network_array_free) { Network(pointer: $0) }
+    }
+
+    public func toString() -> String {
+        return takeString(network_to_string(pointer)!)
+    }
+}
+
+extension Network: CustomStringConvertible {
+    public var description: String {
+        return toString()
+    }
+}
+
+/// Take the ownership of a string returned by the library
+fileprivate func takeString(_ pointer: UnsafeMutablePointer<CChar>) -> String {
+    defer { string_free(pointer) }
+
+    return String(cString: pointer)
+}
+
+/// Take the ownership of a byte buffer returned by the library
+fileprivate func takeBytes(_ buffer: ByteBuffer) -> [UInt8] {
+    defer { byte_buffer_free(buffer) }
+
+    return Array(UnsafeBufferPointer(start: buffer.data, count: Int(buffer.len)))
+}
+
+/// Take the ownership of the objects of an array returned by the library, and release the array
+fileprivate func takeArray<T>(_ array: UnsafeMutablePointer<OpaquePointer?>, _ len: Int, _ free: (UnsafeMutablePointer<OpaquePointer?>?, Int) -> Void, _ take: (OpaquePointer) -> T) -> [T] {
+    let values = (0..<len).map { i -> T in
+        defer { array[i] = nil }
+        return take(array[i]!)
+    }
+    free(array, len)
+
+    return values
+}
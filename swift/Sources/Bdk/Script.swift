@@ -0,0 +1,80 @@
+// Automatically generated by the `derive` crate, do not edit
+
+import BdkFFI
+
+// Not wrapped yet, only available through the C API:
+// - script_instructions: returns a cursor
+// - script_find_nonce_async: async
+// - script_short_hex: takes optional arguments
+public class Script {
+    let pointer: OpaquePointer
+
+    init(pointer: OpaquePointer) {
+        self.pointer = pointer
+    }
+
+    public convenience init(hex: String) throws {
+        var out: OpaquePointer? = nil
+        let code = script_from_hex(hex, &out)
+        guard code == 0 else { throw BitcoinError(code: code) }
+        self.init(pointer: out!)
+    }
+
+    public static func fromBytes(bytes: [UInt8]) -> Script {
+        var out: OpaquePointer? = nil
+        script_from_bytes(bytes, bytes.count, &out)
+        return Script(pointer: out!)
+    }
+
+    deinit {
+        script_destroy(pointer)
+    }
+
+    public func toBytes() -> [UInt8] {
+        return takeBytes(script_to_bytes(pointer))
+    }
+
+    public func toHex() -> String {
+        return takeString(script_to_hex(pointer)!)
+    }
+
+    public func asm() -> String {
+        return takeString(script_asm(pointer)!)
+    }
+
+    public func findNonce(zeroBits: UInt32, token: CancellationToken) throws -> UInt32 {
+        var out: UInt32 = 0
+        let code = script_find_nonce(pointer, zeroBits, token.pointer, &out)
+        guard code == 0 else { throw BitcoinError(code: code) }
+        return out
+    }
+
+    public func spendFee(feeRate: FeeRate) -> UInt32 {
+        return script_spend_fee(pointer, feeRate.pointer)
+    }
+}
+
+/// Take the ownership of a string returned by the library
+fileprivate func takeString(_ pointer: UnsafeMutablePointer<CChar>) -> String {
+    defer { string_free(pointer) }
+
+    return String(cString: pointer)
+}
+
+/// Take the ownership of a byte buffer returned by the library
+fileprivate func takeBytes(_ buffer: ByteBuffer) -> [UInt8] {
+    defer { byte_buffer_free(buffer) }
+
+    return Array(UnsafeBufferPointer(start: buffer.data, count: Int(buffer.len)))
+}
+
+/// Take the ownership of the objects of an array returned by the library, and release the array
+fileprivate func takeArray<T>(_ array: UnsafeMutablePointer<OpaquePointer?>, _ len: Int, _ free: (UnsafeMutablePointer<OpaquePointer?>?, Int) -> Void, _ take: (OpaquePointer) -> T) -> [T] {
+    let values = (0..<len).map { i -> T in
+        defer { array[i] = nil }
+        return take(array[i]!)
+    }
+    free(array, len)
+
+    return values
+}
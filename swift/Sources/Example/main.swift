@@ -0,0 +1,15 @@
+import Bdk
+
+let script = try Script(hex: "a91457d6b4ded38193013643b03b4472e15f80bc465787")
+let network = Network.testnet()
+
+if let address = Address.fromScript(script: script, network: network) {
+    print("Address: \(address)")
+    print("Script: \(address.script.toHex())")
+}
+
+do {
+    _ = try Address(s: "invalid")
+} catch let error as BitcoinError {
+    print("Error: \(error)")
+}
@@ -0,0 +1,232 @@
+//! Check the functions described in `idl/c/bitcoin.json` against the header generated by cbindgen
+//!
+//! `c/bindings.h` must be generated first, by building with the `c` feature and `BDK_FFI_GEN_DIR`
+//! set to the root of the repository, e.g. with `make -C c`.
+#![cfg(all(feature = "c", not(feature = "c-handles")))]
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Function declared in the header, with its types without spaces
+#[derive(Debug)]
+struct Prototype {
+    args: Vec<(String, String)>,
+    output: String,
+}
+
+fn squash(s: &str) -> String {
+    s.split_whitespace().collect()
+}
+
+/// Split `s` at the commas that are not nested in parentheses or angle brackets
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '<' => depth += 1,
+            ')' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        parts.push(s[start..].trim());
+    }
+
+    parts
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Split a declaration like `const char *name` into its name and type
+fn split_declaration(declaration: &str) -> (String, String) {
+    // function pointers have their name inside: `void (*name)(void*, int32_t)`
+    if let Some(start) = declaration.find("(*") {
+        let end = start
+            + declaration[start..]
+                .find(')')
+                .expect("Invalid function pointer");
+        let name = declaration[start + 2..end].trim().to_string();
+        let ty = format!("{}(*){}", &declaration[..start], &declaration[end + 1..]);
+
+        return (name, squash(&ty));
+    }
+
+    let name_start = declaration
+        .rfind(|c| !is_ident_char(c))
+        .map_or(0, |i| i + 1);
+    (
+        declaration[name_start..].to_string(),
+        squash(&declaration[..name_start]),
+    )
+}
+
+fn parse_header(header: &str) -> HashMap<String, Prototype> {
+    let mut code = String::new();
+    let mut rest = header;
+    while let Some(start) = rest.find("/*") {
+        code.push_str(&rest[..start]);
+        let end = rest[start..].find("*/").expect("Unterminated comment");
+        rest = &rest[start + end + 2..];
+    }
+    code.push_str(rest);
+    let code = code
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut prototypes = HashMap::new();
+    for statement in code.split([';', '{', '}']) {
+        let statement = statement.trim();
+        let open = match statement.find('(') {
+            Some(open) if !statement.starts_with("typedef") => open,
+            _ => continue,
+        };
+        let (name, output) = split_declaration(&statement[..open]);
+        let close = statement.rfind(')').expect("Unterminated argument list");
+        let args = match statement[open + 1..close].trim() {
+            "void" => vec![],
+            args => split_top_level(args)
+                .into_iter()
+                .map(split_declaration)
+                .collect(),
+        };
+
+        prototypes.insert(name, Prototype { args, output });
+    }
+
+    prototypes
+}
+
+/// C type of a Rust type of the description, without spaces like the ones of the header
+fn c_type(ty: &str) -> String {
+    let ty = ty.trim();
+
+    if let Some(inner) = ty.strip_prefix("*const ").or_else(|| ty.strip_prefix("&")) {
+        return format!("const{}*", c_type(inner));
+    }
+    if let Some(inner) = ty
+        .strip_prefix("*mut ")
+        .or_else(|| ty.strip_prefix("&mut "))
+    {
+        return format!("{}*", c_type(inner));
+    }
+    if let Some(signature) = ty.strip_prefix("extern \"C\" fn(") {
+        let (args, output) = match signature.rfind(')') {
+            Some(end) => (&signature[..end], signature[end + 1..].trim()),
+            None => panic!("Invalid function pointer `{}`", ty),
+        };
+        let output = match output.strip_prefix("->") {
+            Some(output) => c_type(output),
+            None => "void".to_string(),
+        };
+        let args = split_top_level(args)
+            .into_iter()
+            .map(c_type)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        return format!("{}(*)({})", output, args);
+    }
+
+    match ty {
+        "()" => return "void".into(),
+        "bool" => return "bool".into(),
+        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => {
+            return format!(
+                "{}int{}_t",
+                if ty.starts_with('u') { "u" } else { "" },
+                &ty[1..]
+            )
+        }
+        "usize" => return "uintptr_t".into(),
+        "isize" => return "intptr_t".into(),
+        "libc::c_char" => return "char".into(),
+        "libc::c_void" => return "void".into(),
+        _ => {}
+    }
+
+    // structs, with the generic ones mangled like cbindgen does: `Cursor<String>` is `Cursor_String`
+    let mangled = ty.replace('<', "_").replace(", ", "_").replace('>', "");
+    assert!(
+        mangled.chars().all(is_ident_char),
+        "Unexpected type `{}` in the description",
+        ty
+    );
+    format!("struct{}", mangled)
+}
+
+/// Every function exported in the description of `module` and its submodules
+fn exports<'a>(module: &'a Value, found: &mut Vec<&'a Value>) {
+    let functions = module["functions"].as_array().into_iter().flatten();
+    let methods = module["structs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|s| s["methods"].as_array().into_iter().flatten());
+
+    for function in functions.chain(methods) {
+        let iterator = &function["output"]["iterator"];
+        for export in &[&function["export"], &iterator["next"], &iterator["destroy"]] {
+            if !export.is_null() {
+                found.push(export);
+            }
+        }
+    }
+
+    for submodule in module["modules"].as_array().into_iter().flatten() {
+        exports(submodule, found);
+    }
+}
+
+#[test]
+fn c_description_matches_header() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let header = fs::read_to_string(root.join("c/bindings.h"))
+        .expect("c/bindings.h is missing, build with `BDK_FFI_GEN_DIR` set to generate it");
+    let description: Value =
+        serde_json::from_str(&fs::read_to_string(root.join("idl/c/bitcoin.json")).unwrap())
+            .expect("Invalid description");
+    assert_eq!(description["language"], "c");
+
+    let prototypes = parse_header(&header);
+    let mut found = vec![];
+    exports(&description["module"], &mut found);
+    assert!(!found.is_empty(), "No exported function in the description");
+
+    for export in found {
+        let symbol = export["symbol"].as_str().expect("Missing symbol");
+        let prototype = prototypes
+            .get(symbol)
+            .unwrap_or_else(|| panic!("`{}` is not declared in the header", symbol));
+
+        let output = match &export["output"] {
+            Value::Null => "void".to_string(),
+            output => c_type(output.as_str().expect("Invalid output")),
+        };
+        assert_eq!(output, prototype.output, "output of `{}`", symbol);
+
+        let args = export["args"]
+            .as_array()
+            .expect("Missing arguments")
+            .iter()
+            .map(|arg| {
+                (
+                    arg["name"].as_str().expect("Missing name").to_string(),
+                    c_type(arg["type"].as_str().expect("Missing type")),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(args, prototype.args, "arguments of `{}`", symbol);
+    }
+}
@@ -0,0 +1,18 @@
+const { Address, Network, Script } = require('./pkg/bdk_ffi');
+
+const s = new Script('a91457d6b4ded38193013643b03b4472e15f80bc465787');
+const n = new Network('testnet');
+const a = Address.fromScript(s, n);
+
+console.log(`Address: ${a}`);
+console.log(`Script: ${a.script.toHex()}`);
+
+try {
+    new Script('not hex');
+} catch (e) {
+    console.log(`Error: ${e.name}: ${e.message}`);
+}
+
+a.free();
+n.free();
+s.free();